@@ -57,6 +57,67 @@ function err() {
   echo "[$(date +'%Y-%m-%dT%H:%M:%S%z')]: $*" >&2
 }
 
+# Apply provisioning options generated by the flash utility to ./rootfs
+function run_rootfs_hook() {
+  if [[ -n "${CFU_ROOTFS_HOOK}" ]]; then
+    echo "Applying provisioning options to rootfs ..."
+    if ! sudo env ROOT="$(pwd)/rootfs" bash "${CFU_ROOTFS_HOOK}"; then
+      err "Unable to apply provisioning options"
+      exit 1
+    fi
+  fi
+}
+
+# A soft cancel requested from the flash utility stops here, between steps, before anything is removed or flashed
+function soft_cancel_checkpoint() {
+  if [[ -n "${CFU_SOFT_CANCEL_FILE}" ]] && [[ -e "${CFU_SOFT_CANCEL_FILE}" ]]; then
+    echo "Soft cancel: stopping before $1"
+    exit 75
+  fi
+}
+
+# Stages the user chose to skip, listed space separated in CFU_SKIP_STAGES
+function skip_stage() {
+  [[ " ${CFU_SKIP_STAGES} " == *" $1 "* ]]
+}
+
+# With the download stage skipped a missing archive is an error instead of a download
+function require_download() {
+  if skip_stage download; then
+    err "Download was skipped but $1 is not in ~/openzeka"
+    exit 1
+  fi
+}
+
+# Download a release archive into ~/openzeka, trying the site's HTTP mirrors (base URLs in CFU_MIRRORS,
+# space separated) before NVIDIA's URL. Mirrored files are checked against the published checksums later
+# like any download. A failed attempt leaves no file behind, so the next run downloads again.
+function download_archive() {
+  local file_name="$1" url="$2" mirror
+  for mirror in ${CFU_MIRRORS}; do
+    if sudo -u "${user_name}" wget -O ~/openzeka/"${file_name}" "${mirror%/}/${url##*/}"; then
+      return 0
+    fi
+    echo "Mirror ${mirror} could not provide ${url##*/}"
+    rm -f ~/openzeka/"${file_name}"
+  done
+  if ! sudo -u "${user_name}" wget -O ~/openzeka/"${file_name}" "${url}"; then
+    rm -f ~/openzeka/"${file_name}"
+    return 1
+  fi
+}
+
+# Extract an archive with the flash utility's extractor, which reports progress and can be
+# cancelled; plain tar with the given flags is used when running outside the utility
+function extract_archive() {
+  local archive="$1" destination="$2" tar_flags="$3"
+  if [[ -n "${CFU_EXTRACTOR}" ]]; then
+    sudo "${CFU_EXTRACTOR}" --extract "${archive}" "${destination}" "${CFU_CANCEL_FILE}"
+  else
+    sudo tar ${tar_flags} "${archive}" -C "${destination}"
+  fi
+}
+
 function d315_62(){
     j_version=$(echo "$jetpack_version" | cut -d " " -f 1)
     cfg_folder_name='generic'
@@ -152,7 +213,8 @@ elif [[ ${host_version} == '18.04' ]]; then
   recovery_status=$(lsusb | grep 'NVidia Corp.' | cut -d " " -f 7)
 fi
 
-if [[ ! "${recovery_status^^}" == 'NVIDIA' ]]; then
+# Prefetching only prepares the files and planning changes nothing, no device is needed yet
+if [[ ! "${recovery_status^^}" == 'NVIDIA' ]] && [[ -z "${CFU_PREFETCH_ONLY}" ]] && [[ -z "${CFU_PLAN_ONLY}" ]]; then
   err "Cannot find a force recovery device"
   exit 1
 fi
@@ -179,6 +241,33 @@ filename_1="bsp_files_${device_flashed}_${jetpack_code}.tbz2"
 filename_2="sample_root_files_${device_flashed}_${jetpack_code}.tbz2"
 filename_3="secure_boot_${device_flashed}_${jetpack_code}.tbz2"
 
+# Planning only reports what a flash would use as "CFU_PLAN key=value" lines; nothing is downloaded or changed
+if [[ -n "${CFU_PLAN_ONLY}" ]]; then
+  echo "CFU_PLAN device_flashed=${device_flashed}"
+  echo "CFU_PLAN device_name=${device_name}"
+  echo "CFU_PLAN host_version=${host_version}"
+  echo "CFU_PLAN recovery_device=$([[ "${recovery_status^^}" == 'NVIDIA' ]] && echo true || echo false)"
+  planned=("${filename_1}|${!download_link_1}")
+  if [[ "${device_flashed}" != "D131" ]] && [[ "${device_flashed}" != "D315" ]] && [[ "${device_flashed}" != "J401" ]] && \
+     [[ ! ("${device_flashed}" == "D131L" && "${jetpack_code}" == '6_1') ]]; then
+    planned+=("${filename_2}|${!download_link_2}")
+  fi
+  if [[ "${jetpack_code}" == '4_6_3' || "${jetpack_code}" == '4_6_4' || "${jetpack_code}" == '4_6_5' || "${product}" == "ONX-101" ]] && \
+     [[ "${product}" == 'Xavier' || "${product}" == 'ONX-101' ]]; then
+    planned+=("${filename_3}|${!download_link_3}")
+  fi
+  for entry in "${planned[@]}"; do
+    echo "CFU_PLAN archive=${entry}|$([[ -e ~/openzeka/"${entry%%|*}" ]] && echo cached || echo download)"
+  done
+  prepared_config="${product}_${device_flashed}_${jetpack_code}"
+  if [[ -d ~/openzeka/Linux_for_Tegra ]] && [[ "$(cat ~/openzeka/.cfu_prepared 2>/dev/null)" == "${prepared_config}" ]]; then
+    echo "CFU_PLAN prepared=true"
+  else
+    echo "CFU_PLAN prepared=false"
+  fi
+  exit 0
+fi
+
 # Creating necessary folders and downloading files if they don't exist
 if [[ ! -d ~/openzeka ]]; then
   if ! sudo -u "${user_name}" mkdir ~/openzeka; then
@@ -189,7 +278,8 @@ fi
 
 if [[ ! -e ~/openzeka/"${filename_1}" ]]; then
 echo "downloading file ${filename_1}"
-  if ! sudo -u "${user_name}" wget -O ~/openzeka/"${filename_1}" "${!download_link_1}"; then
+  require_download "${filename_1}"
+  if ! download_archive "${filename_1}" "${!download_link_1}"; then
     err "Unable to download BSP files"
     exit 1
   fi
@@ -202,7 +292,8 @@ if [[ ! -e ~/openzeka/"${filename_2}" ]] && \
    [[ ! ("${device_flashed}" == "D131L" && "${jetpack_code}" == '6_1') ]]; then
 
      echo "downloading file ${filename_2}"
-     if ! sudo -u "${user_name}" wget -O ~/openzeka/"${filename_2}" "${!download_link_2}"; then
+     require_download "${filename_2}"
+     if ! download_archive "${filename_2}" "${!download_link_2}"; then
        err "Unable to download Sample Root Filesystem"
        exit 1
      fi
@@ -214,7 +305,8 @@ if [[  "${jetpack_code}" == '4_6_3' || "${jetpack_code}" == '4_6_4' || "${jetpac
 echo "downloading file ${filename_3}"
   if [[  "${product}" == 'Xavier' || "${product}" == 'ONX-101' ]]; then
       if [[ ! -e ~/openzeka/"${filename_3}" ]]; then
-        if ! sudo -u "${user_name}" wget -O ~/openzeka/"${filename_3}" "${!download_link_3}"; then
+        require_download "${filename_3}"
+        if ! download_archive "${filename_3}" "${!download_link_3}"; then
           err "Unable to download Secure Boot Files"
           exit 1
         fi
@@ -225,82 +317,154 @@ fi
 
 echo "Downloading has been finished!"
 
-# # Removing the old folder
-if [[ -d ~/openzeka/Linux_for_Tegra ]]; then
-  echo "Removing old files..."
-  cd ~/openzeka/ || { err "Failed to change directory"; exit 1; }
-  sudo rm -r Linux_for_Tegra ./*.txt ./*.sh ./*.ko ./*.conf ./*.common ./*.dtsi ./*.dts ./*.dtb Image
+# Checking the archives against the checksums NVIDIA publishes, before anything is extracted
+if [[ -n "${CFU_EXTRACTOR}" ]] && [[ -n "${CFU_PUBLISHED_CHECKSUMS}" ]] && ! skip_stage verification; then
+  for index in 1 2 3; do
+    filename_var="filename_${index}"
+    link_var="download_link_${index}"
+    link="${!link_var}"
+    if [[ -e ~/openzeka/"${!filename_var}" ]]; then
+      if ! "${CFU_EXTRACTOR}" --verify-download ~/openzeka/"${!filename_var}" "${!link}" "${CFU_PUBLISHED_CHECKSUMS}"; then
+        err "${!filename_var} does not match its published checksum, delete it to download it again"
+        exit 1
+      fi
+    fi
+  done
 fi
 
-# Extracting the downloaded files
+# Recording the checksums of the archives used and verifying pinned ones
+artifacts=()
+for artifact in "${filename_1}" "${filename_2}" "${filename_3}"; do
+  [[ -e ~/openzeka/"${artifact}" ]] && artifacts+=("${artifact}")
+done
 
-if [[ "${device_flashed}" == "D131" ]] || [[ "${device_flashed}" == "D315" ]] || [[ "${device_flashed}" == "D131L" && "${jetpack_code}" == '6_1' ]]; then
-  command="zxf"
-elif [[ "${device_flashed}" == "J401" ]]; then
-  command="xpf"
-else
-  command="xf"
+if skip_stage verification; then
+  echo "Skipping checksum verification"
+elif [[ -n "${CFU_ARTIFACT_MANIFEST}" ]]; then
+  (cd ~/openzeka && sha256sum "${artifacts[@]}") > "${CFU_ARTIFACT_MANIFEST}"
 fi
 
-echo "Extracting ${filename_1}, this may take a while..."
-if ! sudo tar ${command} ~/openzeka/"${filename_1}" -C ~/openzeka/; then
-  err "Unable to extract BSP files"
-  exit 1
+if [[ -n "${CFU_ARTIFACT_PINS}" ]] && ! skip_stage verification; then
+  echo "Verifying pinned artifact checksums ..."
+  if ! (cd ~/openzeka && sha256sum --check --strict "${CFU_ARTIFACT_PINS}"); then
+    err "Downloaded or cached artifacts differ from the pinned checksums"
+    exit 1
+  fi
 fi
 
-if [[ "${device_flashed}" != "D131" ]] && \
-   [[ "${device_flashed}" != "D315" ]] && \
-   [[ "${device_flashed}" != "J401" ]] && \
-   [[ ! ("${device_flashed}" == "D131L" && "${jetpack_code}" == '6_1') ]]; then
+soft_cancel_checkpoint "removing old files"
 
-     echo "Extracting ${filename_2}, this may take a while..."
-     if ! sudo tar xpf ~/openzeka/"${filename_2}" -C ~/openzeka/Linux_for_Tegra/rootfs/; then
-       err "Unable to extract Sample Root Filesystem"
-       exit 1
-     fi
+# Files prepared by an earlier prefetch of the same configuration are used as they are
+prepared_stamp=~/openzeka/.cfu_prepared
+prepared_config="${product}_${device_flashed}_${jetpack_code}"
+reuse_prepared=false
+if [[ -d ~/openzeka/Linux_for_Tegra ]] && [[ -f "${prepared_stamp}" ]] && [[ "$(cat "${prepared_stamp}")" == "${prepared_config}" ]]; then
+  echo "Using files prepared in advance for ${prepared_config}"
+  reuse_prepared=true
+  cd ~/openzeka/Linux_for_Tegra/ || { err "Failed to change directory"; exit 1; }
+elif [[ -d ~/openzeka/Linux_for_Tegra ]] && skip_stage extraction; then
+  echo "Skipping extraction, using the existing Linux_for_Tegra"
+  reuse_prepared=true
+  cd ~/openzeka/Linux_for_Tegra/ || { err "Failed to change directory"; exit 1; }
+elif skip_stage extraction; then
+  err "Extraction was skipped but ~/openzeka/Linux_for_Tegra does not exist"
+  exit 1
+fi
+rm -f "${prepared_stamp}"
 
-     if [[ "${jetpack_code}" == '4_6_3' || "${jetpack_code}" == '4_6_4' || "${jetpack_code}" == '4_6_5' ]]; then
-       if [[  "${product}" == 'Xavier' ]]; then
-         echo "Extracting ${filename_3} ..."
-         if ! sudo tar xvjf ~/openzeka/"${filename_3}" -C ~/openzeka/; then
-           err "Unable to extract Secure Boot Files"
-           exit 1
-         fi
-       fi
-       df 
-     fi
+if [[ "${reuse_prepared}" != true ]]; then
 
-fi
+  # # Removing the old folder
+  if [[ -d ~/openzeka/Linux_for_Tegra ]]; then
+    echo "Removing old files..."
+    cd ~/openzeka/ || { err "Failed to change directory"; exit 1; }
+    sudo rm -r Linux_for_Tegra ./*.txt ./*.sh ./*.ko ./*.conf ./*.common ./*.dtsi ./*.dts ./*.dtb Image
+  fi
 
-# Applying binaries, preparing the additional files and flashing the device based on storage device type
+  # Extracting the downloaded files
 
-if [[ "${device_flashed}" != "D131" ]] && [[ "${device_flashed}" != "D315" ]] && [[ "${device_flashed}" != "J401" ]] && [[ ! ("${device_flashed}" == "D131L" && "${jetpack_code}" == '6_1') ]]; then
+  if [[ "${device_flashed}" == "D131" ]] || [[ "${device_flashed}" == "D315" ]] || [[ "${device_flashed}" == "D131L" && "${jetpack_code}" == '6_1' ]]; then
+    command="zxf"
+  elif [[ "${device_flashed}" == "J401" ]]; then
+    command="xpf"
+  else
+    command="xf"
+  fi
 
-  echo "Applying binaries ..."
-  cd ~/openzeka/Linux_for_Tegra/ || { err "Failed to change directory"; exit 1; }
-  if ! sudo ./apply_binaries.sh; then
-    err "Unable to apply binaries"
+  echo "Extracting ${filename_1}, this may take a while..."
+  if ! extract_archive ~/openzeka/"${filename_1}" ~/openzeka/ "${command}"; then
+    err "Unable to extract BSP files"
     exit 1
   fi
 
-  if ! sudo ./tools/l4t_flash_prerequisites.sh; then
-    err "Unable to complete flash prerequisites"
-    exit 1
+  if [[ "${device_flashed}" != "D131" ]] && \
+     [[ "${device_flashed}" != "D315" ]] && \
+     [[ "${device_flashed}" != "J401" ]] && \
+     [[ ! ("${device_flashed}" == "D131L" && "${jetpack_code}" == '6_1') ]]; then
+
+       echo "Extracting ${filename_2}, this may take a while..."
+       if ! extract_archive ~/openzeka/"${filename_2}" ~/openzeka/Linux_for_Tegra/rootfs/ xpf; then
+         err "Unable to extract Sample Root Filesystem"
+         exit 1
+       fi
+
+       if [[ "${jetpack_code}" == '4_6_3' || "${jetpack_code}" == '4_6_4' || "${jetpack_code}" == '4_6_5' ]]; then
+         if [[  "${product}" == 'Xavier' ]]; then
+           echo "Extracting ${filename_3} ..."
+           if ! extract_archive ~/openzeka/"${filename_3}" ~/openzeka/ xvjf; then
+             err "Unable to extract Secure Boot Files"
+             exit 1
+           fi
+         fi
+         df 
+       fi
+
   fi
 
-  if [[  "${product}" == 'ONX-101' ]]; then
-    echo "Extracting and preparing ${filename_3} ..."
-    cd ~/openzeka/ || { err "Failed to change directory"; exit 1; }
-    if ! sudo -u "${user_name}" unzip ~/openzeka/"${filename_3}"; then
-      err "Unable to extract Secure Boot Files"
+  # Applying binaries, preparing the additional files and flashing the device based on storage device type
+
+  if [[ "${device_flashed}" != "D131" ]] && [[ "${device_flashed}" != "D315" ]] && [[ "${device_flashed}" != "J401" ]] && [[ ! ("${device_flashed}" == "D131L" && "${jetpack_code}" == '6_1') ]]; then
+
+    echo "Applying binaries ..."
+    cd ~/openzeka/Linux_for_Tegra/ || { err "Failed to change directory"; exit 1; }
+    if ! sudo ./apply_binaries.sh; then
+      err "Unable to apply binaries"
+      exit 1
+    fi
+
+    if ! sudo ./tools/l4t_flash_prerequisites.sh; then
+      err "Unable to complete flash prerequisites"
       exit 1
     fi
 
-    chmod u+x orin_nx_replace_files.sh
-    sudo ./orin_nx_replace_files.sh
+    if [[  "${product}" == 'ONX-101' ]]; then
+      echo "Extracting and preparing ${filename_3} ..."
+      cd ~/openzeka/ || { err "Failed to change directory"; exit 1; }
+      if ! sudo -u "${user_name}" unzip ~/openzeka/"${filename_3}"; then
+        err "Unable to extract Secure Boot Files"
+        exit 1
+      fi
+
+      chmod u+x orin_nx_replace_files.sh
+      sudo ./orin_nx_replace_files.sh
 
+    fi
   fi
+
+fi
+
+# Stopping here when only prefetching, the next flash of this configuration starts from the prepared files
+if [[ -n "${CFU_PREFETCH_ONLY}" ]]; then
+  echo "${prepared_config}" > "${prepared_stamp}"
+  echo "Files for ${prepared_config} are prepared"
+  exit 0
+fi
+
+# Prepared files are kept for the next flash when a soft cancel stops here
+if [[ -n "${CFU_SOFT_CANCEL_FILE}" ]] && [[ -e "${CFU_SOFT_CANCEL_FILE}" ]]; then
+  echo "${prepared_config}" > "${prepared_stamp}"
 fi
+soft_cancel_checkpoint "flashing the device"
 
 # Flashing the device
 
@@ -316,6 +480,7 @@ if [[ "${storage_device}" == 'Micro SD' ]]; then
     boot_dev='mmcblk0p1'
   fi
 
+  run_rootfs_hook
   echo "sudo ./flash.sh ${device_name} ${boot_dev}"
   if ! sudo ./flash.sh "${device_name}" "${boot_dev}"; then
     err "Unable to flash the device"
@@ -337,6 +502,7 @@ elif [[ "${storage_device}" == 'NVMe SSD' ]]; then
     fi    
 
     cd ~/openzeka/Linux_for_Tegra/ || { err "Failed to change directory"; exit 1; }
+    run_rootfs_hook
     if ! sudo ./tools/kernel_flash/l4t_initrd_flash.sh --external-device nvme0n1p1 -c tools/kernel_flash/"${l4t_config}" \
     -p "-c bootloader/${bootloader_config}/cfg/flash_t234_qspi.xml" --showlogs --network usb0 p3509-a02+p3767-0000 internal; then
       err "Unable to flash the device"
@@ -400,6 +566,7 @@ elif [[ "${storage_device}" == 'NVMe SSD' ]]; then
       fi
     fi
 
+    run_rootfs_hook
     if ! sudo ./tools/kernel_flash/l4t_initrd_flash.sh --external-device nvme0n1p1 -c tools/kernel_flash/flash_l4t_external.xml \
     -p "-c bootloader/${cfg_folder_name}/cfg/flash_t234_qspi.xml" --showlogs --network usb0 "${device_name}" internal; then
       err "Unable to flash the device"
@@ -414,6 +581,7 @@ elif [[ "${storage_device}" == 'NVMe SSD' ]]; then
     fi
 
   else
+    run_rootfs_hook
     echo "./nvsdkmanager_flash.sh --storage nvme0n1p1"
     if ! sudo ./nvsdkmanager_flash.sh --storage nvme0n1p1; then
       err "Unable to flash the device"
@@ -0,0 +1,287 @@
+// CFU - Cordatus Flash Utility - Acceptance Checklists
+// The final QA step of a flashed unit: a checklist of automated checks (job result, first boot, spec steps,
+// commands over SSH) and operator confirmations, evaluated per job. A unit counts as accepted in the
+// history only when every required item passed.
+
+use crate::history::{self, FlashJobRecord};
+use crate::ssh::{run_remote, SshTarget};
+use crate::AppState;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use tauri::{command, Emitter, State};
+
+const CHECKLISTS_FILE: &str = "acceptance_checklists.json";
+const KINDS: &[&str] = &["job_succeeded", "boot_verified", "spec_passed", "ssh_command", "manual"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChecklistItem {
+    pub id: String, // Unique within the checklist
+    pub title: String,
+    pub kind: String, // 'job_succeeded' | 'boot_verified' | 'spec_passed' | 'ssh_command' | 'manual'
+    #[serde(default)]
+    pub command: Option<String>, // For 'ssh_command': run on the device, passes on exit code 0
+    #[serde(default)]
+    pub instructions: Option<String>, // For 'manual': what the operator checks
+    #[serde(default = "default_true")]
+    pub required: bool, // Optional items are recorded but do not decide acceptance
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checklist {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub items: Vec<ChecklistItem>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// One item as evaluated for a job; the item is copied so later checklist edits leave the record as it was
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemResult {
+    pub item: ChecklistItem,
+    pub passed: Option<bool>, // None until checked or confirmed
+    pub detail: Option<String>,
+    pub confirmed_by: Option<String>, // Operator, for manual items
+    pub checked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Acceptance {
+    pub checklist: String,
+    pub results: Vec<ItemResult>,
+    pub status: String, // 'pending' | 'accepted' | 'rejected'
+    pub started_at: DateTime<Utc>,
+    pub decided_at: Option<DateTime<Utc>>,
+}
+
+pub fn load_checklists(app: &tauri::AppHandle) -> Vec<Checklist> {
+    crate::app_data_file(app, CHECKLISTS_FILE)
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_checklists(app: &tauri::AppHandle, checklists: &[Checklist]) -> Result<()> {
+    let path = crate::app_data_file(app, CHECKLISTS_FILE)?;
+    std::fs::write(path, serde_json::to_string_pretty(checklists)?).context("Failed to save acceptance checklists")
+}
+
+fn validate(checklist: &Checklist) -> Result<(), String> {
+    if checklist.name.trim().is_empty() {
+        return Err("The checklist needs a name".to_string());
+    }
+    if checklist.items.is_empty() {
+        return Err("The checklist has no items".to_string());
+    }
+    let mut ids = BTreeSet::new();
+    for item in &checklist.items {
+        if item.id.trim().is_empty() || !ids.insert(item.id.as_str()) {
+            return Err(format!("Item IDs must be set and unique: {:?}", item.id));
+        }
+        if !KINDS.contains(&item.kind.as_str()) {
+            return Err(format!("Unknown kind of item {}: {}", item.id, item.kind));
+        }
+        if item.kind == "ssh_command" && item.command.as_deref().is_none_or(|c| c.trim().is_empty()) {
+            return Err(format!("Item {} runs a command over SSH but has none", item.id));
+        }
+    }
+    Ok(())
+}
+
+// Rejected as soon as a required item fails, accepted once all of them passed
+fn decide(acceptance: &mut Acceptance) {
+    let required = || acceptance.results.iter().filter(|result| result.item.required);
+    let status = if required().any(|result| result.passed == Some(false)) {
+        "rejected"
+    } else if required().all(|result| result.passed == Some(true)) {
+        "accepted"
+    } else {
+        "pending"
+    };
+    acceptance.status = status.to_string();
+    acceptance.decided_at = (status != "pending").then(Utc::now);
+}
+
+// Where automated commands reach the device: the given target, else the one the first boot was checked over
+fn ssh_target(job: &FlashJobRecord, target: Option<SshTarget>) -> Option<SshTarget> {
+    target.or_else(|| job.command.boot_check.as_ref().and_then(|options| options.ssh.clone()))
+}
+
+async fn check(job: &FlashJobRecord, item: &ChecklistItem, target: Option<&SshTarget>) -> (bool, String) {
+    match item.kind.as_str() {
+        "job_succeeded" => (job.status == "success", format!("Job {}", job.status)),
+        "boot_verified" => match &job.verification {
+            Some(verification) => (
+                verification.verified,
+                format!("First boot checked over {} at {}", verification.method, verification.checked_at),
+            ),
+            None => (false, "The first boot was not checked".to_string()),
+        },
+        "spec_passed" if job.spec_results.is_empty() => (false, "No device spec steps were applied".to_string()),
+        "spec_passed" => {
+            let failed: Vec<&str> = job.spec_results.iter().filter(|r| !r.passed).map(|r| r.name.as_str()).collect();
+            if failed.is_empty() {
+                (true, format!("{} spec steps passed", job.spec_results.len()))
+            } else {
+                (false, format!("Failed spec steps: {}", failed.join(", ")))
+            }
+        }
+        "ssh_command" => {
+            let Some(target) = target else {
+                return (false, "No SSH target to run the command on".to_string());
+            };
+            match run_remote(target, item.command.as_deref().unwrap_or_default()).await {
+                Ok(output) => (true, output.trim().to_string()),
+                Err(e) => (false, e.to_string()),
+            }
+        }
+        _ => (false, format!("Unknown kind {}", item.kind)),
+    }
+}
+
+fn find_job(state: &AppState, flash_id: &str) -> Result<FlashJobRecord, String> {
+    let history = state.history.lock().unwrap();
+    history
+        .jobs
+        .iter()
+        .rev()
+        .find(|job| job.flash_id == flash_id)
+        .cloned()
+        .ok_or_else(|| format!("Flash job not found: {}", flash_id))
+}
+
+fn record(app: &tauri::AppHandle, state: &AppState, flash_id: &str, acceptance: &Acceptance) {
+    history::update_history(app, state, |history| {
+        if let Some(record) = history.jobs.iter_mut().find(|r| r.flash_id == flash_id) {
+            record.acceptance = Some(acceptance.clone());
+        }
+    });
+    let _ = app.emit("acceptance-updated", serde_json::json!({
+        "flash_id": flash_id,
+        "acceptance": acceptance,
+    }));
+}
+
+// List acceptance checklists
+#[command]
+pub async fn list_acceptance_checklists(app: tauri::AppHandle) -> Result<Vec<Checklist>, String> {
+    Ok(load_checklists(&app))
+}
+
+// Create or replace a checklist
+#[command]
+pub async fn save_acceptance_checklist(mut checklist: Checklist, app: tauri::AppHandle) -> Result<(), String> {
+    crate::freeze::ensure_not_frozen(&app, "Checklist changes")?;
+    validate(&checklist)?;
+    checklist.updated_at = Utc::now();
+    let mut checklists = load_checklists(&app);
+    checklists.retain(|c| c.name != checklist.name);
+    checklists.push(checklist);
+    save_checklists(&app, &checklists).map_err(|e| e.to_string())
+}
+
+// Delete a checklist; acceptances already recorded keep their copy of it
+#[command]
+pub async fn delete_acceptance_checklist(name: String, app: tauri::AppHandle) -> Result<(), String> {
+    crate::freeze::ensure_not_frozen(&app, "Checklist changes")?;
+    let mut checklists = load_checklists(&app);
+    checklists.retain(|c| c.name != name);
+    save_checklists(&app, &checklists).map_err(|e| e.to_string())
+}
+
+// Evaluate a checklist for a finished job: automated items run now, manual ones wait for
+// confirm_acceptance_item. Starting again replaces the job's previous acceptance, e.g. after rework.
+#[command]
+pub async fn start_acceptance(
+    flash_id: String,
+    checklist: String,
+    target: Option<SshTarget>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Acceptance, String> {
+    let job = find_job(&state, &flash_id)?;
+    if job.status == "running" {
+        return Err(format!("Job {} is still running", flash_id));
+    }
+    let checklist = load_checklists(&app)
+        .into_iter()
+        .find(|c| c.name == checklist)
+        .ok_or_else(|| format!("Acceptance checklist not found: {}", checklist))?;
+    let target = ssh_target(&job, target);
+
+    let mut results = Vec::new();
+    for item in checklist.items {
+        let mut result = ItemResult {
+            item,
+            passed: None,
+            detail: None,
+            confirmed_by: None,
+            checked_at: None,
+        };
+        if result.item.kind != "manual" {
+            let (passed, detail) = check(&job, &result.item, target.as_ref()).await;
+            result.passed = Some(passed);
+            result.detail = Some(detail).filter(|detail| !detail.is_empty());
+            result.checked_at = Some(Utc::now());
+        }
+        results.push(result);
+    }
+    let mut acceptance = Acceptance {
+        checklist: checklist.name,
+        results,
+        status: "pending".to_string(),
+        started_at: Utc::now(),
+        decided_at: None,
+    };
+    decide(&mut acceptance);
+    info!("Acceptance of {} against {}: {}", flash_id, acceptance.checklist, acceptance.status);
+    record(&app, &state, &flash_id, &acceptance);
+    Ok(acceptance)
+}
+
+// Record the operator's verdict on a manual item
+#[command]
+pub async fn confirm_acceptance_item(
+    flash_id: String,
+    item_id: String,
+    passed: bool,
+    note: Option<String>,
+    operator: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Acceptance, String> {
+    let job = find_job(&state, &flash_id)?;
+    let mut acceptance = job
+        .acceptance
+        .ok_or_else(|| format!("No acceptance was started for {}", flash_id))?;
+    if acceptance.status != "pending" {
+        return Err(format!("{} is already {}; start the acceptance again to re-check it", flash_id, acceptance.status));
+    }
+    let result = acceptance
+        .results
+        .iter_mut()
+        .find(|result| result.item.id == item_id)
+        .ok_or_else(|| format!("No item {} in checklist {}", item_id, acceptance.checklist))?;
+    if result.item.kind != "manual" {
+        return Err(format!("Item {} is checked automatically", item_id));
+    }
+    result.passed = Some(passed);
+    result.detail = note.filter(|note| !note.trim().is_empty());
+    result.confirmed_by = operator.filter(|operator| !operator.trim().is_empty());
+    result.checked_at = Some(Utc::now());
+    decide(&mut acceptance);
+    if acceptance.status != "pending" {
+        info!("Acceptance of {} against {}: {}", flash_id, acceptance.checklist, acceptance.status);
+    }
+    record(&app, &state, &flash_id, &acceptance);
+    Ok(acceptance)
+}
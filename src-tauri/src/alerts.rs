@@ -0,0 +1,108 @@
+// CFU - Cordatus Flash Utility - Failure Alerts
+// Rolling failure-rate check per flashing station, raising an event and optional webhook when a fixture starts failing
+
+use crate::history::FlashJobRecord;
+use crate::job_host::JobHost;
+use crate::AppState;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StationFailureAlert {
+    pub station: String,
+    pub failures: usize,
+    pub window_jobs: usize, // Finished jobs the failures were counted over
+    pub failed_stages: Vec<String>,
+    pub last_flash_id: String,
+    pub message: String,
+    pub raised_at: DateTime<Utc>,
+}
+
+fn failures(jobs: &[&FlashJobRecord]) -> usize {
+    jobs.iter().filter(|r| r.status == "failed").count()
+}
+
+// Alert once the window ending at this job crosses the threshold; the window
+// ending at the previous job must have been below it, so a run of failures
+// raises a single alert rather than one per job
+fn evaluate(state: &AppState, flash_id: &str) -> Option<StationFailureAlert> {
+    let settings = state.settings.lock().unwrap().failure_alerts.clone();
+    if !settings.enabled || settings.window_jobs == 0 || settings.max_failures == 0 {
+        return None;
+    }
+
+    let history = state.history.lock().unwrap();
+    let job = history.jobs.iter().find(|r| r.flash_id == flash_id)?;
+    if job.status != "failed" {
+        return None;
+    }
+    let station = job.station.clone()?;
+
+    // Cancelled jobs say nothing about the fixture
+    let finished: Vec<&FlashJobRecord> = history
+        .jobs
+        .iter()
+        .filter(|r| r.station.as_deref() == Some(station.as_str()))
+        .filter(|r| r.status == "success" || r.status == "failed")
+        .collect();
+    let position = finished.iter().position(|r| r.flash_id == flash_id)?;
+    let window = &finished[(position + 1).saturating_sub(settings.window_jobs)..=position];
+    let previous = &finished[position.saturating_sub(settings.window_jobs)..position];
+
+    let count = failures(window);
+    if count < settings.max_failures || failures(previous) >= settings.max_failures {
+        return None;
+    }
+
+    let mut failed_stages: Vec<String> = window
+        .iter()
+        .filter(|r| r.status == "failed")
+        .filter_map(|r| r.failed_stage.clone())
+        .collect();
+    failed_stages.sort();
+    failed_stages.dedup();
+    Some(StationFailureAlert {
+        message: format!(
+            "{} of the last {} jobs on {} failed. Check the USB cable, recovery fixture and power supply.",
+            count,
+            window.len(),
+            station
+        ),
+        station,
+        failures: count,
+        window_jobs: window.len(),
+        failed_stages,
+        last_flash_id: flash_id.to_string(),
+        raised_at: Utc::now(),
+    })
+}
+
+async fn post_webhook(url: &str, alert: &StationFailureAlert) -> Result<()> {
+    reqwest::Client::new()
+        .post(url)
+        .json(alert)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+// Run after each finished job; problems delivering the alert are only logged
+pub async fn check_station_failure_rate(app: &impl JobHost, state: &AppState, flash_id: &str) {
+    let Some(alert) = evaluate(state, flash_id) else {
+        return;
+    };
+    warn!("{}", alert.message);
+    let _ = app.send("station-failure-alert", &alert);
+
+    let webhook_url = state.settings.lock().unwrap().failure_alerts.webhook_url.clone();
+    if webhook_url.is_empty() {
+        return;
+    }
+    match post_webhook(&webhook_url, &alert).await {
+        Ok(()) => info!("Posted failure alert for station {}", alert.station),
+        Err(e) => warn!("Failed to post failure alert webhook: {}", e),
+    }
+}
@@ -0,0 +1,163 @@
+// CFU - Cordatus Flash Utility - Analytics
+// Aggregate statistics over the flash history for the analytics view: durations, failure stages and station throughput
+
+use crate::history::FlashJobRecord;
+use crate::AppState;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tauri::{command, State};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlashTimeStat {
+    pub device_module: String,
+    pub jetpack_version: String,
+    pub storage_device: String,
+    pub jobs: usize, // Successful jobs the times are based on
+    pub avg_secs: i64,
+    pub min_secs: i64,
+    pub max_secs: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StageFailureStat {
+    pub stage: String, // Progress stage, 'unknown' for jobs recorded before stages were tracked
+    pub failures: usize,
+    pub share: f64, // Fraction of all failures
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailureStats {
+    pub finished_jobs: usize, // Succeeded or failed; cancelled jobs are left out
+    pub failed_jobs: usize,
+    pub failure_rate: f64,
+    pub by_stage: Vec<StageFailureStat>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StationThroughput {
+    pub station: String,
+    pub jobs: usize,
+    pub successes: usize,
+    pub failures: usize,
+    pub jobs_per_day: f64, // Over the span between the first and last job
+    pub first_job: DateTime<Utc>,
+    pub last_job: DateTime<Utc>,
+}
+
+// Jobs started within the optional window
+fn jobs_in_range(state: &AppState, since: Option<DateTime<Utc>>, until: Option<DateTime<Utc>>) -> Vec<FlashJobRecord> {
+    let history = state.history.lock().unwrap();
+    history
+        .jobs
+        .iter()
+        .filter(|r| since.is_none_or(|since| r.started_at >= since))
+        .filter(|r| until.is_none_or(|until| r.started_at < until))
+        .cloned()
+        .collect()
+}
+
+fn duration_secs(job: &FlashJobRecord) -> Option<i64> {
+    job.finished_at.map(|finished| (finished - job.started_at).num_seconds())
+}
+
+// Average, fastest and slowest successful flash per module / JetPack / storage
+#[command]
+pub async fn get_flash_time_stats(
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<FlashTimeStat>, String> {
+    let mut groups: BTreeMap<(String, String, String), Vec<i64>> = BTreeMap::new();
+    for job in jobs_in_range(&state, since, until).iter().filter(|r| r.status == "success") {
+        if let Some(secs) = duration_secs(job) {
+            let key = (
+                job.command.device_module.clone(),
+                job.command.jetpack_version.clone(),
+                job.command.storage_device.clone(),
+            );
+            groups.entry(key).or_default().push(secs);
+        }
+    }
+
+    Ok(groups
+        .into_iter()
+        .map(|((device_module, jetpack_version, storage_device), times)| FlashTimeStat {
+            device_module,
+            jetpack_version,
+            storage_device,
+            jobs: times.len(),
+            avg_secs: times.iter().sum::<i64>() / times.len() as i64,
+            min_secs: times.iter().copied().min().unwrap_or_default(),
+            max_secs: times.iter().copied().max().unwrap_or_default(),
+        })
+        .collect())
+}
+
+// Overall failure rate and the stages failures happen in
+#[command]
+pub async fn get_failure_stats(
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<FailureStats, String> {
+    let jobs = jobs_in_range(&state, since, until);
+    let finished = jobs.iter().filter(|r| r.status == "success" || r.status == "failed").count();
+    let mut stages: BTreeMap<String, usize> = BTreeMap::new();
+    for job in jobs.iter().filter(|r| r.status == "failed") {
+        let stage = job.failed_stage.clone().unwrap_or_else(|| "unknown".to_string());
+        *stages.entry(stage).or_default() += 1;
+    }
+    let failed: usize = stages.values().sum();
+
+    let mut by_stage: Vec<StageFailureStat> = stages
+        .into_iter()
+        .map(|(stage, failures)| StageFailureStat {
+            stage,
+            failures,
+            share: failures as f64 / failed as f64,
+        })
+        .collect();
+    by_stage.sort_by_key(|s| std::cmp::Reverse(s.failures));
+
+    Ok(FailureStats {
+        finished_jobs: finished,
+        failed_jobs: failed,
+        failure_rate: if finished == 0 { 0.0 } else { failed as f64 / finished as f64 },
+        by_stage,
+    })
+}
+
+// Jobs, outcomes and jobs per day for each flashing station
+#[command]
+pub async fn get_station_throughput(
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<StationThroughput>, String> {
+    let mut stations: BTreeMap<String, Vec<FlashJobRecord>> = BTreeMap::new();
+    for job in jobs_in_range(&state, since, until) {
+        let station = job.station.clone().unwrap_or_else(|| "unknown".to_string());
+        stations.entry(station).or_default().push(job);
+    }
+
+    Ok(stations
+        .into_iter()
+        .filter_map(|(station, jobs)| {
+            let first_job = jobs.iter().map(|r| r.started_at).min()?;
+            let last_job = jobs.iter().map(|r| r.started_at).max()?;
+            // Count at least one day so a single busy morning does not extrapolate
+            let days = ((last_job - first_job).num_seconds() as f64 / 86_400.0).max(1.0);
+            Some(StationThroughput {
+                station,
+                jobs: jobs.len(),
+                successes: jobs.iter().filter(|r| r.status == "success").count(),
+                failures: jobs.iter().filter(|r| r.status == "failed").count(),
+                jobs_per_day: jobs.len() as f64 / days,
+                first_job,
+                last_job,
+            })
+        })
+        .collect())
+}
@@ -0,0 +1,96 @@
+// CFU - Cordatus Flash Utility - API Tokens
+// Bearer tokens for the HTTP control API; only SHA-256 hashes are stored, the token itself is shown once
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::info;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::command;
+use uuid::Uuid;
+
+const TOKENS_FILE: &str = "api_tokens.json";
+const TOKEN_PREFIX: &str = "cfu_";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: String,
+    pub name: String,
+    pub scope: String, // 'read' (observer) | 'flash'; flash control includes read access
+    pub token_hash: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ApiToken {
+    pub fn allows(&self, scope: &str) -> bool {
+        self.scope == scope || self.scope == "flash"
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CreatedApiToken {
+    pub token: String, // Only returned here
+    pub info: ApiToken,
+}
+
+fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+pub fn load_tokens(app: &tauri::AppHandle) -> Vec<ApiToken> {
+    crate::app_data_file(app, TOKENS_FILE)
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_tokens(app: &tauri::AppHandle, tokens: &[ApiToken]) -> Result<()> {
+    let path = crate::app_data_file(app, TOKENS_FILE)?;
+    std::fs::write(path, serde_json::to_string_pretty(tokens)?).context("Failed to save API tokens")
+}
+
+// The token an `Authorization: Bearer` value refers to, unless unknown or revoked
+pub fn find_token(app: &tauri::AppHandle, bearer: &str) -> Option<ApiToken> {
+    let hash = hash_token(bearer.trim());
+    load_tokens(app).into_iter().find(|t| t.token_hash == hash)
+}
+
+// Create a token; the returned secret cannot be recovered later
+#[command]
+pub async fn create_api_token(name: String, scope: String, app: tauri::AppHandle) -> Result<CreatedApiToken, String> {
+    if !matches!(scope.as_str(), "read" | "flash") {
+        return Err(format!("Unknown API token scope: {}", scope));
+    }
+    let token = format!("{}{}{}", TOKEN_PREFIX, Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let info = ApiToken {
+        id: Uuid::new_v4().to_string(),
+        name,
+        scope,
+        token_hash: hash_token(&token),
+        created_at: Utc::now(),
+    };
+    let mut tokens = load_tokens(&app);
+    tokens.push(info.clone());
+    save_tokens(&app, &tokens).map_err(|e| e.to_string())?;
+    info!("Created {} API token '{}'", info.scope, info.name);
+    Ok(CreatedApiToken { token, info })
+}
+
+// List API tokens (hashes only)
+#[command]
+pub async fn list_api_tokens(app: tauri::AppHandle) -> Result<Vec<ApiToken>, String> {
+    Ok(load_tokens(&app))
+}
+
+// Revoke an API token; requests using it fail immediately
+#[command]
+pub async fn revoke_api_token(id: String, app: tauri::AppHandle) -> Result<(), String> {
+    let mut tokens = load_tokens(&app);
+    let before = tokens.len();
+    tokens.retain(|t| t.id != id);
+    if tokens.len() == before {
+        return Err(format!("API token not found: {}", id));
+    }
+    save_tokens(&app, &tokens).map_err(|e| e.to_string())
+}
@@ -0,0 +1,265 @@
+// CFU - Cordatus Flash Utility - Asset Records
+// MAC address / serial capture during provisioning and per-batch asset export. Batches are kept in the
+// app data directory; while one is active, every successful flash with an SSH target is captured into it.
+
+use crate::fleet;
+use crate::history;
+use crate::job_host::JobHost;
+use crate::ssh::{run_remote, SshTarget};
+use crate::{AppState, FlashCommand};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tauri::{command, State};
+
+const ASSETS_FILE: &str = "asset_batches.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InterfaceMac {
+    pub interface: String,
+    pub mac: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssetRecord {
+    pub batch_id: String,
+    pub serial_number: String,
+    pub hostname: String,
+    pub model: Option<String>,
+    pub l4t_version: Option<String>,
+    pub mac_addresses: Vec<InterfaceMac>,
+    pub captured_at: DateTime<Utc>,
+}
+
+// Records by batch, and the batch flashed units are captured into
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AssetDb {
+    pub active_batch: Option<String>,
+    pub batches: BTreeMap<String, Vec<AssetRecord>>,
+}
+
+pub fn load_assets(app: &impl JobHost) -> AssetDb {
+    let path = match crate::app_data_file(app, ASSETS_FILE) {
+        Ok(path) => path,
+        Err(e) => {
+            warn!("Asset records unavailable: {}", e);
+            return AssetDb::default();
+        }
+    };
+
+    match std::fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            warn!("Invalid asset records file {}: {}", path.display(), e);
+            AssetDb::default()
+        }),
+        Err(_) => AssetDb::default(),
+    }
+}
+
+pub fn save_assets(app: &impl JobHost, assets: &AssetDb) -> Result<()> {
+    let path = crate::app_data_file(app, ASSETS_FILE)?;
+    let json = serde_json::to_string_pretty(assets)?;
+    std::fs::write(&path, json).context("Failed to write asset records")
+}
+
+fn update_assets<T>(app: &impl JobHost, state: &AppState, change: impl FnOnce(&mut AssetDb) -> T) -> Result<T> {
+    let mut assets = state.asset_batches.lock().unwrap();
+    let result = change(&mut assets);
+    save_assets(app, &assets)?;
+    Ok(result)
+}
+
+// Sections are separated by "---" so a single ssh round trip is enough.
+// Only interfaces backed by real hardware (with a /device link) are reported.
+const IDENTITY_SCRIPT: &str = r#"hostname
+echo ---
+for i in /sys/class/net/*; do
+  [ -e "$i/device" ] || continue
+  echo "$(basename "$i") $(cat "$i/address")"
+done
+echo ---
+tr -d '\0' < /proc/device-tree/serial-number 2>/dev/null; echo
+echo ---
+tr -d '\0' < /proc/device-tree/model 2>/dev/null; echo
+echo ---
+cat /etc/nv_tegra_release 2>/dev/null"#;
+
+// Read hostname, MAC addresses, serial and model from a booted device
+pub async fn read_device_identity(target: &SshTarget, batch_id: &str) -> Result<AssetRecord> {
+    let output = run_remote(target, IDENTITY_SCRIPT).await?;
+    let sections: Vec<&str> = output.split("---\n").collect();
+    let section = |index: usize| sections.get(index).map(|s| s.trim()).unwrap_or("");
+
+    let mac_addresses = section(1)
+        .lines()
+        .filter_map(|line| {
+            let (interface, mac) = line.split_once(' ')?;
+            Some(InterfaceMac {
+                interface: interface.to_string(),
+                mac: mac.trim().to_lowercase(),
+            })
+        })
+        .collect();
+
+    let serial_number = section(2).to_string();
+    if serial_number.is_empty() {
+        return Err(anyhow::anyhow!("Device at {} did not report a serial number", target.host));
+    }
+
+    let model = Some(section(3).to_string()).filter(|m| !m.is_empty());
+
+    Ok(AssetRecord {
+        batch_id: batch_id.to_string(),
+        serial_number,
+        hostname: section(0).to_string(),
+        model,
+        l4t_version: crate::parse_nv_tegra_release(section(4)),
+        mac_addresses,
+        captured_at: Utc::now(),
+    })
+}
+
+// Add a captured record to its batch and the fleet, linked to the flash job that produced it if any
+fn add_record(app: &impl JobHost, state: &AppState, record: &AssetRecord, target: &SshTarget, flash_id: Option<&str>) -> Result<()> {
+    fleet::register_device(app, state, record, target);
+    if let Some(flash_id) = flash_id {
+        history::link_device(app, state, flash_id, &record.serial_number);
+    }
+    update_assets(app, state, |assets| {
+        let batch = assets.batches.entry(record.batch_id.clone()).or_default();
+        // Re-capturing the same unit replaces its previous record
+        batch.retain(|r| r.serial_number != record.serial_number);
+        batch.push(record.clone());
+    })
+}
+
+// After a successful flash: capture the unit into the active batch over SSH. Runs after the first boot
+// checks, which the job needs so the board is known to be up; a failed capture is logged and does not fail the job.
+pub async fn capture_flashed(app: &impl JobHost, state: &AppState, flash_id: &str, command: &FlashCommand) {
+    let Some(batch_id) = state.asset_batches.lock().unwrap().active_batch.clone() else {
+        return;
+    };
+    let target = command.boot_check.as_ref().and_then(|options| crate::boot_check::ssh_target(state, flash_id, options));
+    let Some(target) = &target else {
+        warn!("Asset batch {} is active, but flash {} has no first boot check over SSH to capture the unit with", batch_id, flash_id);
+        return;
+    };
+    let captured = match read_device_identity(target, &batch_id).await {
+        Ok(record) => add_record(app, state, &record, target, Some(flash_id)),
+        Err(e) => Err(e),
+    };
+    match captured {
+        Ok(()) => info!("Captured the unit of flash {} into asset batch {}", flash_id, batch_id),
+        Err(e) => warn!("Failed to capture the unit of flash {} into asset batch {}: {:#}", flash_id, batch_id, e),
+    }
+}
+
+// Capture the identity of a provisioned device and add it to a batch,
+// optionally linking it to the flash job that produced it
+#[command]
+pub async fn capture_asset_record(
+    batch_id: String,
+    target: SshTarget,
+    flash_id: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<AssetRecord, String> {
+    info!("Capturing asset record for {} (batch {})", target.host, batch_id);
+
+    let record = read_device_identity(&target, &batch_id)
+        .await
+        .map_err(|e| format!("Failed to capture asset record: {}", e))?;
+
+    add_record(&app, &state, &record, &target, flash_id.as_deref())
+        .map_err(|e| format!("Failed to save asset record: {}", e))?;
+    Ok(record)
+}
+
+// Capture every following successful flash into a batch, or stop with None
+#[command]
+pub async fn set_active_asset_batch(
+    batch_id: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    let batch_id = batch_id.filter(|id| !id.trim().is_empty());
+    info!("Active asset batch: {:?}", batch_id);
+    update_assets(&app, &state, |assets| assets.active_batch = batch_id).map_err(|e| e.to_string())
+}
+
+#[command]
+pub async fn get_active_asset_batch(state: State<'_, Arc<AppState>>) -> Result<Option<String>, String> {
+    Ok(state.asset_batches.lock().unwrap().active_batch.clone())
+}
+
+// Get all asset records captured for a batch
+#[command]
+pub async fn get_asset_batch(batch_id: String, state: State<'_, Arc<AppState>>) -> Result<Vec<AssetRecord>, String> {
+    let assets = state.asset_batches.lock().unwrap();
+    Ok(assets.batches.get(&batch_id).cloned().unwrap_or_default())
+}
+
+// Export a batch as CSV or JSON, returns the number of records written
+#[command]
+pub async fn export_asset_batch(
+    batch_id: String,
+    path: String,
+    format: String,
+    state: State<'_, Arc<AppState>>,
+) -> Result<usize, String> {
+    let records = {
+        let assets = state.asset_batches.lock().unwrap();
+        assets.batches.get(&batch_id).cloned().unwrap_or_default()
+    };
+
+    if records.is_empty() {
+        return Err(format!("Batch {} has no asset records", batch_id));
+    }
+
+    match format.as_str() {
+        "csv" => write_csv(&records, &path),
+        "json" => write_json(&records, &path),
+        other => return Err(format!("Unsupported export format: {}", other)),
+    }
+    .map_err(|e| format!("Failed to export asset batch: {}", e))?;
+
+    info!("Exported {} asset records for batch {} to {}", records.len(), batch_id, path);
+    Ok(records.len())
+}
+
+fn write_csv(records: &[AssetRecord], path: &str) -> Result<()> {
+    let mut writer = csv::Writer::from_path(path).context("Failed to create CSV file")?;
+    writer.write_record(["batch_id", "serial_number", "hostname", "model", "l4t_version", "mac_addresses", "captured_at"])?;
+
+    for record in records {
+        let macs = record
+            .mac_addresses
+            .iter()
+            .map(|m| format!("{}={}", m.interface, m.mac))
+            .collect::<Vec<_>>()
+            .join(";");
+
+        writer.write_record([
+            record.batch_id.as_str(),
+            record.serial_number.as_str(),
+            record.hostname.as_str(),
+            record.model.as_deref().unwrap_or(""),
+            record.l4t_version.as_deref().unwrap_or(""),
+            macs.as_str(),
+            record.captured_at.to_rfc3339().as_str(),
+        ])?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_json(records: &[AssetRecord], path: &str) -> Result<()> {
+    let json = serde_json::to_string_pretty(records)?;
+    std::fs::write(path, json).context("Failed to write JSON file")?;
+    Ok(())
+}
@@ -0,0 +1,177 @@
+// CFU - Cordatus Flash Utility - App Data Export
+// Export and import of settings, profiles, presets, kernel sources and history as one file, to replicate or migrate a station,
+// optionally encrypted with a passphrase since it holds network credentials and device identities
+
+use crate::containers::{self, ContainerPreset};
+use crate::history::{self, HistoryDb};
+use crate::kernel::{self, KernelSourceTree};
+use crate::profiles::{self, FlashProfile};
+use crate::settings::{self, AppSettings};
+use crate::AppState;
+use age::secrecy::SecretString;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::sync::Arc;
+use tauri::{command, State};
+
+// Bumped when the bundle changes incompatibly; older bundles still import
+const APP_DATA_SCHEMA: u32 = 1;
+
+// Start of every age file; anything else is read as plain JSON
+const AGE_HEADER: &[u8] = b"age-encryption.org/v1";
+
+// Scheduled jobs and the version matrix cache are left out: pending runs belong
+// to the old station and the matrix is re-fetched. Attachments stay referenced
+// by their original paths.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppDataBundle {
+    pub schema_version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub exported_from: Option<String>, // Station name
+    pub settings: AppSettings,
+    #[serde(default)]
+    pub profiles: Vec<FlashProfile>,
+    #[serde(default)]
+    pub container_presets: Vec<ContainerPreset>,
+    #[serde(default)]
+    pub kernel_sources: Vec<KernelSourceTree>,
+    #[serde(default)]
+    pub history: HistoryDb,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AppDataSummary {
+    pub profiles: usize,
+    pub container_presets: usize,
+    pub kernel_sources: usize,
+    pub jobs: usize,
+    pub devices: usize,
+}
+
+impl AppDataBundle {
+    fn summary(&self) -> AppDataSummary {
+        AppDataSummary {
+            profiles: self.profiles.len(),
+            container_presets: self.container_presets.len(),
+            kernel_sources: self.kernel_sources.len(),
+            jobs: self.history.jobs.len(),
+            devices: self.history.devices.len(),
+        }
+    }
+}
+
+pub fn collect_bundle(app: &tauri::AppHandle, state: &AppState) -> AppDataBundle {
+    AppDataBundle {
+        schema_version: APP_DATA_SCHEMA,
+        exported_at: Utc::now(),
+        exported_from: history::station_name(state),
+        settings: state.settings.lock().unwrap().clone(),
+        profiles: profiles::load_profiles(app),
+        container_presets: containers::load_presets(app),
+        kernel_sources: kernel::load_sources(app),
+        history: state.history.lock().unwrap().clone(),
+    }
+}
+
+// Replace this station's data with the bundle's, on disk and in memory
+pub fn apply_bundle(app: &tauri::AppHandle, state: &AppState, bundle: AppDataBundle) -> Result<()> {
+    crate::freeze::ensure_not_frozen(app, "Imports of app data").map_err(anyhow::Error::msg)?;
+    if bundle.schema_version > APP_DATA_SCHEMA {
+        return Err(anyhow::anyhow!(
+            "Export was made by a newer version (schema {}, supported {})",
+            bundle.schema_version,
+            APP_DATA_SCHEMA
+        ));
+    }
+    if !state.active_flashes.lock().unwrap().is_empty() {
+        return Err(anyhow::anyhow!("Cannot import while a flash is running"));
+    }
+
+    settings::save_settings(app, &bundle.settings)?;
+    profiles::save_profiles(app, &bundle.profiles)?;
+    containers::save_presets(app, &bundle.container_presets)?;
+    kernel::save_sources(app, &bundle.kernel_sources)?;
+    history::save_history(app, &bundle.history)?;
+
+    *state.settings.lock().unwrap() = bundle.settings;
+    *state.history.lock().unwrap() = bundle.history;
+    Ok(())
+}
+
+// age with a scrypt-derived key; the payload is ChaCha20-Poly1305, so tampering fails decryption
+fn encrypt(plaintext: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    let encryptor = age::Encryptor::with_user_passphrase(SecretString::from(passphrase.to_string()));
+    let mut output = Vec::new();
+    let mut writer = encryptor.wrap_output(&mut output)?;
+    writer.write_all(plaintext)?;
+    writer.finish()?;
+    Ok(output)
+}
+
+fn decrypt(ciphertext: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    let decryptor = age::Decryptor::new(ciphertext).context("Not a valid encrypted export")?;
+    let identity = age::scrypt::Identity::new(SecretString::from(passphrase.to_string()));
+    let mut reader = decryptor
+        .decrypt(std::iter::once(&identity as &dyn age::Identity))
+        .map_err(|_| anyhow::anyhow!("Wrong passphrase or corrupted export"))?;
+    let mut plaintext = Vec::new();
+    reader
+        .read_to_end(&mut plaintext)
+        .map_err(|_| anyhow::anyhow!("Export failed its integrity check"))?;
+    Ok(plaintext)
+}
+
+fn read_bundle(path: &str, passphrase: Option<&str>) -> Result<AppDataBundle> {
+    let content = std::fs::read(path).with_context(|| format!("Failed to read {}", path))?;
+    let json = if content.starts_with(AGE_HEADER) {
+        let passphrase = passphrase
+            .filter(|p| !p.is_empty())
+            .context("This export is encrypted; a passphrase is required")?;
+        decrypt(&content, passphrase)?
+    } else {
+        content
+    };
+    serde_json::from_slice(&json).context("Not a CFU app data export")
+}
+
+// Write all application data to a single JSON file, encrypted when a passphrase is given
+#[command]
+pub async fn export_app_data(
+    path: String,
+    passphrase: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<AppDataSummary, String> {
+    let bundle = collect_bundle(&app, &state);
+    let json = serde_json::to_vec_pretty(&bundle).map_err(|e| e.to_string())?;
+    let content = match passphrase.as_deref().filter(|p| !p.is_empty()) {
+        Some(passphrase) => encrypt(&json, passphrase).map_err(|e| format!("Failed to encrypt export: {}", e))?,
+        None => json,
+    };
+    std::fs::write(&path, content).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+
+    let summary = bundle.summary();
+    info!("Exported app data to {}: {} profiles, {} jobs", path, summary.profiles, summary.jobs);
+    Ok(summary)
+}
+
+// Replace all application data with an exported file; encrypted exports need their passphrase.
+// A frozen station refuses before anything is decrypted.
+#[command]
+pub async fn import_app_data(
+    path: String,
+    passphrase: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<AppDataSummary, String> {
+    crate::freeze::ensure_not_frozen(&app, "Imports of app data")?;
+    let bundle = read_bundle(&path, passphrase.as_deref()).map_err(|e| e.to_string())?;
+
+    let summary = bundle.summary();
+    apply_bundle(&app, &state, bundle).map_err(|e| e.to_string())?;
+    info!("Imported app data from {}: {} profiles, {} jobs", path, summary.profiles, summary.jobs);
+    Ok(summary)
+}
@@ -0,0 +1,144 @@
+// CFU - Cordatus Flash Utility - Batch Variables
+// Template variables ({{ serial }}, {{ slot }}, {{ index }}, {{ operator }} and a batch's own) in profiles and
+// device specs, plus per-batch override files, resolved when a job is created so one spec or profile can
+// provision a batch of differently configured units. In YAML, quote values that start with a variable.
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+// What the job creator knows about the unit being flashed
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JobVariables {
+    pub serial: Option<String>,   // Board serial number
+    pub slot: Option<String>,     // Fixture slot or USB port the unit sits in
+    pub index: Option<u32>,       // Position of the unit in its batch
+    pub operator: Option<String>,
+    pub batch_file: Option<String>, // Override file of the batch, see BatchFile
+}
+
+// A batch override file (YAML or JSON): variables and overrides for every unit, then per unit
+// matched by serial, slot or index. Overrides are merged into the spec or the profile's flash
+// command: objects key by key, anything else replaced.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BatchFile {
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+    #[serde(default)]
+    pub overrides: Value,
+    #[serde(default)]
+    pub units: Vec<BatchUnit>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BatchUnit {
+    #[serde(rename = "match")]
+    pub selector: UnitSelector,
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+    #[serde(default)]
+    pub overrides: Value,
+}
+
+// Every field set must equal the job's
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct UnitSelector {
+    pub serial: Option<String>,
+    pub slot: Option<String>,
+    pub index: Option<u32>,
+}
+
+impl UnitSelector {
+    fn matches(&self, vars: &JobVariables) -> bool {
+        (self.serial.is_some() || self.slot.is_some() || self.index.is_some())
+            && (self.serial.is_none() || self.serial == vars.serial)
+            && (self.slot.is_none() || self.slot == vars.slot)
+            && (self.index.is_none() || self.index == vars.index)
+    }
+}
+
+pub fn load_batch_file(path: &str) -> Result<BatchFile> {
+    let content = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?;
+    serde_yaml::from_str(&content).with_context(|| format!("Invalid batch file {}", path))
+}
+
+// Recursive merge; a null override leaves the value as it is
+fn merge(base: &mut Value, overrides: &Value) {
+    match (base, overrides) {
+        (_, Value::Null) => {}
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                merge(base.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+        (base, overrides) => *base = overrides.clone(),
+    }
+}
+
+// Only plain names are variables, so other uses of braces such as docker's --format '{{.Names}}' stay as they are
+fn variable_pattern() -> Option<&'static Regex> {
+    static PATTERN: OnceLock<Option<Regex>> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"\{\{\s*([A-Za-z_][A-Za-z0-9_]*)\s*\}\}").ok()).as_ref()
+}
+
+fn render(text: &str, variables: &BTreeMap<String, String>, errors: &mut Vec<String>) -> String {
+    let Some(pattern) = variable_pattern() else {
+        return text.to_string();
+    };
+    pattern
+        .replace_all(text, |caps: &regex::Captures| match variables.get(&caps[1]) {
+            Some(value) => value.clone(),
+            None => {
+                errors.push(format!("Variable {} is not set for this unit", &caps[1]));
+                caps[0].to_string()
+            }
+        })
+        .into_owned()
+}
+
+fn render_value(value: &mut Value, variables: &BTreeMap<String, String>, errors: &mut Vec<String>) {
+    match value {
+        Value::String(text) => *text = render(text, variables, errors),
+        Value::Array(items) => items.iter_mut().for_each(|item| render_value(item, variables, errors)),
+        Value::Object(fields) => fields.values_mut().for_each(|field| render_value(field, variables, errors)),
+        _ => {}
+    }
+}
+
+// Apply a batch's overrides for the unit and fill in the variables; returns the variables used.
+// The job's own variables win over the unit's, which win over the batch's.
+pub fn resolve(value: &mut Value, vars: &JobVariables) -> Result<BTreeMap<String, String>> {
+    let batch = match &vars.batch_file {
+        Some(path) => load_batch_file(path)?,
+        None => BatchFile::default(),
+    };
+    let mut variables = batch.variables.clone();
+    merge(value, &batch.overrides);
+    for unit in batch.units.iter().filter(|unit| unit.selector.matches(vars)) {
+        variables.extend(unit.variables.clone());
+        merge(value, &unit.overrides);
+    }
+    let own = [
+        ("serial", vars.serial.clone()),
+        ("slot", vars.slot.clone()),
+        ("index", vars.index.map(|index| index.to_string())),
+        ("operator", vars.operator.clone()),
+    ];
+    variables.extend(own.into_iter().filter_map(|(name, value)| Some((name.to_string(), value?))));
+
+    let mut errors = Vec::new();
+    render_value(value, &variables, &mut errors);
+    errors.sort();
+    errors.dedup();
+    if !errors.is_empty() {
+        return Err(anyhow::anyhow!(errors.join("; ")));
+    }
+    Ok(variables)
+}
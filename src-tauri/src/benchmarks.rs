@@ -0,0 +1,218 @@
+// CFU - Cordatus Flash Utility - Benchmarks
+// trtexec / jetson_benchmarks runs on provisioned devices, recorded per serial to catch underperforming units
+
+use crate::asset::read_device_identity;
+use crate::history;
+use crate::ssh::{run_remote, shell_quote, SshTarget, SUDO};
+use crate::AppState;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tauri::{command, State};
+use uuid::Uuid;
+
+// ResNet-50 ships with the TensorRT samples on every JetPack image
+pub const REFERENCE_ONNX_MODEL: &str = "/usr/src/tensorrt/data/resnet50/ResNet50.onnx";
+pub const TRTEXEC: &str = "/usr/src/tensorrt/bin/trtexec";
+const JETSON_BENCHMARKS_DIR: &str = "~/jetson_benchmarks";
+
+// Below this fraction of the fleet median a unit is flagged
+const UNDERPERFORMING_RATIO: f64 = 0.9;
+const MIN_FLEET_SAMPLES: usize = 3;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkMetric {
+    pub name: String, // e.g. "throughput", "latency_mean", "resnet50"
+    pub value: f64,
+    pub unit: String, // 'qps' | 'ms' | 'fps'
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkResult {
+    pub benchmark_id: String,
+    pub serial_number: String,
+    pub device_model: Option<String>,
+    pub l4t_version: Option<String>,
+    pub suite: String, // 'trtexec' | 'jetson_benchmarks'
+    pub model: String,
+    pub metrics: Vec<BenchmarkMetric>,
+    pub underperforming: bool,
+    pub ran_at: DateTime<Utc>,
+}
+
+impl BenchmarkResult {
+    // The metric used for fleet comparison: higher is better
+    pub fn headline(&self) -> Option<f64> {
+        self.metrics
+            .iter()
+            .find(|m| m.unit == "qps" || m.unit == "fps")
+            .map(|m| m.value)
+    }
+}
+
+fn metric(name: &str, value: f64, unit: &str) -> BenchmarkMetric {
+    BenchmarkMetric {
+        name: name.to_string(),
+        value,
+        unit: unit.to_string(),
+    }
+}
+
+// "Throughput: 1123.45 qps" and "Latency: min = ..., mean = 2.1 ms, ..., percentile(99%) = 3.4 ms"
+fn parse_trtexec(output: &str) -> Vec<BenchmarkMetric> {
+    let mut metrics = Vec::new();
+    let patterns = [
+        (r"Throughput:\s*([\d.]+)\s*qps", "throughput", "qps"),
+        (r"Latency:.*?mean = ([\d.]+) ms", "latency_mean", "ms"),
+        (r"Latency:.*?percentile\(99%\) = ([\d.]+) ms", "latency_p99", "ms"),
+    ];
+    for (pattern, name, unit) in patterns {
+        let value = Regex::new(pattern)
+            .ok()
+            .and_then(|re| re.captures(output))
+            .and_then(|caps| caps[1].parse().ok());
+        if let Some(value) = value {
+            metrics.push(metric(name, value, unit));
+        }
+    }
+    metrics
+}
+
+// jetson_benchmarks ends with a "Model Name  FPS" table
+fn parse_jetson_benchmarks(output: &str) -> Vec<BenchmarkMetric> {
+    let Ok(row) = Regex::new(r"^\s*\d*\s+([A-Za-z][\w\-.]*)\s+([\d.]+)\s*$") else {
+        return Vec::new();
+    };
+    output
+        .lines()
+        .skip_while(|line| !line.contains("Model Name"))
+        .filter_map(|line| row.captures(line))
+        .filter_map(|caps| Some(metric(&caps[1], caps[2].parse().ok()?, "fps")))
+        .collect()
+}
+
+// jetson_benchmarks keeps one model list per module family
+fn benchmark_csv(device_model: Option<&str>) -> &'static str {
+    let model = device_model.unwrap_or_default();
+    if model.contains("Orin") {
+        "orin-benchmarks.csv"
+    } else if model.contains("Xavier NX") {
+        "xavier-nx-benchmarks.csv"
+    } else if model.contains("Xavier") {
+        "xavier-benchmarks.csv"
+    } else {
+        "nano-benchmarks.csv"
+    }
+}
+
+async fn run_suite(target: &SshTarget, suite: &str, model: &str, device_model: Option<&str>) -> Result<Vec<BenchmarkMetric>> {
+    let metrics = match suite {
+        "trtexec" => {
+            let script = format!(
+                "{}$S {} --onnx={} --fp16 --duration=30 2>&1",
+                SUDO,
+                TRTEXEC,
+                shell_quote(model)
+            );
+            parse_trtexec(&run_remote(target, &script).await?)
+        }
+        "jetson_benchmarks" => {
+            // The repository and its models are prepared on the device beforehand
+            let script = format!(
+                "{}cd {dir} && $S python3 benchmark.py --model_name {model} --csv_file_path benchmark_csv/{csv} --model_dir {dir}/models 2>&1",
+                SUDO,
+                dir = JETSON_BENCHMARKS_DIR,
+                model = shell_quote(model),
+                csv = benchmark_csv(device_model)
+            );
+            parse_jetson_benchmarks(&run_remote(target, &script).await?)
+        }
+        other => return Err(anyhow::anyhow!("Unknown benchmark suite: {}", other)),
+    };
+    if metrics.is_empty() {
+        return Err(anyhow::anyhow!("{} produced no results", suite));
+    }
+    Ok(metrics)
+}
+
+// Compare against earlier runs of the same benchmark on the same module type
+fn is_underperforming(result: &BenchmarkResult, previous: &[BenchmarkResult]) -> bool {
+    let Some(value) = result.headline() else {
+        return false;
+    };
+    let mut peers: Vec<f64> = previous
+        .iter()
+        .filter(|p| p.suite == result.suite && p.model == result.model && p.device_model == result.device_model)
+        .filter_map(BenchmarkResult::headline)
+        .collect();
+    if peers.len() < MIN_FLEET_SAMPLES {
+        return false;
+    }
+    peers.sort_by(|a, b| a.total_cmp(b));
+    let median = peers[peers.len() / 2];
+    value < median * UNDERPERFORMING_RATIO
+}
+
+// Run a benchmark on a booted device and record the result under its serial number
+#[command]
+pub async fn run_benchmark(
+    target: SshTarget,
+    suite: String,
+    model: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<BenchmarkResult, String> {
+    let identity = read_device_identity(&target, "")
+        .await
+        .map_err(|e| format!("Failed to identify device: {}", e))?;
+    let model = model.unwrap_or_else(|| match suite.as_str() {
+        "trtexec" => REFERENCE_ONNX_MODEL.to_string(),
+        _ => "all".to_string(),
+    });
+
+    info!("Running {} benchmark ({}) on {}", suite, model, identity.serial_number);
+    let metrics = run_suite(&target, &suite, &model, identity.model.as_deref())
+        .await
+        .map_err(|e| format!("Benchmark failed: {}", e))?;
+
+    let mut result = BenchmarkResult {
+        benchmark_id: Uuid::new_v4().to_string(),
+        serial_number: identity.serial_number,
+        device_model: identity.model,
+        l4t_version: identity.l4t_version,
+        suite,
+        model,
+        metrics,
+        underperforming: false,
+        ran_at: Utc::now(),
+    };
+
+    let previous = state.history.lock().unwrap().benchmarks.clone();
+    result.underperforming = is_underperforming(&result, &previous);
+    if result.underperforming {
+        warn!("Device {} is below the fleet median for {}", result.serial_number, result.model);
+    }
+
+    let record = result.clone();
+    history::update_history(&app, &state, |history| history.benchmarks.push(record));
+    Ok(result)
+}
+
+// All recorded benchmark results for a device, newest first
+#[command]
+pub async fn get_device_benchmarks(
+    serial_number: String,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<BenchmarkResult>, String> {
+    let history = state.history.lock().unwrap();
+    Ok(history
+        .benchmarks
+        .iter()
+        .rev()
+        .filter(|b| b.serial_number == serial_number)
+        .cloned()
+        .collect())
+}
@@ -0,0 +1,6 @@
+// CFU - Cordatus Flash Utility - Headless CLI
+// `cfu-cli flash` / `cfu-cli ci` / `cfu-cli validate-spec` for factory provisioning and CI rigs, without starting the Tauri GUI
+
+fn main() {
+    std::process::exit(cfu_core::cli::main());
+}
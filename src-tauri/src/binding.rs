@@ -0,0 +1,95 @@
+// CFU - Cordatus Flash Utility - Device Binding
+// Ties a job to one board by USB serial number and/or physical port, so in a multi-device setup a job
+// never flashes another unit that happens to be in recovery mode with the wrong profile
+
+use crate::job_host::JobHost;
+use crate::{AppState, JetsonDevice};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceBinding {
+    #[serde(default)]
+    pub serial_number: Option<String>, // USB serial the board reports in recovery mode
+    #[serde(default)]
+    pub port_path: Option<String>, // Physical port, e.g. "1-2.3"
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BindingMismatch {
+    pub flash_id: String,
+    pub expected: DeviceBinding,
+    pub found: Option<DeviceBinding>, // The board on the bound port, if any
+    pub reason: String,
+}
+
+impl DeviceBinding {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.serial_number.is_none() && self.port_path.is_none() {
+            return Err("A device binding needs a serial number, a port or both".to_string());
+        }
+        Ok(())
+    }
+
+    // The board this binding points at: the one on the bound port, or the one with the bound serial
+    pub fn matches_location(&self, device: &JetsonDevice) -> bool {
+        let usb = found(device);
+        match &self.port_path {
+            Some(port) => usb.port_path.as_ref() == Some(port),
+            None => usb.serial_number == self.serial_number,
+        }
+    }
+}
+
+fn found(device: &JetsonDevice) -> DeviceBinding {
+    let usb = device.usb_info.as_ref();
+    DeviceBinding {
+        serial_number: usb.and_then(|info| info.serial_number.clone()),
+        port_path: usb.and_then(|info| info.port_path.clone()),
+    }
+}
+
+// The bound board among the connected ones, or why it cannot be flashed
+fn check<'a>(binding: &DeviceBinding, devices: &'a [JetsonDevice]) -> Result<&'a JetsonDevice, (String, Option<DeviceBinding>)> {
+    let Some(device) = devices.iter().find(|device| binding.matches_location(device)) else {
+        let reason = match (&binding.port_path, &binding.serial_number) {
+            (Some(port), _) => format!("No board in recovery mode on port {}", port),
+            (None, Some(serial)) => format!("No board with serial {} is in recovery mode", serial),
+            (None, None) => "Empty device binding".to_string(),
+        };
+        return Err((reason, None));
+    };
+    let actual = found(device);
+    if let Some(serial) = &binding.serial_number {
+        if actual.serial_number.as_ref() != Some(serial) {
+            let reason = format!(
+                "The board on port {} reports serial {}, the job is bound to {}",
+                actual.port_path.as_deref().unwrap_or("unknown"),
+                actual.serial_number.as_deref().unwrap_or("none"),
+                serial
+            );
+            return Err((reason, Some(actual)));
+        }
+    }
+    Ok(device)
+}
+
+// Refuse to continue unless the bound board is the one connected; a different board on the bound
+// port is reported to the UI as device-binding-mismatch
+pub async fn enforce(app: &impl JobHost, state: &AppState, flash_id: &str, binding: &DeviceBinding) -> anyhow::Result<()> {
+    let devices = crate::scan_usb_devices(state).await.map_err(|e| anyhow::anyhow!(e))?;
+    let (reason, found) = match check(binding, &devices) {
+        Ok(_) => return Ok(()),
+        Err(mismatch) => mismatch,
+    };
+    warn!("Flash {} refused: {}", flash_id, reason);
+    if found.is_some() {
+        let _ = app.send("device-binding-mismatch", BindingMismatch {
+            flash_id: flash_id.to_string(),
+            expected: binding.clone(),
+            found,
+            reason: reason.clone(),
+        });
+    }
+    Err(anyhow::anyhow!("Device binding: {}", reason))
+}
@@ -0,0 +1,244 @@
+// CFU - Cordatus Flash Utility - First Boot Verification
+// Optional phase after a flash: waits for the board to boot, then checks over SSH or the debug UART
+// that it runs the flashed L4T release, has the expected hostname and booted from the selected
+// storage, so "complete" means the board came up and not only that the flash script exited 0
+
+use crate::job_host::JobHost;
+use crate::settings::LabFixtureSettings;
+use crate::ssh::{run_remote, SshTarget};
+use crate::{history, lab, recovery, AppState, FlashCommand};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(600);
+const SSH_POLL_INTERVAL: Duration = Duration::from_secs(5);
+// The markers are quoted in the command so its echo on the console does not match them
+const CHECK_SCRIPT: &str = "echo CFU-''BEGIN; head -n 1 /etc/nv_tegra_release 2>/dev/null; echo ---; hostname; echo ---; \
+root=$(findmnt -n -o SOURCE /); echo \"$root\"; echo ---; lsblk -n -o PARTLABEL \"$root\" 2>/dev/null; echo ---; \
+cat /sys/block/$(lsblk -n -o PKNAME \"$root\" 2>/dev/null)/device/type 2>/dev/null; echo CFU-''END";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BootCheckOptions {
+    pub method: String, // 'ssh' | 'serial'; empty uses what is configured, SSH first
+    pub ssh: Option<SshTarget>, // Defaults to the lab fixture's SSH host
+    pub serial_port: Option<String>, // Defaults to the lab fixture's debug UART
+    pub serial_baud: Option<u32>,
+    pub hostname: Option<String>, // Expected hostname; unchecked when unset
+    pub timeout_secs: Option<u64>, // How long the board gets to boot, 10 minutes by default
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BootCheck {
+    pub name: String, // 'l4t' | 'hostname' | 'storage' | 'partition'
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+    pub passed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BootVerification {
+    pub method: String, // 'ssh' | 'serial'
+    pub checks: Vec<BootCheck>,
+    pub verified: bool,
+    pub checked_at: DateTime<Utc>,
+}
+
+enum Channel {
+    Ssh(SshTarget),
+    Serial { port: String, baud: u32, user: String },
+}
+
+fn job_fixture(state: &AppState, flash_id: &str) -> Option<LabFixtureSettings> {
+    let fixture_name = {
+        let history = state.history.lock().unwrap();
+        history.jobs.iter().rev().find(|job| job.flash_id == flash_id).and_then(|job| job.fixture.clone())
+    };
+    fixture_name.and_then(|name| lab::find_fixture(state, &name).ok())
+}
+
+// The SSH target of the flashed board: the job's options, else the lab fixture it was flashed on
+pub fn ssh_target(state: &AppState, flash_id: &str, options: &BootCheckOptions) -> Option<SshTarget> {
+    options.ssh.clone().or_else(|| {
+        let fixture = job_fixture(state, flash_id).filter(|f| !f.ssh_host.is_empty())?;
+        Some(SshTarget {
+            host: fixture.ssh_host,
+            user: fixture.ssh_user,
+            port: None,
+            identity_file: None,
+        })
+    })
+}
+
+// Where to reach the board: the job's options, else the lab fixture it was flashed on
+fn channel(state: &AppState, flash_id: &str, command: &FlashCommand, options: &BootCheckOptions) -> Result<Channel> {
+    let fixture = job_fixture(state, flash_id);
+    let ssh = ssh_target(state, flash_id, options);
+    let serial = options
+        .serial_port
+        .clone()
+        .map(|port| (port, options.serial_baud.unwrap_or(115_200)))
+        .or_else(|| {
+            let fixture = fixture.as_ref().filter(|f| !f.serial_port.is_empty())?;
+            Some((fixture.serial_port.clone(), options.serial_baud.unwrap_or(fixture.serial_baud)))
+        });
+    match (options.method.as_str(), ssh, serial) {
+        ("ssh" | "", Some(target), _) => Ok(Channel::Ssh(target)),
+        ("serial" | "", _, Some((port, baud))) => Ok(Channel::Serial {
+            port,
+            baud,
+            user: command.user_name.clone(),
+        }),
+        (method, _, _) => Err(anyhow::anyhow!(
+            "No {} connection to verify the first boot over",
+            if method.is_empty() { "SSH or serial" } else { method }
+        )),
+    }
+}
+
+async fn run_over_ssh(target: &SshTarget, timeout: Duration) -> Result<String> {
+    let started = Instant::now();
+    loop {
+        match run_remote(target, CHECK_SCRIPT).await {
+            Ok(output) => return Ok(output),
+            Err(e) if started.elapsed() >= timeout => {
+                return Err(e.context(format!("{} did not answer over SSH within {}s", target.host, timeout.as_secs())))
+            }
+            Err(_) => tokio::time::sleep(SSH_POLL_INTERVAL).await,
+        }
+    }
+}
+
+// Wait for a login or shell prompt on the console, log in and run the checks
+fn run_over_serial(port: &str, baud: u32, user: &str, password: Option<String>, timeout: Duration) -> Result<String> {
+    let mut console = recovery::open_console(port, baud)?;
+    let console = console.as_mut();
+    let started = Instant::now();
+    let output = loop {
+        let output = recovery::send_line(console, "")?;
+        let prompt = output.trim_end();
+        if prompt.ends_with("login:") || prompt.ends_with('$') || prompt.ends_with('#') {
+            break output;
+        }
+        if started.elapsed() >= timeout {
+            return Err(anyhow::anyhow!("No login prompt on {} within {}s", port, timeout.as_secs()));
+        }
+    };
+    recovery::console_login(console, &output, user, &password)?;
+    let output = recovery::send_line(console, CHECK_SCRIPT)?;
+    recovery::send_line(console, "exit")?;
+    Ok(output)
+}
+
+// Storage the root filesystem should be on, by device name and, for mmcblk, the card type
+fn storage_matches(storage: &str, root: &str, card_type: &str) -> Option<bool> {
+    match storage {
+        "NVMe SSD" => Some(root.starts_with("/dev/nvme")),
+        "Micro SD" => Some(root.starts_with("/dev/mmcblk") && card_type == "SD"),
+        "eMMC" => Some(root.starts_with("/dev/mmcblk") && card_type == "MMC"),
+        "USB" => Some(root.starts_with("/dev/sd")),
+        _ => None,
+    }
+}
+
+fn evaluate(state: &AppState, command: &FlashCommand, options: &BootCheckOptions, output: &str) -> Result<Vec<BootCheck>> {
+    let output = output.replace('\r', "");
+    let body = output
+        .split_once("CFU-BEGIN\n")
+        .and_then(|(_, rest)| rest.split_once("CFU-END"))
+        .map(|(body, _)| body)
+        .context("The board did not answer the checks")?;
+    let sections: Vec<&str> = body.split("---\n").map(str::trim).collect();
+    let section = |index: usize| sections.get(index).copied().filter(|s| !s.is_empty()).map(str::to_string);
+
+    // Compare resolved releases, as drift detection does
+    let (expected_l4t, actual_l4t) = {
+        let matrix = state.version_matrix.lock().unwrap();
+        let resolve = |version: &str| matrix.resolve(version).map(|r| r.l4t.clone());
+        let actual = section(0).as_deref().and_then(crate::parse_nv_tegra_release);
+        (
+            resolve(&command.jetpack_version),
+            actual.map(|v| resolve(&v).unwrap_or(v)),
+        )
+    };
+    let hostname = section(1);
+    let root = section(2).unwrap_or_default();
+    let partition = section(3);
+    let card_type = section(4).unwrap_or_default();
+
+    let mut checks = vec![
+        BootCheck {
+            name: "l4t".to_string(),
+            passed: actual_l4t.is_some() && (expected_l4t.is_none() || expected_l4t == actual_l4t),
+            expected: expected_l4t,
+            actual: actual_l4t,
+        },
+        BootCheck {
+            name: "hostname".to_string(),
+            passed: options.hostname.is_none() || options.hostname == hostname,
+            expected: options.hostname.clone(),
+            actual: hostname,
+        },
+        BootCheck {
+            name: "partition".to_string(),
+            passed: matches!(partition.as_deref(), Some("APP" | "APP_b")),
+            expected: Some("APP".to_string()),
+            actual: partition,
+        },
+    ];
+    if let Some(passed) = storage_matches(&command.storage_device, &root, &card_type) {
+        checks.push(BootCheck {
+            name: "storage".to_string(),
+            expected: Some(command.storage_device.clone()),
+            actual: Some(root),
+            passed,
+        });
+    }
+    Ok(checks)
+}
+
+// Run the first boot checks of a finished flash and record the result with the job
+pub async fn verify(
+    app: &impl JobHost,
+    state: &AppState,
+    flash_id: &str,
+    command: &FlashCommand,
+    options: &BootCheckOptions,
+) -> Result<BootVerification> {
+    let timeout = options.timeout_secs.map(Duration::from_secs).unwrap_or(DEFAULT_TIMEOUT);
+    let (method, output) = match channel(state, flash_id, command, options)? {
+        Channel::Ssh(target) => {
+            info!("Waiting for {} to boot, verifying over SSH at {}", flash_id, target.host);
+            ("ssh", run_over_ssh(&target, timeout).await?)
+        }
+        Channel::Serial { port, baud, user } => {
+            if state.serial_consoles.lock().unwrap().values().any(|console| console.port == port) {
+                return Err(anyhow::anyhow!("Close the serial console on {} to verify the first boot over it", port));
+            }
+            info!("Waiting for {} to boot, verifying over {}", flash_id, port);
+            let password = recovery::login_password()?;
+            let output = tokio::task::spawn_blocking(move || run_over_serial(&port, baud, &user, password, timeout)).await??;
+            ("serial", output)
+        }
+    };
+    let checks = evaluate(state, command, options, &output)?;
+    let verification = BootVerification {
+        method: method.to_string(),
+        verified: checks.iter().all(|check| check.passed),
+        checks,
+        checked_at: Utc::now(),
+    };
+    if !verification.verified {
+        for check in verification.checks.iter().filter(|check| !check.passed) {
+            warn!(
+                "First boot check {} failed for {}: expected {:?}, found {:?}",
+                check.name, flash_id, check.expected, check.actual
+            );
+        }
+    }
+    history::record_verification(app, state, flash_id, verification.clone());
+    Ok(verification)
+}
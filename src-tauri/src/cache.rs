@@ -0,0 +1,42 @@
+// CFU - Cordatus Flash Utility - Command Cache
+// Short-lived response caching for expensive commands, so a UI that polls too eagerly does not rescan USB or the host each time
+
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+#[derive(Debug)]
+pub struct TtlCache<T> {
+    entry: Mutex<Option<(Instant, T)>>,
+}
+
+impl<T> Default for TtlCache<T> {
+    fn default() -> Self {
+        Self { entry: Mutex::new(None) }
+    }
+}
+
+impl<T: Clone> TtlCache<T> {
+    // Return the cached value if younger than `ttl`, otherwise refresh it. Callers
+    // arriving during a refresh wait for it instead of starting their own.
+    pub async fn get_or_refresh<E, F, Fut>(&self, ttl: Duration, refresh: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut entry = self.entry.lock().await;
+        if let Some((fetched_at, value)) = entry.as_ref() {
+            if fetched_at.elapsed() < ttl {
+                return Ok(value.clone());
+            }
+        }
+        let value = refresh().await?;
+        *entry = Some((Instant::now(), value.clone()));
+        Ok(value)
+    }
+
+    // Drop the cached value so the next call refreshes, e.g. after the underlying state changed
+    pub async fn invalidate(&self) {
+        *self.entry.lock().await = None;
+    }
+}
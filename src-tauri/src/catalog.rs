@@ -0,0 +1,239 @@
+// CFU - Cordatus Flash Utility - Image Catalog
+// Prebuilt Cordatus and partner images per module, from the bundled catalog and a configurable feed.
+// An image is downloaded like a one-file release (mirrors, resume, checksum) and written to the
+// confirmed SD card or USB drive, as an alternative to assembling a stock BSP. Feeds can be required
+// to carry an ed25519 signature, which catalog_publish adds when a team publishes its own images.
+
+use crate::downloads::{self, ManifestFile, ReleaseManifest};
+use crate::{checksum, image, AppState};
+use anyhow::{Context, Result};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{command, State};
+
+const BUNDLED_CATALOG: &str = include_str!("../../data/image_catalog.json");
+const CACHE_FILE: &str = "image_catalog.json";
+const FETCH_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImageCatalog {
+    pub schema: u32,
+    pub images: Vec<CatalogImage>,
+    #[serde(default)]
+    pub signature: Option<String>, // Hex ed25519 signature of the images, see signed_bytes
+}
+
+// A raw disk image; compressed images are not supported by the writer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogImage {
+    pub id: String,
+    pub name: String,
+    pub publisher: String, // e.g. "Cordatus" or the partner's name
+    #[serde(default)]
+    pub description: String,
+    pub modules: Vec<String>, // Module names as in the device matrix, e.g. "Orin Nano"
+    #[serde(default)]
+    pub jetpack_version: Option<String>,
+    pub url: String,
+    #[serde(default)]
+    pub size: Option<u64>,
+    pub sha256: String,
+}
+
+impl ImageCatalog {
+    fn bundled() -> Self {
+        serde_json::from_str(BUNDLED_CATALOG).unwrap_or_default()
+    }
+
+    // What the signature covers: the images as compact JSON, in the order of the fields above
+    pub fn signed_bytes(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(&self.images)?)
+    }
+
+    pub fn verify(&self, public_key: &str) -> Result<()> {
+        let key: [u8; 32] = from_hex(public_key)?
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("The catalog public key must be 32 bytes"))?;
+        let key = VerifyingKey::from_bytes(&key).context("Invalid catalog public key")?;
+        let signature: [u8; 64] = from_hex(self.signature.as_deref().context("The image catalog is not signed")?)?
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("The catalog signature must be 64 bytes"))?;
+        key.verify(&self.signed_bytes()?, &Signature::from_bytes(&signature))
+            .context("The image catalog signature does not match")
+    }
+
+    // Images of `other` replace ours with the same ID
+    pub fn merge(mut self, other: ImageCatalog) -> Self {
+        self.images.retain(|image| !other.images.iter().any(|o| o.id == image.id));
+        self.images.extend(other.images);
+        self
+    }
+}
+
+pub fn from_hex(hex: &str) -> Result<Vec<u8>> {
+    let hex = hex.trim();
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return Err(anyhow::anyhow!("Invalid hex string"));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).context("Invalid hex string"))
+        .collect()
+}
+
+impl CatalogImage {
+    pub fn file_name(&self) -> String {
+        let name = self.url.rsplit('/').next().unwrap_or_default();
+        if name.is_empty() {
+            format!("{}.img", self.id)
+        } else {
+            name.to_string()
+        }
+    }
+
+    fn manifest(&self) -> ReleaseManifest {
+        ReleaseManifest {
+            schema: downloads::MANIFEST_SCHEMA,
+            release: self.id.clone(),
+            files: vec![ManifestFile {
+                url: self.url.clone(),
+                size: self.size,
+                sha256: Some(self.sha256.clone()),
+                destination: self.file_name(),
+                ipfs_cid: None,
+                magnet: None,
+            }],
+        }
+    }
+}
+
+pub async fn fetch(url: &str) -> Result<ImageCatalog> {
+    reqwest::Client::new()
+        .get(url)
+        .timeout(FETCH_TIMEOUT)
+        .send()
+        .await
+        .context("Failed to download image catalog")?
+        .error_for_status()?
+        .json()
+        .await
+        .context("Invalid image catalog")
+}
+
+// The bundled catalog with the feed merged in; an unreachable feed falls back to the last one fetched
+async fn load(app: &tauri::AppHandle, state: &AppState) -> Result<ImageCatalog> {
+    let path = crate::app_data_file(app, CACHE_FILE)?;
+    let (url, public_key) = {
+        let settings = state.settings.lock().unwrap();
+        (settings.image_catalog_url.clone(), settings.image_catalog_public_key.clone())
+    };
+    if url.is_empty() {
+        return Ok(ImageCatalog::bundled());
+    }
+    let trusted = |feed: &ImageCatalog| public_key.is_empty() || feed.verify(&public_key).is_ok();
+    let fetched = fetch(&url).await.and_then(|feed| {
+        if !public_key.is_empty() {
+            feed.verify(&public_key)?;
+        }
+        Ok(feed)
+    });
+    let feed = match fetched {
+        Ok(feed) => {
+            std::fs::write(&path, serde_json::to_string_pretty(&feed)?).context("Failed to cache image catalog")?;
+            Some(feed)
+        }
+        Err(e) => {
+            warn!("Using cached image catalog: {:#}", e);
+            std::fs::read_to_string(&path)
+                .ok()
+                .and_then(|content| serde_json::from_str(&content).ok())
+                .filter(trusted)
+        }
+    };
+    Ok(match feed {
+        Some(feed) => ImageCatalog::bundled().merge(feed),
+        None => ImageCatalog::bundled(),
+    })
+}
+
+async fn find(app: &tauri::AppHandle, state: &AppState, id: &str) -> Result<CatalogImage> {
+    load(app, state)
+        .await?
+        .images
+        .into_iter()
+        .find(|image| image.id == id)
+        .with_context(|| format!("No image {} in the catalog", id))
+}
+
+// Download an image into app_data/images/<id>, unless a verified copy is already there
+async fn fetch_image(app: &tauri::AppHandle, image: &CatalogImage) -> Result<PathBuf> {
+    let directory = crate::app_data_file(app, "images")?.join(downloads::release_dir_name(&image.id));
+    let path = directory.join(image.file_name());
+    if path.is_file() {
+        let existing = path.clone();
+        let sha256 = tokio::task::spawn_blocking(move || checksum::sha256_file(&existing)).await??;
+        if sha256.eq_ignore_ascii_case(&image.sha256) {
+            return Ok(path);
+        }
+        warn!("{} does not match the catalog checksum, downloading it again", path.display());
+        std::fs::remove_file(&path).ok();
+    }
+    info!("Downloading catalog image {} from {}", image.id, image.url);
+    downloads::fetch_release(app, &image.manifest(), &directory).await?;
+    Ok(path)
+}
+
+// Images in the catalog, only those for `module` when given
+#[command]
+pub async fn get_image_catalog(
+    module: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<CatalogImage>, String> {
+    let catalog = load(&app, &state).await.map_err(|e| e.to_string())?;
+    Ok(catalog
+        .images
+        .into_iter()
+        .filter(|image| module.as_ref().is_none_or(|module| image.modules.contains(module)))
+        .collect())
+}
+
+// Download and verify a catalog image, returning its path
+#[command]
+pub async fn download_catalog_image(
+    id: String,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<String, String> {
+    let image = find(&app, &state, &id).await.map_err(|e| e.to_string())?;
+    let path = fetch_image(&app, &image).await.map_err(|e| format!("Failed to download image {}: {:#}", id, e))?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+// Download a catalog image if needed and write it to a device confirmed with describe_write_target,
+// emitting release-download-progress and then image-progress
+#[command]
+pub async fn flash_catalog_image(
+    id: String,
+    device: String,
+    confirmation: String,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<image::ImageTransfer, String> {
+    let catalog_image = find(&app, &state, &id).await.map_err(|e| e.to_string())?;
+    let path = fetch_image(&app, &catalog_image)
+        .await
+        .map_err(|e| format!("Failed to download image {}: {:#}", id, e))?;
+    let denied = state.settings.lock().unwrap().denied_block_devices.clone();
+    info!("Writing catalog image {} to {}", id, device);
+    tokio::task::spawn_blocking(move || {
+        image::write_image_blocking(&app, &path.to_string_lossy(), &device, &confirmation, &denied, false)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
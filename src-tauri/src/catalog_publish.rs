@@ -0,0 +1,209 @@
+// CFU - Cordatus Flash Utility - Catalog Publishing
+// Publishes a team's own built or golden image to a shared catalog: the image is uploaded next to the
+// catalog (S3 bucket or HTTP server), its entry is merged into <prefix>/catalog.json and the catalog is
+// signed, so every station reading that feed can flash it
+
+use crate::catalog::{self, CatalogImage, ImageCatalog};
+use crate::settings::CatalogPublishSettings;
+use crate::{checksum, credentials, uploads, AppState};
+use anyhow::{Context, Result};
+use ed25519_dalek::{Signer, SigningKey};
+use log::info;
+use s3::error::S3Error;
+use s3::Bucket;
+use serde::Deserialize;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tauri::{command, State};
+
+const SIGNING_ACCOUNT: &str = "catalog_signing";
+const HTTP_ACCOUNT: &str = "catalog_publish";
+const CATALOG_FILE: &str = "catalog.json";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PublishRequest {
+    pub image_path: String, // Raw disk image, e.g. from backup_device
+    pub id: String,         // Replaces the catalog entry with the same ID
+    pub name: String,
+    #[serde(default)]
+    pub publisher: Option<String>, // Defaults to the station name
+    #[serde(default)]
+    pub description: String,
+    pub modules: Vec<String>,
+    #[serde(default)]
+    pub jetpack_version: Option<String>,
+}
+
+enum Store {
+    S3(Box<Bucket>),
+    Http { base_url: String, token: Option<String> },
+}
+
+impl Store {
+    fn open(state: &AppState, settings: &CatalogPublishSettings) -> Result<Self> {
+        match settings.destination.as_str() {
+            "s3" => Ok(Store::S3(uploads::bucket(&state.settings.lock().unwrap().artifact_upload)?)),
+            "http" if !settings.http_url.is_empty() => Ok(Store::Http {
+                base_url: settings.http_url.trim_end_matches('/').to_string(),
+                token: credentials::get_secret(HTTP_ACCOUNT)?,
+            }),
+            "http" => Err(anyhow::anyhow!("Publishing over HTTP needs an upload URL")),
+            other => Err(anyhow::anyhow!("Unknown catalog destination: {}", other)),
+        }
+    }
+
+    async fn put_file(&self, key: &str, path: &Path) -> Result<()> {
+        let mut file = tokio::fs::File::open(path)
+            .await
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        match self {
+            Store::S3(bucket) => {
+                bucket.put_object_stream(&mut file, key).await?;
+                Ok(())
+            }
+            Store::Http { base_url, token } => put(base_url, token, key, file.into(), "application/octet-stream").await,
+        }
+    }
+
+    async fn put_json(&self, key: &str, content: Vec<u8>) -> Result<()> {
+        match self {
+            Store::S3(bucket) => {
+                bucket.put_object_with_content_type(key, &content, "application/json").await?;
+                Ok(())
+            }
+            Store::Http { base_url, token } => put(base_url, token, key, content.into(), "application/json").await,
+        }
+    }
+
+    // The published catalog, or an empty one before the first image is published
+    async fn catalog(&self, key: &str, public_url: &str) -> Result<ImageCatalog> {
+        let empty = ImageCatalog { schema: 1, ..Default::default() };
+        match self {
+            Store::S3(bucket) => match bucket.get_object(key).await {
+                Ok(response) => serde_json::from_slice(response.bytes()).context("Invalid published catalog"),
+                Err(S3Error::HttpFailWithBody(404, _)) => Ok(empty),
+                Err(e) => Err(e.into()),
+            },
+            Store::Http { .. } => {
+                let response = reqwest::Client::new().get(format!("{}/{}", public_url, key)).send().await?;
+                if response.status() == reqwest::StatusCode::NOT_FOUND {
+                    return Ok(empty);
+                }
+                Ok(response.error_for_status()?.json().await.context("Invalid published catalog")?)
+            }
+        }
+    }
+}
+
+async fn put(base_url: &str, token: &Option<String>, key: &str, body: reqwest::Body, content_type: &str) -> Result<()> {
+    let mut request = reqwest::Client::new()
+        .put(format!("{}/{}", base_url, key))
+        .header(reqwest::header::CONTENT_TYPE, content_type)
+        .body(body);
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    request.send().await?.error_for_status().with_context(|| format!("Failed to upload {}", key))?;
+    Ok(())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn signing_key() -> Result<SigningKey> {
+    let secret = credentials::get_secret(SIGNING_ACCOUNT)?
+        .context("No catalog signing key stored; generate one or import the team's key first")?;
+    let bytes: [u8; 32] = catalog::from_hex(&secret)?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("The catalog signing key must be 32 bytes"))?;
+    Ok(SigningKey::from_bytes(&bytes))
+}
+
+// <prefix>/<path>
+fn key(settings: &CatalogPublishSettings, path: &str) -> String {
+    let prefix = settings.prefix.trim_matches('/');
+    if prefix.is_empty() {
+        path.to_string()
+    } else {
+        format!("{}/{}", prefix, path)
+    }
+}
+
+async fn publish(state: &AppState, request: PublishRequest) -> Result<CatalogImage> {
+    let settings = state.settings.lock().unwrap().catalog_publish.clone();
+    if settings.public_url.is_empty() {
+        return Err(anyhow::anyhow!("Set the public URL stations download the catalog from"));
+    }
+    if request.id.trim().is_empty() || request.modules.is_empty() {
+        return Err(anyhow::anyhow!("A published image needs an ID and at least one module"));
+    }
+    let key_pair = signing_key()?;
+    let store = Store::open(state, &settings)?;
+    let public_url = settings.public_url.trim_end_matches('/').to_string();
+
+    let path = PathBuf::from(&request.image_path);
+    let size = std::fs::metadata(&path).with_context(|| format!("Image not found: {}", path.display()))?.len();
+    let hashed = path.clone();
+    let sha256 = tokio::task::spawn_blocking(move || checksum::sha256_file(&hashed)).await??;
+    let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).context("Invalid image path")?;
+    let image_key = key(&settings, &format!("images/{}/{}", crate::downloads::release_dir_name(&request.id), file_name));
+    info!("Uploading {} to the image catalog as {}", path.display(), image_key);
+    store.put_file(&image_key, &path).await?;
+
+    let image = CatalogImage {
+        id: request.id,
+        name: request.name,
+        publisher: request
+            .publisher
+            .or_else(|| crate::history::station_name(state))
+            .unwrap_or_else(|| "Unknown".to_string()),
+        description: request.description,
+        modules: request.modules,
+        jetpack_version: request.jetpack_version,
+        url: format!("{}/{}", public_url, image_key),
+        size: Some(size),
+        sha256,
+    };
+
+    // Read, merge and sign the current catalog; another station publishing at the same moment can still
+    // overwrite this entry, publishing again restores it
+    let catalog_key = key(&settings, CATALOG_FILE);
+    let published = store.catalog(&catalog_key, &public_url).await?;
+    let mut updated = published.merge(ImageCatalog { schema: 1, images: vec![image.clone()], signature: None });
+    updated.signature = Some(to_hex(&key_pair.sign(&updated.signed_bytes()?).to_bytes()));
+    store.put_json(&catalog_key, serde_json::to_vec_pretty(&updated)?).await?;
+    info!("Published image {} to {}/{}", image.id, public_url, catalog_key);
+    Ok(image)
+}
+
+// Upload an image to the shared catalog and sign the updated catalog
+#[command]
+pub async fn publish_catalog_image(request: PublishRequest, state: State<'_, Arc<AppState>>) -> Result<CatalogImage, String> {
+    let id = request.id.clone();
+    publish(&state, request).await.map_err(|e| format!("Failed to publish image {}: {:#}", id, e))
+}
+
+// Create the signing key for publishing and return its public key, which stations set as
+// image_catalog_public_key. Stations publishing to the same catalog import the same key instead.
+#[command]
+pub async fn generate_catalog_signing_key() -> Result<String, String> {
+    let generate = || -> Result<String> {
+        if credentials::get_secret(SIGNING_ACCOUNT)?.is_some() {
+            return Err(anyhow::anyhow!("A catalog signing key is already stored; delete it first to replace it"));
+        }
+        let mut seed = [0u8; 32];
+        std::fs::File::open("/dev/urandom")?.read_exact(&mut seed)?;
+        let key_pair = SigningKey::from_bytes(&seed);
+        credentials::set_secret(SIGNING_ACCOUNT, &to_hex(&seed))?;
+        Ok(to_hex(key_pair.verifying_key().as_bytes()))
+    };
+    generate().map_err(|e| e.to_string())
+}
+
+// Public key of the stored signing key
+#[command]
+pub async fn get_catalog_public_key() -> Result<String, String> {
+    signing_key().map(|key| to_hex(key.verifying_key().as_bytes())).map_err(|e| e.to_string())
+}
@@ -0,0 +1,72 @@
+// CFU - Cordatus Flash Utility - Checksums
+// SHA-256 of large archives and images over memory-mapped files, hashing several files in parallel. A single
+// digest is inherently sequential, so parallelism is across files; results still match sha256sum and published
+// checksums. Files that may still be written or truncated while they are hashed are read instead of mapped,
+// as is MD5, used only where a release publishes nothing else.
+
+use anyhow::{Context, Result};
+use md5::Md5;
+use memmap2::Mmap;
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+// Hash in slices so the kernel can read ahead while earlier pages are hashed
+const HASH_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+pub fn sha256_file(path: &Path) -> Result<String> {
+    digest_file::<Sha256>(path)
+}
+
+// For files another process may change meanwhile, such as downloads in progress: a read of a truncated
+// file just ends early, where a mapping of it would kill the process
+pub fn sha256_file_streamed(path: &Path) -> Result<String> {
+    digest_streamed::<Sha256>(path)
+}
+
+pub fn md5_file_streamed(path: &Path) -> Result<String> {
+    digest_streamed::<Md5>(path)
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn digest_streamed<D: Digest>(path: &Path) -> Result<String> {
+    let mut file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = D::new();
+    let mut buffer = vec![0u8; 1024 * 1024];
+    loop {
+        let read = file.read(&mut buffer).with_context(|| format!("Failed to read {}", path.display()))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hex(&hasher.finalize()))
+}
+
+fn digest_file<D: Digest>(path: &Path) -> Result<String> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = D::new();
+    // Zero-length files cannot be mapped
+    if file.metadata()?.len() > 0 {
+        // Safety: the mapping is read-only and dropped before returning. The file must not be truncated
+        // while it is mapped: touching pages past its new end raises SIGBUS, which kills the process
+        // rather than failing the read. Callers hash files nothing else writes, or use the streamed variants.
+        let map = unsafe { Mmap::map(&file) }.with_context(|| format!("Failed to map {}", path.display()))?;
+        #[cfg(unix)]
+        map.advise(memmap2::Advice::Sequential).ok();
+        for chunk in map.chunks(HASH_CHUNK_SIZE) {
+            hasher.update(chunk);
+        }
+    }
+    Ok(hex(&hasher.finalize()))
+}
+
+// Checksums of several files, computed in parallel, in input order
+pub fn sha256_files<P: AsRef<Path> + Sync>(paths: &[P]) -> Vec<Result<String>> {
+    paths.par_iter().map(|path| sha256_file(path.as_ref())).collect()
+}
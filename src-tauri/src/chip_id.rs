@@ -0,0 +1,95 @@
+// CFU - Cordatus Flash Utility - Chip Identity
+// Reads the boot ROM chip UID (BR_CID) of a board in recovery mode with the tegrarcm tool of the extracted
+// BSP, and derives an identifier for the board that stays the same when it is re-plugged on another port
+
+use crate::{peers, AppState, JetsonDevice};
+use anyhow::{Context, Result};
+use log::info;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{command, State};
+
+const READ_TIMEOUT: Duration = Duration::from_secs(20);
+
+// Prefers the chip UID, which the board cannot change; the USB serial is the fallback
+pub fn stable_id(device: &JetsonDevice) -> Option<String> {
+    if let Some(uid) = &device.chip_uid {
+        return Some(format!("cid-{}", uid.trim_start_matches("0x").to_lowercase()));
+    }
+    let serial = device.usb_info.as_ref()?.serial_number.as_ref()?;
+    Some(format!("usb-{}", serial))
+}
+
+// Fill in the chip UID read earlier for this exact enumeration, and the stable ID
+pub fn annotate(state: &AppState, device: &mut JetsonDevice) {
+    if let Some(usb) = &device.usb_info {
+        device.chip_uid = state.chip_uids.lock().unwrap().get(&usb.device_path).cloned();
+    }
+    device.stable_id = stable_id(device);
+}
+
+// Nano (T210) only has the first generation tool
+fn tool(module: &str) -> &'static str {
+    if module.starts_with("Nano") {
+        "tegrarcm"
+    } else {
+        "tegrarcm_v2"
+    }
+}
+
+// "BR_CID: 0x8A0123..." in the tool output
+fn parse_uid(output: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        let (_, value) = line.split_once("BR_CID:")?;
+        let value = value.trim();
+        (!value.is_empty()).then(|| value.to_string())
+    })
+}
+
+async fn read_uid(device: &JetsonDevice) -> Result<String> {
+    let usb = device.usb_info.as_ref().context("Not a USB device")?;
+    if !usb.is_recovery_mode {
+        return Err(anyhow::anyhow!("The chip UID can only be read in recovery mode"));
+    }
+    let bootloader = peers::artifact_dir().join("Linux_for_Tegra").join("bootloader");
+    let executable = bootloader.join(tool(&device.module));
+    if !executable.exists() {
+        return Err(anyhow::anyhow!("{} not found; extract a BSP for this module first", executable.display()));
+    }
+    let mut command = tokio::process::Command::new(&executable);
+    command.current_dir(&bootloader).arg("--uid").kill_on_drop(true);
+    if let Some(port) = &usb.port_path {
+        command.args(["--instance", port]);
+    }
+    let output = tokio::time::timeout(READ_TIMEOUT, command.output())
+        .await
+        .context("Timed out reading the chip UID")?
+        .with_context(|| format!("Failed to run {}", executable.display()))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    parse_uid(&stdout).with_context(|| {
+        format!("No BR_CID in the {} output: {}", tool(&device.module), String::from_utf8_lossy(&output.stderr).trim())
+    })
+}
+
+// Read the chip UID of a connected board in recovery mode. Not done on every scan: it opens an RCM
+// session, which must not race a flash that is starting on the same board.
+#[command]
+pub async fn read_chip_id(device_id: String, state: State<'_, Arc<AppState>>) -> Result<JetsonDevice, String> {
+    let device = state
+        .connected_devices
+        .lock()
+        .unwrap()
+        .get(&device_id)
+        .cloned()
+        .ok_or_else(|| format!("Device {} is not connected", device_id))?;
+    let uid = read_uid(&device).await.map_err(|e| format!("{:#}", e))?;
+    info!("Chip UID of {}: {}", device_id, uid);
+    let path = device.usb_info.as_ref().map(|usb| usb.device_path.clone()).unwrap_or_default();
+    state.chip_uids.lock().unwrap().insert(path, uid);
+    state.usb_scan_cache.invalidate().await;
+
+    let mut device = device;
+    annotate(&state, &mut device);
+    state.connected_devices.lock().unwrap().insert(device_id, device.clone());
+    Ok(device)
+}
@@ -0,0 +1,943 @@
+// CFU - Cordatus Flash Utility - Command Line Mode
+// Headless `flash` for scripts and `ci` for hardware-in-the-loop pipelines (wait for a device, flash a profile,
+// wait for boot, run a test over SSH): stable exit codes per outcome and an optional `--json` result document.
+// `validate-spec` lints a device spec for CI. Served by the cfu-cli binary, and by the app itself as `--flash` / `--ci`.
+// Jobs go through the app's own flash pipeline: in the running app when there is one, so the two never
+// race for the same board, else on a headless job host, see job_host.rs
+
+use crate::asset;
+use crate::batch::JobVariables;
+use crate::binding::DeviceBinding;
+use crate::failures::FlashFailure;
+use crate::job_host::{Headless, JobHost};
+use crate::joblog;
+use crate::profiles::{self, FlashProfile};
+use crate::ssh::{self, SshTarget};
+use crate::device_matrix::{self, MatrixRow};
+use crate::{freeze, history, native_flash, settings, version_matrix, AppState, FlashCommand, JetsonDevice};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Exit codes; part of the CLI contract, do not renumber
+pub const EXIT_SUCCESS: i32 = 0;
+pub const EXIT_ERROR: i32 = 1; // Unexpected internal error
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_DEVICE_NOT_FOUND: i32 = 3;
+pub const EXIT_PREFLIGHT_FAILED: i32 = 4;
+pub const EXIT_FLASH_FAILED: i32 = 5;
+pub const EXIT_BOOT_TIMEOUT: i32 = 6;
+pub const EXIT_TEST_FAILED: i32 = 7;
+pub const EXIT_SPEC_INVALID: i32 = 8;
+pub const EXIT_CANCELLED: i32 = 130; // Interrupted with Ctrl-C, as shells report it
+
+// Bumped when fields of the result document change incompatibly
+const RESULT_SCHEMA: u32 = 1;
+const LOG_TAIL_LINES: usize = 20;
+const DEVICE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+const BOOT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+// Where the app keeps its data on Linux, for profiles looked up by name
+const APP_IDENTIFIER: &str = "ai.cordatus.flash-utility";
+
+const USAGE: &str = "Usage: cfu-cli flash --module <module> (--l4t <version> | --jetpack <version>) --storage <storage>
+                [--product <product>] [--user <name>] [--keep-files] [--wait-device <secs>] [--json]
+       cfu-cli ci (--profile <name> | --profile-file <path>) [--wait-device <secs>]
+                [--ssh <user@host> [--ssh-port <port>] [--ssh-key <path>] [--boot-timeout <secs>] [--test <command>]]
+                [--json]
+       cfu-cli validate-spec <spec.yaml> [--batch-file <path>] [--serial <serial>] [--slot <slot>] [--index <n>]
+                [--operator <name>] [--json]
+
+The app binary accepts the same as `--flash`, `--ci` and `--validate-spec`.
+Names are matched against the device matrix: `--module orin-nx --l4t 36.4.3 --storage nvme` is the same as
+`--module \"Orin NX\" --jetpack \"6.2 - L4T 36.4.3\" --storage \"NVMe SSD\"`. --product is needed only when
+several products carry the module.
+`ci` runs a profile's whole flash command, including its provisioning, kernel, stage skips, device binding,
+delta and first boot settings and its pinned artifacts.
+Jobs are checked and run like the app's and recorded in its history and job logs, in the app data directory,
+where profiles given by name are read from too; set CFU_DATA_DIR to use another one. While the app is running,
+the job runs in it instead and shows up there; its output and result are reported here as usual.
+
+Exit codes:
+  0  succeeded
+  1  unexpected error
+  2  invalid arguments
+  3  no Jetson device in recovery mode found
+  4  preflight failed (unsupported configuration, flash script missing)
+  5  flash failed; with --json, \"stage\" names the stage it failed in
+  6  the device did not come up over SSH after flashing
+  7  the test command failed
+  8  the device spec has errors
+  130  cancelled with Ctrl-C";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Flash,
+    Ci,
+}
+
+impl Mode {
+    // How the app binary is asked for the mode, also on a request forwarded to it
+    fn flag(self) -> &'static str {
+        match self {
+            Mode::Flash => "--flash",
+            Mode::Ci => "--ci",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct CliArgs {
+    product: String,
+    module: String,
+    jetpack: String,
+    l4t: String,
+    storage: String,
+    user: String,
+    keep_files: bool,
+    json: bool,
+    wait_device_secs: u64, // 0 checks once
+    profile: Option<String>,
+    profile_file: Option<String>,
+    ssh: Option<SshTarget>,
+    boot_timeout_secs: u64,
+    test_command: Option<String>,
+    command: Option<FlashCommand>, // The profile's whole flash command, in ci mode
+    variables: BTreeMap<String, String>, // Template variables the profile was resolved with
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CliStep {
+    pub name: String,   // 'wait_device' | 'preflight' | 'flash' | 'boot' | 'test'
+    pub status: String, // 'passed' | 'failed'
+    pub duration_secs: f64,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestResult {
+    pub command: String,
+    pub exit_code: i32,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+// The `--json` document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CliResult {
+    pub schema_version: u32,
+    pub result: String, // 'success' | 'error' | 'usage_error' | 'device_not_found' | 'preflight_failed' | 'flash_failed' | 'cancelled' | 'boot_timeout' | 'test_failed'
+    pub exit_code: i32,
+    pub message: String,
+    pub profile: Option<String>,
+    pub flash_id: Option<String>, // The job in the app's history
+    pub stage: Option<String>, // Last progress stage reached; where a failed flash stopped
+    pub device: Option<JetsonDevice>,
+    pub steps: Vec<CliStep>,
+    pub test: Option<TestResult>,
+    pub warnings: Vec<String>,
+    pub log_tail: Vec<String>,
+    pub failure: Option<FlashFailure>, // Known failure the flash output reported
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+}
+
+impl CliResult {
+    fn new(started_at: DateTime<Utc>) -> Self {
+        Self {
+            schema_version: RESULT_SCHEMA,
+            result: "success".to_string(),
+            exit_code: EXIT_SUCCESS,
+            message: String::new(),
+            profile: None,
+            flash_id: None,
+            stage: None,
+            device: None,
+            steps: Vec::new(),
+            test: None,
+            warnings: Vec::new(),
+            log_tail: Vec::new(),
+            failure: None,
+            started_at,
+            finished_at: started_at,
+        }
+    }
+
+    fn step(&mut self, name: &str, started: Instant, passed: bool, message: impl Into<String>) {
+        self.steps.push(CliStep {
+            name: name.to_string(),
+            status: if passed { "passed" } else { "failed" }.to_string(),
+            duration_secs: started.elapsed().as_secs_f64(),
+            message: message.into(),
+        });
+    }
+
+    fn fail(mut self, result: &str, exit_code: i32, message: impl Into<String>) -> Self {
+        self.result = result.to_string();
+        self.exit_code = exit_code;
+        self.message = message.into();
+        self
+    }
+
+    fn succeeded(&self) -> bool {
+        self.exit_code == EXIT_SUCCESS
+    }
+}
+
+fn parse_args(mode: Mode, args: &[String]) -> Result<CliArgs, String> {
+    let mut parsed = CliArgs {
+        user: std::env::var("SUDO_USER").or_else(|_| std::env::var("USER")).unwrap_or_default(),
+        boot_timeout_secs: 600,
+        ..Default::default()
+    };
+    let (mut ssh_destination, mut ssh_port, mut ssh_key) = (None, None, None);
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().cloned().ok_or_else(|| format!("{} needs a value", arg));
+        let number = |value: String| value.parse::<u64>().map_err(|_| format!("{} needs a number", arg));
+        match (mode, arg.as_str()) {
+            (Mode::Flash, "--product") => parsed.product = value()?,
+            (Mode::Flash, "--module") => parsed.module = value()?,
+            (Mode::Flash, "--jetpack") => parsed.jetpack = value()?,
+            (Mode::Flash, "--l4t") => parsed.l4t = value()?,
+            (Mode::Flash, "--storage") => parsed.storage = value()?,
+            (Mode::Flash, "--user") => parsed.user = value()?,
+            (Mode::Flash, "--keep-files") => parsed.keep_files = true,
+            (Mode::Ci, "--profile") => parsed.profile = Some(value()?),
+            (Mode::Ci, "--profile-file") => parsed.profile_file = Some(value()?),
+            (Mode::Ci, "--ssh") => ssh_destination = Some(value()?),
+            (Mode::Ci, "--ssh-port") => ssh_port = Some(number(value()?)?),
+            (Mode::Ci, "--ssh-key") => ssh_key = Some(value()?),
+            (Mode::Ci, "--boot-timeout") => parsed.boot_timeout_secs = number(value()?)?,
+            (Mode::Ci, "--test") => parsed.test_command = Some(value()?),
+            (_, "--wait-device") => parsed.wait_device_secs = number(value()?)?,
+            (_, "--json") => parsed.json = true,
+            _ => return Err(format!("Unknown argument: {}", arg)),
+        }
+    }
+
+    if let Some(destination) = ssh_destination {
+        let (user, host) = destination
+            .split_once('@')
+            .ok_or("--ssh needs <user>@<host>")?;
+        let target = SshTarget {
+            host: host.to_string(),
+            user: user.to_string(),
+            port: ssh_port.map(|port| u16::try_from(port).map_err(|_| "Invalid --ssh-port")).transpose()?,
+            identity_file: ssh_key,
+        };
+        target.validate().map_err(|e| e.to_string())?;
+        parsed.ssh = Some(target);
+    } else if parsed.test_command.is_some() {
+        return Err("--test needs --ssh".to_string());
+    }
+    Ok(parsed)
+}
+
+// Fill in the job from the profile in ci mode and check that it is complete. The profile's flash
+// command is run as a whole, like the app's flash_profile does.
+fn prepare_args(mode: Mode, mut parsed: CliArgs, host: &impl JobHost) -> Result<CliArgs, String> {
+    if mode == Mode::Ci {
+        let profile = load_profile(&parsed, host)?;
+        let (mut command, variables) = profiles::resolve_command(profile.command, &JobVariables::default())
+            .map_err(|e| format!("Invalid profile {}: {}", profile.name, e))?;
+        command.pinned_artifacts = profile.pins;
+        if command.user_name.is_empty() {
+            command.user_name = parsed.user.clone();
+        }
+        parsed.product = command.product.clone();
+        parsed.module = command.device_module.clone();
+        parsed.jetpack = command.jetpack_version.clone();
+        parsed.storage = command.storage_device.clone();
+        parsed.user = command.user_name.clone();
+        parsed.command = Some(command);
+        parsed.variables = variables;
+        parsed.profile = Some(profile.name);
+    }
+    for (name, value) in [
+        ("--module", &parsed.module),
+        ("--storage", &parsed.storage),
+        ("--user", &parsed.user),
+    ] {
+        if value.is_empty() {
+            return Err(format!("{} is required", name));
+        }
+    }
+    if parsed.jetpack.is_empty() && parsed.l4t.is_empty() {
+        return Err("--l4t or --jetpack is required".to_string());
+    }
+    Ok(parsed)
+}
+
+// "Orin NX" -> "orin-nx", "Nano - 4GB" -> "nano-4gb"
+fn slug(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+// A name matches its slug and any shortening of it at a dash, so "nano" matches "Nano - 4GB"
+fn matches_name(name: &str, wanted: &str) -> bool {
+    let (name, wanted) = (slug(name), slug(wanted));
+    name == wanted || name.starts_with(&format!("{}-", wanted))
+}
+
+// "36.4" and "36.4.0" are the same release
+fn same_version(a: &str, b: &str) -> bool {
+    let trim = |version: &str| version.trim().trim_end_matches(".0").to_string();
+    trim(a) == trim(b)
+}
+
+// Matrix releases read "6.2 - L4T 36.4.3"
+fn matches_release(row: &str, jetpack: &str, l4t: &str) -> bool {
+    let (row_jetpack, row_l4t) = row.split_once(" - L4T ").unwrap_or((row, ""));
+    (jetpack.is_empty() || row == jetpack || row_jetpack == jetpack) && (l4t.is_empty() || same_version(row_l4t, l4t))
+}
+
+pub(crate) fn storage_name(storage: &str) -> String {
+    match slug(storage).as_str() {
+        "nvme" | "nvme-ssd" | "ssd" => "NVMe SSD".to_string(),
+        "sd" | "sdcard" | "microsd" | "micro-sd" => "Micro SD".to_string(),
+        _ => storage.to_string(),
+    }
+}
+
+// The device matrix row a selection of possibly short names points to, for the CLI and device specs;
+// an ambiguous selection is refused rather than guessed. Rows without a storage column leave the
+// choice to the flash script and take the storage as given.
+pub(crate) fn select_row(
+    matrix: &device_matrix::DeviceMatrix,
+    product: &str,
+    module: &str,
+    jetpack: &str,
+    l4t: &str,
+    storage: &str,
+) -> Result<MatrixRow, String> {
+    let storage = storage_name(storage);
+    let rows: Vec<&MatrixRow> = matrix
+        .rows
+        .iter()
+        .filter(|row| {
+            matches_name(&row.module, module)
+                && (product.is_empty() || matches_name(&row.product, product))
+                && matches_release(&row.jetpack, jetpack, l4t)
+                && (row.storage.is_empty() || slug(&row.storage) == slug(&storage))
+        })
+        .collect();
+    let Some(row) = rows.first() else {
+        return Err(format!(
+            "No entry of the device matrix matches module {} with release {} on {}",
+            module,
+            if l4t.is_empty() { jetpack } else { l4t },
+            storage
+        ));
+    };
+    for (field, names) in [
+        ("product", rows.iter().map(|r| r.product.as_str()).collect::<BTreeSet<_>>()),
+        ("module", rows.iter().map(|r| r.module.as_str()).collect::<BTreeSet<_>>()),
+    ] {
+        if names.len() > 1 {
+            let names: Vec<&str> = names.into_iter().collect();
+            return Err(format!("The selection matches several boards ({}); narrow down the {}", names.join(", "), field));
+        }
+    }
+    let mut row = (*row).clone();
+    if row.storage.is_empty() {
+        row.storage = storage;
+    }
+    Ok(row)
+}
+
+// Canonical product / module / release / storage names from the device matrix, so a rig can pass short names
+// The device matrix shipped with the app
+async fn read_matrix() -> Result<device_matrix::DeviceMatrix, String> {
+    let working_dir = crate::get_working_directory().await?;
+    let path = Path::new(&working_dir).join("data").join("template.csv");
+    let content = std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    device_matrix::parse_matrix(&content).map_err(|e| e.to_string())
+}
+
+async fn resolve_selection(args: &mut CliArgs) -> Result<(), String> {
+    let matrix = read_matrix().await?;
+    let row = select_row(&matrix, &args.product, &args.module, &args.jetpack, &args.l4t, &args.storage)?;
+    args.product = row.product;
+    args.module = row.module;
+    args.jetpack = row.jetpack;
+    args.storage = row.storage;
+    Ok(())
+}
+
+fn data_dir() -> Option<PathBuf> {
+    if let Ok(dir) = std::env::var("CFU_DATA_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    let base = std::env::var("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| std::env::var("HOME").map(|home| Path::new(&home).join(".local/share")))
+        .ok()?;
+    Some(base.join(APP_IDENTIFIER))
+}
+
+// The settings live in the config directory, next to the data directory unless CFU_DATA_DIR holds both
+fn config_dir() -> Option<PathBuf> {
+    if let Ok(dir) = std::env::var("CFU_DATA_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    let base = std::env::var("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| std::env::var("HOME").map(|home| Path::new(&home).join(".config")))
+        .ok()?;
+    Some(base.join(APP_IDENTIFIER))
+}
+
+fn headless_host() -> Result<Headless, String> {
+    match (data_dir(), config_dir()) {
+        (Some(data_dir), Some(config_dir)) => Ok(Headless::new(data_dir, config_dir)),
+        _ => Err("Cannot locate the app data directory; set CFU_DATA_DIR".to_string()),
+    }
+}
+
+// A profile by name is looked up like the app does, i.e. its frozen copy on a frozen station
+fn load_profile(args: &CliArgs, host: &impl JobHost) -> Result<FlashProfile, String> {
+    match (&args.profile_file, &args.profile) {
+        (Some(file), _) => {
+            let content = std::fs::read_to_string(file).map_err(|e| format!("Failed to read {}: {}", file, e))?;
+            serde_json::from_str(&content).map_err(|e| format!("Invalid profile file {}: {}", file, e))
+        }
+        (None, Some(name)) => freeze::profile_for_flash(host, name).map_err(|e| e.to_string()),
+        (None, None) => Err("--profile or --profile-file is required".to_string()),
+    }
+}
+
+// A Jetson in recovery mode, the bound one if the job has a binding, polling until the deadline
+async fn wait_for_device(state: &AppState, wait_secs: u64, binding: Option<&DeviceBinding>) -> Result<JetsonDevice, String> {
+    let deadline = Instant::now() + Duration::from_secs(wait_secs);
+    loop {
+        let devices = crate::scan_usb_devices(state).await?;
+        if let Some(device) = devices.into_iter().find(|d| {
+            d.usb_info.as_ref().is_some_and(|usb| usb.is_recovery_mode) && binding.is_none_or(|b| b.matches_location(d))
+        }) {
+            return Ok(device);
+        }
+        if Instant::now() >= deadline {
+            return Err("No Jetson device in recovery mode found".to_string());
+        }
+        tokio::time::sleep(DEVICE_POLL_INTERVAL).await;
+    }
+}
+
+// The app's state over its data directory, so a job runs with the app's settings and ends up in its history
+fn headless_state(host: &Headless) -> Arc<AppState> {
+    let state = AppState::default();
+    *state.settings.lock().unwrap() = settings::load_settings(host);
+    *state.version_matrix.lock().unwrap() = version_matrix::load_version_matrix(host);
+    *state.history.lock().unwrap() = history::load_history(host);
+    *state.asset_batches.lock().unwrap() = asset::load_assets(host);
+    Arc::new(state)
+}
+
+fn flash_command(args: &CliArgs) -> FlashCommand {
+    if let Some(command) = &args.command {
+        return command.clone();
+    }
+    FlashCommand {
+        product: args.product.clone(),
+        device_module: args.module.clone(),
+        jetpack_version: args.jetpack.clone(),
+        storage_device: args.storage.clone(),
+        keep_files: args.keep_files,
+        user_name: args.user.clone(),
+        provisioning: None,
+        custom_kernel: None,
+        pinned_artifacts: Vec::new(),
+        operator: None,
+        skip_stages: Vec::new(),
+        wait_for_device_secs: None,
+        device_binding: None,
+        delta: false,
+        boot_check: None,
+    }
+}
+
+// Job output on the terminal; with --json everything goes to stderr, keeping stdout for the document
+fn echo(stream: &str, line: &str, json: bool) {
+    if stream == "stdout" && !json {
+        println!("{}", line);
+    } else {
+        eprintln!("{}", line);
+    }
+}
+
+// Where a job's output goes and its interruption comes from
+enum Console {
+    Terminal { json: bool },
+    #[cfg(unix)]
+    Forwarded {
+        writer: tokio::sync::Mutex<tokio::net::unix::OwnedWriteHalf>,
+        reader: tokio::sync::Mutex<tokio::net::unix::OwnedReadHalf>, // Only ever closed by cfu-cli
+    },
+}
+
+impl Console {
+    async fn line(&self, stream: &str, line: &str) {
+        match self {
+            Console::Terminal { json } => echo(stream, line, *json),
+            #[cfg(unix)]
+            Console::Forwarded { writer, .. } => {
+                let reply = CliReply::Output {
+                    stream: stream.to_string(),
+                    line: line.to_string(),
+                };
+                send_reply(&mut *writer.lock().await, &reply).await;
+            }
+        }
+    }
+
+    // Ctrl-C, or the forwarding cfu-cli going away or passing on its Ctrl-C
+    async fn interrupted(&self) {
+        match self {
+            Console::Terminal { .. } => {
+                let _ = tokio::signal::ctrl_c().await;
+            }
+            #[cfg(unix)]
+            Console::Forwarded { reader, .. } => {
+                use tokio::io::AsyncReadExt;
+                let mut reader = reader.lock().await;
+                let mut buffer = [0u8; 64];
+                while matches!(reader.read(&mut buffer).await, Ok(n) if n > 0) {}
+            }
+        }
+    }
+}
+
+// Run the job like the app does, showing its output as it is logged and stopping it as a hard cancel
+// when interrupted: the flash runs in a session of its own, which the terminal's interrupt does not reach
+async fn run_job<H: JobHost>(host: &H, state: &Arc<AppState>, console: &Console, flash_id: &str, command: FlashCommand) {
+    let mut output = joblog::follow(state, flash_id);
+    let mut job = tokio::spawn(crate::run_flash(command, flash_id.to_string(), Arc::clone(state), host.clone()));
+    let interrupted = console.interrupted();
+    tokio::pin!(interrupted);
+    let mut cancelled = false;
+    loop {
+        tokio::select! {
+            Some((stream, line)) = output.recv() => console.line(&stream, &line).await,
+            _ = &mut job => break,
+            _ = &mut interrupted, if !cancelled => {
+                cancelled = true;
+                console.line("stderr", &format!("Cancelling flash {}", flash_id)).await;
+                crate::cancel_flash(host, state, flash_id).await;
+            }
+        }
+    }
+    while let Ok((stream, line)) = output.try_recv() {
+        console.line(&stream, &line).await;
+    }
+}
+
+async fn flash<H: JobHost>(args: &CliArgs, host: &H, state: &Arc<AppState>, console: &Console, mut result: CliResult) -> CliResult {
+    let command = flash_command(args);
+
+    let started = Instant::now();
+    let wait_secs = args.wait_device_secs.max(command.wait_for_device_secs.unwrap_or(0));
+    match wait_for_device(state, wait_secs, command.device_binding.as_ref()).await {
+        Ok(device) => {
+            result.step("wait_device", started, true, format!("{} {}", device.product, device.module));
+            result.device = Some(device);
+        }
+        Err(e) => {
+            result.step("wait_device", started, false, e.clone());
+            return result.fail("device_not_found", EXIT_DEVICE_NOT_FOUND, e);
+        }
+    }
+
+    // The same checks the app runs before a job starts
+    let started = Instant::now();
+    if !native_flash::selected(state, &command).await {
+        if let Err(e) = crate::get_script_path().await {
+            result.step("preflight", started, false, e.clone());
+            return result.fail("preflight_failed", EXIT_PREFLIGHT_FAILED, e);
+        }
+    }
+    let flash_id = match crate::begin_flash(host, state, &command) {
+        Ok((flash_id, compatibility)) => {
+            result.warnings = compatibility.warnings;
+            flash_id
+        }
+        Err(e) => {
+            result.step("preflight", started, false, e.clone());
+            return result.fail("preflight_failed", EXIT_PREFLIGHT_FAILED, e);
+        }
+    };
+    result.flash_id = Some(flash_id.clone());
+    if let Some(profile) = &args.profile {
+        history::update_history(host, state, |history| {
+            if let Some(record) = history.jobs.iter_mut().find(|r| r.flash_id == flash_id) {
+                record.profile = Some(profile.clone());
+                record.variables = args.variables.clone();
+            }
+        });
+    }
+    result.step("preflight", started, true, "");
+
+    let started = Instant::now();
+    run_job(host, state, console, &flash_id, command).await;
+    let job = state.history.lock().unwrap().jobs.iter().find(|job| job.flash_id == flash_id).cloned();
+    result.log_tail = joblog::tail(host, &flash_id, LOG_TAIL_LINES).unwrap_or_default();
+    let stage = crate::timeline::last_stage(host, &flash_id).unwrap_or_else(|| "preparing".to_string());
+    result.stage = Some(stage.clone());
+
+    let Some(job) = job else {
+        return result.fail("error", EXIT_ERROR, format!("Flash job {} is missing from the history", flash_id));
+    };
+    match job.status.as_str() {
+        "success" => {
+            result.stage = Some("complete".to_string());
+            result.step("flash", started, true, "");
+            result.message = "Flash process completed successfully".to_string();
+            result
+        }
+        "cancelled" => {
+            result.step("flash", started, false, "Cancelled");
+            result.fail("cancelled", EXIT_CANCELLED, format!("Flash cancelled during {}", stage))
+        }
+        _ => {
+            result.failure = job.failure;
+            let error = job.error.unwrap_or_else(|| format!("job ended as {}", job.status));
+            let message = match &result.failure {
+                Some(failure) => format!("Flash failed during {}: {} ({})", stage, failure.summary, error),
+                None => format!("Flash failed during {}: {}", stage, error),
+            };
+            result.step("flash", started, false, message.clone());
+            result.fail("flash_failed", EXIT_FLASH_FAILED, message)
+        }
+    }
+}
+
+async fn wait_for_boot(target: &SshTarget, timeout_secs: u64) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(timeout_secs);
+    while Instant::now() < deadline {
+        if ssh::run_remote(target, "true").await.is_ok() {
+            return true;
+        }
+        tokio::time::sleep(BOOT_POLL_INTERVAL).await;
+    }
+    false
+}
+
+async fn run_test(target: &SshTarget, command: &str) -> Result<TestResult, String> {
+    let child = ssh::spawn_remote(target, command).map_err(|e| e.to_string())?;
+    let output = child.wait_with_output().await.map_err(|e| e.to_string())?;
+    let tail = |bytes: &[u8]| {
+        let text = String::from_utf8_lossy(bytes);
+        let lines: Vec<&str> = text.lines().collect();
+        lines[lines.len().saturating_sub(LOG_TAIL_LINES * 5)..].join("\n")
+    };
+    Ok(TestResult {
+        command: command.to_string(),
+        exit_code: output.status.code().unwrap_or(-1),
+        stdout: tail(&output.stdout),
+        stderr: tail(&output.stderr),
+    })
+}
+
+// Flash, then wait for the device to boot and run the test on it
+async fn ci<H: JobHost>(args: &CliArgs, host: &H, state: &Arc<AppState>, console: &Console, mut result: CliResult) -> CliResult {
+    result.profile = args.profile.clone();
+    let mut result = flash(args, host, state, console, result).await;
+    let Some(target) = args.ssh.as_ref().filter(|_| result.succeeded()) else {
+        return result;
+    };
+
+    let started = Instant::now();
+    if !wait_for_boot(target, args.boot_timeout_secs).await {
+        let message = format!("{} did not answer over SSH within {}s", target.host, args.boot_timeout_secs);
+        result.step("boot", started, false, message.clone());
+        return result.fail("boot_timeout", EXIT_BOOT_TIMEOUT, message);
+    }
+    result.step("boot", started, true, target.host.clone());
+
+    let Some(command) = &args.test_command else {
+        result.message = "Flashed and booted".to_string();
+        return result;
+    };
+    let started = Instant::now();
+    match run_test(target, command).await {
+        Ok(test) if test.exit_code == 0 => {
+            result.step("test", started, true, "");
+            result.test = Some(test);
+            result.message = "Flashed, booted and passed the test".to_string();
+            result
+        }
+        Ok(test) => {
+            let message = format!("Test command exited with {}", test.exit_code);
+            result.step("test", started, false, message.clone());
+            result.test = Some(test);
+            result.fail("test_failed", EXIT_TEST_FAILED, message)
+        }
+        Err(e) => {
+            result.step("test", started, false, e.clone());
+            result.fail("test_failed", EXIT_TEST_FAILED, e)
+        }
+    }
+}
+
+fn finish(mut result: CliResult, json: bool) -> i32 {
+    result.finished_at = Utc::now();
+    if json {
+        match serde_json::to_string_pretty(&result) {
+            Ok(document) => println!("{}", document),
+            Err(e) => eprintln!("Failed to write result: {}", e),
+        }
+    } else if result.succeeded() {
+        println!("{}", result.message);
+    } else {
+        eprintln!("{}", result.message);
+        for hint in result.failure.iter().flat_map(|failure| &failure.remediation) {
+            eprintln!("  - {}", hint);
+        }
+    }
+    result.exit_code
+}
+
+// A flash or ci request on the given job host, i.e. the app or a headless one
+async fn execute<H: JobHost>(mode: Mode, args: &[String], host: &H, state: &Arc<AppState>, console: &Console) -> CliResult {
+    let json = args.iter().any(|a| a == "--json");
+    let result = CliResult::new(Utc::now());
+    let mut parsed = match parse_args(mode, args).and_then(|parsed| prepare_args(mode, parsed, host)) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            let message = if json { e } else { format!("{}\n\n{}", e, USAGE) };
+            return result.fail("usage_error", EXIT_USAGE, message);
+        }
+    };
+    match mode {
+        Mode::Flash => match resolve_selection(&mut parsed).await {
+            Ok(()) => flash(&parsed, host, state, console, result).await,
+            Err(e) => result.fail("usage_error", EXIT_USAGE, e),
+        },
+        Mode::Ci => ci(&parsed, host, state, console, result).await,
+    }
+}
+
+// What the running app sends back to a forwarded request, one JSON document per line
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum CliReply {
+    Output { stream: String, line: String }, // 'stdout' | 'stderr'
+    Finished { result: Box<CliResult> },
+}
+
+#[cfg(unix)]
+async fn send_reply(writer: &mut tokio::net::unix::OwnedWriteHalf, reply: &CliReply) {
+    use tokio::io::AsyncWriteExt;
+    if let Ok(line) = serde_json::to_string(reply) {
+        let _ = writer.write_all(format!("{}\n", line).as_bytes()).await;
+    }
+}
+
+// Paths in the request are relative to this process, not to the app it is forwarded to
+#[cfg(unix)]
+fn forwarded_args(mode: Mode, args: &[String]) -> Vec<String> {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let mut forwarded = vec![mode.flag().to_string()];
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        forwarded.push(arg.clone());
+        if matches!(arg.as_str(), "--profile-file" | "--ssh-key") {
+            if let Some(path) = iter.next() {
+                forwarded.push(cwd.join(path).display().to_string());
+            }
+        }
+    }
+    forwarded
+}
+
+// Show a request's output and result as the running app reports them; Ctrl-C is passed on by closing
+// our side of the connection, and the app answers with the cancelled result
+#[cfg(unix)]
+async fn relay(stream: std::os::unix::net::UnixStream, json: bool) -> i32 {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+    let lost = |message: String| finish(CliResult::new(Utc::now()).fail("error", EXIT_ERROR, message), json);
+    let stream = match stream.set_nonblocking(true).and_then(|()| tokio::net::UnixStream::from_std(stream)) {
+        Ok(stream) => stream,
+        Err(e) => return lost(format!("Failed to reach the running CFU app: {}", e)),
+    };
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = tokio::io::BufReader::new(reader).lines();
+    let mut interrupted = false;
+    loop {
+        tokio::select! {
+            line = lines.next_line() => match line.map(|line| line.map(|line| serde_json::from_str::<CliReply>(&line))) {
+                Ok(Some(Ok(CliReply::Output { stream, line }))) => echo(&stream, &line, json),
+                Ok(Some(Ok(CliReply::Finished { result }))) => return finish(*result, json),
+                Ok(Some(Err(e))) => return lost(format!("Invalid reply from the running CFU app: {}", e)),
+                Ok(None) | Err(_) => return lost("Lost the connection to the running CFU app".to_string()),
+            },
+            _ = tokio::signal::ctrl_c(), if !interrupted => {
+                interrupted = true;
+                let _ = writer.shutdown().await;
+            }
+        }
+    }
+}
+
+// Answer a request forwarded by cfu-cli, see instance.rs: the job runs in this app, reported back over the connection
+#[cfg(unix)]
+pub async fn serve(app: tauri::AppHandle, args: Vec<String>, stream: std::os::unix::net::UnixStream) {
+    use tauri::Manager;
+    let Ok(stream) = stream.set_nonblocking(true).and_then(|()| tokio::net::UnixStream::from_std(stream)) else {
+        return;
+    };
+    let (reader, writer) = stream.into_split();
+    let console = Console::Forwarded {
+        writer: tokio::sync::Mutex::new(writer),
+        reader: tokio::sync::Mutex::new(reader),
+    };
+    let state = Arc::clone(&app.state::<Arc<AppState>>());
+    let result = match args.first().map(String::as_str) {
+        Some("--flash") => execute(Mode::Flash, &args[1..], &app, &state, &console).await,
+        Some("--ci") => execute(Mode::Ci, &args[1..], &app, &state, &console).await,
+        _ => CliResult::new(Utc::now()).fail("usage_error", EXIT_USAGE, format!("Unsupported request {:?}", args)),
+    };
+    if let Console::Forwarded { writer, .. } = &console {
+        send_reply(&mut *writer.lock().await, &CliReply::Finished { result: Box::new(result) }).await;
+    }
+}
+
+fn run(mode: Mode, args: &[String]) -> i32 {
+    let json = args.iter().any(|a| a == "--json");
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            let result = CliResult::new(Utc::now()).fail("error", EXIT_ERROR, format!("Failed to start runtime: {}", e));
+            return finish(result, json);
+        }
+    };
+    // A running app owns the boards and its jobs, so the job is run there rather than beside it
+    #[cfg(unix)]
+    if let Some(stream) = crate::instance::forward_request(forwarded_args(mode, args)) {
+        eprintln!("CFU is running, the job runs in the app");
+        return runtime.block_on(relay(stream, json));
+    }
+    let host = match headless_host() {
+        Ok(host) => host,
+        Err(e) => return finish(CliResult::new(Utc::now()).fail("error", EXIT_ERROR, e), json),
+    };
+    let state = headless_state(&host);
+    let result = runtime.block_on(execute(mode, args, &host, &state, &Console::Terminal { json }));
+    finish(result, json)
+}
+
+fn parse_spec_args(args: &[String]) -> Result<(String, JobVariables, bool), String> {
+    let (mut path, mut vars, mut json) = (None, JobVariables::default(), false);
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().cloned().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--batch-file" => vars.batch_file = Some(value()?),
+            "--serial" => vars.serial = Some(value()?),
+            "--slot" => vars.slot = Some(value()?),
+            "--index" => vars.index = Some(value()?.parse().map_err(|_| "--index needs a number")?),
+            "--operator" => vars.operator = Some(value()?),
+            "--json" => json = true,
+            other if !other.starts_with("--") && path.is_none() => path = Some(other.to_string()),
+            _ => return Err(format!("Unknown argument: {}", arg)),
+        }
+    }
+    let path = path.ok_or("validate-spec needs the path of a spec")?;
+    Ok((path, vars, json))
+}
+
+// Lint a device spec without a device, for one unit of a batch when its variables are given: the lint
+// document on stdout with --json, else one line per issue
+fn validate_spec(args: &[String]) -> i32 {
+    let (path, vars, json) = match parse_spec_args(args) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+    let lint = tokio::runtime::Runtime::new().map_err(|e| e.to_string()).and_then(|runtime| {
+        runtime.block_on(async {
+            let spec = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+            let matrix = read_matrix().await?;
+            Ok(crate::device_spec::lint(&spec, &vars, &matrix, &version_matrix::VersionMatrix::bundled()))
+        })
+    });
+    let lint = match lint {
+        Ok(lint) => lint,
+        Err(e) => {
+            eprintln!("{}", e);
+            return EXIT_ERROR;
+        }
+    };
+    if json {
+        match serde_json::to_string_pretty(&lint) {
+            Ok(document) => println!("{}", document),
+            Err(e) => eprintln!("Failed to write result: {}", e),
+        }
+    } else {
+        for (severity, issues) in [("error", &lint.errors), ("warning", &lint.warnings)] {
+            for issue in issues {
+                eprintln!("{}: {}: {}", path, severity, issue);
+            }
+        }
+        println!("{}: {} errors, {} warnings", path, lint.errors.len(), lint.warnings.len());
+    }
+    if lint.valid {
+        EXIT_SUCCESS
+    } else {
+        EXIT_SPEC_INVALID
+    }
+}
+
+// `cfu --flash ...` / `cfu --ci ...` on the app binary; returns the exit code, or None when the app was started normally
+pub fn run_from_args() -> Option<i32> {
+    let args: Vec<String> = std::env::args().collect();
+    let mode = match args.get(1).map(String::as_str) {
+        Some("--flash") => Mode::Flash,
+        Some("--ci") => Mode::Ci,
+        Some("--validate-spec") => return Some(validate_spec(&args[2..])),
+        _ => return None,
+    };
+    Some(run(mode, &args[2..]))
+}
+
+// The cfu-cli binary: `cfu-cli flash ...` / `cfu-cli ci ...`. It is also the extractor and checksum
+// helper of the flash script it runs, like the app binary.
+pub fn main() -> i32 {
+    if let Some(code) = crate::extract::run_from_args() {
+        return code;
+    }
+    if let Some(code) = crate::release_checksums::run_from_args() {
+        return code;
+    }
+    if let Some(code) = crate::rootfs_snapshot::run_from_args() {
+        return code;
+    }
+    let args: Vec<String> = std::env::args().collect();
+    let mode = match args.get(1).map(String::as_str) {
+        Some("flash" | "--flash") => Mode::Flash,
+        Some("ci" | "--ci") => Mode::Ci,
+        Some("validate-spec" | "--validate-spec") => return validate_spec(&args[2..]),
+        Some("help" | "--help" | "-h") => {
+            println!("{}", USAGE);
+            return EXIT_SUCCESS;
+        }
+        Some(other) => {
+            eprintln!("Unknown command: {}\n\n{}", other, USAGE);
+            return EXIT_USAGE;
+        }
+        None => {
+            eprintln!("{}", USAGE);
+            return EXIT_USAGE;
+        }
+    };
+    run(mode, &args[2..])
+}
@@ -0,0 +1,160 @@
+// CFU - Cordatus Flash Utility - Connectivity Checks
+// Reachability of NVIDIA download servers / container registries and bandwidth estimation
+
+use crate::AppState;
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{command, State};
+
+// BSP + sample rootfs for a typical JetPack release
+pub const TYPICAL_JETPACK_DOWNLOAD_BYTES: u64 = 2_400_000_000;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+const BANDWIDTH_SAMPLE_TIME: Duration = Duration::from_secs(8);
+const BANDWIDTH_SAMPLE_BYTES: u64 = 64 * 1024 * 1024;
+
+// A large file on NVIDIA's CDN, only partially downloaded to sample throughput
+const DEFAULT_BANDWIDTH_PROBE_URL: &str =
+    "https://developer.nvidia.com/downloads/embedded/l4t/r36_release_v4.3/release/Jetson_Linux_r36.4.3_aarch64.tbz2";
+
+const ENDPOINTS: &[(&str, &str, &str)] = &[
+    ("NVIDIA Developer", "nvidia", "https://developer.nvidia.com"),
+    ("NVIDIA Downloads", "nvidia", "https://developer.download.nvidia.com"),
+    ("NVIDIA NGC Registry", "registry", "https://nvcr.io/v2/"),
+    ("Docker Hub Registry", "registry", "https://registry-1.docker.io/v2/"),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EndpointStatus {
+    pub name: String,
+    pub category: String, // 'nvidia' | 'registry'
+    pub url: String,
+    pub reachable: bool,
+    pub status_code: Option<u16>,
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectivityReport {
+    pub endpoints: Vec<EndpointStatus>,
+    pub bandwidth_bytes_per_sec: Option<f64>,
+    pub estimated_download_seconds: Option<u64>,
+    pub checked_at: DateTime<Utc>,
+}
+
+// Estimated seconds to download the given number of bytes at a measured rate
+pub fn download_eta_seconds(bytes: u64, bandwidth_bytes_per_sec: f64) -> Option<u64> {
+    if bandwidth_bytes_per_sec > 0.0 {
+        Some((bytes as f64 / bandwidth_bytes_per_sec).ceil() as u64)
+    } else {
+        None
+    }
+}
+
+async fn probe_endpoint(client: &reqwest::Client, name: &str, category: &str, url: &str) -> EndpointStatus {
+    let started = Instant::now();
+    let result = client.get(url).send().await;
+
+    // Any HTTP answer (registries reply 401 to anonymous /v2/) means the host is reachable
+    let (reachable, status_code, error) = match result {
+        Ok(response) => (true, Some(response.status().as_u16()), None),
+        Err(e) => (false, None, Some(e.to_string())),
+    };
+
+    EndpointStatus {
+        name: name.to_string(),
+        category: category.to_string(),
+        url: url.to_string(),
+        reachable,
+        status_code,
+        latency_ms: reachable.then(|| started.elapsed().as_millis() as u64),
+        error,
+    }
+}
+
+// Download part of a large file and compute the sustained rate. Every wait is bounded: the answer by
+// PROBE_TIMEOUT, each chunk by what is left of the sample time, so a stalled server ends the sample.
+async fn measure_bandwidth(client: &reqwest::Client, url: &str) -> anyhow::Result<f64> {
+    let mut response = tokio::time::timeout(PROBE_TIMEOUT, client.get(url).send())
+        .await
+        .map_err(|_| anyhow::anyhow!("Bandwidth probe did not answer within {}s", PROBE_TIMEOUT.as_secs()))??
+        .error_for_status()?;
+    let started = Instant::now();
+    let remaining = || BANDWIDTH_SAMPLE_TIME.saturating_sub(started.elapsed());
+    let mut received: u64 = 0;
+
+    while let Ok(chunk) = tokio::time::timeout(remaining(), response.chunk()).await {
+        let Some(chunk) = chunk? else {
+            break;
+        };
+        received += chunk.len() as u64;
+        if started.elapsed() >= BANDWIDTH_SAMPLE_TIME || received >= BANDWIDTH_SAMPLE_BYTES {
+            break;
+        }
+    }
+
+    let elapsed = started.elapsed().as_secs_f64();
+    if received == 0 || elapsed <= 0.0 {
+        return Err(anyhow::anyhow!("No data received from bandwidth probe"));
+    }
+    Ok(received as f64 / elapsed)
+}
+
+// Check reachability of download servers and registries and measure bandwidth
+#[command]
+pub async fn check_connectivity(state: State<'_, Arc<AppState>>) -> Result<ConnectivityReport, String> {
+    info!("Running connectivity check...");
+
+    let probe_client = reqwest::Client::builder()
+        .timeout(PROBE_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let mut endpoints = Vec::new();
+    for (name, category, url) in ENDPOINTS {
+        endpoints.push(probe_endpoint(&probe_client, name, category, url).await);
+    }
+
+    let nvidia_reachable = endpoints.iter().any(|e| e.category == "nvidia" && e.reachable);
+    let bandwidth_bytes_per_sec = if nvidia_reachable {
+        let probe_url = {
+            let settings = state.settings.lock().unwrap();
+            if settings.bandwidth_probe_url.is_empty() {
+                DEFAULT_BANDWIDTH_PROBE_URL.to_string()
+            } else {
+                settings.bandwidth_probe_url.clone()
+            }
+        };
+        // The sample is time-bounded by measure_bandwidth, an overall request timeout would cut it short
+        let bandwidth_client = reqwest::Client::builder()
+            .connect_timeout(PROBE_TIMEOUT)
+            .build()
+            .map_err(|e| e.to_string())?;
+        match measure_bandwidth(&bandwidth_client, &probe_url).await {
+            Ok(rate) => Some(rate),
+            Err(e) => {
+                warn!("Bandwidth measurement failed: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    if let Some(rate) = bandwidth_bytes_per_sec {
+        info!("Measured download bandwidth: {:.1} MB/s", rate / 1_000_000.0);
+        *state.download_bandwidth.lock().unwrap() = Some(rate);
+    }
+
+    Ok(ConnectivityReport {
+        endpoints,
+        bandwidth_bytes_per_sec,
+        estimated_download_seconds: bandwidth_bytes_per_sec
+            .and_then(|rate| download_eta_seconds(TYPICAL_JETPACK_DOWNLOAD_BYTES, rate)),
+        checked_at: Utc::now(),
+    })
+}
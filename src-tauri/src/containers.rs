@@ -0,0 +1,410 @@
+// CFU - Cordatus Flash Utility - Container Builds
+// Custom image builds with BuildKit progress streaming and run presets for deploying to host or target
+
+use crate::fleet;
+use crate::models;
+use crate::ssh::{run_remote, shell_quote, SshTarget, SUDO};
+use crate::AppState;
+use anyhow::{Context, Result};
+use log::info;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::process::Stdio;
+use std::sync::Arc;
+use tauri::{command, Emitter, State};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command as TokioCommand;
+use uuid::Uuid;
+
+const DEFAULT_PLATFORM: &str = "linux/arm64";
+const PRESETS_FILE: &str = "container_presets.json";
+
+// Use sudo only where the login user is not in the docker group
+const REMOTE_DOCKER: &str = r#"D=docker; docker info >/dev/null 2>&1 || D="sudo -n docker"; "#;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContainerBuildResult {
+    pub build_id: String,
+    pub image: String,
+    pub platform: String,
+    pub pushed: bool,
+    pub digest: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContainerBuildProgress {
+    pub build_id: String,
+    pub line: String,
+    pub step: Option<u32>,  // Current Dockerfile step from "[stage 3/7]"
+    pub total: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortMapping {
+    pub host: u16,
+    pub container: u16,
+    #[serde(default = "default_protocol")]
+    pub protocol: String, // 'tcp' | 'udp'
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VolumeMount {
+    pub host_path: String,
+    pub container_path: String,
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+// How to run a container image, used for both host and target deployments
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContainerPreset {
+    pub name: String, // Also the container name
+    pub image: String,
+    #[serde(default)]
+    pub ports: Vec<PortMapping>,
+    #[serde(default)]
+    pub volumes: Vec<VolumeMount>,
+    #[serde(default)]
+    pub devices: Vec<String>, // e.g. "/dev/video0"
+    #[serde(default)]
+    pub env: Vec<String>, // "KEY=value"
+    #[serde(default = "default_true")]
+    pub nvidia_runtime: bool,
+    #[serde(default = "default_restart_policy")]
+    pub restart_policy: String, // 'no' | 'always' | 'unless-stopped' | 'on-failure'
+    #[serde(default)]
+    pub command: Vec<String>,
+    #[serde(default)]
+    pub assets: Vec<models::ModelAsset>, // Placed on the target before the container starts
+}
+
+fn default_protocol() -> String {
+    "tcp".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_restart_policy() -> String {
+    "unless-stopped".to_string()
+}
+
+impl ContainerPreset {
+    pub fn validate(&self) -> Result<()> {
+        let name_ok = self.name.chars().next().is_some_and(|c| c.is_ascii_alphanumeric())
+            && self.name.chars().all(|c| c.is_ascii_alphanumeric() || "_.-".contains(c));
+        if !name_ok {
+            return Err(anyhow::anyhow!("Invalid container name: {}", self.name));
+        }
+        if self.image.trim().is_empty() {
+            return Err(anyhow::anyhow!("Preset {} has no image", self.name));
+        }
+        if !["no", "always", "unless-stopped", "on-failure"].contains(&self.restart_policy.as_str()) {
+            return Err(anyhow::anyhow!("Unknown restart policy: {}", self.restart_policy));
+        }
+        if let Some(port) = self.ports.iter().find(|p| p.protocol != "tcp" && p.protocol != "udp") {
+            return Err(anyhow::anyhow!("Unknown port protocol: {}", port.protocol));
+        }
+        if let Some(asset) = self.assets.iter().find(|a| !a.target_path.starts_with('/')) {
+            return Err(anyhow::anyhow!("Asset {} needs an absolute target path", asset.name));
+        }
+        Ok(())
+    }
+
+    // Arguments for `docker run`; a foreground container is supervised by
+    // systemd instead of the docker restart policy
+    pub fn run_args(&self, detached: bool) -> Vec<String> {
+        let mut args = vec!["run".to_string(), "--name".to_string(), self.name.clone()];
+        if detached {
+            args.push("-d".to_string());
+            args.push(format!("--restart={}", self.restart_policy));
+        } else {
+            args.push("--rm".to_string());
+        }
+        if self.nvidia_runtime {
+            args.push("--runtime=nvidia".to_string());
+        }
+        for port in &self.ports {
+            args.push("-p".to_string());
+            args.push(format!("{}:{}/{}", port.host, port.container, port.protocol));
+        }
+        for volume in &self.volumes {
+            args.push("-v".to_string());
+            args.push(format!(
+                "{}:{}{}",
+                volume.host_path,
+                volume.container_path,
+                if volume.read_only { ":ro" } else { "" }
+            ));
+        }
+        for device in &self.devices {
+            args.push(format!("--device={}", device));
+        }
+        for var in &self.env {
+            args.push("-e".to_string());
+            args.push(var.clone());
+        }
+        args.push(self.image.clone());
+        args.extend(self.command.iter().cloned());
+        args
+    }
+
+    pub fn unit_name(&self) -> String {
+        format!("cfu-container-{}.service", self.name)
+    }
+
+    // systemd unit running the container in the foreground
+    pub fn systemd_unit(&self) -> String {
+        let exec: Vec<String> = self.run_args(false).iter().map(|arg| systemd_quote(arg)).collect();
+        format!(
+            "[Unit]\nDescription=CFU container {name}\nAfter=docker.service network-online.target\nRequires=docker.service\n\n\
+             [Service]\nRestart=always\nRestartSec=5\nExecStartPre=-/usr/bin/docker rm -f {name}\n\
+             ExecStart=/usr/bin/docker {exec}\nExecStop=/usr/bin/docker stop {name}\n\n\
+             [Install]\nWantedBy=multi-user.target\n",
+            name = self.name,
+            exec = exec.join(" ")
+        )
+    }
+}
+
+// Quote an argument for an Exec= line: systemd expands $ and % specifiers itself
+fn systemd_quote(arg: &str) -> String {
+    let escaped = arg.replace('\\', "\\\\").replace('"', "\\\"").replace('$', "$$").replace('%', "%%");
+    if escaped.contains(char::is_whitespace) || escaped.is_empty() {
+        format!("\"{}\"", escaped)
+    } else {
+        escaped
+    }
+}
+
+pub fn load_presets(app: &tauri::AppHandle) -> Vec<ContainerPreset> {
+    crate::app_data_file(app, PRESETS_FILE)
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_presets(app: &tauri::AppHandle, presets: &[ContainerPreset]) -> Result<()> {
+    let path = crate::app_data_file(app, PRESETS_FILE)?;
+    std::fs::write(path, serde_json::to_string_pretty(presets)?).context("Failed to save container presets")
+}
+
+// Frozen copies while the station is frozen
+pub fn find_preset(app: &tauri::AppHandle, name: &str) -> Result<ContainerPreset> {
+    crate::freeze::container_presets(app)
+        .into_iter()
+        .find(|preset| preset.name == name)
+        .with_context(|| format!("Container preset not found: {}", name))
+}
+
+// Install the preset as an enabled systemd unit on the target and start it
+async fn deploy_systemd_unit(preset: &ContainerPreset, target: &SshTarget) -> Result<String> {
+    let unit = preset.unit_name();
+    let script = format!(
+        "{sudo}set -e; $S docker rm -f {name} >/dev/null 2>&1 || true; \
+         printf '%s' {content} | $S tee /etc/systemd/system/{unit} >/dev/null; \
+         $S systemctl daemon-reload; $S systemctl enable --now docker {unit}",
+        sudo = SUDO,
+        name = shell_quote(&preset.name),
+        content = shell_quote(&preset.systemd_unit()),
+        unit = unit
+    );
+    run_remote(target, &script).await?;
+    Ok(unit)
+}
+
+// Replace any container with the same name, then start it from the preset
+async fn deploy_preset(preset: &ContainerPreset, target: Option<&SshTarget>) -> Result<String> {
+    let args = preset.run_args(true);
+    match target {
+        Some(target) => {
+            let quoted: Vec<String> = args.iter().map(|arg| shell_quote(arg)).collect();
+            // Make sure the docker daemon itself comes up on boot
+            let script = format!(
+                "{}{}$S systemctl enable docker >/dev/null 2>&1; $D rm -f {} >/dev/null 2>&1; $D {}",
+                SUDO,
+                REMOTE_DOCKER,
+                shell_quote(&preset.name),
+                quoted.join(" ")
+            );
+            Ok(run_remote(target, &script).await?.trim().to_string())
+        }
+        None => {
+            TokioCommand::new("docker").args(["rm", "-f", &preset.name]).output().await.ok();
+            let output = TokioCommand::new("docker").args(&args).output().await.context("Failed to run docker")?;
+            if !output.status.success() {
+                return Err(anyhow::anyhow!("docker run failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+            }
+            Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        }
+    }
+}
+
+// BuildKit plain output: "#8 [builder 3/7] RUN apt-get update"
+fn parse_step(line: &str, step_regex: &Regex) -> (Option<u32>, Option<u32>) {
+    match step_regex.captures(line) {
+        Some(caps) => (caps[1].parse().ok(), caps[2].parse().ok()),
+        None => (None, None),
+    }
+}
+
+async fn run_build(
+    app: &tauri::AppHandle,
+    build_id: &str,
+    context_path: &str,
+    dockerfile: Option<&str>,
+    image: &str,
+    platform: &str,
+    push: bool,
+) -> Result<Option<String>> {
+    let metadata_file = std::env::temp_dir().join(format!("cfu-build-{}.json", build_id));
+
+    let mut cmd = TokioCommand::new("docker");
+    cmd.args(["buildx", "build", "--progress=plain", "--platform", platform, "-t", image])
+        .arg("--metadata-file")
+        .arg(&metadata_file)
+        .arg(if push { "--push" } else { "--load" });
+    if let Some(dockerfile) = dockerfile {
+        cmd.args(["-f", dockerfile]);
+    }
+    cmd.arg(context_path)
+        .env("DOCKER_BUILDKIT", "1")
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+
+    let mut child = cmd.spawn().context("Failed to start docker buildx")?;
+    let step_regex = Regex::new(r"^#\d+ \[[^\]]*?(\d+)/(\d+)\]")?;
+    let mut tail = Vec::new();
+
+    // BuildKit writes its progress to stderr
+    if let Some(stderr) = child.stderr.take() {
+        let mut lines = BufReader::new(stderr).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let (step, total) = parse_step(&line, &step_regex);
+            let _ = app.emit("container-build-progress", ContainerBuildProgress {
+                build_id: build_id.to_string(),
+                line: line.clone(),
+                step,
+                total,
+            });
+            tail.push(line);
+            if tail.len() > 20 {
+                tail.remove(0);
+            }
+        }
+    }
+
+    let status = child.wait().await?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("docker build failed:\n{}", tail.join("\n")));
+    }
+
+    let digest = std::fs::read_to_string(&metadata_file)
+        .ok()
+        .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+        .and_then(|metadata| metadata["containerimage.digest"].as_str().map(str::to_string));
+    std::fs::remove_file(&metadata_file).ok();
+    Ok(digest)
+}
+
+// Build an image for Jetson targets (cross-built through QEMU on x86 hosts) and optionally push it
+#[command]
+pub async fn build_container(
+    context_path: String,
+    dockerfile: Option<String>,
+    image: String,
+    platform: Option<String>,
+    push: bool,
+    app: tauri::AppHandle,
+) -> Result<ContainerBuildResult, String> {
+    let build_id = Uuid::new_v4().to_string();
+    let platform = platform.unwrap_or_else(|| DEFAULT_PLATFORM.to_string());
+    info!("Building container {} ({}) from {}", image, platform, context_path);
+
+    let digest = run_build(&app, &build_id, &context_path, dockerfile.as_deref(), &image, &platform, push)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(ContainerBuildResult {
+        build_id,
+        image,
+        platform,
+        pushed: push,
+        digest,
+    })
+}
+
+// List saved container run presets
+#[command]
+pub async fn list_container_presets(app: tauri::AppHandle) -> Result<Vec<ContainerPreset>, String> {
+    Ok(crate::freeze::container_presets(&app))
+}
+
+// Create or replace a container run preset
+#[command]
+pub async fn save_container_preset(preset: ContainerPreset, app: tauri::AppHandle) -> Result<(), String> {
+    crate::freeze::ensure_not_frozen(&app, "Container preset changes")?;
+    preset.validate().map_err(|e| e.to_string())?;
+    let mut presets = load_presets(&app);
+    presets.retain(|p| p.name != preset.name);
+    presets.push(preset);
+    save_presets(&app, &presets).map_err(|e| e.to_string())
+}
+
+// Delete a container run preset
+#[command]
+pub async fn delete_container_preset(name: String, app: tauri::AppHandle) -> Result<(), String> {
+    crate::freeze::ensure_not_frozen(&app, "Container preset changes")?;
+    let mut presets = load_presets(&app);
+    presets.retain(|p| p.name != name);
+    save_presets(&app, &presets).map_err(|e| e.to_string())
+}
+
+// Run a preset on this host, or on a booted device when a target is given.
+// `autostart` on a target: 'restart-policy' (docker restarts it on boot) or
+// 'systemd' (an enabled unit owns the container). Returns the container ID or unit name.
+#[command]
+pub async fn deploy_container(
+    preset_name: String,
+    target: Option<SshTarget>,
+    autostart: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<String, String> {
+    let mut preset = find_preset(&app, &preset_name).map_err(|e| e.to_string())?;
+    preset.validate().map_err(|e| e.to_string())?;
+    info!(
+        "Deploying container preset {} to {}",
+        preset.name,
+        target.as_ref().map(|t| t.host.as_str()).unwrap_or("host")
+    );
+
+    if let Some(target) = &target {
+        for asset in &preset.assets {
+            models::place_asset(&app, &preset.name, asset, target)
+                .await
+                .map_err(|e| format!("Failed to place {}: {}", asset.name, e))?;
+        }
+    }
+
+    let result = match (autostart.as_deref(), target.as_ref()) {
+        (Some("systemd"), Some(target)) => deploy_systemd_unit(&preset, target).await,
+        (Some("systemd"), None) => return Err("systemd auto-start is only available for target deployments".to_string()),
+        (Some("restart-policy"), _) => {
+            if preset.restart_policy == "no" {
+                preset.restart_policy = "unless-stopped".to_string();
+            }
+            deploy_preset(&preset, target.as_ref()).await
+        }
+        (Some(other), _) => return Err(format!("Unknown auto-start mode: {}", other)),
+        (None, _) => deploy_preset(&preset, target.as_ref()).await,
+    };
+    let container = result.map_err(|e| format!("Failed to deploy {}: {}", preset.name, e))?;
+
+    if let Some(target) = &target {
+        fleet::record_deployment(&app, &state, &preset, target, autostart.as_deref(), &container);
+    }
+    Ok(container)
+}
@@ -0,0 +1,385 @@
+// CFU - Cordatus Flash Utility - Control API
+// Optional REST/WebSocket server for driving a station from the factory network; every request
+// needs a bearer token, read-only (observer) tokens see state and events, flash tokens start and cancel
+// jobs. Served over TLS with a provided certificate or one the app generates.
+
+use crate::history::FlashJobRecord;
+use crate::settings::ControlApiSettings;
+use crate::{api_tokens, lab, AppState, FlashCommand, FlashProgress, JetsonDevice};
+use anyhow::{Context, Result};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, Query, State};
+use axum::http::header::AUTHORIZATION;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use axum_server::tls_rustls::RustlsConfig;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tauri::{command, Listener, Manager};
+use tokio::sync::broadcast;
+
+const CERT_DIR: &str = "control_api";
+
+// App events forwarded to WebSocket subscribers
+const OBSERVED_EVENTS: &[&str] = &[
+    "flash-progress",
+    "flash-progress-update",
+    "flash-compatibility-warning",
+    "scheduled-job-started",
+    "station-failure-alert",
+    "prefetch-progress",
+    "release-download-progress",
+    "image-progress",
+    "maintenance-status",
+];
+
+type ApiError = (StatusCode, String);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiCertificate {
+    pub pem: String,
+    pub sha256_fingerprint: String, // For clients that pin the certificate
+    pub self_signed: bool,
+}
+
+#[derive(Clone)]
+struct ApiContext {
+    app: tauri::AppHandle,
+    state: Arc<AppState>,
+    events: broadcast::Sender<String>,
+    observer_only: bool,
+}
+
+impl ApiContext {
+    // Unknown tokens are 401, known tokens without the scope 403. In observer mode nothing
+    // can start or cancel jobs, whatever the token allows.
+    fn authorize(&self, bearer: Option<&str>, scope: &str) -> Result<(), ApiError> {
+        if self.observer_only && scope != "read" {
+            return Err((StatusCode::FORBIDDEN, "The control API is in observer mode".to_string()));
+        }
+        let bearer = bearer.ok_or((StatusCode::UNAUTHORIZED, "Missing bearer token".to_string()))?;
+        let token = api_tokens::find_token(&self.app, bearer)
+            .ok_or((StatusCode::UNAUTHORIZED, "Unknown or revoked API token".to_string()))?;
+        if !token.allows(scope) {
+            return Err((StatusCode::FORBIDDEN, format!("Token '{}' does not allow {} access", token.name, scope)));
+        }
+        Ok(())
+    }
+}
+
+fn bearer(headers: &HeaderMap) -> Option<&str> {
+    headers.get(AUTHORIZATION)?.to_str().ok()?.strip_prefix("Bearer ")
+}
+
+// Start the server in the background when enabled in settings
+pub fn start(app: tauri::AppHandle) {
+    let state = Arc::clone(app.state::<Arc<AppState>>().inner());
+    let settings = state.settings.lock().unwrap().control_api.clone();
+    if !settings.enabled {
+        return;
+    }
+
+    let (events, _) = broadcast::channel(256);
+    for name in OBSERVED_EVENTS {
+        let sender = events.clone();
+        app.listen_any(*name, move |event| {
+            let payload: serde_json::Value = serde_json::from_str(event.payload()).unwrap_or_default();
+            // No subscribers is not an error
+            let _ = sender.send(serde_json::json!({ "event": name, "payload": payload }).to_string());
+        });
+    }
+    let context = ApiContext {
+        app,
+        state,
+        events,
+        observer_only: settings.observer_only,
+    };
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = serve(context, &settings).await {
+            warn!("Control API unavailable: {:#}", e);
+        }
+    });
+}
+
+fn self_signed_paths(app: &tauri::AppHandle) -> Result<(PathBuf, PathBuf)> {
+    let dir = crate::app_data_file(app, CERT_DIR)?;
+    std::fs::create_dir_all(&dir).context("Failed to create certificate directory")?;
+    Ok((dir.join("certificate.pem"), dir.join("private_key.pem")))
+}
+
+// A certificate for localhost, this host's names and the bind address
+fn generate_self_signed(app: &tauri::AppHandle, bind_address: &str) -> Result<(PathBuf, PathBuf)> {
+    let mut names = vec!["localhost".to_string(), "127.0.0.1".to_string()];
+    if let Ok(host) = sys_info::hostname() {
+        names.push(format!("{}.local", host));
+        names.push(host);
+    }
+    if !matches!(bind_address, "0.0.0.0" | "127.0.0.1" | "::") {
+        names.push(bind_address.to_string());
+    }
+    let generated = rcgen::generate_simple_self_signed(names).context("Failed to generate certificate")?;
+
+    let (cert_path, key_path) = self_signed_paths(app)?;
+    std::fs::write(&key_path, generated.key_pair.serialize_pem()).context("Failed to write private key")?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&key_path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    std::fs::write(&cert_path, generated.cert.pem()).context("Failed to write certificate")?;
+    info!("Generated self-signed control API certificate {}", cert_path.display());
+    Ok((cert_path, key_path))
+}
+
+// Certificate and key to serve with, or None for plain HTTP
+pub fn tls_files(app: &tauri::AppHandle, settings: &ControlApiSettings) -> Result<Option<(PathBuf, PathBuf)>> {
+    match settings.tls.as_str() {
+        "provided" => {
+            if settings.certificate_path.is_empty() || settings.private_key_path.is_empty() {
+                return Err(anyhow::anyhow!("TLS needs a certificate and private key file"));
+            }
+            Ok(Some((PathBuf::from(&settings.certificate_path), PathBuf::from(&settings.private_key_path))))
+        }
+        "self_signed" => {
+            let (cert_path, key_path) = self_signed_paths(app)?;
+            if cert_path.is_file() && key_path.is_file() {
+                Ok(Some((cert_path, key_path)))
+            } else {
+                generate_self_signed(app, &settings.bind_address).map(Some)
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+async fn serve(context: ApiContext, settings: &ControlApiSettings) -> Result<()> {
+    let tls = tls_files(&context.app, settings)?;
+    let (bind_address, port) = (settings.bind_address.as_str(), settings.port);
+    let router = Router::new()
+        .route("/api/status", get(status))
+        .route("/api/devices", get(list_devices))
+        .route("/api/history", get(list_history))
+        .route("/api/flashes", get(list_flashes).post(start_flash))
+        .route("/api/flashes/:id", get(get_flash).delete(cancel_flash))
+        .route("/api/lab", get(lab_state))
+        .route("/api/fixtures", get(list_fixtures))
+        .route("/api/fixtures/:name/:action", post(control_fixture))
+        .route("/api/events", get(events))
+        .with_state(context);
+
+    if let Some((cert_path, key_path)) = tls {
+        let config = RustlsConfig::from_pem_file(&cert_path, &key_path)
+            .await
+            .with_context(|| format!("Failed to load TLS certificate {}", cert_path.display()))?;
+        let address = tokio::net::lookup_host((bind_address, port))
+            .await?
+            .next()
+            .with_context(|| format!("Cannot resolve {}", bind_address))?;
+        info!("Control API listening on https://{}", address);
+        axum_server::bind_rustls(address, config).serve(router.into_make_service()).await?;
+        return Ok(());
+    }
+
+    let listener = tokio::net::TcpListener::bind((bind_address, port))
+        .await
+        .with_context(|| format!("Failed to listen on {}:{}", bind_address, port))?;
+    info!("Control API listening on http://{}:{}", bind_address, port);
+    axum::serve(listener, router).await?;
+    Ok(())
+}
+
+async fn status(State(context): State<ApiContext>, headers: HeaderMap) -> Result<Json<serde_json::Value>, ApiError> {
+    context.authorize(bearer(&headers), "read")?;
+    let running: Vec<String> = context.state.flash_progress.lock().unwrap().keys().cloned().collect();
+    Ok(Json(serde_json::json!({
+        "station": crate::history::station_name(&context.state),
+        "running_flashes": running,
+        "observer_only": context.observer_only,
+    })))
+}
+
+async fn list_devices(State(context): State<ApiContext>, headers: HeaderMap) -> Result<Json<Vec<JetsonDevice>>, ApiError> {
+    context.authorize(bearer(&headers), "read")?;
+    Ok(Json(context.state.connected_devices.lock().unwrap().values().cloned().collect()))
+}
+
+#[derive(Deserialize)]
+struct HistoryParams {
+    limit: Option<usize>,
+}
+
+async fn list_history(
+    State(context): State<ApiContext>,
+    headers: HeaderMap,
+    Query(params): Query<HistoryParams>,
+) -> Result<Json<Vec<FlashJobRecord>>, ApiError> {
+    context.authorize(bearer(&headers), "read")?;
+    let history = context.state.history.lock().unwrap();
+    let limit = params.limit.unwrap_or(50);
+    Ok(Json(history.jobs.iter().rev().take(limit).cloned().collect()))
+}
+
+async fn list_flashes(
+    State(context): State<ApiContext>,
+    headers: HeaderMap,
+) -> Result<Json<HashMap<String, FlashProgress>>, ApiError> {
+    context.authorize(bearer(&headers), "read")?;
+    Ok(Json(context.state.flash_progress.lock().unwrap().clone()))
+}
+
+async fn get_flash(
+    State(context): State<ApiContext>,
+    headers: HeaderMap,
+    Path(flash_id): Path<String>,
+) -> Result<Json<FlashProgress>, ApiError> {
+    context.authorize(bearer(&headers), "read")?;
+    let progress = context.state.flash_progress.lock().unwrap().get(&flash_id).cloned();
+    progress
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, format!("No running flash {}", flash_id)))
+}
+
+async fn start_flash(
+    State(context): State<ApiContext>,
+    headers: HeaderMap,
+    Json(command): Json<FlashCommand>,
+) -> Result<Response, ApiError> {
+    context.authorize(bearer(&headers), "flash")?;
+    let flash_id = crate::launch_flash(command, Arc::clone(&context.state), context.app.clone())
+        .await
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e))?;
+    Ok((StatusCode::ACCEPTED, Json(serde_json::json!({ "flash_id": flash_id }))).into_response())
+}
+
+#[derive(Deserialize)]
+struct CancelParams {
+    mode: Option<String>, // 'hard' (default) | 'soft'
+}
+
+async fn cancel_flash(
+    State(context): State<ApiContext>,
+    headers: HeaderMap,
+    Path(flash_id): Path<String>,
+    Query(params): Query<CancelParams>,
+) -> Result<StatusCode, ApiError> {
+    context.authorize(bearer(&headers), "flash")?;
+    match params.mode.as_deref().unwrap_or("hard") {
+        "hard" => crate::cancel_flash(&context.app, &context.state, &flash_id).await,
+        "soft" => {
+            crate::soft_cancel_flash(&context.app, &context.state, &flash_id)
+                .map_err(|e| (StatusCode::CONFLICT, e))?;
+            // The job keeps running until the script reaches its next checkpoint
+            return Ok(StatusCode::ACCEPTED);
+        }
+        other => return Err((StatusCode::BAD_REQUEST, format!("Unknown cancel mode: {}", other))),
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn lab_state(State(context): State<ApiContext>, headers: HeaderMap) -> Result<Json<lab::LabState>, ApiError> {
+    context.authorize(bearer(&headers), "read")?;
+    Ok(Json(lab::lab_state(&context.state)))
+}
+
+async fn list_fixtures(State(context): State<ApiContext>, headers: HeaderMap) -> Result<Json<Vec<lab::LabFixture>>, ApiError> {
+    context.authorize(bearer(&headers), "read")?;
+    Ok(Json(lab::list_fixtures(&context.state)))
+}
+
+// Power and recovery hooks for lab schedulers; switching a fixture's power can interrupt a flash
+async fn control_fixture(
+    State(context): State<ApiContext>,
+    headers: HeaderMap,
+    Path((name, action)): Path<(String, String)>,
+) -> Result<StatusCode, ApiError> {
+    context.authorize(bearer(&headers), "flash")?;
+    lab::find_fixture(&context.state, &name).map_err(|e| (StatusCode::NOT_FOUND, e.to_string()))?;
+    lab::control_fixture(&context.state, &name, &action)
+        .await
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, format!("{:#}", e)))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct EventParams {
+    access_token: Option<String>,
+}
+
+// A snapshot of running flashes, then every observed app event as `{ "event", "payload" }`.
+// Browsers cannot set headers on a WebSocket, so the token may also be passed as `?access_token=`.
+async fn events(
+    State(context): State<ApiContext>,
+    headers: HeaderMap,
+    Query(params): Query<EventParams>,
+    upgrade: WebSocketUpgrade,
+) -> Result<Response, ApiError> {
+    context.authorize(bearer(&headers).or(params.access_token.as_deref()), "read")?;
+    Ok(upgrade.on_upgrade(move |socket| stream_events(socket, context)))
+}
+
+async fn stream_events(mut socket: WebSocket, context: ApiContext) {
+    let mut events = context.events.subscribe();
+    let snapshot = context.state.flash_progress.lock().unwrap().clone();
+    let snapshot = serde_json::json!({ "event": "snapshot", "payload": snapshot }).to_string();
+    if socket.send(Message::Text(snapshot)).await.is_err() {
+        return;
+    }
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(json) => {
+                    if socket.send(Message::Text(json)).await.is_err() {
+                        return;
+                    }
+                }
+                // A slow client misses events rather than holding up the others
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+            // Anything from the client other than a close is ignored
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => continue,
+            },
+        }
+    }
+}
+
+// The certificate the control API serves, for installing on clients or pinning. Regenerating
+// replaces the self-signed certificate; the server picks it up on the next start.
+#[command]
+pub async fn get_control_api_certificate(
+    regenerate: Option<bool>,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<ApiCertificate, String> {
+    let settings = state.settings.lock().unwrap().control_api.clone();
+    let load = || -> Result<ApiCertificate> {
+        if regenerate.unwrap_or(false) {
+            if settings.tls != "self_signed" {
+                return Err(anyhow::anyhow!("Only self-signed certificates can be regenerated"));
+            }
+            generate_self_signed(&app, &settings.bind_address)?;
+        }
+        let (cert_path, _) = tls_files(&app, &settings)?.context("TLS is off for the control API")?;
+        let pem = std::fs::read_to_string(&cert_path).with_context(|| format!("Failed to read {}", cert_path.display()))?;
+        let der = pem::parse(&pem).context("Invalid certificate PEM")?;
+        let fingerprint = Sha256::digest(der.contents())
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<Vec<_>>()
+            .join(":");
+        Ok(ApiCertificate {
+            pem,
+            sha256_fingerprint: fingerprint,
+            self_signed: settings.tls == "self_signed",
+        })
+    };
+    load().map_err(|e| e.to_string())
+}
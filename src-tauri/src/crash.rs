@@ -0,0 +1,194 @@
+// CFU - Cordatus Flash Utility - Crash Reporting
+// Panic capture with recent log context, written to the app data directory and optionally submitted on the next start
+
+use crate::AppState;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::{error, info, Log, Metadata, Record};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use tauri::{command, State};
+use uuid::Uuid;
+
+const CRASH_DIR: &str = "crash_reports";
+
+// Log lines kept in memory for the next crash report
+const RECENT_LOG_LINES: usize = 200;
+
+static RECENT_LOG: OnceLock<Mutex<VecDeque<String>>> = OnceLock::new();
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashReport {
+    pub report_id: String,
+    pub app_version: String,
+    pub os: String,
+    pub thread: String,
+    pub message: String,
+    pub location: Option<String>, // file:line of the panic
+    pub backtrace: String,
+    pub recent_log: Vec<String>,
+    pub crashed_at: DateTime<Utc>,
+    #[serde(default)]
+    pub submitted: bool,
+}
+
+// env_logger output as before, plus a ring buffer of info and above for crash reports
+struct CrashLogger {
+    inner: env_logger::Logger,
+}
+
+impl Log for CrashLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::Level::Info || self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if record.level() <= log::Level::Info {
+            let line = format!("{} {:<5} {}: {}", Utc::now().to_rfc3339(), record.level(), record.target(), record.args());
+            let mut recent = RECENT_LOG.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner());
+            if recent.len() == RECENT_LOG_LINES {
+                recent.pop_front();
+            }
+            recent.push_back(line);
+        }
+        if self.inner.matches(record) {
+            self.inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+// Replaces env_logger::init(); RUST_LOG still controls what is printed
+pub fn init_logging() {
+    let inner = env_logger::Builder::from_default_env().build();
+    let max_level = inner.filter().max(log::LevelFilter::Info);
+    if log::set_boxed_logger(Box::new(CrashLogger { inner })).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+fn crash_dir(app: &tauri::AppHandle) -> Result<PathBuf> {
+    let dir = crate::app_data_file(app, CRASH_DIR)?;
+    std::fs::create_dir_all(&dir).context("Failed to create crash report directory")?;
+    Ok(dir)
+}
+
+fn write_report(dir: &Path, info: &std::panic::PanicHookInfo) -> Result<PathBuf> {
+    let message = info
+        .payload()
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| info.payload().downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string());
+    let recent_log = RECENT_LOG
+        .get()
+        .map(|recent| recent.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect())
+        .unwrap_or_default();
+
+    let report = CrashReport {
+        report_id: Uuid::new_v4().to_string(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        os: format!(
+            "{} {}",
+            sys_info::os_type().unwrap_or_default(),
+            sys_info::os_release().unwrap_or_default()
+        ),
+        thread: std::thread::current().name().unwrap_or("unnamed").to_string(),
+        message,
+        location: info.location().map(|l| format!("{}:{}", l.file(), l.line())),
+        backtrace: std::backtrace::Backtrace::force_capture().to_string(),
+        recent_log,
+        crashed_at: Utc::now(),
+        submitted: false,
+    };
+    let path = dir.join(format!("crash-{}.json", report.report_id));
+    std::fs::write(&path, serde_json::to_string_pretty(&report)?)?;
+    Ok(path)
+}
+
+// Write a report for every panic, then run the default hook
+pub fn install_panic_hook(app: &tauri::AppHandle) {
+    let dir = match crash_dir(app) {
+        Ok(dir) => dir,
+        Err(e) => {
+            error!("Crash reports disabled: {}", e);
+            return;
+        }
+    };
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        match write_report(&dir, info) {
+            Ok(path) => error!("Backend panic, crash report written to {}", path.display()),
+            Err(e) => error!("Backend panic, failed to write crash report: {}", e),
+        }
+        default_hook(info);
+    }));
+}
+
+fn load_reports(app: &tauri::AppHandle) -> Result<Vec<(PathBuf, CrashReport)>> {
+    let mut reports: Vec<(PathBuf, CrashReport)> = std::fs::read_dir(crash_dir(app)?)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| {
+            let report = serde_json::from_str(&std::fs::read_to_string(&path).ok()?).ok()?;
+            Some((path, report))
+        })
+        .collect();
+    reports.sort_by_key(|(_, r)| std::cmp::Reverse(r.crashed_at));
+    Ok(reports)
+}
+
+fn find_report(app: &tauri::AppHandle, report_id: &str) -> Result<(PathBuf, CrashReport)> {
+    load_reports(app)?
+        .into_iter()
+        .find(|(_, r)| r.report_id == report_id)
+        .with_context(|| format!("Crash report not found: {}", report_id))
+}
+
+// Crash reports on disk, newest first; the frontend offers to submit unsubmitted ones
+#[command]
+pub async fn list_crash_reports(app: tauri::AppHandle) -> Result<Vec<CrashReport>, String> {
+    load_reports(&app)
+        .map(|reports| reports.into_iter().map(|(_, r)| r).collect())
+        .map_err(|e| e.to_string())
+}
+
+// Send a crash report to the configured endpoint
+#[command]
+pub async fn submit_crash_report(
+    report_id: String,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    let url = state.settings.lock().unwrap().crash_report_url.clone();
+    if url.is_empty() {
+        return Err("No crash report endpoint configured".to_string());
+    }
+    let (path, mut report) = find_report(&app, &report_id).map_err(|e| e.to_string())?;
+
+    reqwest::Client::new()
+        .post(&url)
+        .json(&report)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Failed to submit crash report: {}", e))?;
+
+    report.submitted = true;
+    let json = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+    std::fs::write(&path, json).map_err(|e| e.to_string())?;
+    info!("Submitted crash report {}", report_id);
+    Ok(())
+}
+
+// Delete a crash report
+#[command]
+pub async fn delete_crash_report(report_id: String, app: tauri::AppHandle) -> Result<(), String> {
+    let (path, _) = find_report(&app, &report_id).map_err(|e| e.to_string())?;
+    std::fs::remove_file(path).map_err(|e| e.to_string())
+}
@@ -0,0 +1,73 @@
+// CFU - Cordatus Flash Utility - Credentials
+// API tokens and passwords kept in the OS keyring instead of the settings file
+
+use anyhow::{Context, Result};
+use log::info;
+use tauri::command;
+
+const KEYRING_SERVICE: &str = "cordatus-flash-utility";
+
+// Accounts the frontend may manage through the credential commands
+const KNOWN_ACCOUNTS: &[&str] = &["huggingface", "ngc", "smtp", "s3", "fleet_server", "catalog_publish", "catalog_signing", "uart_login"];
+
+fn entry(account: &str) -> Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, account).context("Keyring unavailable")
+}
+
+// Read a secret, treating a missing entry as None
+pub fn get_secret(account: &str) -> Result<Option<String>> {
+    match entry(account)?.get_password() {
+        Ok(secret) => Ok(Some(secret)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(anyhow::anyhow!("Failed to read {} credential: {}", account, e)),
+    }
+}
+
+pub fn set_secret(account: &str, secret: &str) -> Result<()> {
+    entry(account)?
+        .set_password(secret)
+        .with_context(|| format!("Failed to store {} credential", account))
+}
+
+pub fn delete_secret(account: &str) -> Result<()> {
+    match entry(account)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(anyhow::anyhow!("Failed to delete {} credential: {}", account, e)),
+    }
+}
+
+fn check_account(account: &str) -> Result<(), String> {
+    if KNOWN_ACCOUNTS.contains(&account) {
+        Ok(())
+    } else {
+        Err(format!("Unknown credential: {}", account))
+    }
+}
+
+// Store a token in the OS keyring
+#[command]
+pub async fn set_credential(account: String, secret: String) -> Result<(), String> {
+    check_account(&account)?;
+    set_secret(&account, secret.trim()).map_err(|e| e.to_string())?;
+    info!("Stored {} credential", account);
+    Ok(())
+}
+
+// Remove a token from the OS keyring
+#[command]
+pub async fn delete_credential(account: String) -> Result<(), String> {
+    check_account(&account)?;
+    delete_secret(&account).map_err(|e| e.to_string())
+}
+
+// Report which credentials are configured, without revealing them
+#[command]
+pub async fn list_credentials() -> Result<Vec<String>, String> {
+    let mut configured = Vec::new();
+    for account in KNOWN_ACCOUNTS {
+        if get_secret(account).map_err(|e| e.to_string())?.is_some() {
+            configured.push(account.to_string());
+        }
+    }
+    Ok(configured)
+}
@@ -0,0 +1,134 @@
+// CFU - Cordatus Flash Utility - Delta Flashing
+// For developers reflashing the same board over and over: partition images are built without flashing,
+// compared with the checksums recorded at that board's last delta flash, and only the partitions whose
+// image changed are written with `flash.sh -k`
+
+use crate::{checksum, native_flash, recovery, AppState, FlashCommand, JetsonDevice};
+use anyhow::{Context, Result};
+use log::warn;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+// Where flash.sh leaves the partition layout it built the images for, newest L4T first
+const LAYOUT_FILES: [&str; 3] = ["bootloader/signed/flash.xml.tmp", "bootloader/flash.xml.tmp", "bootloader/flash.xml"];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PartitionChecksum {
+    pub name: String, // Partition name as passed to flash.sh -k, e.g. "APP" or "A_kernel"
+    pub file: String, // Image file in bootloader/
+    pub sha256: String,
+}
+
+pub fn validate(command: &FlashCommand) -> Result<()> {
+    if !command.delta {
+        return Ok(());
+    }
+    if !native_flash::is_supported(command) || command.storage_device != "Micro SD" {
+        return Err(anyhow::anyhow!(
+            "Delta flashing is available for developer kits written with flash.sh, not {} {} on {}",
+            command.product,
+            command.device_module,
+            command.storage_device
+        ));
+    }
+    Ok(())
+}
+
+// Partitions with an image file, as named in the layout; the first entry wins where a name repeats
+fn partition_files(l4t: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let layout = LAYOUT_FILES
+        .iter()
+        .map(|file| l4t.join(file))
+        .find(|path| path.is_file())
+        .context("flash.sh left no partition layout behind")?;
+    let content = std::fs::read_to_string(&layout).with_context(|| format!("Failed to read {}", layout.display()))?;
+    let partition = Regex::new(r#"(?s)<partition\s+name="([^"]+)"[^>]*>(.*?)</partition>"#)?;
+    let filename = Regex::new(r"<filename>\s*([^<\s]+)\s*</filename>")?;
+    let mut files: Vec<(String, PathBuf)> = Vec::new();
+    for captures in partition.captures_iter(&content) {
+        let name = captures[1].to_string();
+        let Some(file) = filename.captures(&captures[2]).map(|c| c[1].to_string()) else {
+            continue;
+        };
+        // Signed images are flashed in place of the plain ones where both exist
+        let image = [l4t.join("bootloader/signed").join(&file), l4t.join("bootloader").join(&file)]
+            .into_iter()
+            .find(|path| path.is_file());
+        if let Some(image) = image {
+            if !files.iter().any(|(existing, _)| *existing == name) {
+                files.push((name, image));
+            }
+        }
+    }
+    Ok(files)
+}
+
+// Checksums of the images just built, hashed in parallel
+pub fn checksums(l4t: &Path) -> Result<Vec<PartitionChecksum>> {
+    let files = partition_files(l4t)?;
+    let paths: Vec<&PathBuf> = files.iter().map(|(_, path)| path).collect();
+    files
+        .iter()
+        .zip(checksum::sha256_files(&paths))
+        .map(|((name, path), sha256)| {
+            Ok(PartitionChecksum {
+                name: name.clone(),
+                file: path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default(),
+                sha256: sha256?,
+            })
+        })
+        .collect()
+}
+
+// Checksums recorded at the last successful delta flash of this board and module
+pub fn previous(state: &AppState, board_id: &str, module: &str) -> Option<Vec<PartitionChecksum>> {
+    let history = state.history.lock().unwrap();
+    history
+        .jobs
+        .iter()
+        .rev()
+        .find(|job| {
+            job.status == "success"
+                && job.board_id.as_deref() == Some(board_id)
+                && job.command.device_module == module
+                && !job.partition_checksums.is_empty()
+        })
+        .map(|job| job.partition_checksums.clone())
+}
+
+// Partitions whose image is new or differs from the previous flash
+pub fn changed(current: &[PartitionChecksum], previous: &[PartitionChecksum]) -> Vec<String> {
+    current
+        .iter()
+        .filter(|partition| !previous.iter().any(|p| p.name == partition.name && p.sha256 == partition.sha256))
+        .map(|partition| partition.name.clone())
+        .collect()
+}
+
+// The board being flashed: the bound one, or the only one in recovery mode
+pub async fn board(state: &AppState, command: &FlashCommand) -> Option<JetsonDevice> {
+    let devices = crate::scan_usb_devices(state).await.ok()?;
+    let mut candidates = devices.into_iter().filter(|device| {
+        device.usb_info.as_ref().is_some_and(|usb| usb.is_recovery_mode)
+            && command.device_binding.as_ref().is_none_or(|binding| binding.matches_location(device))
+    });
+    match (candidates.next(), candidates.next()) {
+        (Some(device), None) => Some(device),
+        _ => None,
+    }
+}
+
+// flash.sh reboots the board after each partition; a lab fixture on its port puts it straight back
+// into recovery, otherwise the operator does
+pub async fn reenter_recovery(state: &AppState, board: &JetsonDevice) {
+    let Some(port) = board.usb_info.as_ref().and_then(|usb| usb.port_path.clone()) else {
+        return;
+    };
+    let fixture = state.settings.lock().unwrap().lab_fixtures.iter().find(|f| f.usb_port_path == port).cloned();
+    if let Some(fixture) = fixture.filter(|f| recovery::method(f).is_some()) {
+        if let Err(e) = recovery::trigger(&fixture).await {
+            warn!("Could not put {} back into recovery mode: {:#}", fixture.name, e);
+        }
+    }
+}
@@ -0,0 +1,334 @@
+// CFU - Cordatus Flash Utility - Device Matrix
+// Parsed board / JetPack / storage matrix from template.csv, served to the frontend as filtered queries.
+// Remote updates are held as pending with a diff against the active matrix until an operator applies them
+
+use crate::AppState;
+use anyhow::{Context, Result};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tauri::{command, Emitter, Manager, State};
+
+const MATRIX_FILE: &str = "template.csv";
+const UPDATED_FILE: &str = "device_matrix.csv";
+const PENDING_FILE: &str = "device_matrix.pending.csv";
+const FROZEN_FILE: &str = "device_matrix.frozen.csv";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatrixRow {
+    #[serde(rename = "Vendor")]
+    pub vendor: String,
+    #[serde(rename = "Product")]
+    pub product: String,
+    #[serde(rename = "Module")]
+    pub module: String,
+    #[serde(rename = "Jetpack")]
+    pub jetpack: String,
+    #[serde(rename = "Storage")]
+    pub storage: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DeviceMatrix {
+    pub rows: Vec<MatrixRow>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Board {
+    pub vendor: String,
+    pub product: String,
+    pub module: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoardVersion {
+    pub jetpack: String,
+    pub storage: Vec<String>, // In matrix order
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BoardRelease {
+    #[serde(flatten)]
+    pub board: Board,
+    pub jetpack: String,
+}
+
+// What applying a pending matrix would change in the options the UI offers
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MatrixDiff {
+    pub new_boards: Vec<Board>,
+    pub removed_boards: Vec<Board>,
+    pub added_versions: Vec<BoardRelease>, // JetPack/L4T releases newly offered for a board
+    pub removed_versions: Vec<BoardRelease>,
+    pub added_combos: Vec<MatrixRow>, // Individual board / release / storage rows
+    pub removed_combos: Vec<MatrixRow>,
+}
+
+impl MatrixDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_combos.is_empty() && self.removed_combos.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingMatrix {
+    pub rows: usize,
+    pub diff: MatrixDiff,
+}
+
+// The copy a frozen station was validated with first, then a remotely updated copy, then the bundled
+// resource, then the development checkout
+pub fn matrix_path(app: &tauri::AppHandle) -> Result<PathBuf> {
+    let frozen = crate::app_data_file(app, FROZEN_FILE).ok();
+    let updated = crate::app_data_file(app, UPDATED_FILE).ok();
+    let bundled = app.path().resource_dir().ok().map(|dir| dir.join(MATRIX_FILE));
+    frozen
+        .into_iter()
+        .chain(updated)
+        .chain(bundled)
+        .chain([PathBuf::from("./data").join(MATRIX_FILE), PathBuf::from("../data").join(MATRIX_FILE)])
+        .find(|path| path.exists())
+        .context("Device matrix template.csv not found")
+}
+
+pub fn parse_matrix(content: &[u8]) -> Result<DeviceMatrix> {
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(content);
+    let rows = reader
+        .deserialize()
+        .collect::<Result<Vec<MatrixRow>, _>>()
+        .context("Invalid device matrix")?;
+    Ok(DeviceMatrix { rows })
+}
+
+async fn read_matrix(app: &tauri::AppHandle) -> Result<DeviceMatrix> {
+    let path = matrix_path(app)?;
+    let content = tokio::fs::read(&path).await.with_context(|| format!("Failed to read {}", path.display()))?;
+    let matrix = tokio::task::spawn_blocking(move || parse_matrix(&content)).await??;
+    info!("Loaded device matrix from {}: {} rows", path.display(), matrix.rows.len());
+    Ok(matrix)
+}
+
+// Parsed on the first query and kept until the matrix is replaced
+pub async fn device_matrix(app: &tauri::AppHandle, state: &AppState) -> Result<Arc<DeviceMatrix>, String> {
+    if let Some(matrix) = state.device_matrix.lock().unwrap().clone() {
+        return Ok(matrix);
+    }
+    let matrix = Arc::new(read_matrix(app).await.map_err(|e| e.to_string())?);
+    *state.device_matrix.lock().unwrap() = Some(Arc::clone(&matrix));
+    Ok(matrix)
+}
+
+impl MatrixRow {
+    fn board(&self) -> Board {
+        Board {
+            vendor: self.vendor.clone(),
+            product: self.product.clone(),
+            module: self.module.clone(),
+        }
+    }
+
+    fn key(&self) -> (&str, &str, &str, &str, &str) {
+        (&self.vendor, &self.product, &self.module, &self.jetpack, &self.storage)
+    }
+}
+
+pub fn diff_matrices(old: &DeviceMatrix, new: &DeviceMatrix) -> MatrixDiff {
+    let boards = |m: &DeviceMatrix| m.rows.iter().map(MatrixRow::board).collect::<BTreeSet<_>>();
+    let releases = |m: &DeviceMatrix| m.rows.iter().map(|row| (row.board(), row.jetpack.clone())).collect::<BTreeSet<_>>();
+    fn combos(m: &DeviceMatrix) -> BTreeSet<(&str, &str, &str, &str, &str)> {
+        m.rows.iter().map(MatrixRow::key).collect()
+    }
+    let to_releases = |pairs: Vec<&(Board, String)>| {
+        pairs
+            .into_iter()
+            .map(|(board, jetpack)| BoardRelease {
+                board: board.clone(),
+                jetpack: jetpack.clone(),
+            })
+            .collect()
+    };
+
+    let (old_boards, new_boards) = (boards(old), boards(new));
+    let (old_releases, new_releases) = (releases(old), releases(new));
+    let (old_combos, new_combos) = (combos(old), combos(new));
+    MatrixDiff {
+        new_boards: new_boards.difference(&old_boards).cloned().collect(),
+        removed_boards: old_boards.difference(&new_boards).cloned().collect(),
+        added_versions: to_releases(new_releases.difference(&old_releases).collect()),
+        removed_versions: to_releases(old_releases.difference(&new_releases).collect()),
+        added_combos: new.rows.iter().filter(|row| !old_combos.contains(&row.key())).cloned().collect(),
+        removed_combos: old.rows.iter().filter(|row| !new_combos.contains(&row.key())).cloned().collect(),
+    }
+}
+
+fn parse_update(content: &[u8]) -> Result<DeviceMatrix> {
+    let matrix = parse_matrix(content)?;
+    if matrix.rows.is_empty() {
+        return Err(anyhow::anyhow!("Device matrix contains no rows"));
+    }
+    Ok(matrix)
+}
+
+fn install_matrix(app: &tauri::AppHandle, state: &AppState, content: &[u8], matrix: DeviceMatrix) -> Result<usize> {
+    let path = crate::app_data_file(app, UPDATED_FILE)?;
+    std::fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+    let rows = matrix.rows.len();
+    *state.device_matrix.lock().unwrap() = Some(Arc::new(matrix));
+    info!("Installed updated device matrix: {} rows", rows);
+    Ok(rows)
+}
+
+// Hold a matrix received from elsewhere (e.g. the fleet server) for review; an update that changes
+// nothing the UI offers is installed right away. Returns the diff against the active matrix
+pub async fn stage_matrix(app: &tauri::AppHandle, state: &AppState, content: &[u8]) -> Result<MatrixDiff> {
+    crate::freeze::ensure_not_frozen(app, "Device matrix updates").map_err(anyhow::Error::msg)?;
+    let matrix = parse_update(content)?;
+    let active = device_matrix(app, state).await.map_err(anyhow::Error::msg)?;
+    let diff = diff_matrices(&active, &matrix);
+    if diff.is_empty() {
+        install_matrix(app, state, content, matrix)?;
+        return Ok(diff);
+    }
+
+    let path = crate::app_data_file(app, PENDING_FILE)?;
+    std::fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+    info!(
+        "Device matrix update pending review: {} rows added, {} removed",
+        diff.added_combos.len(),
+        diff.removed_combos.len()
+    );
+    let _ = app.emit("device-matrix-update-pending", &diff);
+    Ok(diff)
+}
+
+// Keep a copy of the active matrix so neither updates nor a new bundled template change it; returns its rows
+pub async fn freeze_matrix(app: &tauri::AppHandle, state: &AppState) -> Result<usize> {
+    let path = matrix_path(app)?;
+    let frozen = crate::app_data_file(app, FROZEN_FILE)?;
+    std::fs::copy(&path, &frozen).with_context(|| format!("Failed to copy {}", path.display()))?;
+    *state.device_matrix.lock().unwrap() = None;
+    Ok(device_matrix(app, state).await.map_err(anyhow::Error::msg)?.rows.len())
+}
+
+pub fn unfreeze_matrix(app: &tauri::AppHandle, state: &AppState) -> Result<()> {
+    let frozen = crate::app_data_file(app, FROZEN_FILE)?;
+    match std::fs::remove_file(&frozen) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            return Err(e).with_context(|| format!("Failed to remove {}", frozen.display()))
+        }
+        _ => {}
+    }
+    *state.device_matrix.lock().unwrap() = None;
+    Ok(())
+}
+
+fn read_pending(app: &tauri::AppHandle) -> Result<Option<Vec<u8>>> {
+    let path = crate::app_data_file(app, PENDING_FILE)?;
+    match std::fs::read(&path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+fn discard_pending(app: &tauri::AppHandle) -> Result<()> {
+    let path = crate::app_data_file(app, PENDING_FILE)?;
+    match std::fs::remove_file(&path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e).with_context(|| format!("Failed to remove {}", path.display())),
+        _ => Ok(()),
+    }
+}
+
+// Boards in the matrix, optionally for one vendor
+#[command]
+pub async fn get_boards(
+    vendor: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<Board>, String> {
+    let matrix = device_matrix(&app, &state).await?;
+    let mut boards: Vec<Board> = matrix
+        .rows
+        .iter()
+        .filter(|row| vendor.as_ref().is_none_or(|v| &row.vendor == v))
+        .map(|row| Board {
+            vendor: row.vendor.clone(),
+            product: row.product.clone(),
+            module: row.module.clone(),
+        })
+        .collect();
+    boards.sort();
+    boards.dedup();
+    Ok(boards)
+}
+
+// JetPack releases and their storage options for one board, newest first as listed in the matrix
+#[command]
+pub async fn get_versions_for_board(
+    product: String,
+    module: String,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<BoardVersion>, String> {
+    let matrix = device_matrix(&app, &state).await?;
+    let mut order: Vec<&str> = Vec::new();
+    let mut storage: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for row in matrix.rows.iter().filter(|row| row.product == product && row.module == module) {
+        let options = storage.entry(&row.jetpack).or_insert_with(|| {
+            order.push(&row.jetpack);
+            Vec::new()
+        });
+        if !options.contains(&row.storage) {
+            options.push(row.storage.clone());
+        }
+    }
+    if order.is_empty() {
+        return Err(format!("Board not in device matrix: {} {}", product, module));
+    }
+
+    Ok(order
+        .into_iter()
+        .map(|jetpack| BoardVersion {
+            jetpack: jetpack.to_string(),
+            storage: storage.remove(jetpack).unwrap_or_default(),
+        })
+        .collect())
+}
+
+// The device matrix update waiting for review, with what it would change
+#[command]
+pub async fn get_pending_device_matrix(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Option<PendingMatrix>, String> {
+    let Some(content) = read_pending(&app).map_err(|e| e.to_string())? else {
+        return Ok(None);
+    };
+    let matrix = parse_update(&content).map_err(|e| e.to_string())?;
+    let active = device_matrix(&app, &state).await?;
+    Ok(Some(PendingMatrix {
+        rows: matrix.rows.len(),
+        diff: diff_matrices(&active, &matrix),
+    }))
+}
+
+// Make the pending device matrix the active one
+#[command]
+pub async fn apply_device_matrix_update(app: tauri::AppHandle, state: State<'_, Arc<AppState>>) -> Result<usize, String> {
+    crate::freeze::ensure_not_frozen(&app, "Device matrix updates")?;
+    let content = read_pending(&app)
+        .map_err(|e| e.to_string())?
+        .ok_or("No device matrix update pending")?;
+    let matrix = parse_update(&content).map_err(|e| e.to_string())?;
+    let rows = install_matrix(&app, &state, &content, matrix).map_err(|e| e.to_string())?;
+    discard_pending(&app).map_err(|e| e.to_string())?;
+    Ok(rows)
+}
+
+// Drop the pending device matrix and keep the active one
+#[command]
+pub async fn discard_device_matrix_update(app: tauri::AppHandle) -> Result<(), String> {
+    discard_pending(&app).map_err(|e| e.to_string())
+}
@@ -0,0 +1,606 @@
+// CFU - Cordatus Flash Utility - Device Specs
+// "Device as code": a YAML spec of the module, release, storage, users, network, packages, containers and
+// tests a device should have. It is validated and compiled into a flash command plus the post-flash steps
+// run over SSH once the device has booted; profiles export to specs as the simpler starting point.
+
+use crate::provisioning::ProvisioningOptions;
+use crate::ssh::{run_remote, shell_quote, SshTarget, SUDO};
+use crate::batch::{self, JobVariables};
+use crate::device_matrix::{DeviceMatrix, MatrixRow};
+use crate::version_matrix::VersionMatrix;
+use crate::{boot_check, cli, device_matrix, history, profiles, AppState, FlashCommand};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{command, Emitter, State};
+
+const JOB_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceSpec {
+    pub name: String,
+    #[serde(default)]
+    pub product: String, // Only needed where the module is on several carrier boards
+    pub module: String,  // Short names work as on the CLI, e.g. "orin-nx"
+    #[serde(default)]
+    pub l4t: String,
+    #[serde(default)]
+    pub jetpack: String,
+    pub storage: String,
+    pub users: Vec<SpecUser>, // The first one is created by the flash, the others afterwards
+    #[serde(default)]
+    pub network: Option<SpecNetwork>,
+    #[serde(default)]
+    pub packages: Vec<String>, // apt packages, "name" or "name=version"
+    #[serde(default)]
+    pub containers: Vec<String>, // Images pulled with docker, e.g. "nvcr.io/nvidia/l4t-pytorch:r35.2.1-pth2.0-py3"
+    #[serde(default)]
+    pub tests: Vec<SpecTest>,
+    #[serde(default)]
+    pub provisioning: Option<ProvisioningOptions>, // Applied to the rootfs before flashing
+    #[serde(default)]
+    pub connect: Option<SshTarget>, // How the booted device is reached; needed for every post-flash step
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpecUser {
+    pub name: String,
+    #[serde(default)]
+    pub ssh_keys: Vec<String>,
+    #[serde(default)]
+    pub groups: Vec<String>, // e.g. "sudo", "docker"
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpecNetwork {
+    #[serde(default)]
+    pub hostname: Option<String>,
+    #[serde(default)]
+    pub interface: Option<String>, // For a static address, "eth0" by default
+    #[serde(default)]
+    pub address: Option<String>, // Static address in CIDR notation; DHCP when unset
+    #[serde(default)]
+    pub gateway: Option<String>,
+    #[serde(default)]
+    pub dns: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpecTest {
+    pub name: String,
+    pub run: String, // Shell command on the device; passes on exit code 0
+}
+
+// One post-flash step as run on the device
+#[derive(Debug, Clone, Serialize)]
+pub struct SpecStep {
+    pub name: String,
+    pub kind: String, // 'users' | 'hostname' | 'packages' | 'containers' | 'test' | 'address'
+    pub script: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SpecPlan {
+    pub command: FlashCommand,
+    pub steps: Vec<SpecStep>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SpecValidation {
+    pub valid: bool,
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+    pub plan: Option<SpecPlan>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SpecIssue {
+    pub field: String, // Where in the spec, e.g. "users[1].groups"; empty for the document as a whole
+    pub message: String,
+}
+
+// Lint result of a spec file, for the UI and `cfu-cli validate-spec` in CI
+#[derive(Debug, Clone, Serialize)]
+pub struct SpecLint {
+    pub valid: bool, // No errors; warnings do not stop a flash
+    pub errors: Vec<SpecIssue>,
+    pub warnings: Vec<SpecIssue>,
+}
+
+impl std::fmt::Display for SpecIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.field.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.field, self.message)
+        }
+    }
+}
+
+fn issue(field: impl Into<String>, message: impl Into<String>) -> SpecIssue {
+    SpecIssue {
+        field: field.into(),
+        message: message.into(),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpecStepResult {
+    pub name: String,
+    pub kind: String,
+    pub passed: bool,
+    pub output: String,
+    pub finished_at: DateTime<Utc>,
+}
+
+// Parse a spec for one unit: its batch overrides applied and its variables filled in.
+// Returns the variables used, for the job's history.
+fn parse(spec: &str, vars: &JobVariables) -> Result<(DeviceSpec, BTreeMap<String, String>), String> {
+    let mut value: serde_json::Value = serde_yaml::from_str(spec).map_err(|e| format!("Invalid device spec: {}", e))?;
+    let variables = batch::resolve(&mut value, vars).map_err(|e| e.to_string())?;
+    let spec = serde_json::from_value(value).map_err(|e| format!("Invalid device spec: {}", e))?;
+    Ok((spec, variables))
+}
+
+fn valid_name(pattern: &str, value: &str) -> bool {
+    Regex::new(pattern).is_ok_and(|re| re.is_match(value))
+}
+
+// Everything wrong with a spec apart from the board selection
+fn check(spec: &DeviceSpec) -> Vec<SpecIssue> {
+    let mut errors = Vec::new();
+    if spec.name.trim().is_empty() {
+        errors.push(issue("name", "The spec needs a name"));
+    }
+    if spec.l4t.is_empty() && spec.jetpack.is_empty() {
+        errors.push(issue("l4t", "Set l4t or jetpack"));
+    }
+    if spec.users.is_empty() {
+        errors.push(issue("users", "At least one user is needed, the first one is created by the flash"));
+    }
+    for (index, user) in spec.users.iter().enumerate() {
+        if !valid_name(r"^[a-z_][a-z0-9_-]{0,31}$", &user.name) {
+            errors.push(issue(format!("users[{}].name", index), format!("Invalid user name: {}", user.name)));
+        }
+        for group in user.groups.iter().filter(|g| !valid_name(r"^[a-z_][a-z0-9_-]{0,31}$", g)) {
+            errors.push(issue(format!("users[{}].groups", index), format!("Invalid group of {}: {}", user.name, group)));
+        }
+        for key in user.ssh_keys.iter().filter(|k| k.split_whitespace().count() < 2) {
+            errors.push(issue(format!("users[{}].ssh_keys", index), format!("Invalid SSH key of {}: {}", user.name, key)));
+        }
+    }
+    if let Some(network) = &spec.network {
+        if let Some(hostname) = network.hostname.as_deref().filter(|h| !valid_name(r"^[a-zA-Z0-9]([a-zA-Z0-9-]{0,62})$", h)) {
+            errors.push(issue("network.hostname", format!("Invalid hostname: {}", hostname)));
+        }
+        if let Some(address) = network.address.as_deref().filter(|a| !valid_name(r"^\d{1,3}(\.\d{1,3}){3}/\d{1,2}$", a)) {
+            errors.push(issue(
+                "network.address",
+                format!("The static address must be in CIDR notation, e.g. 192.168.1.50/24: {}", address),
+            ));
+        }
+        if network.address.is_none() && (network.gateway.is_some() || !network.dns.is_empty()) {
+            errors.push(issue("network.address", "A gateway or DNS servers need a static address"));
+        }
+    }
+    for (index, package) in spec.packages.iter().enumerate() {
+        if !valid_name(r"^[a-z0-9][a-z0-9+.-]+(=[A-Za-z0-9.+:~-]+)?$", package) {
+            errors.push(issue(format!("packages[{}]", index), format!("Invalid package: {}", package)));
+        }
+    }
+    for (index, image) in spec.containers.iter().enumerate() {
+        if image.trim().is_empty() || image.contains(char::is_whitespace) {
+            errors.push(issue(format!("containers[{}]", index), format!("Invalid container image: {:?}", image)));
+        }
+    }
+    if let Some(Err(e)) = spec.provisioning.as_ref().map(ProvisioningOptions::validate) {
+        errors.push(issue("provisioning", e.to_string()));
+    }
+    errors
+}
+
+// The L4T release a container image tag was built for, e.g. "r35.2.1-pth2.0-py3" -> (35, 2)
+fn image_l4t(image: &str) -> Option<(u32, u32)> {
+    let name = image.rsplit('/').next()?;
+    let (_, tag) = name.split_once(':')?;
+    let caps = Regex::new(r"(?:^|[-_])r(\d+)\.(\d+)").ok()?.captures(tag)?;
+    Some((caps[1].parse().ok()?, caps[2].parse().ok()?))
+}
+
+// Container images against the release the spec flashes: L4T images only run on the major release
+// they were built for, and the known jetson-containers only on some modules
+fn check_containers(spec: &DeviceSpec, module: &str, l4t: &str, errors: &mut Vec<SpecIssue>, warnings: &mut Vec<SpecIssue>) {
+    let mut release = l4t.split('.').map(|part| part.parse::<u32>().ok());
+    let (Some(Some(major)), Some(Some(minor))) = (release.next(), release.next()) else {
+        return;
+    };
+    let known = crate::known_containers();
+    for (index, image) in spec.containers.iter().enumerate() {
+        let field = format!("containers[{}]", index);
+        match image_l4t(image) {
+            Some((image_major, _)) if image_major != major => errors.push(issue(
+                field.clone(),
+                format!("{} is built for L4T R{}, the spec flashes L4T {}", image, image_major, l4t),
+            )),
+            Some((_, image_minor)) if image_minor != minor => warnings.push(issue(
+                field.clone(),
+                format!("{} is built for L4T R{}.{}, the spec flashes L4T {}", image, major, image_minor, l4t),
+            )),
+            Some(_) => {}
+            None if image.contains("l4t-") || image.starts_with("dustynv/") => warnings.push(issue(
+                field.clone(),
+                format!("{} is not pinned to an L4T release; tag it for L4T {}", image, l4t),
+            )),
+            None => {}
+        }
+        let name = image.rsplit('/').next().unwrap_or(image);
+        let name = name.split(':').next().unwrap_or(name);
+        if let Some(container) = known.iter().find(|c| c.name == name) {
+            if !container.supported_devices.iter().any(|device| module.contains(device.as_str())) {
+                warnings.push(issue(
+                    field,
+                    format!("{} supports {}, not {}", name, container.supported_devices.join(", "), module),
+                ));
+            }
+        }
+    }
+}
+
+// Check a parsed spec against the device matrix and the release data; returns the board it selects
+fn lint_spec(spec: &DeviceSpec, matrix: &DeviceMatrix, versions: &VersionMatrix) -> (SpecLint, Option<MatrixRow>) {
+    let mut errors = check(spec);
+    let mut warnings = Vec::new();
+    let row = cli::select_row(matrix, &spec.product, &spec.module, &spec.jetpack, &spec.l4t, &spec.storage)
+        .map_err(|e| errors.push(issue("module", e)))
+        .ok();
+    if let Some(row) = &row {
+        // The flashing host is not known here, so host rules are left to the pre-flight checks
+        let report = versions.check(&row.module, &row.jetpack, None);
+        let field = if spec.l4t.is_empty() { "jetpack" } else { "l4t" };
+        errors.extend(report.blocks.iter().map(|block| issue(field, block.clone())));
+        warnings.extend(report.warnings.iter().map(|warning| issue(field, warning.clone())));
+        if let Some(release) = &report.release {
+            check_containers(spec, &row.module, &release.l4t, &mut errors, &mut warnings);
+        }
+    }
+    if !steps(spec).is_empty() && spec.connect.is_none() {
+        errors.push(issue("connect", "Users, network, packages, containers and tests are applied over SSH; set connect"));
+    }
+    for (field, names) in [
+        ("users", spec.users.iter().map(|u| u.name.as_str()).collect::<Vec<_>>()),
+        ("packages", spec.packages.iter().map(|p| p.split('=').next().unwrap_or(p)).collect()),
+        ("containers", spec.containers.iter().map(String::as_str).collect()),
+        ("tests", spec.tests.iter().map(|t| t.name.as_str()).collect()),
+    ] {
+        for (index, name) in names.iter().enumerate() {
+            if names[..index].contains(name) {
+                warnings.push(issue(format!("{}[{}]", field, index), format!("{} is listed more than once", name)));
+            }
+        }
+    }
+    let lint = SpecLint {
+        valid: errors.is_empty(),
+        errors,
+        warnings,
+    };
+    (lint, row)
+}
+
+// Lint a spec document for one unit without compiling it
+pub fn lint(spec: &str, vars: &JobVariables, matrix: &DeviceMatrix, versions: &VersionMatrix) -> SpecLint {
+    match parse(spec, vars) {
+        Ok((spec, _)) => lint_spec(&spec, matrix, versions).0,
+        Err(e) => SpecLint {
+            valid: false,
+            errors: vec![issue("", e)],
+            warnings: Vec::new(),
+        },
+    }
+}
+
+// The post-flash steps, in the order they run. A new static address cuts the SSH session, so it comes last.
+fn steps(spec: &DeviceSpec) -> Vec<SpecStep> {
+    let mut steps = Vec::new();
+    let mut users = String::from(SUDO);
+    for (index, user) in spec.users.iter().enumerate() {
+        let name = shell_quote(&user.name);
+        if index > 0 {
+            users.push_str(&format!("id -u {name} >/dev/null 2>&1 || $S useradd -m -s /bin/bash {name}; "));
+        }
+        if !user.groups.is_empty() {
+            users.push_str(&format!("$S usermod -aG {} {name}; ", shell_quote(&user.groups.join(","))));
+        }
+        if !user.ssh_keys.is_empty() {
+            users.push_str(&format!(
+                "h=$(getent passwd {name} | cut -d: -f6); $S install -d -m 700 -o {name} -g {name} \"$h/.ssh\"; \
+                 $S touch \"$h/.ssh/authorized_keys\"; $S chown {name}: \"$h/.ssh/authorized_keys\"; $S chmod 600 \"$h/.ssh/authorized_keys\"; "
+            ));
+            for key in &user.ssh_keys {
+                let key = shell_quote(key.trim());
+                users.push_str(&format!(
+                    "$S grep -qxF {key} \"$h/.ssh/authorized_keys\" || echo {key} | $S tee -a \"$h/.ssh/authorized_keys\" >/dev/null; "
+                ));
+            }
+        }
+    }
+    if users.len() > SUDO.len() {
+        steps.push(SpecStep {
+            name: "Users".to_string(),
+            kind: "users".to_string(),
+            script: format!("set -e; {}", users),
+        });
+    }
+
+    let network = spec.network.clone().unwrap_or_default();
+    if let Some(hostname) = &network.hostname {
+        let hostname = shell_quote(hostname);
+        steps.push(SpecStep {
+            name: "Hostname".to_string(),
+            kind: "hostname".to_string(),
+            script: format!(
+                "set -e; {SUDO}$S hostnamectl set-hostname {hostname}; \
+                 $S sed -i \"s/^127\\.0\\.1\\.1.*/127.0.1.1\\t\"{hostname}\"/\" /etc/hosts"
+            ),
+        });
+    }
+    if !spec.packages.is_empty() {
+        let packages: Vec<String> = spec.packages.iter().map(|p| shell_quote(p)).collect();
+        steps.push(SpecStep {
+            name: "Packages".to_string(),
+            kind: "packages".to_string(),
+            script: format!(
+                "set -e; {SUDO}$S apt-get update; $S env DEBIAN_FRONTEND=noninteractive apt-get install -y {}",
+                packages.join(" ")
+            ),
+        });
+    }
+    if !spec.containers.is_empty() {
+        let pulls: Vec<String> = spec.containers.iter().map(|c| format!("$S docker pull {}", shell_quote(c))).collect();
+        steps.push(SpecStep {
+            name: "Containers".to_string(),
+            kind: "containers".to_string(),
+            script: format!("set -e; {SUDO}{}", pulls.join("; ")),
+        });
+    }
+    for test in &spec.tests {
+        steps.push(SpecStep {
+            name: test.name.clone(),
+            kind: "test".to_string(),
+            script: test.run.clone(),
+        });
+    }
+    if let Some(address) = &network.address {
+        let interface = shell_quote(network.interface.as_deref().unwrap_or("eth0"));
+        let mut settings = format!("ipv4.method manual ipv4.addresses {}", shell_quote(address));
+        if let Some(gateway) = &network.gateway {
+            settings.push_str(&format!(" ipv4.gateway {}", shell_quote(gateway)));
+        }
+        if !network.dns.is_empty() {
+            settings.push_str(&format!(" ipv4.dns {}", shell_quote(&network.dns.join(" "))));
+        }
+        // Applied in the background so the command returns before the address changes
+        steps.push(SpecStep {
+            name: "Static address".to_string(),
+            kind: "address".to_string(),
+            script: format!(
+                "set -e; {SUDO}c=$(nmcli -g GENERAL.CONNECTION device show {interface}); $S nmcli connection modify \"$c\" {settings}; \
+                 (sleep 2; $S nmcli connection up \"$c\") >/dev/null 2>&1 &"
+            ),
+        });
+    }
+    steps
+}
+
+async fn compile(app: &tauri::AppHandle, state: &AppState, spec: &DeviceSpec) -> SpecValidation {
+    let matrix = match device_matrix::device_matrix(app, state).await {
+        Ok(matrix) => matrix,
+        Err(e) => return SpecValidation { valid: false, errors: vec![e], warnings: Vec::new(), plan: None },
+    };
+    let versions = state.version_matrix.lock().unwrap().clone();
+    let (lint, row) = lint_spec(spec, &matrix, &versions);
+    let errors: Vec<String> = lint.errors.iter().map(SpecIssue::to_string).collect();
+    let warnings = lint.warnings.iter().map(SpecIssue::to_string).collect();
+    let (Some(row), true) = (row, lint.valid) else {
+        return SpecValidation { valid: false, errors, warnings, plan: None };
+    };
+    let steps = steps(spec);
+    let command = FlashCommand {
+        product: row.product,
+        device_module: row.module,
+        jetpack_version: row.jetpack,
+        storage_device: row.storage,
+        keep_files: false,
+        user_name: spec.users[0].name.clone(),
+        provisioning: spec.provisioning.clone(),
+        custom_kernel: None,
+        pinned_artifacts: Vec::new(),
+        operator: None,
+        skip_stages: Vec::new(),
+        wait_for_device_secs: None,
+        device_binding: None,
+        delta: false,
+        // The job only completes once the device answers, so the post-flash steps can start right away
+        boot_check: spec.connect.clone().map(|target| boot_check::BootCheckOptions {
+            method: "ssh".to_string(),
+            ssh: Some(target),
+            ..Default::default()
+        }),
+    };
+    SpecValidation {
+        valid: true,
+        errors,
+        warnings,
+        plan: Some(SpecPlan { command, steps }),
+    }
+}
+
+// Run the post-flash steps in order, stopping at the first failed one other than a test
+async fn apply_steps(app: &tauri::AppHandle, target: &SshTarget, flash_id: Option<&str>, steps: &[SpecStep]) -> Vec<SpecStepResult> {
+    let mut results = Vec::new();
+    for step in steps {
+        let (passed, output) = match run_remote(target, &step.script).await {
+            Ok(output) => (true, output),
+            Err(e) => (false, e.to_string()),
+        };
+        if passed {
+            info!("Device spec step {} passed on {}", step.name, target.host);
+        } else {
+            warn!("Device spec step {} failed on {}: {}", step.name, target.host, output);
+        }
+        let result = SpecStepResult {
+            name: step.name.clone(),
+            kind: step.kind.clone(),
+            passed,
+            output: output.trim().to_string(),
+            finished_at: Utc::now(),
+        };
+        let _ = app.emit("device-spec-step", serde_json::json!({
+            "flash_id": flash_id,
+            "host": target.host,
+            "result": result
+        }));
+        results.push(result);
+        if !passed && step.kind != "test" {
+            break;
+        }
+    }
+    results
+}
+
+// Wait for a launched flash and apply the post-flash steps when it succeeded
+async fn finish_after_flash(app: tauri::AppHandle, state: Arc<AppState>, flash_id: String, target: Option<SshTarget>, steps: Vec<SpecStep>) {
+    let status = loop {
+        tokio::time::sleep(JOB_POLL_INTERVAL).await;
+        let status = {
+            let history = state.history.lock().unwrap();
+            history.jobs.iter().rev().find(|job| job.flash_id == flash_id).map(|job| job.status.clone())
+        };
+        match status.as_deref() {
+            Some("running") => continue,
+            status => break status.unwrap_or_default().to_string(),
+        }
+    };
+    let results = match (&target, status.as_str()) {
+        (Some(target), "success") if !steps.is_empty() => apply_steps(&app, target, Some(&flash_id), &steps).await,
+        _ => Vec::new(),
+    };
+    let passed = results.iter().all(|r| r.passed) && status == "success";
+    history::record_spec_results(&app, &state, &flash_id, results.clone());
+    let _ = app.emit("device-spec-finished", serde_json::json!({
+        "flash_id": flash_id,
+        "status": status,
+        "passed": passed,
+        "results": results
+    }));
+}
+
+// Validate a spec and show the flash command and post-flash steps it compiles to
+#[command]
+pub async fn validate_device_spec(
+    spec: String,
+    variables: Option<JobVariables>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<SpecValidation, String> {
+    let spec = match parse(&spec, &variables.unwrap_or_default()) {
+        Ok((spec, _)) => spec,
+        Err(e) => return Ok(SpecValidation { valid: false, errors: vec![e], warnings: Vec::new(), plan: None }),
+    };
+    Ok(compile(&app, &state, &spec).await)
+}
+
+// Lint a spec file against the device matrix, the version matrix and container compatibility
+#[command]
+pub async fn validate_spec(
+    path: String,
+    variables: Option<JobVariables>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<SpecLint, String> {
+    let spec = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let matrix = device_matrix::device_matrix(&app, &state).await?;
+    let versions = state.version_matrix.lock().unwrap().clone();
+    Ok(lint(&spec, &variables.unwrap_or_default(), &matrix, &versions))
+}
+
+// Flash a device from a spec and apply the rest of it once it has booted; returns the flash ID.
+// Step results follow as device-spec-step events and device-spec-finished. `variables` identify the unit
+// within its batch, see batch.rs.
+#[command]
+pub async fn run_device_spec(
+    spec: String,
+    variables: Option<JobVariables>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<String, String> {
+    let vars = variables.unwrap_or_default();
+    let (spec, variables) = parse(&spec, &vars)?;
+    let validation = compile(&app, &state, &spec).await;
+    let Some(mut plan) = validation.plan else {
+        return Err(validation.errors.join("; "));
+    };
+    plan.command.operator = vars.operator;
+    let flash_id = crate::launch_flash(plan.command, Arc::clone(state.inner()), app.clone()).await?;
+    history::record_variables(&app, &state, &flash_id, variables);
+    info!("Flashing device spec {} as {}", spec.name, flash_id);
+    tokio::spawn(finish_after_flash(app, Arc::clone(state.inner()), flash_id.clone(), spec.connect, plan.steps));
+    Ok(flash_id)
+}
+
+// Apply a spec's post-flash steps to a device that is already running, e.g. after editing the spec;
+// `target` overrides the spec's connect
+#[command]
+pub async fn apply_device_spec(
+    spec: String,
+    target: Option<SshTarget>,
+    variables: Option<JobVariables>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<SpecStepResult>, String> {
+    let (mut spec, _) = parse(&spec, &variables.unwrap_or_default())?;
+    if target.is_some() {
+        spec.connect = target;
+    }
+    let validation = compile(&app, &state, &spec).await;
+    let (Some(plan), Some(target)) = (validation.plan, spec.connect) else {
+        return Err(validation.errors.join("; "));
+    };
+    Ok(apply_steps(&app, &target, None, &plan.steps).await)
+}
+
+// A profile as a device spec, to continue from it as code
+#[command]
+pub async fn export_profile_spec(name: String, app: tauri::AppHandle) -> Result<String, String> {
+    let profile = profiles::find_profile(&app, &name).map_err(|e| e.to_string())?;
+    let command = profile.command;
+    let spec = DeviceSpec {
+        name: profile.name,
+        product: command.product,
+        module: command.device_module,
+        l4t: String::new(),
+        jetpack: command.jetpack_version,
+        storage: command.storage_device,
+        users: vec![SpecUser {
+            name: command.user_name,
+            ssh_keys: Vec::new(),
+            groups: Vec::new(),
+        }],
+        network: None,
+        packages: Vec::new(),
+        containers: Vec::new(),
+        tests: Vec::new(),
+        provisioning: command.provisioning,
+        connect: command.boot_check.and_then(|check| check.ssh),
+    };
+    serde_yaml::to_string(&spec).map_err(|e| e.to_string())
+}
@@ -0,0 +1,287 @@
+// CFU - Cordatus Flash Utility - Release Downloads
+// Multi-file releases (BSP, sample rootfs, overlays, public sources) described by a manifest and
+// fetched as a unit: files are resumed and verified in a staging directory and only moved into
+// place once every one of them is complete
+
+use crate::checksum;
+use crate::mirrors::{self, Source};
+use crate::AppState;
+use anyhow::{Context, Result};
+use log::{info, warn};
+use reqwest::header::RANGE;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tauri::{command, Emitter, Manager};
+use tokio::io::AsyncWriteExt;
+
+pub const MANIFEST_SCHEMA: u32 = 1;
+const CHECKPOINT_FILE: &str = "checkpoint.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReleaseManifest {
+    pub schema: u32,
+    pub release: String, // e.g. "r36.4.3"
+    pub files: Vec<ManifestFile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestFile {
+    pub url: String,
+    #[serde(default)]
+    pub size: Option<u64>,
+    #[serde(default)]
+    pub sha256: Option<String>,
+    pub destination: String, // Relative to the release directory
+    #[serde(default)]
+    pub ipfs_cid: Option<String>, // Lets IPFS mirrors serve the file
+    #[serde(default)]
+    pub magnet: Option<String>, // Lets torrent mirrors serve the file
+}
+
+// Progress of a release download, kept next to the staged files. Tied to the manifest
+// it was made for, so a changed manifest starts over instead of mixing files.
+#[derive(Debug, Serialize, Deserialize)]
+struct Checkpoint {
+    manifest: ReleaseManifest,
+    completed: Vec<String>, // Destinations downloaded and verified
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReleaseDownload {
+    pub release: String,
+    pub directory: String,
+    pub files: usize,
+    pub resumed_files: usize,
+    pub downloaded_bytes: u64,
+}
+
+pub fn load_manifest(path: &Path) -> Result<ReleaseManifest> {
+    let content = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let manifest: ReleaseManifest =
+        serde_json::from_str(&content).with_context(|| format!("Invalid release manifest {}", path.display()))?;
+    validate_manifest(&manifest)?;
+    Ok(manifest)
+}
+
+pub fn validate_manifest(manifest: &ReleaseManifest) -> Result<()> {
+    if manifest.schema != MANIFEST_SCHEMA {
+        return Err(anyhow::anyhow!("Unsupported release manifest schema {}", manifest.schema));
+    }
+    if manifest.release.trim().is_empty() || manifest.files.is_empty() {
+        return Err(anyhow::anyhow!("Release manifest needs a release name and at least one file"));
+    }
+    let mut destinations = HashSet::new();
+    for file in &manifest.files {
+        let relative = Path::new(&file.destination);
+        if file.destination.is_empty() || !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(anyhow::anyhow!("Invalid destination '{}' in release manifest", file.destination));
+        }
+        if !destinations.insert(&file.destination) {
+            return Err(anyhow::anyhow!("Destination '{}' appears twice in release manifest", file.destination));
+        }
+    }
+    Ok(())
+}
+
+pub fn release_dir_name(release: &str) -> String {
+    release
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+        .collect()
+}
+
+fn staging_dir(directory: &Path, release: &str) -> PathBuf {
+    directory.join(format!(".cfu-partial-{}", release_dir_name(release)))
+}
+
+fn part_path(staged: &Path) -> PathBuf {
+    let mut name = staged.as_os_str().to_owned();
+    name.push(".part");
+    PathBuf::from(name)
+}
+
+fn load_checkpoint(staging: &Path, manifest: &ReleaseManifest) -> Checkpoint {
+    let saved = std::fs::read_to_string(staging.join(CHECKPOINT_FILE))
+        .ok()
+        .and_then(|content| serde_json::from_str::<Checkpoint>(&content).ok());
+    match saved {
+        Some(checkpoint) if checkpoint.manifest == *manifest => checkpoint,
+        Some(_) => {
+            warn!("Release manifest for {} changed, discarding partial download", manifest.release);
+            std::fs::remove_dir_all(staging).ok();
+            Checkpoint { manifest: manifest.clone(), completed: Vec::new() }
+        }
+        None => Checkpoint { manifest: manifest.clone(), completed: Vec::new() },
+    }
+}
+
+fn save_checkpoint(staging: &Path, checkpoint: &Checkpoint) -> Result<()> {
+    let json = serde_json::to_string_pretty(checkpoint)?;
+    std::fs::write(staging.join(CHECKPOINT_FILE), json).context("Failed to write download checkpoint")
+}
+
+// Continue a partial file with a Range request; servers that ignore it send the whole file again
+pub async fn download_resumable(url: &str, part: &Path, on_chunk: &mut impl FnMut(u64)) -> Result<()> {
+    let offset = tokio::fs::metadata(part).await.map(|m| m.len()).unwrap_or(0);
+    let mut request = reqwest::Client::new().get(url);
+    if offset > 0 {
+        request = request.header(RANGE, format!("bytes={}-", offset));
+    }
+    let response = request.send().await?;
+    if offset > 0 && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        return Ok(()); // Already complete
+    }
+    let mut response = response.error_for_status().with_context(|| format!("Failed to download {}", url))?;
+
+    let resumed = offset > 0 && response.status() == StatusCode::PARTIAL_CONTENT;
+    let mut file = if resumed {
+        tokio::fs::OpenOptions::new().append(true).open(part).await?
+    } else {
+        tokio::fs::File::create(part).await?
+    };
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk).await?;
+        on_chunk(chunk.len() as u64);
+    }
+    file.flush().await?;
+    Ok(())
+}
+
+pub(crate) async fn fetch_from(source: &Source, part: &Path, on_chunk: &mut impl FnMut(u64)) -> Result<()> {
+    match source {
+        Source::Http { url, .. } => download_resumable(url, part, on_chunk).await,
+        Source::Torrent { tracker, magnet, .. } => {
+            mirrors::fetch_torrent(tracker, magnet, part).await?;
+            on_chunk(tokio::fs::metadata(part).await?.len());
+            Ok(())
+        }
+    }
+}
+
+async fn verify_file(file: &ManifestFile, path: &Path) -> Result<()> {
+    let size = tokio::fs::metadata(path).await?.len();
+    if let Some(expected) = file.size {
+        if size != expected {
+            return Err(anyhow::anyhow!("{} is {} bytes, expected {}", file.destination, size, expected));
+        }
+    }
+    if let Some(expected) = &file.sha256 {
+        let path = path.to_path_buf();
+        // Staged files may still be written by an earlier attempt's transfer, so they are not mapped
+        let actual = tokio::task::spawn_blocking(move || checksum::sha256_file_streamed(&path)).await??;
+        if !actual.eq_ignore_ascii_case(expected) {
+            return Err(anyhow::anyhow!("Checksum mismatch for {}", file.destination));
+        }
+    }
+    Ok(())
+}
+
+// Fetch every file of a release into `directory`. An interrupted download resumes where it
+// stopped; nothing appears in `directory` until the whole release is verified.
+pub async fn fetch_release(app: &tauri::AppHandle, manifest: &ReleaseManifest, directory: &Path) -> Result<ReleaseDownload> {
+    validate_manifest(manifest)?;
+    let staging = staging_dir(directory, &manifest.release);
+    let mut checkpoint = load_checkpoint(&staging, manifest);
+    std::fs::create_dir_all(&staging).context("Failed to create download staging directory")?;
+    let resumed_files = checkpoint.completed.len();
+
+    let mirror_settings = app.state::<Arc<AppState>>().settings.lock().unwrap().mirrors.clone();
+    let total: Option<u64> = manifest.files.iter().map(|f| f.size).sum();
+    let mut downloaded_bytes: u64 = 0;
+    let mut last_emit: u64 = 0;
+
+    for file in &manifest.files {
+        let staged = staging.join(&file.destination);
+        if checkpoint.completed.contains(&file.destination) && staged.is_file() {
+            continue;
+        }
+        if let Some(parent) = staged.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let part = part_path(&staged);
+        let mut on_chunk = |bytes: u64| {
+            downloaded_bytes += bytes;
+            if downloaded_bytes - last_emit >= 4 * 1024 * 1024 {
+                last_emit = downloaded_bytes;
+                let _ = app.emit(
+                    "release-download-progress",
+                    serde_json::json!({ "release": manifest.release, "file": file.destination, "downloaded": downloaded_bytes, "total": total }),
+                );
+            }
+        };
+
+        // Mirrors first, then the original URL; a failed transfer leaves the partial file for the next source
+        let mut result = Err(anyhow::anyhow!("No source for {}", file.destination));
+        for source in mirrors::sources(&mirror_settings, file) {
+            info!("Downloading {} for release {} from {}", file.destination, manifest.release, source.name());
+            if let Err(e) = fetch_from(&source, &part, &mut on_chunk).await {
+                warn!("Download of {} from {} failed: {:#}", file.destination, source.name(), e);
+                result = Err(e);
+                continue;
+            }
+            result = verify_file(file, &part).await;
+            match &result {
+                Ok(()) => break,
+                Err(e) => {
+                    // A corrupt partial file would fail the same way on every resume
+                    warn!("{} from {} is corrupt: {:#}", file.destination, source.name(), e);
+                    std::fs::remove_file(&part).ok();
+                }
+            }
+        }
+        result?;
+        std::fs::rename(&part, &staged)?;
+        checkpoint.completed.push(file.destination.clone());
+        save_checkpoint(&staging, &checkpoint)?;
+    }
+
+    // Everything is verified, so the release can be moved into place
+    for file in &manifest.files {
+        let target = directory.join(&file.destination);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::rename(staging.join(&file.destination), &target)
+            .with_context(|| format!("Failed to move {} into place", file.destination))?;
+    }
+    std::fs::remove_dir_all(&staging).ok();
+    let _ = app.emit(
+        "release-download-progress",
+        serde_json::json!({ "release": manifest.release, "file": null, "downloaded": downloaded_bytes, "total": total }),
+    );
+
+    Ok(ReleaseDownload {
+        release: manifest.release.clone(),
+        directory: directory.to_string_lossy().to_string(),
+        files: manifest.files.len(),
+        resumed_files,
+        downloaded_bytes,
+    })
+}
+
+// Read and validate a release manifest file
+#[command]
+pub async fn read_release_manifest(path: String) -> Result<ReleaseManifest, String> {
+    load_manifest(Path::new(&path)).map_err(|e| e.to_string())
+}
+
+// Download a release; the default directory is app_data/releases/<release>
+#[command]
+pub async fn download_release(
+    manifest: ReleaseManifest,
+    directory: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<ReleaseDownload, String> {
+    let fetch = async {
+        let directory = match directory {
+            Some(directory) => PathBuf::from(directory),
+            None => crate::app_data_file(&app, "releases")?.join(release_dir_name(&manifest.release)),
+        };
+        fetch_release(&app, &manifest, &directory).await
+    };
+    fetch.await.map_err(|e| format!("Failed to download release {}: {:#}", manifest.release, e))
+}
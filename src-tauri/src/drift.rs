@@ -0,0 +1,171 @@
+// CFU - Cordatus Flash Utility - Drift Detection
+// Compare a booted device's L4T release, installed packages and containers against the profile it was provisioned with
+
+use crate::asset::read_device_identity;
+use crate::passport::list_containers;
+use crate::profiles::{self, FlashProfile};
+use crate::ssh::{run_remote, SshTarget};
+use crate::AppState;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use tauri::{command, State};
+
+const PACKAGES_SCRIPT: &str = r#"dpkg-query -W -f='${db:Status-Abbrev} ${Package} ${Version}\n' | awk '$1 == "ii" { print $2, $3 }'"#;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageChange {
+    pub name: String,
+    pub expected: Option<String>, // None: installed on the device only
+    pub actual: Option<String>,   // None: missing from the device
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DriftReport {
+    pub serial_number: String,
+    pub profile: String,
+    pub expected_l4t: Option<String>,
+    pub actual_l4t: Option<String>,
+    pub packages: Vec<PackageChange>,
+    pub missing_containers: Vec<String>,
+    pub unexpected_containers: Vec<String>,
+    pub drifted: bool,
+    pub checked_at: DateTime<Utc>,
+}
+
+async fn read_packages(target: &SshTarget) -> Result<BTreeMap<String, String>> {
+    let output = run_remote(target, PACKAGES_SCRIPT).await?;
+    Ok(output
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .map(|(name, version)| (name.to_string(), version.trim().to_string()))
+        .collect())
+}
+
+fn compare_packages(expected: &BTreeMap<String, String>, actual: &BTreeMap<String, String>) -> Vec<PackageChange> {
+    let names: BTreeSet<&String> = expected.keys().chain(actual.keys()).collect();
+    names
+        .into_iter()
+        .filter(|name| expected.get(*name) != actual.get(*name))
+        .map(|name| PackageChange {
+            name: name.clone(),
+            expected: expected.get(name).cloned(),
+            actual: actual.get(name).cloned(),
+        })
+        .collect()
+}
+
+// The profile of the device's most recent profile-based flash
+fn provisioned_profile(state: &AppState, serial_number: &str) -> Option<String> {
+    let history = state.history.lock().unwrap();
+    history
+        .jobs
+        .iter()
+        .rev()
+        .filter(|r| r.serial_number.as_deref() == Some(serial_number) && r.status == "success")
+        .find_map(|r| r.profile.clone())
+}
+
+// Record a reference device's packages and containers as the profile's drift baseline
+#[command]
+pub async fn capture_profile_baseline(
+    name: String,
+    target: SshTarget,
+    app: tauri::AppHandle,
+) -> Result<FlashProfile, String> {
+    crate::freeze::ensure_not_frozen(&app, "Profile changes")?;
+    let packages = read_packages(&target)
+        .await
+        .map_err(|e| format!("Failed to list packages: {}", e))?;
+    let containers = list_containers(&target)
+        .await
+        .map_err(|e| format!("Failed to list containers: {}", e))?;
+
+    let mut profiles = profiles::load_profiles(&app);
+    let profile = profiles
+        .iter_mut()
+        .find(|p| p.name == name)
+        .ok_or_else(|| format!("Profile not found: {}", name))?;
+    profile.packages = packages;
+    profile.containers = containers.into_iter().map(|c| c.name).collect();
+    profile.updated_at = Utc::now();
+    let updated = profile.clone();
+    profiles::save_profiles(&app, &profiles).map_err(|e| e.to_string())?;
+
+    info!("Captured baseline for profile {}: {} packages", name, updated.packages.len());
+    Ok(updated)
+}
+
+// Compare a booted device against its profile; the profile defaults to the one it was last flashed from
+#[command]
+pub async fn detect_drift(
+    target: SshTarget,
+    profile: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<DriftReport, String> {
+    let identity = read_device_identity(&target, "")
+        .await
+        .map_err(|e| format!("Failed to identify device: {}", e))?;
+    let profile_name = profile
+        .or_else(|| provisioned_profile(&state, &identity.serial_number))
+        .ok_or_else(|| format!("No profile recorded for {}", identity.serial_number))?;
+    let profile = profiles::find_profile(&app, &profile_name).map_err(|e| e.to_string())?;
+
+    // Compare resolved releases, so "6.2 - L4T 36.4.3" matches "L4T 36.4.3"
+    let (expected_l4t, actual_l4t) = {
+        let matrix = state.version_matrix.lock().unwrap();
+        let resolve = |version: &str| matrix.resolve(version).map(|r| r.l4t.clone());
+        (
+            resolve(&profile.command.jetpack_version),
+            identity.l4t_version.as_deref().map(|v| resolve(v).unwrap_or_else(|| v.to_string())),
+        )
+    };
+
+    // Without a baseline only the L4T release can be checked
+    let packages = if profile.packages.is_empty() {
+        Vec::new()
+    } else {
+        let actual = read_packages(&target)
+            .await
+            .map_err(|e| format!("Failed to list packages: {}", e))?;
+        compare_packages(&profile.packages, &actual)
+    };
+
+    let running: Vec<String> = list_containers(&target)
+        .await
+        .map_err(|e| format!("Failed to list containers: {}", e))?
+        .into_iter()
+        .map(|c| c.name)
+        .collect();
+    let missing_containers: Vec<String> = profile.containers.iter().filter(|c| !running.contains(c)).cloned().collect();
+    let unexpected_containers: Vec<String> = running.into_iter().filter(|c| !profile.containers.contains(c)).collect();
+
+    let l4t_drifted = expected_l4t.is_some() && expected_l4t != actual_l4t;
+    let drifted = l4t_drifted || !packages.is_empty() || !missing_containers.is_empty() || !unexpected_containers.is_empty();
+    if drifted {
+        warn!(
+            "Device {} drifted from profile {}: {} package changes, {} missing and {} unexpected containers",
+            identity.serial_number,
+            profile.name,
+            packages.len(),
+            missing_containers.len(),
+            unexpected_containers.len()
+        );
+    }
+
+    Ok(DriftReport {
+        serial_number: identity.serial_number,
+        profile: profile.name,
+        expected_l4t,
+        actual_l4t,
+        packages,
+        missing_containers,
+        unexpected_containers,
+        drifted,
+        checked_at: Utc::now(),
+    })
+}
@@ -0,0 +1,119 @@
+// CFU - Cordatus Flash Utility - Archive Extraction
+// Streaming tar extraction (gzip, bzip2, zstd or plain) with progress and cancellation, run by the flash
+// script through `cfu --extract` in place of `tar xf` so the preparing phase shows real movement
+
+use crate::job_host::JobHost;
+use anyhow::{Context, Result};
+use std::cell::Cell;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+// Flag file the app creates to stop a running extraction between entries
+pub fn cancel_file(app: &impl JobHost, flash_id: &str) -> Result<PathBuf> {
+    crate::app_data_file(app, &format!("cancel_{}", flash_id))
+}
+
+// Counts compressed bytes consumed, which tracks progress through the archive;
+// the count lives outside so it stays readable while the decoder owns the reader
+struct CountingReader<'a, R> {
+    inner: R,
+    consumed: &'a Cell<u64>,
+}
+
+impl<R: Read> Read for CountingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.consumed.set(self.consumed.get() + read as u64);
+        Ok(read)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExtractProgress {
+    pub percent: u32,
+    pub entries: u64,
+}
+
+fn decoder<'a>(magic: &[u8], reader: impl Read + 'a) -> Result<Box<dyn Read + 'a>> {
+    Ok(match magic {
+        [0x1f, 0x8b, ..] => Box::new(flate2::read::GzDecoder::new(reader)),
+        [b'B', b'Z', b'h', ..] => Box::new(bzip2::read::BzDecoder::new(reader)),
+        [0x28, 0xb5, 0x2f, 0xfd, ..] => Box::new(zstd::stream::read::Decoder::new(reader)?),
+        _ => Box::new(reader),
+    })
+}
+
+// Extract like `tar xpf`, keeping permissions and ownership since rootfs archives need both
+pub fn extract_archive(
+    archive: &Path,
+    dest: &Path,
+    cancel: Option<&Path>,
+    mut on_progress: impl FnMut(&ExtractProgress),
+) -> Result<u64> {
+    let mut file = File::open(archive).with_context(|| format!("Failed to open {}", archive.display()))?;
+    let total = file.metadata()?.len().max(1);
+    let mut magic = [0u8; 4];
+    let magic_len = file.read(&mut magic)?;
+    drop(file);
+
+    let consumed = Cell::new(0u64);
+    let reader = CountingReader {
+        inner: BufReader::new(File::open(archive)?),
+        consumed: &consumed,
+    };
+    let mut tar = tar::Archive::new(decoder(&magic[..magic_len], reader)?);
+    tar.set_preserve_permissions(true);
+    tar.set_preserve_ownerships(true);
+    tar.set_preserve_mtime(true);
+    tar.set_unpack_xattrs(true);
+    tar.set_overwrite(true);
+    std::fs::create_dir_all(dest).with_context(|| format!("Failed to create {}", dest.display()))?;
+
+    let mut progress = ExtractProgress { percent: 0, entries: 0 };
+    for entry in tar.entries()? {
+        if cancel.is_some_and(|path| path.exists()) {
+            return Err(anyhow::anyhow!("Extraction cancelled"));
+        }
+        let mut entry = entry?;
+        entry
+            .unpack_in(dest)
+            .with_context(|| format!("Failed to extract {}", entry.path().unwrap_or_default().display()))?;
+        progress.entries += 1;
+        let percent = ((consumed.get() * 100) / total).min(100) as u32;
+        if percent != progress.percent {
+            progress.percent = percent;
+            on_progress(&progress);
+        }
+    }
+    Ok(progress.entries)
+}
+
+// `cfu --extract <archive> <dest> [cancel-file]`, called by the flash script; returns
+// the exit code, or None when the process was started normally
+pub fn run_from_args() -> Option<i32> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) != Some("--extract") {
+        return None;
+    }
+    let (Some(archive), Some(dest)) = (args.get(2), args.get(3)) else {
+        eprintln!("Usage: {} --extract <archive> <destination> [cancel-file]", args[0]);
+        return Some(2);
+    };
+    let cancel = args.get(4).map(PathBuf::from);
+    let name = Path::new(archive).file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+
+    let result = extract_archive(Path::new(archive), Path::new(dest), cancel.as_deref(), |progress| {
+        println!("Extracting {}: {}% ({} files)", name, progress.percent, progress.entries);
+    });
+    match result {
+        Ok(entries) => {
+            println!("Extracting {}: 100% ({} files)", name, entries);
+            Some(0)
+        }
+        Err(e) => {
+            eprintln!("{:#}", e);
+            Some(if cancel.is_some_and(|path| path.exists()) { 130 } else { 1 })
+        }
+    }
+}
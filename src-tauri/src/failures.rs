@@ -0,0 +1,128 @@
+// CFU - Cordatus Flash Utility - Failure Classification
+// Recognizes the common ways a flash fails from the lines the flash tools print, so a failed job reports
+// a typed error code and what to do about it instead of only the script's exit code
+
+use log::warn;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlashFailure {
+    pub code: String, // 'usb_timeout' | 'no_space' | 'not_in_recovery' | 'wrong_board_id' | 'download_failed' | 'corrupt_archive' | 'missing_tool' | 'sudo_password'
+    pub summary: String,
+    pub remediation: Vec<String>,
+    pub line: String, // Output line the failure was recognized from
+}
+
+struct Rule {
+    code: &'static str,
+    pattern: &'static str,
+    summary: &'static str,
+    remediation: &'static [&'static str],
+}
+
+// Checked in order, so the more specific patterns come first
+const RULES: &[Rule] = &[
+    Rule {
+        code: "no_space",
+        pattern: r"(?i)no space left on device",
+        summary: "The host ran out of disk space",
+        remediation: &[
+            "Free space on the disk holding the BSP directory, a system image needs tens of GB",
+            "Delete BSP directories of releases that are no longer flashed from the cache settings",
+        ],
+    },
+    Rule {
+        code: "wrong_board_id",
+        pattern: r"(?i)(board ?id.*(does not match|mismatch|not supported)|chip ?id.*mismatch|invalid target board|unsupported board|unknown board (id|sku)|no matching board config)",
+        summary: "The connected board is not the module selected for this job",
+        remediation: &[
+            "Check that the selected module and carrier match the board in recovery mode",
+            "Developer kits and production modules of the same family use different configurations, compare the SKU on the module label",
+        ],
+    },
+    Rule {
+        code: "not_in_recovery",
+        pattern: r"(?i)(probing the target board failed|cannot find a force recovery|no jetson device (found|in recovery)|could not find .*recovery mode|rcm .*not found|device not found.*recovery)",
+        summary: "No board in force recovery mode was found",
+        remediation: &[
+            "Hold the REC (FC_REC) button, press and release RESET, then release REC",
+            "Check that `lsusb` lists an NVIDIA Corp. device (ID 0955:7xxx)",
+            "Use the USB-C or micro-USB port the carrier board documents for flashing",
+        ],
+    },
+    Rule {
+        code: "usb_timeout",
+        pattern: r"(?i)(usb (write|read|transfer).*(fail|timeout|timed out)|might be timeout in usb|tegrarcm.*timed? ?out|libusb.*(timeout|error)|error: return value \d+ .*usb|failed to send .*to target)",
+        summary: "The USB transfer to the board timed out",
+        remediation: &[
+            "Connect the board directly to a host USB port, not through a hub or front panel port",
+            "Try a shorter or known-good USB cable",
+            "Disable USB autosuspend: echo -1 | sudo tee /sys/module/usbcore/parameters/autosuspend",
+            "In a virtual machine, pass the NVIDIA USB device through for both the recovery and the flashing IDs",
+        ],
+    },
+    Rule {
+        code: "download_failed",
+        pattern: r"(?i)(temporary failure in name resolution|unable to resolve host address|unable to establish ssl connection|error 40[34]: |connection timed out\.? retrying)",
+        summary: "Downloading a BSP component failed",
+        remediation: &[
+            "Check the host's network connection and proxy settings",
+            "Retry the job, finished downloads are reused",
+        ],
+    },
+    Rule {
+        code: "corrupt_archive",
+        pattern: r"(?i)(unexpected end of file|unexpected eof in archive|data integrity error|invalid compressed data|not in gzip format)",
+        summary: "A BSP archive is incomplete or corrupt",
+        remediation: &[
+            "Delete the cached archive so it is downloaded again",
+            "Verify the archive checksums from the release settings",
+        ],
+    },
+    Rule {
+        code: "missing_tool",
+        pattern: r"(?i)(qemu-aarch64-static.*not found|: (command )?not found$|no module named )",
+        summary: "A tool the flash scripts need is not installed on the host",
+        remediation: &[
+            "Run sudo ./tools/l4t_flash_prerequisites.sh in the Linux_for_Tegra directory",
+            "Check the host environment report for missing packages",
+        ],
+    },
+    Rule {
+        code: "sudo_password",
+        pattern: r"(?i)sudo: (a password is required|a terminal is required|no tty present)",
+        summary: "The flash scripts need root but sudo asked for a password",
+        remediation: &["Allow the flashing user to run sudo without a password, or start the job from a root session"],
+    },
+];
+
+// A rule whose pattern does not compile is left out, so the others still classify
+fn compiled() -> &'static Vec<(&'static Rule, Regex)> {
+    static COMPILED: OnceLock<Vec<(&'static Rule, Regex)>> = OnceLock::new();
+    COMPILED.get_or_init(|| {
+        RULES
+            .iter()
+            .filter_map(|rule| match Regex::new(rule.pattern) {
+                Ok(pattern) => Some((rule, pattern)),
+                Err(e) => {
+                    warn!("Skipping failure rule {}: {}", rule.code, e);
+                    None
+                }
+            })
+            .collect()
+    })
+}
+
+// The failure an output line reports, if it is one of the known ones
+pub fn classify(line: &str) -> Option<FlashFailure> {
+    let line = line.trim();
+    let (rule, _) = compiled().iter().find(|(_, pattern)| pattern.is_match(line))?;
+    Some(FlashFailure {
+        code: rule.code.to_string(),
+        summary: rule.summary.to_string(),
+        remediation: rule.remediation.iter().map(|hint| hint.to_string()).collect(),
+        line: line.to_string(),
+    })
+}
@@ -0,0 +1,329 @@
+// CFU - Cordatus Flash Utility - Fleet
+// Registry of provisioned devices (address, identity, tags, deployments) and exports for configuration management and IaC tools
+
+use crate::asset::AssetRecord;
+use crate::containers::{self, ContainerPreset};
+use crate::history;
+use crate::job_host::JobHost;
+use crate::profiles::{self, FlashProfile};
+use crate::ssh::SshTarget;
+use crate::AppState;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use tauri::{command, State};
+
+// Bumped when the fleet state document changes incompatibly
+const FLEET_STATE_SCHEMA: u32 = 1;
+
+// Every exported host is in this group, tag groups are nested under it
+const ALL_DEVICES_GROUP: &str = "cfu_devices";
+
+const STARTER_PLAYBOOK: &str = r#"# Starter playbook generated by Cordatus Flash Utility
+- name: Configure provisioned Jetson devices
+  hosts: cfu_devices
+  become: true
+  gather_facts: true
+  tasks:
+    - name: Report device identity
+      ansible.builtin.debug:
+        msg: "{{ inventory_hostname }} ({{ serial_number }}) runs {{ l4t_version | default('unknown L4T') }}"
+
+    - name: Refresh the apt cache
+      ansible.builtin.apt:
+        update_cache: true
+        cache_valid_time: 3600
+"#;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceRecord {
+    pub serial_number: String,
+    pub hostname: String,
+    pub address: String, // Host the device was last reached at
+    pub ssh_user: String,
+    pub ssh_port: Option<u16>,
+    pub model: Option<String>,
+    pub l4t_version: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub last_seen: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContainerDeployment {
+    pub preset: String,
+    pub image: String,
+    pub address: String,
+    pub serial_number: Option<String>, // Known when the address belongs to a registered device
+    pub autostart: Option<String>,     // 'restart-policy' | 'systemd'
+    pub container: String,             // Container ID or systemd unit name
+    pub deployed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FleetDevice {
+    #[serde(flatten)]
+    pub device: DeviceRecord,
+    pub profile: Option<String>, // From the last successful flash
+    pub last_flashed_at: Option<DateTime<Utc>>,
+    pub containers: Vec<ContainerDeployment>, // Latest deployment per preset
+}
+
+// Machine-readable snapshot for IaC and asset systems, e.g. Terraform's jsondecode()
+#[derive(Debug, Clone, Serialize)]
+pub struct FleetState {
+    pub schema_version: u32,
+    pub generated_at: DateTime<Utc>,
+    pub devices: Vec<FleetDevice>,
+    pub profiles: Vec<FlashProfile>,
+    pub container_presets: Vec<ContainerPreset>,
+    pub deployments: Vec<ContainerDeployment>,
+}
+
+// Add or refresh a device from a captured identity, keeping its tags
+pub fn register_device(app: &impl JobHost, state: &AppState, record: &AssetRecord, target: &SshTarget) {
+    history::update_history(app, state, |history| {
+        let tags = history
+            .devices
+            .iter()
+            .find(|d| d.serial_number == record.serial_number)
+            .map(|d| d.tags.clone())
+            .unwrap_or_default();
+        history.devices.retain(|d| d.serial_number != record.serial_number);
+        history.devices.push(DeviceRecord {
+            serial_number: record.serial_number.clone(),
+            hostname: record.hostname.clone(),
+            address: target.host.clone(),
+            ssh_user: target.user.clone(),
+            ssh_port: target.port,
+            model: record.model.clone(),
+            l4t_version: record.l4t_version.clone(),
+            tags,
+            last_seen: Utc::now(),
+        });
+    });
+}
+
+// Remember a container deployment to a device
+pub fn record_deployment(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    preset: &ContainerPreset,
+    target: &SshTarget,
+    autostart: Option<&str>,
+    container: &str,
+) {
+    history::update_history(app, state, |history| {
+        let serial_number = history
+            .devices
+            .iter()
+            .find(|d| d.address == target.host)
+            .map(|d| d.serial_number.clone());
+        history.deployments.push(ContainerDeployment {
+            preset: preset.name.clone(),
+            image: preset.image.clone(),
+            address: target.host.clone(),
+            serial_number,
+            autostart: autostart.map(str::to_string),
+            container: container.to_string(),
+            deployed_at: Utc::now(),
+        });
+    });
+}
+
+fn build_fleet_state(app: &tauri::AppHandle, state: &AppState) -> FleetState {
+    let history = state.history.lock().unwrap();
+    let devices = history
+        .devices
+        .iter()
+        .map(|device| {
+            let last_job = history
+                .jobs
+                .iter()
+                .rev()
+                .find(|r| r.serial_number.as_deref() == Some(device.serial_number.as_str()) && r.status == "success");
+            let mut latest: BTreeMap<&str, &ContainerDeployment> = BTreeMap::new();
+            let on_device = |d: &&ContainerDeployment| match &d.serial_number {
+                Some(serial) => *serial == device.serial_number,
+                None => d.address == device.address,
+            };
+            for deployment in history.deployments.iter().filter(on_device) {
+                latest.insert(&deployment.preset, deployment);
+            }
+            FleetDevice {
+                device: device.clone(),
+                profile: last_job.and_then(|r| r.profile.clone()),
+                last_flashed_at: last_job.and_then(|r| r.finished_at),
+                containers: latest.into_values().cloned().collect(),
+            }
+        })
+        .collect();
+
+    FleetState {
+        schema_version: FLEET_STATE_SCHEMA,
+        generated_at: Utc::now(),
+        devices,
+        profiles: profiles::load_profiles(app),
+        container_presets: containers::load_presets(app),
+        deployments: history.deployments.clone(),
+    }
+}
+
+// Ansible group names: lowercase letters, digits and underscores
+fn group_name(tag: &str) -> String {
+    let name: String = tag
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("tag_{}", name)
+    } else {
+        name
+    }
+}
+
+fn ini_value(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// Hostnames are used as inventory names unless several devices share one
+fn inventory_names(devices: &[DeviceRecord]) -> Vec<String> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for device in devices {
+        *counts.entry(device.hostname.as_str()).or_default() += 1;
+    }
+    devices
+        .iter()
+        .map(|d| {
+            if d.hostname.is_empty() || counts[d.hostname.as_str()] > 1 {
+                format!("jetson-{}", d.serial_number.to_lowercase())
+            } else {
+                d.hostname.clone()
+            }
+        })
+        .collect()
+}
+
+fn render_inventory(devices: &[DeviceRecord]) -> String {
+    let names = inventory_names(devices);
+    let mut out = format!("# Generated by Cordatus Flash Utility on {}\n\n", Utc::now().to_rfc3339());
+
+    out.push_str(&format!("[{}]\n", ALL_DEVICES_GROUP));
+    for (device, name) in devices.iter().zip(&names) {
+        let mut vars = vec![
+            format!("ansible_host={}", device.address),
+            format!("ansible_user={}", device.ssh_user),
+            format!("serial_number={}", ini_value(&device.serial_number)),
+        ];
+        if let Some(port) = device.ssh_port {
+            vars.push(format!("ansible_port={}", port));
+        }
+        if let Some(model) = &device.model {
+            vars.push(format!("jetson_model={}", ini_value(model)));
+        }
+        if let Some(l4t) = &device.l4t_version {
+            vars.push(format!("l4t_version={}", ini_value(l4t)));
+        }
+        out.push_str(&format!("{} {}\n", name, vars.join(" ")));
+    }
+
+    let mut groups: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for (device, name) in devices.iter().zip(&names) {
+        for tag in &device.tags {
+            groups.entry(group_name(tag)).or_default().push(name);
+        }
+    }
+    for (group, hosts) in &groups {
+        out.push_str(&format!("\n[{}]\n{}\n", group, hosts.join("\n")));
+    }
+    if !groups.is_empty() {
+        out.push_str(&format!("\n[{}:children]\n", ALL_DEVICES_GROUP));
+        for group in groups.keys() {
+            out.push_str(&format!("{}\n", group));
+        }
+    }
+    out
+}
+
+fn write_file(path: &str, content: &str) -> Result<()> {
+    std::fs::write(path, content).with_context(|| format!("Failed to write {}", path))
+}
+
+// All registered devices, most recently seen first
+#[command]
+pub async fn list_devices(state: State<'_, Arc<AppState>>) -> Result<Vec<DeviceRecord>, String> {
+    let mut devices = state.history.lock().unwrap().devices.clone();
+    devices.sort_by_key(|d| std::cmp::Reverse(d.last_seen));
+    Ok(devices)
+}
+
+// Replace the tags of a registered device
+#[command]
+pub async fn set_device_tags(
+    serial_number: String,
+    tags: Vec<String>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    if !state.history.lock().unwrap().devices.iter().any(|d| d.serial_number == serial_number) {
+        return Err(format!("Device not registered: {}", serial_number));
+    }
+    let tags: Vec<String> = tags.iter().map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect();
+    history::update_history(&app, &state, |history| {
+        if let Some(device) = history.devices.iter_mut().find(|d| d.serial_number == serial_number) {
+            device.tags = tags;
+        }
+    });
+    Ok(())
+}
+
+// Export registered devices as an INI Ansible inventory, one group per tag,
+// optionally with a starter playbook; `tags` limits the export to matching devices
+#[command]
+pub async fn export_ansible_inventory(
+    path: String,
+    playbook_path: Option<String>,
+    tags: Option<Vec<String>>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<usize, String> {
+    let devices: Vec<DeviceRecord> = {
+        let history = state.history.lock().unwrap();
+        history
+            .devices
+            .iter()
+            .filter(|d| tags.as_ref().is_none_or(|tags| d.tags.iter().any(|t| tags.contains(t))))
+            .cloned()
+            .collect()
+    };
+    if devices.is_empty() {
+        return Err("No registered devices to export".to_string());
+    }
+
+    write_file(&path, &render_inventory(&devices)).map_err(|e| e.to_string())?;
+    if let Some(playbook_path) = &playbook_path {
+        write_file(playbook_path, STARTER_PLAYBOOK).map_err(|e| e.to_string())?;
+    }
+
+    info!("Exported {} devices to Ansible inventory {}", devices.len(), path);
+    Ok(devices.len())
+}
+
+// Regenerate the fleet state document, writing it to `path` when given
+#[command]
+pub async fn export_fleet_state(
+    path: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<FleetState, String> {
+    let fleet = build_fleet_state(&app, &state);
+    if let Some(path) = &path {
+        let json = serde_json::to_string_pretty(&fleet).map_err(|e| e.to_string())?;
+        write_file(path, &json).map_err(|e| e.to_string())?;
+        info!("Exported fleet state ({} devices) to {}", fleet.devices.len(), path);
+    }
+    Ok(fleet)
+}
@@ -0,0 +1,240 @@
+// CFU - Cordatus Flash Utility - Fleet Server Sync
+// Client for a central Cordatus server: pushes this station's job records and device inventory, pulls the
+// shared profiles and device matrix so every site flashes from the same configuration
+
+use crate::history::FlashJobRecord;
+use crate::profiles::{self, FlashProfile};
+use crate::{credentials, device_matrix, AppState};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use reqwest::header::{ETAG, IF_NONE_MATCH};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{command, Emitter, Manager, State};
+
+const SYNC_STATE_FILE: &str = "fleet_sync.json";
+
+// Jobs are pushed in batches so a long offline period does not become one huge request
+const JOB_BATCH_SIZE: usize = 200;
+
+// What has already been exchanged with the server
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SyncState {
+    jobs_pushed_until: Option<DateTime<Utc>>, // Finish time of the newest pushed job
+    device_matrix_etag: Option<String>,
+    last_synced: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FleetSyncResult {
+    pub jobs_pushed: usize,
+    pub devices_pushed: usize,
+    pub profiles_updated: Vec<String>,
+    pub device_matrix_changes: Option<device_matrix::MatrixDiff>, // Set when a new device matrix arrived
+    pub synced_at: DateTime<Utc>,
+}
+
+fn load_state(app: &tauri::AppHandle) -> SyncState {
+    crate::app_data_file(app, SYNC_STATE_FILE)
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_state(app: &tauri::AppHandle, sync_state: &SyncState) -> Result<()> {
+    let path = crate::app_data_file(app, SYNC_STATE_FILE)?;
+    std::fs::write(path, serde_json::to_string_pretty(sync_state)?).context("Failed to save fleet sync state")
+}
+
+struct FleetServer {
+    client: reqwest::Client,
+    base_url: String,
+    token: String,
+    station: String,
+}
+
+impl FleetServer {
+    fn from_state(state: &AppState) -> Result<Self> {
+        let settings = state.settings.lock().unwrap().fleet_server.clone();
+        if !settings.enabled || settings.url.trim().is_empty() {
+            return Err(anyhow::anyhow!("Fleet server sync is not configured"));
+        }
+        let token = credentials::get_secret("fleet_server")?.context("No fleet server token stored")?;
+        let station = crate::history::station_name(state).context("Station name unknown")?;
+        Ok(Self {
+            client: reqwest::Client::builder().timeout(Duration::from_secs(60)).build()?,
+            base_url: settings.url.trim().trim_end_matches('/').to_string(),
+            token,
+            station,
+        })
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        self.client
+            .request(method, format!("{}/api/v1/{}", self.base_url, path))
+            .bearer_auth(&self.token)
+    }
+}
+
+// Finished jobs newer than the last push, oldest first
+async fn push_jobs(server: &FleetServer, state: &AppState, sync_state: &mut SyncState) -> Result<usize> {
+    let mut jobs: Vec<FlashJobRecord> = state
+        .history
+        .lock()
+        .unwrap()
+        .jobs
+        .iter()
+        .filter(|job| job.finished_at.is_some_and(|at| sync_state.jobs_pushed_until.is_none_or(|until| at > until)))
+        .cloned()
+        .collect();
+    jobs.sort_by_key(|job| job.finished_at);
+
+    for batch in jobs.chunks(JOB_BATCH_SIZE) {
+        server
+            .request(reqwest::Method::POST, &format!("stations/{}/jobs", server.station))
+            .json(&serde_json::json!({ "jobs": batch }))
+            .send()
+            .await?
+            .error_for_status()
+            .context("Fleet server rejected job records")?;
+        sync_state.jobs_pushed_until = batch.last().and_then(|job| job.finished_at);
+    }
+    Ok(jobs.len())
+}
+
+// The whole inventory each time; the server keeps the newest record per serial number
+async fn push_devices(server: &FleetServer, state: &AppState) -> Result<usize> {
+    let devices = state.history.lock().unwrap().devices.clone();
+    server
+        .request(reqwest::Method::PUT, &format!("stations/{}/devices", server.station))
+        .json(&serde_json::json!({ "devices": devices }))
+        .send()
+        .await?
+        .error_for_status()
+        .context("Fleet server rejected device inventory")?;
+    Ok(devices.len())
+}
+
+// Shared profiles replace local ones of the same name when newer; local-only profiles are kept
+async fn pull_profiles(server: &FleetServer, app: &tauri::AppHandle) -> Result<Vec<String>> {
+    let remote: Vec<FlashProfile> = server
+        .request(reqwest::Method::GET, "profiles")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+        .context("Invalid profile list from fleet server")?;
+
+    let mut local = profiles::load_profiles(app);
+    let mut updated = Vec::new();
+    for profile in remote {
+        match local.iter_mut().find(|p| p.name == profile.name) {
+            Some(existing) if existing.updated_at >= profile.updated_at => {}
+            Some(existing) => {
+                updated.push(profile.name.clone());
+                *existing = profile;
+            }
+            None => {
+                updated.push(profile.name.clone());
+                local.push(profile);
+            }
+        }
+    }
+    if !updated.is_empty() {
+        profiles::save_profiles(app, &local)?;
+    }
+    Ok(updated)
+}
+
+// Only downloaded when it changed since the last pull; held for review if it changes the offered options
+async fn pull_device_matrix(
+    server: &FleetServer,
+    app: &tauri::AppHandle,
+    state: &AppState,
+    sync_state: &mut SyncState,
+) -> Result<Option<device_matrix::MatrixDiff>> {
+    let mut request = server.request(reqwest::Method::GET, "device-matrix");
+    if let Some(etag) = &sync_state.device_matrix_etag {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+    let response = request.send().await?;
+    if matches!(response.status(), StatusCode::NOT_MODIFIED | StatusCode::NOT_FOUND) {
+        return Ok(None);
+    }
+    let response = response.error_for_status()?;
+    let etag = response.headers().get(ETAG).and_then(|v| v.to_str().ok()).map(str::to_string);
+    let content = response.bytes().await?;
+    let diff = device_matrix::stage_matrix(app, state, &content).await?;
+    sync_state.device_matrix_etag = etag;
+    Ok(Some(diff))
+}
+
+async fn sync(app: &tauri::AppHandle, state: &AppState) -> Result<FleetSyncResult> {
+    let server = FleetServer::from_state(state)?;
+    let mut sync_state = load_state(app);
+
+    // Saved after each step so a later failure does not push the same jobs again
+    let jobs_pushed = push_jobs(&server, state, &mut sync_state).await;
+    save_state(app, &sync_state)?;
+    let jobs_pushed = jobs_pushed?;
+    let devices_pushed = push_devices(&server, state).await?;
+    // A frozen station only reports; shared configuration is not pulled until it is unfrozen
+    let (profiles_updated, device_matrix_changes) = if crate::freeze::is_frozen(app) {
+        (Vec::new(), None)
+    } else {
+        (
+            pull_profiles(&server, app).await?,
+            pull_device_matrix(&server, app, state, &mut sync_state).await?,
+        )
+    };
+
+    let now = Utc::now();
+    sync_state.last_synced = Some(now);
+    save_state(app, &sync_state)?;
+    info!(
+        "Fleet server sync: {} jobs and {} devices pushed, {} profiles updated",
+        jobs_pushed,
+        devices_pushed,
+        profiles_updated.len()
+    );
+    Ok(FleetSyncResult {
+        jobs_pushed,
+        devices_pushed,
+        profiles_updated,
+        device_matrix_changes,
+        synced_at: now,
+    })
+}
+
+// Sync periodically for the lifetime of the app when a fleet server is configured
+pub fn start(app: tauri::AppHandle) {
+    let state = Arc::clone(app.state::<Arc<AppState>>().inner());
+    let settings = state.settings.lock().unwrap().fleet_server.clone();
+    if !settings.enabled || settings.interval_minutes == 0 {
+        return;
+    }
+    let interval = Duration::from_secs(u64::from(settings.interval_minutes) * 60);
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match sync(&app, &state).await {
+                Ok(result) => {
+                    let _ = app.emit("fleet-sync-finished", &result);
+                }
+                Err(e) => warn!("Fleet server sync failed: {:#}", e),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+// Push jobs and devices to the fleet server and pull profiles and the device matrix now
+#[command]
+pub async fn sync_fleet_server(app: tauri::AppHandle, state: State<'_, Arc<AppState>>) -> Result<FleetSyncResult, String> {
+    sync(&app, &state).await.map_err(|e| format!("Fleet server sync failed: {:#}", e))
+}
@@ -0,0 +1,192 @@
+// CFU - Cordatus Flash Utility - Station Freeze
+// Locks a production station to validated versions of the device matrix, container presets and flash profiles;
+// remote updates and edits are refused and only the frozen profiles can be flashed until an admin unfreezes it
+
+use crate::containers::{self, ContainerPreset};
+use crate::job_host::JobHost;
+use crate::profiles::{self, FlashProfile};
+use crate::{device_matrix, AppState, FlashCommand};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::info;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tauri::{command, State};
+
+const FREEZE_FILE: &str = "station_freeze.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StationFreeze {
+    frozen_at: DateTime<Utc>,
+    frozen_by: Option<String>,
+    passphrase_hash: String, // SHA-256 of the admin passphrase needed to unfreeze
+    profiles: Vec<FlashProfile>, // Validated copies; later edits to the profile store do not apply
+    container_presets: Vec<ContainerPreset>,
+    device_matrix_rows: usize,
+    version_matrix_revision: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FreezeStatus {
+    pub frozen: bool,
+    pub frozen_at: Option<DateTime<Utc>>,
+    pub frozen_by: Option<String>,
+    pub profiles: Vec<String>,
+    pub container_presets: Vec<String>,
+    pub device_matrix_rows: usize,
+    pub version_matrix_revision: Option<u32>,
+}
+
+fn hash_passphrase(passphrase: &str) -> String {
+    format!("{:x}", Sha256::digest(passphrase.as_bytes()))
+}
+
+fn load_freeze(app: &impl JobHost) -> Option<StationFreeze> {
+    crate::app_data_file(app, FREEZE_FILE)
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+}
+
+pub fn is_frozen(app: &tauri::AppHandle) -> bool {
+    load_freeze(app).is_some()
+}
+
+// Error for an action a frozen station refuses, e.g. "Device matrix updates"
+pub fn ensure_not_frozen(app: &tauri::AppHandle, action: &str) -> Result<(), String> {
+    if is_frozen(app) {
+        return Err(format!("{} are disabled while the station is frozen", action));
+    }
+    Ok(())
+}
+
+// The profile to flash: the frozen copy while frozen (only frozen profiles are allowed), the saved one otherwise
+pub fn profile_for_flash(app: &impl JobHost, name: &str) -> Result<FlashProfile> {
+    match load_freeze(app) {
+        Some(freeze) => freeze
+            .profiles
+            .into_iter()
+            .find(|p| p.name == name)
+            .with_context(|| format!("Profile {} is not allowed on this frozen station", name)),
+        None => profiles::find_profile(app, name),
+    }
+}
+
+// Container presets to deploy from: the frozen copies while frozen
+pub fn container_presets(app: &tauri::AppHandle) -> Vec<ContainerPreset> {
+    match load_freeze(app) {
+        Some(freeze) => freeze.container_presets,
+        None => containers::load_presets(app),
+    }
+}
+
+// While frozen a flash must match a frozen profile's board, release, storage, kernel and pinned artifacts
+pub fn check_flash(app: &impl JobHost, command: &FlashCommand) -> Result<(), String> {
+    let Some(freeze) = load_freeze(app) else {
+        return Ok(());
+    };
+    let allowed = freeze.profiles.iter().any(|profile| {
+        let frozen = &profile.command;
+        frozen.product == command.product
+            && frozen.device_module == command.device_module
+            && frozen.jetpack_version == command.jetpack_version
+            && frozen.storage_device == command.storage_device
+            && serde_json::to_value(&frozen.custom_kernel).ok() == serde_json::to_value(&command.custom_kernel).ok()
+            && profile.pins == command.pinned_artifacts
+    });
+    if allowed {
+        Ok(())
+    } else {
+        Err("The station is frozen; only its frozen profiles can be flashed".to_string())
+    }
+}
+
+fn status(freeze: Option<StationFreeze>) -> FreezeStatus {
+    match freeze {
+        Some(freeze) => FreezeStatus {
+            frozen: true,
+            frozen_at: Some(freeze.frozen_at),
+            frozen_by: freeze.frozen_by,
+            profiles: freeze.profiles.into_iter().map(|p| p.name).collect(),
+            container_presets: freeze.container_presets.into_iter().map(|p| p.name).collect(),
+            device_matrix_rows: freeze.device_matrix_rows,
+            version_matrix_revision: Some(freeze.version_matrix_revision),
+        },
+        None => FreezeStatus {
+            frozen: false,
+            frozen_at: None,
+            frozen_by: None,
+            profiles: Vec::new(),
+            container_presets: Vec::new(),
+            device_matrix_rows: 0,
+            version_matrix_revision: None,
+        },
+    }
+}
+
+// Whether the station is frozen and at which versions
+#[command]
+pub async fn get_station_freeze(app: tauri::AppHandle) -> Result<FreezeStatus, String> {
+    Ok(status(load_freeze(&app)))
+}
+
+// Freeze the station at the active device matrix, version matrix and container presets, allowing only the given profiles
+#[command]
+pub async fn freeze_station(
+    profiles: Vec<String>,
+    passphrase: String,
+    frozen_by: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<FreezeStatus, String> {
+    if is_frozen(&app) {
+        return Err("The station is already frozen".to_string());
+    }
+    if passphrase.len() < 4 {
+        return Err("The unfreeze passphrase must have at least 4 characters".to_string());
+    }
+    if profiles.is_empty() {
+        return Err("Select at least one profile to allow".to_string());
+    }
+    let allowed = profiles
+        .iter()
+        .map(|name| profiles::find_profile(&app, name))
+        .collect::<Result<Vec<_>>>()
+        .map_err(|e| e.to_string())?;
+
+    let device_matrix_rows = device_matrix::freeze_matrix(&app, &state).await.map_err(|e| e.to_string())?;
+    let freeze = StationFreeze {
+        frozen_at: Utc::now(),
+        frozen_by,
+        passphrase_hash: hash_passphrase(&passphrase),
+        profiles: allowed,
+        container_presets: containers::load_presets(&app),
+        device_matrix_rows,
+        version_matrix_revision: state.version_matrix.lock().unwrap().revision,
+    };
+    let path = crate::app_data_file(&app, FREEZE_FILE).map_err(|e| e.to_string())?;
+    let json = serde_json::to_string_pretty(&freeze).map_err(|e| e.to_string())?;
+    std::fs::write(&path, json).map_err(|e| format!("Failed to save station freeze: {}", e))?;
+
+    info!("Station frozen with {} allowed profiles", freeze.profiles.len());
+    Ok(status(Some(freeze)))
+}
+
+// Lift the freeze; remote updates apply again from the next sync
+#[command]
+pub async fn unfreeze_station(
+    passphrase: String,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    let freeze = load_freeze(&app).ok_or("The station is not frozen")?;
+    if hash_passphrase(&passphrase) != freeze.passphrase_hash {
+        return Err("Wrong unfreeze passphrase".to_string());
+    }
+    device_matrix::unfreeze_matrix(&app, &state).map_err(|e| e.to_string())?;
+    let path = crate::app_data_file(&app, FREEZE_FILE).map_err(|e| e.to_string())?;
+    std::fs::remove_file(path).map_err(|e| format!("Failed to remove station freeze: {}", e))?;
+    info!("Station unfrozen");
+    Ok(())
+}
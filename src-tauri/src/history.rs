@@ -0,0 +1,373 @@
+// CFU - Cordatus Flash Utility - History
+// Persistent record of flash jobs, their exact configuration, known devices and per-device results, stored as JSON in the app data directory
+
+use crate::acceptance::Acceptance;
+use crate::benchmarks::BenchmarkResult;
+use crate::boot_check::BootVerification;
+use crate::delta::PartitionChecksum;
+use crate::failures::FlashFailure;
+use crate::device_spec::SpecStepResult;
+use crate::fleet::{ContainerDeployment, DeviceRecord};
+use crate::job_host::JobHost;
+use crate::notes::{JobAttachment, JobNote};
+use crate::profiles::ArtifactPin;
+use crate::thermal::ThermalReport;
+use crate::{AppState, FlashCommand};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tauri::{command, State};
+
+const HISTORY_FILE: &str = "history.json";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryDb {
+    pub jobs: Vec<FlashJobRecord>,
+    pub benchmarks: Vec<BenchmarkResult>,
+    pub thermal_reports: Vec<ThermalReport>,
+    pub devices: Vec<DeviceRecord>,
+    pub deployments: Vec<ContainerDeployment>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlashJobRecord {
+    pub flash_id: String,
+    pub command: FlashCommand,
+    #[serde(default)]
+    pub serial_number: Option<String>, // Set once the flashed device is identified
+    #[serde(default)]
+    pub profile: Option<String>, // Profile the job was started from
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub variables: BTreeMap<String, String>, // Template variables the profile or spec was resolved with, see batch.rs
+    #[serde(default)]
+    pub artifacts: Vec<ArtifactPin>, // Checksums of the BSP archives and overlays actually used
+    #[serde(default)]
+    pub station: Option<String>, // Flashing station that ran the job
+    #[serde(default)]
+    pub fixture: Option<String>, // Lab fixture holding the device in recovery mode when the job started
+    #[serde(default)]
+    pub failed_stage: Option<String>, // Progress stage the job was in when it failed
+    #[serde(default)]
+    pub failure: Option<FlashFailure>, // Known failure the job's output reported, when it failed
+    #[serde(default)]
+    pub cancel_mode: Option<String>, // 'soft' (stopped between steps) | 'hard' (killed) for cancelled jobs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped_stages: Vec<String>, // Stages the operator chose to skip
+    #[serde(default)]
+    pub board_id: Option<String>, // Stable ID of the board (chip UID based), when known at the start or from a delta flash
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub partition_checksums: Vec<PartitionChecksum>, // Images written by a delta flash
+    #[serde(default)]
+    pub verification: Option<BootVerification>, // First boot checks, when the job asked for them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub spec_results: Vec<SpecStepResult>, // Post-flash steps of a job started from a device spec
+    #[serde(default)]
+    pub acceptance: Option<Acceptance>, // QA checklist of the flashed unit, see acceptance.rs
+    #[serde(default)]
+    pub notes: Vec<JobNote>,
+    #[serde(default)]
+    pub attachments: Vec<JobAttachment>,
+    pub status: String, // 'running' | 'success' | 'failed' | 'cancelled'
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+// Filters for the history browser; unset fields match everything
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct HistoryQuery {
+    pub serial_number: Option<String>,
+    pub device_module: Option<String>,
+    pub jetpack_version: Option<String>,
+    pub status: Option<String>,
+    pub operator: Option<String>,
+    pub profile: Option<String>,
+    pub station: Option<String>,
+    pub acceptance: Option<String>, // 'pending' | 'accepted' | 'rejected'
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub search: Option<String>, // Case-insensitive match on the job ID, serial number, error and notes
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryPage {
+    pub total: usize, // Matching jobs before pagination
+    pub offset: usize,
+    pub jobs: Vec<FlashJobRecord>,
+}
+
+impl HistoryQuery {
+    fn matches(&self, job: &FlashJobRecord) -> bool {
+        let equals = |filter: &Option<String>, value: Option<&str>| {
+            filter.as_deref().is_none_or(|f| value.is_some_and(|v| v.eq_ignore_ascii_case(f)))
+        };
+        equals(&self.serial_number, job.serial_number.as_deref())
+            && equals(&self.device_module, Some(&job.command.device_module))
+            && equals(&self.jetpack_version, Some(&job.command.jetpack_version))
+            && equals(&self.status, Some(&job.status))
+            && equals(&self.operator, job.command.operator.as_deref())
+            && equals(&self.profile, job.profile.as_deref())
+            && equals(&self.station, job.station.as_deref())
+            && equals(&self.acceptance, job.acceptance.as_ref().map(|a| a.status.as_str()))
+            && self.since.is_none_or(|since| job.started_at >= since)
+            && self.until.is_none_or(|until| job.started_at < until)
+            && self.search.as_deref().is_none_or(|search| {
+                let search = search.to_lowercase();
+                let contains = |text: &str| text.to_lowercase().contains(&search);
+                contains(&job.flash_id)
+                    || job.serial_number.as_deref().is_some_and(contains)
+                    || job.error.as_deref().is_some_and(contains)
+                    || job.notes.iter().any(|n| contains(&n.text))
+            })
+    }
+}
+
+// Load the history from disk, starting empty if missing or unreadable
+pub fn load_history(app: &impl JobHost) -> HistoryDb {
+    let path = match crate::app_data_file(app, HISTORY_FILE) {
+        Ok(path) => path,
+        Err(e) => {
+            warn!("History unavailable: {}", e);
+            return HistoryDb::default();
+        }
+    };
+
+    match std::fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            warn!("Invalid history file {}: {}", path.display(), e);
+            HistoryDb::default()
+        }),
+        Err(_) => HistoryDb::default(),
+    }
+}
+
+pub fn save_history(app: &impl JobHost, history: &HistoryDb) -> Result<()> {
+    let path = crate::app_data_file(app, HISTORY_FILE)?;
+    let json = serde_json::to_string_pretty(history)?;
+    std::fs::write(&path, json).context("Failed to write history")
+}
+
+// Apply a change to the history and persist it; failures are logged, since
+// bookkeeping must never abort a flash
+pub fn update_history(app: &impl JobHost, state: &AppState, change: impl FnOnce(&mut HistoryDb)) {
+    let mut history = state.history.lock().unwrap();
+    change(&mut history);
+    if let Err(e) = save_history(app, &history) {
+        warn!("Failed to save history: {}", e);
+    }
+}
+
+// The configured station name, or the host name
+pub fn station_name(state: &AppState) -> Option<String> {
+    let configured = state.settings.lock().unwrap().station_name.trim().to_string();
+    if configured.is_empty() {
+        sys_info::hostname().ok()
+    } else {
+        Some(configured)
+    }
+}
+
+// The stable ID of the board the job will flash, when it is the only one in recovery mode it can take
+fn recovery_board(state: &AppState, command: &FlashCommand) -> Option<String> {
+    let devices = state.connected_devices.lock().unwrap();
+    let mut waiting = devices.values().filter(|device| {
+        device.usb_info.as_ref().is_some_and(|usb| usb.is_recovery_mode)
+            && command.device_binding.as_ref().is_none_or(|binding| binding.matches_location(device))
+    });
+    match (waiting.next(), waiting.next()) {
+        (Some(device), None) => device.stable_id.clone(),
+        _ => None,
+    }
+}
+
+pub fn record_started(app: &impl JobHost, state: &AppState, flash_id: &str, command: &FlashCommand) {
+    let record = FlashJobRecord {
+        flash_id: flash_id.to_string(),
+        command: command.clone(),
+        serial_number: None,
+        profile: None,
+        variables: BTreeMap::new(),
+        artifacts: Vec::new(),
+        station: station_name(state),
+        fixture: crate::lab::recovery_fixture(state),
+        failed_stage: None,
+        failure: None,
+        cancel_mode: None,
+        skipped_stages: command.skip_stages.clone(),
+        board_id: recovery_board(state, command),
+        partition_checksums: Vec::new(),
+        verification: None,
+        spec_results: Vec::new(),
+        acceptance: None,
+        notes: Vec::new(),
+        attachments: Vec::new(),
+        status: "running".to_string(),
+        error: None,
+        started_at: Utc::now(),
+        finished_at: None,
+    };
+    update_history(app, state, |history| history.jobs.push(record));
+}
+
+// Only the first outcome sticks, so a cancelled job is not later marked failed
+pub fn record_finished(app: &impl JobHost, state: &AppState, flash_id: &str, status: &str, error: Option<String>) {
+    let stage = state.flash_progress.lock().unwrap().get(flash_id).map(|p| p.stage.clone());
+    let failure = if status == "failed" { crate::joblog::failure(state, flash_id) } else { None };
+    update_history(app, state, |history| {
+        if let Some(record) = history.jobs.iter_mut().find(|r| r.flash_id == flash_id && r.status == "running") {
+            record.status = status.to_string();
+            record.error = error;
+            if status == "failed" {
+                record.failed_stage = stage;
+                record.failure = failure;
+            }
+            record.finished_at = Some(Utc::now());
+        }
+    });
+}
+
+// mode: 'soft' | 'hard'
+pub fn record_cancelled(app: &impl JobHost, state: &AppState, flash_id: &str, mode: &str) {
+    update_history(app, state, |history| {
+        if let Some(record) = history.jobs.iter_mut().find(|r| r.flash_id == flash_id && r.status == "running") {
+            record.status = "cancelled".to_string();
+            record.cancel_mode = Some(mode.to_string());
+            record.finished_at = Some(Utc::now());
+        }
+    });
+}
+
+pub fn record_artifacts(app: &impl JobHost, state: &AppState, flash_id: &str, artifacts: Vec<ArtifactPin>) {
+    update_history(app, state, |history| {
+        if let Some(record) = history.jobs.iter_mut().find(|r| r.flash_id == flash_id) {
+            record.artifacts = artifacts;
+        }
+    });
+}
+
+// What a delta flash left on the board, for the next delta flash to compare against
+pub fn record_partitions(app: &impl JobHost, state: &AppState, flash_id: &str, board_id: &str, checksums: Vec<PartitionChecksum>) {
+    update_history(app, state, |history| {
+        if let Some(record) = history.jobs.iter_mut().find(|r| r.flash_id == flash_id) {
+            record.board_id = Some(board_id.to_string());
+            record.partition_checksums = checksums;
+        }
+    });
+}
+
+pub fn record_verification(app: &impl JobHost, state: &AppState, flash_id: &str, verification: BootVerification) {
+    update_history(app, state, |history| {
+        if let Some(record) = history.jobs.iter_mut().find(|r| r.flash_id == flash_id) {
+            record.verification = Some(verification);
+        }
+    });
+}
+
+pub fn record_variables(app: &tauri::AppHandle, state: &AppState, flash_id: &str, variables: BTreeMap<String, String>) {
+    update_history(app, state, |history| {
+        if let Some(record) = history.jobs.iter_mut().find(|r| r.flash_id == flash_id) {
+            record.variables = variables;
+        }
+    });
+}
+
+pub fn record_spec_results(app: &tauri::AppHandle, state: &AppState, flash_id: &str, results: Vec<SpecStepResult>) {
+    update_history(app, state, |history| {
+        if let Some(record) = history.jobs.iter_mut().find(|r| r.flash_id == flash_id) {
+            record.spec_results = results;
+        }
+    });
+}
+
+// Attach the serial number of the device a job flashed
+pub fn link_device(app: &impl JobHost, state: &AppState, flash_id: &str, serial_number: &str) {
+    update_history(app, state, |history| {
+        if let Some(record) = history.jobs.iter_mut().find(|r| r.flash_id == flash_id) {
+            record.serial_number = Some(serial_number.to_string());
+        }
+    });
+}
+
+// List past flash jobs, newest first
+#[command]
+pub async fn get_flash_history(
+    limit: Option<usize>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<FlashJobRecord>, String> {
+    let history = state.history.lock().unwrap();
+    Ok(history.jobs.iter().rev().take(limit.unwrap_or(usize::MAX)).cloned().collect())
+}
+
+// Filtered, paginated job list for the history browser, newest first
+#[command]
+pub async fn query_flash_history(
+    query: HistoryQuery,
+    state: State<'_, Arc<AppState>>,
+) -> Result<HistoryPage, String> {
+    let history = state.history.lock().unwrap();
+    let matching: Vec<&FlashJobRecord> = history.jobs.iter().rev().filter(|r| query.matches(r)).collect();
+    Ok(HistoryPage {
+        total: matching.len(),
+        offset: query.offset,
+        jobs: matching
+            .into_iter()
+            .skip(query.offset)
+            .take(query.limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect(),
+    })
+}
+
+// Get the full record of a single flash job
+#[command]
+pub async fn get_flash_job(flash_id: String, state: State<'_, Arc<AppState>>) -> Result<FlashJobRecord, String> {
+    let history = state.history.lock().unwrap();
+    history
+        .jobs
+        .iter()
+        .find(|r| r.flash_id == flash_id)
+        .cloned()
+        .ok_or_else(|| format!("Flash job not found: {}", flash_id))
+}
+
+// Reflash a device with the configuration of the last successful job before its
+// most recent one, e.g. after a new configuration turned out to misbehave
+#[command]
+pub async fn rollback_device(
+    serial_number: String,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    window: tauri::Window,
+) -> Result<String, String> {
+    let (previous, command) = {
+        let history = state.history.lock().unwrap();
+        let mut jobs = history
+            .jobs
+            .iter()
+            .filter(|r| r.serial_number.as_deref() == Some(serial_number.as_str()));
+        // Skip the current configuration, whatever its outcome
+        jobs.next_back();
+        jobs.rfind(|r| r.status == "success")
+            .map(|r| {
+                // Pin the artifacts it used, so a changed BSP archive fails instead of flashing something else
+                let mut command = r.command.clone();
+                if command.pinned_artifacts.is_empty() {
+                    command.pinned_artifacts = r.artifacts.clone();
+                }
+                (r.flash_id.clone(), command)
+            })
+            .ok_or_else(|| format!("No earlier successful flash recorded for {}", serial_number))?
+    };
+
+    info!("Rolling back {} to the configuration of flash {}", serial_number, previous);
+    let flash_id = crate::start_flash_process(command, state.clone(), window).await?;
+    link_device(&app, &state, &flash_id, &serial_number);
+    Ok(flash_id)
+}
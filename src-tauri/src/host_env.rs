@@ -0,0 +1,255 @@
+// CFU - Cordatus Flash Utility - Host Environment
+// Virtual machine / USB passthrough and packaging sandbox detection with flashing-specific guidance
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+use tauri::command;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VirtualizationInfo {
+    pub is_virtual_machine: bool,
+    pub hypervisor: Option<String>, // 'virtualbox' | 'vmware' | 'qemu' | 'hyperv' | 'wsl' | 'parallels' | other
+    pub has_usb3_controller: bool,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SandboxInfo {
+    pub kind: Option<String>, // 'flatpak' | 'snap' | 'appimage'
+    pub app_id: Option<String>,
+    pub usb_access: bool,
+    pub missing_permissions: Vec<String>,
+    pub remediation: Vec<String>,
+}
+
+impl SandboxInfo {
+    pub fn is_restricted(&self) -> bool {
+        !self.missing_permissions.is_empty()
+    }
+
+    // One-line explanation suitable for error messages
+    pub fn describe(&self) -> String {
+        format!(
+            "running as {} without {} ({})",
+            self.kind.as_deref().unwrap_or("a restricted process"),
+            self.missing_permissions.join(", "),
+            self.remediation.join("; ")
+        )
+    }
+}
+
+// Ask systemd first, then fall back to DMI strings and the WSL kernel signature
+fn detect_hypervisor() -> Option<String> {
+    if let Ok(version) = std::fs::read_to_string("/proc/version") {
+        if version.to_lowercase().contains("microsoft") {
+            return Some("wsl".to_string());
+        }
+    }
+
+    if let Ok(output) = Command::new("systemd-detect-virt").arg("--vm").output() {
+        let virt = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if output.status.success() && !virt.is_empty() && virt != "none" {
+            return Some(match virt.as_str() {
+                "oracle" => "virtualbox".to_string(),
+                "kvm" => "qemu".to_string(),
+                "microsoft" => "hyperv".to_string(),
+                _ => virt,
+            });
+        }
+    }
+
+    let dmi = ["sys_vendor", "product_name"]
+        .iter()
+        .filter_map(|field| std::fs::read_to_string(format!("/sys/class/dmi/id/{}", field)).ok())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+
+    [
+        ("virtualbox", "virtualbox"),
+        ("innotek", "virtualbox"),
+        ("vmware", "vmware"),
+        ("qemu", "qemu"),
+        ("parallels", "parallels"),
+        ("virtual machine", "hyperv"),
+    ]
+    .iter()
+    .find(|(needle, _)| dmi.contains(needle))
+    .map(|(_, name)| name.to_string())
+}
+
+// SuperSpeed root hubs report a speed of 5000 Mbit/s or more
+fn has_usb3_controller() -> bool {
+    let Ok(entries) = std::fs::read_dir("/sys/bus/usb/devices") else {
+        return false;
+    };
+
+    entries
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("usb"))
+        .filter_map(|entry| std::fs::read_to_string(entry.path().join("speed")).ok())
+        .filter_map(|speed| speed.trim().parse::<u32>().ok())
+        .any(|speed| speed >= 5000)
+}
+
+fn hypervisor_guidance(hypervisor: &str) -> Option<&'static str> {
+    match hypervisor {
+        "virtualbox" => Some("VirtualBox: enable the USB 3.0 (xHCI) controller and add a USB device filter for vendor 0955 with no product ID, so the Jetson is re-attached automatically each time it re-enumerates during flashing"),
+        "vmware" => Some("VMware: set USB compatibility to 3.1 and enable automatic connection of new USB devices; the Jetson re-enumerates several times and each new device must reach the guest"),
+        "qemu" => Some("QEMU/KVM: pass through the whole USB controller via PCI passthrough, or use a vendor-level usbredir filter (0955); per-device passthrough drops the board when it re-enumerates"),
+        "hyperv" => Some("Hyper-V does not support USB device passthrough; flash from a physical Linux host instead"),
+        "wsl" => Some("WSL2: attach the device with 'usbipd attach --auto-attach' so it survives re-enumeration; initrd flashing also needs the USB network gadget to be forwarded"),
+        "parallels" => Some("Parallels: connect the NVIDIA device to the guest and enable automatic reconnection for it; the Jetson re-enumerates during flashing"),
+        _ => None,
+    }
+}
+
+pub fn detect_virtualization_info() -> VirtualizationInfo {
+    let hypervisor = detect_hypervisor();
+    let has_usb3_controller = has_usb3_controller();
+    let mut warnings = Vec::new();
+
+    if let Some(hypervisor) = &hypervisor {
+        warnings.push(
+            "Running inside a virtual machine: recovery-mode flashing reboots the Jetson several times and each re-enumeration must be passed through to the VM, otherwise flashing stalls or times out".to_string(),
+        );
+        if let Some(guidance) = hypervisor_guidance(hypervisor) {
+            warnings.push(guidance.to_string());
+        }
+        if !has_usb3_controller {
+            warnings.push("No USB 3.0 controller visible in the VM; Orin-based modules fail to flash over an emulated USB 2.0 controller".to_string());
+        }
+    }
+
+    VirtualizationInfo {
+        is_virtual_machine: hypervisor.is_some(),
+        hypervisor,
+        has_usb3_controller,
+        warnings,
+    }
+}
+
+// Read a key from a section of the Flatpak metadata file
+fn flatpak_info_value(info: &str, section: &str, key: &str) -> Option<String> {
+    let mut in_section = false;
+    for line in info.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_section = line == format!("[{}]", section);
+        } else if in_section {
+            if let Some(value) = line.strip_prefix(&format!("{}=", key)) {
+                return Some(value.to_string());
+            }
+        }
+    }
+    None
+}
+
+fn snap_interface_connected(interface: &str) -> bool {
+    Command::new("snapctl")
+        .args(["is-connected", interface])
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+pub fn detect_sandbox_info() -> SandboxInfo {
+    let usb_access = std::fs::read_dir("/dev/bus/usb").is_ok();
+    let mut info = SandboxInfo {
+        kind: None,
+        app_id: None,
+        usb_access,
+        missing_permissions: Vec::new(),
+        remediation: Vec::new(),
+    };
+
+    if let Ok(flatpak_info) = std::fs::read_to_string("/.flatpak-info") {
+        let app_id = flatpak_info_value(&flatpak_info, "Application", "name")
+            .or_else(|| std::env::var("FLATPAK_ID").ok())
+            .unwrap_or_else(|| "ai.cordatus.flash-utility".to_string());
+        let devices = flatpak_info_value(&flatpak_info, "Context", "devices").unwrap_or_default();
+        let filesystems = flatpak_info_value(&flatpak_info, "Context", "filesystems").unwrap_or_default();
+
+        if !devices.split(';').any(|d| d == "all") {
+            info.missing_permissions.push("raw USB device access (--device=all)".to_string());
+            info.remediation.push(format!("flatpak override --user --device=all {}", app_id));
+        }
+        if !filesystems.split(';').any(|f| f == "home" || f == "host") {
+            info.missing_permissions.push("home directory access for the ~/openzeka workspace".to_string());
+            info.remediation.push(format!("flatpak override --user --filesystem=home {}", app_id));
+        }
+
+        info.kind = Some("flatpak".to_string());
+        info.app_id = Some(app_id);
+    } else if let Ok(snap_name) = std::env::var("SNAP_NAME") {
+        for (interface, purpose) in [("raw-usb", "raw USB device access"), ("hardware-observe", "USB/sysfs enumeration")] {
+            if !snap_interface_connected(interface) {
+                info.missing_permissions.push(format!("{} ({} interface)", purpose, interface));
+                info.remediation.push(format!("sudo snap connect {}:{}", snap_name, interface));
+            }
+        }
+
+        info.kind = Some("snap".to_string());
+        info.app_id = Some(snap_name);
+    } else if std::env::var("APPIMAGE").is_ok() {
+        // AppImages are not confined, but they ship no udev rules
+        info.kind = Some("appimage".to_string());
+        if !usb_access {
+            info.missing_permissions.push("read access to /dev/bus/usb".to_string());
+        }
+    }
+
+    if !usb_access && info.kind.as_deref() != Some("flatpak") && info.kind.as_deref() != Some("snap") {
+        info.remediation.push(
+            "install a udev rule granting access to NVIDIA devices: SUBSYSTEM==\"usb\", ATTR{idVendor}==\"0955\", MODE=\"0666\"".to_string(),
+        );
+    }
+
+    info
+}
+
+// Detect whether the app runs confined and which permissions are missing
+#[command]
+pub async fn detect_sandbox() -> Result<SandboxInfo, String> {
+    let info = tokio::task::spawn_blocking(detect_sandbox_info)
+        .await
+        .map_err(|e| e.to_string())?;
+    if info.is_restricted() {
+        warn!("Sandbox restrictions detected: {}", info.describe());
+    }
+    Ok(info)
+}
+
+// Request the missing device access where the sandbox allows it (Flatpak
+// with host access through flatpak-spawn); otherwise return the manual steps
+#[command]
+pub async fn request_sandbox_device_access() -> Result<Vec<String>, String> {
+    let info = detect_sandbox_info();
+    if !info.is_restricted() {
+        return Ok(Vec::new());
+    }
+
+    if info.kind.as_deref() == Some("flatpak") {
+        let app_id = info.app_id.clone().unwrap_or_default();
+        let status = Command::new("flatpak-spawn")
+            .args(["--host", "flatpak", "override", "--user", "--device=all", "--filesystem=home", &app_id])
+            .status();
+        if matches!(status, Ok(status) if status.success()) {
+            info!("Granted device access to {}, restart required", app_id);
+            return Ok(vec!["Device access granted, restart the application to apply it".to_string()]);
+        }
+    }
+
+    Err(format!("Cannot request access automatically: {}", info.describe()))
+}
+
+// Detect VM environments and return flashing guidance
+#[command]
+pub async fn detect_virtualization() -> Result<VirtualizationInfo, String> {
+    let info = tokio::task::spawn_blocking(detect_virtualization_info)
+        .await
+        .map_err(|e| e.to_string())?;
+    info!("Virtualization: {:?} (USB 3.0: {})", info.hypervisor, info.has_usb3_controller);
+    Ok(info)
+}
@@ -0,0 +1,391 @@
+// CFU - Cordatus Flash Utility - Raw Images
+// Writing disk images to SD cards and USB drives and backing devices up to image files,
+// with O_DIRECT aligned I/O so progress reflects what actually reached the device, and
+// sparse files on the image side so mostly-empty storage stays small and quick to restore
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::{FileTypeExt, OpenOptionsExt};
+use std::os::unix::io::AsRawFd;
+use crate::{write_target, AppState};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+use tauri::{command, Emitter, State};
+
+// Large enough to keep an SD card busy, a multiple of every logical block size
+const IO_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+const DIRECT_IO_ALIGNMENT: usize = 4096;
+const PROGRESS_INTERVAL_MS: u128 = 500;
+
+// Granularity of zero detection when writing sparse backups
+const SPARSE_BLOCK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageProgress {
+    pub operation: String, // 'write' | 'backup'
+    pub device: String,
+    pub bytes_done: u64,
+    pub total_bytes: u64,
+    pub bytes_per_sec: f64, // Over the last progress interval
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageTransfer {
+    pub device: String,
+    pub image_path: String,
+    pub bytes: u64,        // Image size
+    pub stored_bytes: u64, // Actually written: excludes skipped holes and zero blocks
+    pub seconds: f64,
+    pub bytes_per_sec: f64,
+    pub direct_io: bool, // False when the device or filesystem refused O_DIRECT
+}
+
+// Heap buffer aligned for O_DIRECT
+struct AlignedBuffer {
+    storage: Vec<u8>,
+    offset: usize,
+}
+
+impl AlignedBuffer {
+    fn new(len: usize) -> Self {
+        let storage = vec![0u8; len + DIRECT_IO_ALIGNMENT];
+        let offset = storage.as_ptr().align_offset(DIRECT_IO_ALIGNMENT);
+        Self { storage, offset }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        let len = self.storage.len() - DIRECT_IO_ALIGNMENT;
+        &mut self.storage[self.offset..self.offset + len]
+    }
+}
+
+struct ProgressReporter<'a> {
+    app: &'a tauri::AppHandle,
+    operation: &'static str,
+    device: &'a str,
+    total_bytes: u64,
+    last_report: Instant,
+    last_bytes: u64,
+}
+
+impl<'a> ProgressReporter<'a> {
+    fn new(app: &'a tauri::AppHandle, operation: &'static str, device: &'a str, total_bytes: u64) -> Self {
+        Self {
+            app,
+            operation,
+            device,
+            total_bytes,
+            last_report: Instant::now(),
+            last_bytes: 0,
+        }
+    }
+
+    fn update(&mut self, bytes_done: u64, force: bool) {
+        let elapsed = self.last_report.elapsed();
+        if !force && elapsed.as_millis() < PROGRESS_INTERVAL_MS {
+            return;
+        }
+        let bytes_per_sec = (bytes_done - self.last_bytes) as f64 / elapsed.as_secs_f64().max(0.001);
+        let _ = self.app.emit(
+            "image-progress",
+            ImageProgress {
+                operation: self.operation.to_string(),
+                device: self.device.to_string(),
+                bytes_done,
+                total_bytes: self.total_bytes,
+                bytes_per_sec,
+            },
+        );
+        self.last_report = Instant::now();
+        self.last_bytes = bytes_done;
+    }
+}
+
+// Kernel names of a block device and, for a whole disk, its partitions ("sdb", "sdb1", ...). Names are
+// compared whole, since a prefix of /dev/nvme0n1 is also a prefix of /dev/nvme0n10.
+fn device_and_partitions(device: &str) -> Result<Vec<String>> {
+    let resolved = std::fs::canonicalize(device).with_context(|| format!("Device not found: {}", device))?;
+    let name = resolved
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .context("Invalid device path")?;
+    let mut names = vec![name.clone()];
+    if let Ok(entries) = std::fs::read_dir(Path::new("/sys/block").join(&name)) {
+        names.extend(
+            entries
+                .flatten()
+                .filter(|entry| entry.path().join("partition").exists())
+                .map(|entry| entry.file_name().to_string_lossy().to_string()),
+        );
+    }
+    Ok(names)
+}
+
+// Refuse anything but an unmounted block device; a mounted partition of it counts as mounted
+fn check_target_device(device: &str) -> Result<()> {
+    let metadata = std::fs::metadata(device).with_context(|| format!("Device not found: {}", device))?;
+    if !metadata.file_type().is_block_device() {
+        return Err(anyhow::anyhow!("{} is not a block device", device));
+    }
+    let names = device_and_partitions(device)?;
+    let mounts = std::fs::read_to_string("/proc/mounts").unwrap_or_default();
+    // Mount sources may be /dev/disk links, so they are resolved before comparing
+    let mounted = mounts.lines().find(|line| {
+        line.split_whitespace()
+            .next()
+            .filter(|source| source.starts_with("/dev/"))
+            .and_then(|source| std::fs::canonicalize(source).ok())
+            .and_then(|path| path.file_name().map(|n| n.to_string_lossy().to_string()))
+            .is_some_and(|source| names.contains(&source))
+    });
+    if let Some(mount) = mounted {
+        return Err(anyhow::anyhow!("{} is mounted ({}), unmount it first", device, mount));
+    }
+    Ok(())
+}
+
+// Open with O_DIRECT, falling back to buffered I/O where it is not supported
+fn open_direct(path: &str, options: &OpenOptions) -> Result<(File, bool)> {
+    let mut direct = options.clone();
+    direct.custom_flags(libc::O_DIRECT);
+    match direct.open(path) {
+        Ok(file) => Ok((file, true)),
+        Err(e) if e.raw_os_error() == Some(libc::EINVAL) => {
+            warn!("O_DIRECT not supported for {}, using buffered I/O", path);
+            Ok((options.open(path).with_context(|| format!("Failed to open {}", path))?, false))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => Err(anyhow::anyhow!(
+            "Permission denied opening {}; add the user to the 'disk' group or run with elevated rights",
+            path
+        )),
+        Err(e) => Err(e).with_context(|| format!("Failed to open {}", path)),
+    }
+}
+
+// Fill the buffer as far as the reader allows; short only at end of input
+fn read_full(reader: &mut impl Read, buffer: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..])? {
+            0 => break,
+            read => filled += read,
+        }
+    }
+    Ok(filled)
+}
+
+// Byte ranges of the image holding data, via SEEK_DATA / SEEK_HOLE. Filesystems
+// without hole tracking report the whole file as one range.
+fn data_ranges(file: &File, len: u64) -> Result<Vec<(u64, u64)>> {
+    let fd = file.as_raw_fd();
+    let mut ranges = Vec::new();
+    let mut offset: u64 = 0;
+    while offset < len {
+        // Safety: plain lseek calls on a descriptor we own
+        let start = unsafe { libc::lseek(fd, offset as libc::off_t, libc::SEEK_DATA) };
+        if start < 0 {
+            let error = std::io::Error::last_os_error();
+            if error.raw_os_error() == Some(libc::ENXIO) {
+                break; // Only a hole remains
+            }
+            if error.raw_os_error() == Some(libc::EINVAL) {
+                return Ok(vec![(0, len)]);
+            }
+            return Err(error.into());
+        }
+        let end = unsafe { libc::lseek(fd, start, libc::SEEK_HOLE) };
+        let end = if end < 0 { len } else { (end as u64).min(len) };
+        ranges.push((start as u64, end));
+        offset = end;
+    }
+    Ok(ranges)
+}
+
+pub fn write_image_blocking(
+    app: &tauri::AppHandle,
+    image_path: &str,
+    device: &str,
+    confirmation: &str,
+    denied: &[String],
+    skip_holes: bool,
+) -> Result<ImageTransfer> {
+    check_target_device(device)?;
+    let target = write_target::confirm(device, confirmation, denied)?;
+    info!("Writing {} to {}", image_path, target.description);
+    let mut image = File::open(image_path).with_context(|| format!("Failed to open {}", image_path))?;
+    let total_bytes = image.metadata()?.len();
+    let (mut target, direct_io) = open_direct(device, OpenOptions::new().write(true))?;
+    let device_size = target.seek(SeekFrom::End(0))?;
+    if total_bytes > device_size {
+        return Err(anyhow::anyhow!("Image ({} bytes) is larger than {} ({} bytes)", total_bytes, device, device_size));
+    }
+    let ranges = if skip_holes { data_ranges(&image, total_bytes)? } else { vec![(0, total_bytes)] };
+
+    let started = Instant::now();
+    let mut progress = ProgressReporter::new(app, "write", device, total_bytes);
+    let mut buffer = AlignedBuffer::new(IO_CHUNK_SIZE);
+    let mut written: u64 = 0;
+    for (start, end) in ranges {
+        image.seek(SeekFrom::Start(start))?;
+        target.seek(SeekFrom::Start(start))?;
+        let mut reader = (&mut image).take(end - start);
+        let mut offset = start;
+        loop {
+            let read = read_full(&mut reader, buffer.as_mut_slice())?;
+            if read == 0 {
+                break;
+            }
+            if direct_io && read % DIRECT_IO_ALIGNMENT != 0 {
+                // O_DIRECT needs whole blocks; the unaligned tail goes through the page cache
+                let mut tail = OpenOptions::new().write(true).open(device)?;
+                tail.seek(SeekFrom::Start(offset))?;
+                tail.write_all(&buffer.as_mut_slice()[..read])?;
+                tail.sync_all()?;
+                target.seek(SeekFrom::Start(offset + read as u64))?;
+            } else {
+                target.write_all(&buffer.as_mut_slice()[..read])?;
+            }
+            offset += read as u64;
+            written += read as u64;
+            progress.update(offset, false);
+        }
+    }
+    target.sync_all().context("Failed to flush the device")?;
+    progress.update(total_bytes, true);
+
+    let seconds = started.elapsed().as_secs_f64();
+    info!(
+        "Wrote {} to {}: {} of {} bytes in {:.1}s",
+        image_path, device, written, total_bytes, seconds
+    );
+    Ok(ImageTransfer {
+        device: device.to_string(),
+        image_path: image_path.to_string(),
+        bytes: total_bytes,
+        stored_bytes: written,
+        seconds,
+        bytes_per_sec: written as f64 / seconds.max(0.001),
+        direct_io,
+    })
+}
+
+// Write the non-zero runs of a chunk and seek over the zero ones, leaving holes in the image
+fn write_sparse(image: &mut File, chunk: &[u8]) -> Result<u64> {
+    let mut stored = 0;
+    for block in chunk.chunks(SPARSE_BLOCK_SIZE) {
+        if block.iter().all(|&b| b == 0) {
+            image.seek(SeekFrom::Current(block.len() as i64))?;
+        } else {
+            image.write_all(block)?;
+            stored += block.len() as u64;
+        }
+    }
+    Ok(stored)
+}
+
+fn backup_device_blocking(
+    app: &tauri::AppHandle,
+    device: &str,
+    image_path: &str,
+    denied: &[String],
+    sparse: bool,
+) -> Result<ImageTransfer> {
+    check_target_device(device)?;
+    write_target::describe_allowed(device, denied)?;
+    let (mut source, direct_io) = open_direct(device, OpenOptions::new().read(true))?;
+    let total_bytes = source.seek(SeekFrom::End(0))?;
+    source.seek(SeekFrom::Start(0))?;
+    if Path::new(image_path).exists() {
+        return Err(anyhow::anyhow!("{} already exists", image_path));
+    }
+    let mut image = File::create(image_path).with_context(|| format!("Failed to create {}", image_path))?;
+
+    let started = Instant::now();
+    let mut progress = ProgressReporter::new(app, "backup", device, total_bytes);
+    let mut buffer = AlignedBuffer::new(IO_CHUNK_SIZE);
+    let mut copied: u64 = 0;
+    let mut stored: u64 = 0;
+    loop {
+        let read = read_full(&mut source, buffer.as_mut_slice())?;
+        if read == 0 {
+            break;
+        }
+        let chunk = &buffer.as_mut_slice()[..read];
+        if sparse {
+            stored += write_sparse(&mut image, chunk)?;
+        } else {
+            image.write_all(chunk)?;
+            stored += read as u64;
+        }
+        copied += read as u64;
+        progress.update(copied, false);
+    }
+    // A trailing hole is only recorded once the file length covers it
+    image.set_len(copied)?;
+    image.sync_all()?;
+    progress.update(copied, true);
+
+    let seconds = started.elapsed().as_secs_f64();
+    info!(
+        "Backed up {} to {}: {} bytes, {} stored, in {:.1}s",
+        device, image_path, copied, stored, seconds
+    );
+    Ok(ImageTransfer {
+        device: device.to_string(),
+        image_path: image_path.to_string(),
+        bytes: copied,
+        stored_bytes: stored,
+        seconds,
+        bytes_per_sec: copied as f64 / seconds.max(0.001),
+        direct_io,
+    })
+}
+
+// Write a raw disk image to an SD card or USB drive, emitting image-progress. With
+// `skip_holes`, holes in a sparse image are not written, so the device keeps its old
+// content there; fine for filesystem free space, which is what holes usually are.
+// `confirmation` is the token of the describe_write_target summary the user approved.
+#[command]
+pub async fn write_image(
+    image_path: String,
+    device: String,
+    confirmation: String,
+    skip_holes: Option<bool>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<ImageTransfer, String> {
+    let skip_holes = skip_holes.unwrap_or(false);
+    let denied = state.settings.lock().unwrap().denied_block_devices.clone();
+    tokio::task::spawn_blocking(move || write_image_blocking(&app, &image_path, &device, &confirmation, &denied, skip_holes))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+// Copy a whole device to a new image file, emitting image-progress; zero blocks
+// become holes unless `sparse` is false. Uploaded to the artifact bucket when configured.
+#[command]
+pub async fn backup_device(
+    device: String,
+    image_path: String,
+    sparse: Option<bool>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<ImageTransfer, String> {
+    let sparse = sparse.unwrap_or(true);
+    let path = image_path.clone();
+    let denied = state.settings.lock().unwrap().denied_block_devices.clone();
+    let transfer = tokio::task::spawn_blocking(move || backup_device_blocking(&app, &device, &path, &denied, sparse))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+    // The backup itself succeeded, so a failed upload is only logged
+    if let Err(e) = crate::uploads::upload_backup(&state, Path::new(&image_path)).await {
+        warn!("Failed to upload backup {}: {:#}", image_path, e);
+    }
+    Ok(transfer)
+}
@@ -0,0 +1,105 @@
+// CFU - Cordatus Flash Utility - Single Instance
+// Keeps one running instance per user so two windows never fight over USB devices; later launches hand their arguments over a local socket.
+// cfu-cli hands over its flash and ci requests the same way and stays connected for the job's output and result.
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use tauri::{Emitter, Manager};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Activation {
+    pub args: Vec<String>, // Command line of the second launch, without the program name
+    pub cwd: String,
+    #[serde(default)]
+    pub cli: bool, // A cfu-cli request, answered over the same connection, see cli::serve
+}
+
+fn socket_path() -> PathBuf {
+    let dir = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+    let user = std::env::var("USER").unwrap_or_else(|_| "default".to_string());
+    dir.join(format!("cfu-{}.sock", user))
+}
+
+fn current_activation() -> Activation {
+    Activation {
+        args: std::env::args().skip(1).collect(),
+        cwd: std::env::current_dir().map(|d| d.display().to_string()).unwrap_or_default(),
+        cli: false,
+    }
+}
+
+// Pass this launch to an instance that is already running; true means this process should exit
+pub fn hand_off_to_running_instance() -> bool {
+    let path = socket_path();
+    let Ok(mut stream) = UnixStream::connect(&path) else {
+        // Nobody listening: a previous instance exited without cleaning up
+        let _ = std::fs::remove_file(&path);
+        return false;
+    };
+    let message = serde_json::to_string(&current_activation()).unwrap_or_default();
+    match writeln!(stream, "{}", message) {
+        Ok(()) => {
+            info!("CFU is already running, activation forwarded to it");
+            true
+        }
+        Err(e) => {
+            warn!("Failed to reach the running instance, starting anyway: {}", e);
+            false
+        }
+    }
+}
+
+// Hand a cfu-cli request (the app binary's arguments, e.g. `--ci --profile rig`) to a running instance;
+// the connection then carries the replies, see cli::serve. None when no instance is running.
+pub fn forward_request(args: Vec<String>) -> Option<UnixStream> {
+    let mut stream = UnixStream::connect(socket_path()).ok()?;
+    let activation = Activation {
+        args,
+        cli: true,
+        ..current_activation()
+    };
+    let message = serde_json::to_string(&activation).ok()?;
+    writeln!(stream, "{}", message).ok()?;
+    Some(stream)
+}
+
+// Accept activations from later launches: focus the main window and pass their arguments to the frontend,
+// or run the request of a cfu-cli
+pub fn listen_for_activations(app: tauri::AppHandle) {
+    let path = socket_path();
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("Single-instance socket {} unavailable: {}", path.display(), e);
+            return;
+        }
+    };
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let mut line = String::new();
+            if BufReader::new(&stream).read_line(&mut line).is_err() {
+                continue;
+            }
+            let Ok(activation) = serde_json::from_str::<Activation>(&line) else {
+                continue;
+            };
+            if activation.cli {
+                info!("Running a cfu-cli request: {:?}", activation.args);
+                tauri::async_runtime::spawn(crate::cli::serve(app.clone(), activation.args, stream));
+                continue;
+            }
+            info!("Activated by a second launch with args {:?}", activation.args);
+            if let Some(window) = app.get_webview_window("main") {
+                let _ = window.unminimize();
+                let _ = window.show();
+                let _ = window.set_focus();
+            }
+            let _ = app.emit("instance-activated", &activation);
+        }
+    });
+}
@@ -0,0 +1,39 @@
+// CFU - Cordatus Flash Utility - I/O Priority
+// Long-running jobs (extraction, image assembly, flashing) run at the lowest best-effort I/O priority
+// so the host desktop stays usable, unless "maximum speed" is selected in settings
+
+use crate::AppState;
+use tokio::process::Command as TokioCommand;
+
+#[cfg(target_os = "linux")]
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+#[cfg(target_os = "linux")]
+const IOPRIO_CLASS_BE: libc::c_int = 2;
+#[cfg(target_os = "linux")]
+const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+#[cfg(target_os = "linux")]
+const IOPRIO_LOWEST_LEVEL: libc::c_int = 7;
+
+// Lower the I/O priority of a job before it starts; sudo, tar and flash.sh all inherit it.
+// Best-effort rather than idle, so a busy desktop slows a flash down instead of stalling it.
+pub fn apply(state: &AppState, cmd: &mut TokioCommand) {
+    if state.settings.lock().unwrap().maximum_io_speed {
+        return;
+    }
+
+    #[cfg(target_os = "linux")]
+    // Safety: only the async-signal-safe ioprio_set syscall runs between fork and exec
+    unsafe {
+        cmd.pre_exec(|| {
+            libc::syscall(
+                libc::SYS_ioprio_set,
+                IOPRIO_WHO_PROCESS,
+                0,
+                (IOPRIO_CLASS_BE << IOPRIO_CLASS_SHIFT) | IOPRIO_LOWEST_LEVEL,
+            );
+            Ok(())
+        });
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = cmd;
+}
@@ -0,0 +1,58 @@
+// CFU - Cordatus Flash Utility - Job Host
+// What the flash pipeline needs from around it: where the app data lives and where events go. The app
+// handle provides both in the GUI; cfu-cli runs the same pipeline on a headless host over the same data
+// directory, so its jobs land in the history and job logs the app shows.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::PathBuf;
+use tauri::{Emitter, Manager};
+
+pub trait JobHost: Clone + Send + Sync + 'static {
+    fn data_dir(&self) -> Result<PathBuf>;
+    fn config_dir(&self) -> Result<PathBuf>; // Where the settings are
+
+    // Emit an event to the front end, if there is one
+    fn send<S: Serialize + Clone>(&self, event: &str, payload: S) -> Result<()>;
+}
+
+impl JobHost for tauri::AppHandle {
+    fn data_dir(&self) -> Result<PathBuf> {
+        self.path().app_data_dir().context("Failed to resolve app data directory")
+    }
+
+    fn config_dir(&self) -> Result<PathBuf> {
+        self.path().app_config_dir().context("Failed to resolve config directory")
+    }
+
+    fn send<S: Serialize + Clone>(&self, event: &str, payload: S) -> Result<()> {
+        self.emit(event, payload).with_context(|| format!("Failed to emit {}", event))
+    }
+}
+
+// cfu-cli without a running app: no front end, so events go nowhere
+#[derive(Debug, Clone)]
+pub struct Headless {
+    data_dir: PathBuf,
+    config_dir: PathBuf,
+}
+
+impl Headless {
+    pub fn new(data_dir: PathBuf, config_dir: PathBuf) -> Self {
+        Self { data_dir, config_dir }
+    }
+}
+
+impl JobHost for Headless {
+    fn data_dir(&self) -> Result<PathBuf> {
+        Ok(self.data_dir.clone())
+    }
+
+    fn config_dir(&self) -> Result<PathBuf> {
+        Ok(self.config_dir.clone())
+    }
+
+    fn send<S: Serialize + Clone>(&self, _event: &str, _payload: S) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+// CFU - Cordatus Flash Utility - Job Output
+// Flash script output per job: the full log on disk and only a bounded ring of recent lines in memory.
+// Each logged line carries its time and stream ("<RFC 3339 time> <stdout|stderr> <line>") so the file
+// can be attached to a support ticket as is.
+
+use crate::failures::{self, FlashFailure};
+use crate::job_host::JobHost;
+use crate::AppState;
+use anyhow::{Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader, LineWriter, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tauri::{command, State};
+use tokio::sync::mpsc;
+
+const LOG_DIR: &str = "job_logs";
+// First line of a job log, before its output
+const HEADER_PREFIX: &str = "# Cordatus Flash Utility";
+
+// Lines kept in memory per running job
+const RECENT_OUTPUT_LINES: usize = 500;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LogEntry {
+    pub time: Option<DateTime<Utc>>, // None for logs written before lines were timestamped
+    pub stream: String,              // 'stdout' | 'stderr'
+    pub line: String,
+}
+
+impl LogEntry {
+    fn parse(text: &str) -> Self {
+        let structured = text.split_once(' ').and_then(|(time, rest)| {
+            let time = DateTime::parse_from_rfc3339(time).ok()?.with_timezone(&Utc);
+            let (stream, line) = rest.split_once(' ').unwrap_or((rest, ""));
+            matches!(stream, "stdout" | "stderr").then(|| (time, stream, line))
+        });
+        match structured {
+            Some((time, stream, line)) => Self {
+                time: Some(time),
+                stream: stream.to_string(),
+                line: line.to_string(),
+            },
+            None => Self {
+                time: None,
+                stream: "stdout".to_string(),
+                line: text.to_string(),
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct JobOutput {
+    recent: VecDeque<String>,
+    file: Option<LineWriter<File>>,
+    failure: Option<FlashFailure>, // First known failure the output reported
+}
+
+pub fn log_path(app: &impl JobHost, flash_id: &str) -> Result<PathBuf> {
+    let dir = crate::app_data_file(app, LOG_DIR)?;
+    std::fs::create_dir_all(&dir).context("Failed to create job log directory")?;
+    Ok(dir.join(format!("{}.log", flash_id)))
+}
+
+// What the board printed on its debug UART during the job, see serial
+pub fn boot_log_path(app: &tauri::AppHandle, flash_id: &str) -> Result<PathBuf> {
+    Ok(log_path(app, flash_id)?.with_extension("boot.log"))
+}
+
+// Start capturing a job's output; without a log file only the ring is kept
+pub fn open(app: &impl JobHost, state: &AppState, flash_id: &str) {
+    let file = log_path(app, flash_id)
+        .and_then(|path| File::create(&path).with_context(|| format!("Failed to create {}", path.display())));
+    let file = match file {
+        Ok(file) => {
+            let mut file = LineWriter::new(file);
+            let header = writeln!(
+                file,
+                "{} {}, job {}, started {}",
+                HEADER_PREFIX,
+                env!("CARGO_PKG_VERSION"),
+                flash_id,
+                Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+            );
+            header.is_ok().then_some(file)
+        }
+        Err(e) => {
+            warn!("Job output for {} is not saved: {}", flash_id, e);
+            None
+        }
+    };
+    state.job_output.lock().unwrap().insert(
+        flash_id.to_string(),
+        JobOutput {
+            recent: VecDeque::with_capacity(RECENT_OUTPUT_LINES),
+            file,
+            failure: None,
+        },
+    );
+}
+
+pub fn append(state: &AppState, flash_id: &str, line: &str) {
+    append_stream(state, flash_id, "stdout", line);
+}
+
+pub fn append_stream(state: &AppState, flash_id: &str, stream: &str, line: &str) {
+    if let Some(follower) = state.job_followers.lock().unwrap().get(flash_id) {
+        let _ = follower.send((stream.to_string(), line.to_string()));
+    }
+    let mut outputs = state.job_output.lock().unwrap();
+    let Some(output) = outputs.get_mut(flash_id) else {
+        return;
+    };
+    if output.recent.len() == RECENT_OUTPUT_LINES {
+        output.recent.pop_front();
+    }
+    output.recent.push_back(line.to_string());
+    if output.failure.is_none() {
+        if let Some(failure) = failures::classify(line) {
+            info!("Job {} output reports {}: {}", flash_id, failure.code, failure.line);
+            output.failure = Some(failure);
+        }
+    }
+    if let Some(file) = output.file.as_mut() {
+        let time = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+        if let Err(e) = writeln!(file, "{} {} {}", time, stream, line) {
+            warn!("Failed to write job log for {}, keeping memory only: {}", flash_id, e);
+            output.file = None;
+        }
+    }
+}
+
+// The first known failure a running job's output reported
+pub fn failure(state: &AppState, flash_id: &str) -> Option<FlashFailure> {
+    state.job_output.lock().unwrap().get(flash_id).and_then(|output| output.failure.clone())
+}
+
+// Receive a job's output lines (stream, line) as they are logged, until the job ends; for cfu-cli
+pub fn follow(state: &AppState, flash_id: &str) -> mpsc::UnboundedReceiver<(String, String)> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    state.job_followers.lock().unwrap().insert(flash_id.to_string(), sender);
+    receiver
+}
+
+// Release a finished job's buffer; its output stays readable from disk
+pub fn close(state: &AppState, flash_id: &str) {
+    state.job_output.lock().unwrap().remove(flash_id);
+    state.job_followers.lock().unwrap().remove(flash_id);
+}
+
+fn tail_file(path: &PathBuf, lines: usize) -> Result<Vec<String>> {
+    let file = File::open(path).with_context(|| format!("No output recorded at {}", path.display()))?;
+    let mut recent = VecDeque::with_capacity(lines);
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.starts_with(HEADER_PREFIX) {
+            continue;
+        }
+        if recent.len() == lines {
+            recent.pop_front();
+        }
+        recent.push_back(line);
+    }
+    Ok(recent.into())
+}
+
+// The last `lines` lines of a finished job's log
+pub fn tail(app: &impl JobHost, flash_id: &str, lines: usize) -> Result<Vec<String>> {
+    let recent = tail_file(&log_path(app, flash_id)?, lines)?;
+    Ok(recent.iter().map(|text| LogEntry::parse(text).line).collect())
+}
+
+// The last `lines` lines a job printed, from memory while it runs and from its log afterwards
+#[command]
+pub async fn get_recent_output(
+    flash_id: String,
+    lines: Option<usize>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<String>, String> {
+    let lines = lines.unwrap_or(100);
+    if let Some(output) = state.job_output.lock().unwrap().get(&flash_id) {
+        let skip = output.recent.len().saturating_sub(lines);
+        return Ok(output.recent.iter().skip(skip).cloned().collect());
+    }
+    tail(&app, &flash_id, lines).map_err(|e| e.to_string())
+}
+
+fn read_log(path: &PathBuf) -> Result<Vec<LogEntry>> {
+    let file = File::open(path).with_context(|| format!("No output recorded at {}", path.display()))?;
+    let mut entries = Vec::new();
+    for text in BufReader::new(file).lines() {
+        let text = text?;
+        if !text.starts_with(HEADER_PREFIX) {
+            entries.push(LogEntry::parse(&text));
+        }
+    }
+    Ok(entries)
+}
+
+// A job's full log, with the time and stream of each line
+#[command]
+pub async fn get_flash_log(flash_id: String, app: tauri::AppHandle) -> Result<Vec<LogEntry>, String> {
+    let path = log_path(&app, &flash_id).map_err(|e| e.to_string())?;
+    tokio::task::spawn_blocking(move || read_log(&path))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+// Copy a job's log to `path`, e.g. to attach it to a support ticket; a boot log is copied next to it
+#[command]
+pub async fn export_flash_log(flash_id: String, path: String, app: tauri::AppHandle) -> Result<(), String> {
+    let export = || -> Result<()> {
+        let source = log_path(&app, &flash_id)?;
+        if !source.is_file() {
+            return Err(anyhow::anyhow!("No output recorded for job {}", flash_id));
+        }
+        let destination = PathBuf::from(&path);
+        std::fs::copy(&source, &destination).with_context(|| format!("Failed to write {}", destination.display()))?;
+        let boot_log = boot_log_path(&app, &flash_id)?;
+        if boot_log.is_file() {
+            let boot_destination = destination.with_extension("boot.log");
+            std::fs::copy(&boot_log, &boot_destination).with_context(|| format!("Failed to write {}", boot_destination.display()))?;
+        }
+        info!("Exported log of job {} to {}", flash_id, destination.display());
+        Ok(())
+    };
+    export().map_err(|e| e.to_string())
+}
+
+// The last `lines` lines of a job's boot log
+#[command]
+pub async fn get_boot_log(flash_id: String, lines: Option<usize>, app: tauri::AppHandle) -> Result<Vec<String>, String> {
+    let path = boot_log_path(&app, &flash_id).map_err(|e| e.to_string())?;
+    tail_file(&path, lines.unwrap_or(100)).map_err(|e| e.to_string())
+}
@@ -0,0 +1,582 @@
+// CFU - Cordatus Flash Utility - Kernel Builds
+// Cross-toolchain management, L4T kernel sources with user patches, and module / custom kernel builds
+
+use crate::ssh::shell_quote;
+use crate::AppState;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::info;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use tauri::{command, Emitter, Manager, State};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::process::Command as TokioCommand;
+use uuid::Uuid;
+
+// (L4T major, name, archive URL, SHA-256 of the archive when pinned)
+type ToolchainEntry = (&'static str, &'static str, &'static str, Option<&'static str>);
+
+// Toolchains NVIDIA documents for each L4T major release. URLs point at the archives themselves, not the
+// pages linking them. Unpinned archives are checked to be archives and their digest is logged for pinning.
+const TOOLCHAINS: &[ToolchainEntry] = &[
+    (
+        "36",
+        "aarch64--glibc--stable-2022.08-1",
+        "https://developer.nvidia.com/downloads/embedded/l4t/r36_release_v3.0/toolchain/aarch64--glibc--stable-2022.08-1.tar.bz2",
+        None,
+    ),
+    (
+        "35",
+        "aarch64--glibc--stable-final",
+        "https://developer.download.nvidia.com/embedded/L4T/bootlin/aarch64--glibc--stable-final.tar.gz",
+        None,
+    ),
+    (
+        "32",
+        "gcc-linaro-7.3.1-2018.05-x86_64_aarch64-linux-gnu",
+        "http://releases.linaro.org/components/toolchain/binaries/7.3-2018.05/aarch64-linux-gnu/gcc-linaro-7.3.1-2018.05-x86_64_aarch64-linux-gnu.tar.xz",
+        None,
+    ),
+];
+
+// gzip, bzip2 and xz; anything else, e.g. an HTML page, is refused before tar sees it
+const ARCHIVE_MAGIC: &[&[u8]] = &[b"\x1f\x8b", b"BZh", b"\xfd7zXZ\x00"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolchainInfo {
+    pub name: String,
+    pub l4t_major: String,
+    pub url: String,
+    pub installed: bool,
+    pub cross_compile: Option<String>, // CROSS_COMPILE prefix, e.g. ".../bin/aarch64-buildroot-linux-gnu-"
+}
+
+const SOURCES_FILE: &str = "kernel_sources.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppliedPatch {
+    pub name: String,
+    pub sha256: String,
+    pub applied_at: DateTime<Utc>,
+}
+
+// An unpacked L4T kernel tree and the patches applied on top of it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KernelSourceTree {
+    pub source_id: String,
+    pub l4t_version: String,
+    pub kernel_dir: String,
+    pub patches: Vec<AppliedPatch>,
+    pub fetched_at: DateTime<Utc>,
+}
+
+// Output of a kernel or module build, selectable as the custom kernel of a flash job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KernelArtifacts {
+    pub build_id: String,
+    pub l4t_version: String,
+    pub toolchain: String,
+    pub source_dir: String,
+    pub kernel_release: Option<String>, // `make kernelrelease`, the /lib/modules directory name
+    pub image: Option<String>,
+    pub modules_dir: Option<String>, // INSTALL_MOD_PATH of a full kernel build
+    pub modules: Vec<String>,        // Out-of-tree .ko files
+    #[serde(default)]
+    pub patches: Vec<AppliedPatch>, // Patches in the source tree at build time
+    pub built_at: DateTime<Utc>,
+}
+
+fn toolchains_dir(app: &tauri::AppHandle) -> Result<PathBuf> {
+    let dir = crate::app_data_file(app, "toolchains")?;
+    std::fs::create_dir_all(&dir).context("Failed to create toolchain directory")?;
+    Ok(dir)
+}
+
+fn l4t_major(l4t_version: &str) -> &str {
+    l4t_version.split('.').next().unwrap_or(l4t_version)
+}
+
+// Accept both "36.4.3" and JetPack strings like "6.2 - L4T 36.4.3"
+fn resolve_l4t(state: &AppState, version: &str) -> Result<String> {
+    state
+        .version_matrix
+        .lock()
+        .unwrap()
+        .resolve(version)
+        .map(|release| release.l4t.clone())
+        .with_context(|| format!("Unknown L4T release: {}", version))
+}
+
+// Toolchain archives differ in layout, so look for the gcc driver a few levels down
+fn find_cross_compile(dir: &Path, depth: usize) -> Option<String> {
+    let entries = std::fs::read_dir(dir).ok()?;
+    let mut subdirs = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if path.is_dir() {
+            subdirs.push(path);
+        } else if name.starts_with("aarch64") && name.ends_with("-gcc") && dir.ends_with("bin") {
+            let prefix = path.to_string_lossy();
+            return Some(prefix.trim_end_matches("gcc").to_string());
+        }
+    }
+    if depth == 0 {
+        return None;
+    }
+    subdirs.iter().find_map(|sub| find_cross_compile(sub, depth - 1))
+}
+
+fn toolchain_info(app: &tauri::AppHandle, entry: &ToolchainEntry) -> ToolchainInfo {
+    let (major, name, url, _) = *entry;
+    let cross_compile = toolchains_dir(app)
+        .ok()
+        .and_then(|dir| find_cross_compile(&dir.join(name), 3));
+    ToolchainInfo {
+        name: name.to_string(),
+        l4t_major: major.to_string(),
+        url: url.to_string(),
+        installed: cross_compile.is_some(),
+        cross_compile,
+    }
+}
+
+fn installed_toolchain(app: &tauri::AppHandle, l4t_version: &str) -> Result<ToolchainInfo> {
+    let entry = TOOLCHAINS
+        .iter()
+        .find(|(major, _, _, _)| *major == l4t_major(l4t_version))
+        .with_context(|| format!("No known toolchain for L4T {}", l4t_version))?;
+    let info = toolchain_info(app, entry);
+    if !info.installed {
+        return Err(anyhow::anyhow!("Toolchain {} is not installed", info.name));
+    }
+    Ok(info)
+}
+
+// NVIDIA publishes the BSP sources per release: 36.4.3 -> r36_release_v4.3
+fn public_sources_url(l4t_version: &str) -> String {
+    let parts: Vec<&str> = l4t_version.split('.').collect();
+    let major = parts.first().copied().unwrap_or("36");
+    let minor = parts.get(1).copied().unwrap_or("0");
+    let patch = parts.get(2).copied().unwrap_or("0");
+    if major == "32" {
+        format!("https://developer.nvidia.com/embedded/l4t/r32_release_v{}.{}/sources/t186/public_sources.tbz2", minor, patch)
+    } else {
+        format!(
+            "https://developer.nvidia.com/downloads/embedded/l4t/r{}_release_v{}.{}/sources/public_sources.tbz2",
+            major, minor, patch
+        )
+    }
+}
+
+pub fn load_sources(app: &tauri::AppHandle) -> Vec<KernelSourceTree> {
+    crate::app_data_file(app, SOURCES_FILE)
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_sources(app: &tauri::AppHandle, sources: &[KernelSourceTree]) -> Result<()> {
+    let path = crate::app_data_file(app, SOURCES_FILE)?;
+    std::fs::write(path, serde_json::to_string_pretty(sources)?).context("Failed to save kernel source list")
+}
+
+// Patches recorded for the tree containing `dir`, so builds carry their provenance
+fn patches_for_dir(app: &tauri::AppHandle, dir: &str) -> Vec<AppliedPatch> {
+    load_sources(app)
+        .into_iter()
+        .find(|tree| Path::new(dir).starts_with(&tree.kernel_dir))
+        .map(|tree| tree.patches)
+        .unwrap_or_default()
+}
+
+fn find_file(dir: &Path, name: &str, depth: usize) -> Option<PathBuf> {
+    let entries = std::fs::read_dir(dir).ok()?;
+    let mut subdirs = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if entry.file_name() == name {
+            return Some(path);
+        }
+        if path.is_dir() && depth > 0 {
+            subdirs.push(path);
+        }
+    }
+    subdirs.iter().find_map(|sub| find_file(sub, name, depth - 1))
+}
+
+// The kernel tree is the directory holding the top-level Makefile and arch/arm64
+// (kernel-jammy-src on r36, kernel-5.10 on r35, kernel-4.9 on r32)
+fn find_kernel_tree(dir: &Path, depth: usize) -> Option<PathBuf> {
+    if dir.join("Makefile").is_file() && dir.join("arch/arm64").is_dir() {
+        return Some(dir.to_path_buf());
+    }
+    if depth == 0 {
+        return None;
+    }
+    std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .find_map(|entry| find_kernel_tree(&entry.path(), depth - 1))
+}
+
+// Stream a URL to disk, emitting `event` with byte counts as it goes
+pub async fn download_with_progress(app: &tauri::AppHandle, url: &str, dest: &Path, event: &str, id: &str) -> Result<()> {
+    let mut response = reqwest::get(url).await?.error_for_status()?;
+    let total = response.content_length();
+    let mut file = tokio::fs::File::create(dest).await.context("Failed to create download file")?;
+    let mut downloaded: u64 = 0;
+    let mut last_emit: u64 = 0;
+
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk).await?;
+        downloaded += chunk.len() as u64;
+        if downloaded - last_emit >= 4 * 1024 * 1024 {
+            last_emit = downloaded;
+            let _ = app.emit(event, serde_json::json!({ "id": id, "downloaded": downloaded, "total": total }));
+        }
+    }
+    file.flush().await?;
+    let _ = app.emit(event, serde_json::json!({ "id": id, "downloaded": downloaded, "total": total }));
+    Ok(())
+}
+
+pub async fn extract_archive(app: &tauri::AppHandle, archive: &Path, dest: &Path) -> Result<()> {
+    tokio::fs::create_dir_all(dest).await?;
+    let mut cmd = TokioCommand::new("tar");
+    cmd.arg("xf").arg(archive).arg("-C").arg(dest);
+    crate::io_priority::apply(&app.state::<Arc<AppState>>(), &mut cmd);
+    let output = cmd.output().await.context("Failed to run tar")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "Failed to extract {}: {}",
+            archive.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+// Run make with the cross toolchain, forwarding every output line as "kernel-build-log"
+async fn run_make(app: &tauri::AppHandle, build_id: &str, dir: &Path, cross_compile: &str, args: &[String]) -> Result<String> {
+    let mut child = TokioCommand::new("bash")
+        .arg("-c")
+        .arg(r#"exec make -j"$(nproc)" ARCH=arm64 CROSS_COMPILE="$CFU_CROSS_COMPILE" "$@" 2>&1"#)
+        .arg("make")
+        .args(args)
+        .env("CFU_CROSS_COMPILE", cross_compile)
+        .current_dir(dir)
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Failed to start make")?;
+
+    let mut last_line = String::new();
+    if let Some(stdout) = child.stdout.take() {
+        let mut lines = BufReader::new(stdout).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let _ = app.emit("kernel-build-log", serde_json::json!({ "build_id": build_id, "line": line }));
+            last_line = line;
+        }
+    }
+
+    let status = child.wait().await?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("make {} failed: {}", args.join(" "), last_line));
+    }
+    Ok(last_line)
+}
+
+// Shell snippet for the rootfs hook installing the custom kernel into the L4T tree
+pub fn install_section(artifacts: &KernelArtifacts) -> String {
+    let mut section = format!("\n# Custom kernel: build {}\n", artifacts.build_id);
+    if let Some(image) = &artifacts.image {
+        section.push_str(&format!(
+            "cp {image} \"$ROOT/../kernel/Image\"\ncp {image} \"$ROOT/boot/Image\"\n",
+            image = shell_quote(image)
+        ));
+    }
+    if let Some(modules_dir) = &artifacts.modules_dir {
+        section.push_str(&format!(
+            "cp -a {}/lib/modules/. \"$ROOT/lib/modules/\"\n",
+            shell_quote(modules_dir)
+        ));
+    }
+    if !artifacts.modules.is_empty() {
+        let kver = match &artifacts.kernel_release {
+            Some(release) => shell_quote(release),
+            None => "\"$(ls \"$ROOT/lib/modules\" | sort -V | tail -n 1)\"".to_string(),
+        };
+        section.push_str(&format!("KVER={}\nmkdir -p \"$ROOT/lib/modules/$KVER/updates\"\n", kver));
+        for module in &artifacts.modules {
+            section.push_str(&format!("cp {} \"$ROOT/lib/modules/$KVER/updates/\"\n", shell_quote(module)));
+        }
+        section.push_str("depmod -b \"$ROOT\" \"$KVER\"\n");
+    }
+    section
+}
+
+pub fn validate_artifacts(artifacts: &KernelArtifacts) -> Result<()> {
+    let files = artifacts.image.iter().chain(&artifacts.modules_dir).chain(&artifacts.modules);
+    for file in files {
+        if !Path::new(file).exists() {
+            return Err(anyhow::anyhow!("Kernel artifact missing: {}", file));
+        }
+    }
+    Ok(())
+}
+
+// List known cross toolchains and whether they are installed
+#[command]
+pub async fn list_toolchains(app: tauri::AppHandle) -> Result<Vec<ToolchainInfo>, String> {
+    Ok(TOOLCHAINS.iter().map(|entry| toolchain_info(&app, entry)).collect())
+}
+
+// List fetched kernel source trees and their applied patches
+#[command]
+pub async fn list_kernel_sources(app: tauri::AppHandle) -> Result<Vec<KernelSourceTree>, String> {
+    Ok(load_sources(&app))
+}
+
+// Download public_sources for a release and unpack its kernel tree
+#[command]
+pub async fn fetch_kernel_sources(
+    l4t_version: String,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<KernelSourceTree, String> {
+    let fetch = async {
+        let l4t = resolve_l4t(&state, &l4t_version)?;
+        let source_id = Uuid::new_v4().to_string();
+        let dir = crate::app_data_file(&app, &format!("kernel_sources/{}", source_id))?;
+        std::fs::create_dir_all(&dir)?;
+        let archive = dir.join("public_sources.tbz2");
+        let url = public_sources_url(&l4t);
+
+        info!("Fetching L4T {} sources from {}", l4t, url);
+        download_with_progress(&app, &url, &archive, "kernel-source-progress", &source_id).await?;
+        extract_archive(&app, &archive, &dir).await?;
+        tokio::fs::remove_file(&archive).await.ok();
+
+        let kernel_archive = find_file(&dir, "kernel_src.tbz2", 4).context("kernel_src.tbz2 not found in public sources")?;
+        let kernel_root = dir.join("kernel");
+        extract_archive(&app, &kernel_archive, &kernel_root).await?;
+        let kernel_dir = find_kernel_tree(&kernel_root, 3).context("No kernel tree found in kernel_src.tbz2")?;
+
+        let tree = KernelSourceTree {
+            source_id,
+            l4t_version: l4t,
+            kernel_dir: kernel_dir.to_string_lossy().to_string(),
+            patches: Vec::new(),
+            fetched_at: Utc::now(),
+        };
+        let mut sources = load_sources(&app);
+        sources.push(tree.clone());
+        save_sources(&app, &sources)?;
+        anyhow::Ok(tree)
+    };
+    fetch.await.map_err(|e| format!("Failed to fetch kernel sources: {}", e))
+}
+
+// Apply patch files (in order, -p1) to a fetched kernel tree and record them
+#[command]
+pub async fn apply_kernel_patches(
+    source_id: String,
+    patch_files: Vec<String>,
+    app: tauri::AppHandle,
+) -> Result<KernelSourceTree, String> {
+    let mut sources = load_sources(&app);
+    let tree = sources
+        .iter_mut()
+        .find(|tree| tree.source_id == source_id)
+        .ok_or_else(|| format!("Kernel source tree not found: {}", source_id))?;
+
+    for patch_file in &patch_files {
+        let contents = std::fs::read(patch_file).map_err(|e| format!("Cannot read {}: {}", patch_file, e))?;
+        let output = TokioCommand::new("patch")
+            .args(["-p1", "--forward", "--batch", "-i", patch_file])
+            .current_dir(&tree.kernel_dir)
+            .output()
+            .await
+            .map_err(|e| format!("Failed to run patch: {}", e))?;
+
+        // Earlier patches stay recorded, the tree really contains them
+        if !output.status.success() {
+            save_sources(&app, &sources).map_err(|e| e.to_string())?;
+            return Err(format!(
+                "Patch {} does not apply: {}",
+                patch_file,
+                String::from_utf8_lossy(&output.stdout).trim()
+            ));
+        }
+
+        info!("Applied {} to kernel tree {}", patch_file, source_id);
+        tree.patches.push(AppliedPatch {
+            name: Path::new(patch_file)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| patch_file.clone()),
+            sha256: format!("{:x}", Sha256::digest(&contents)),
+            applied_at: Utc::now(),
+        });
+    }
+
+    let tree = tree.clone();
+    save_sources(&app, &sources).map_err(|e| e.to_string())?;
+    Ok(tree)
+}
+
+async fn verify_toolchain_archive(archive: &Path, name: &str, sha256: Option<&str>) -> Result<()> {
+    let mut head = [0u8; 6];
+    let read = tokio::fs::File::open(archive).await?.read(&mut head).await?;
+    if !ARCHIVE_MAGIC.iter().any(|magic| head[..read].starts_with(magic)) {
+        return Err(anyhow::anyhow!("The download of toolchain {} is not a compressed tar archive", name));
+    }
+    let path = archive.to_path_buf();
+    let actual = tokio::task::spawn_blocking(move || crate::checksum::sha256_file(&path)).await??;
+    match sha256 {
+        Some(expected) if !actual.eq_ignore_ascii_case(expected) => {
+            Err(anyhow::anyhow!("Toolchain {} does not match its pinned checksum, got {}", name, actual))
+        }
+        Some(_) => Ok(()),
+        None => {
+            info!("Toolchain {} has no pinned checksum, its archive has SHA-256 {}", name, actual);
+            Ok(())
+        }
+    }
+}
+
+// Download and unpack the cross toolchain for an L4T release
+#[command]
+pub async fn install_toolchain(
+    l4t_version: String,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<ToolchainInfo, String> {
+    let l4t = resolve_l4t(&state, &l4t_version).map_err(|e| e.to_string())?;
+    let entry = TOOLCHAINS
+        .iter()
+        .find(|(major, _, _, _)| *major == l4t_major(&l4t))
+        .ok_or_else(|| format!("No known toolchain for L4T {}", l4t))?;
+    let (_, name, url, sha256) = *entry;
+
+    let install = async {
+        let dir = toolchains_dir(&app)?;
+        let archive = dir.join(format!("{}.tar", name));
+        info!("Downloading toolchain {} from {}", name, url);
+        download_with_progress(&app, url, &archive, "toolchain-download-progress", name).await?;
+        verify_toolchain_archive(&archive, name, sha256).await?;
+        extract_archive(&app, &archive, &dir.join(name)).await?;
+        tokio::fs::remove_file(&archive).await.ok();
+        anyhow::Ok(())
+    };
+    install.await.map_err(|e| format!("Failed to install toolchain {}: {}", name, e))?;
+
+    let info = toolchain_info(&app, entry);
+    if !info.installed {
+        return Err(format!("Toolchain {} was extracted but no aarch64 gcc was found", name));
+    }
+    Ok(info)
+}
+
+// Build a custom kernel (Image + in-tree modules) from an L4T kernel source tree
+#[command]
+pub async fn build_kernel(
+    l4t_version: String,
+    source_dir: String,
+    defconfig: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<KernelArtifacts, String> {
+    let build = async {
+        let l4t = resolve_l4t(&state, &l4t_version)?;
+        let toolchain = installed_toolchain(&app, &l4t)?;
+        let cross_compile = toolchain.cross_compile.clone().unwrap_or_default();
+        let build_id = Uuid::new_v4().to_string();
+        let out_dir = crate::app_data_file(&app, &format!("kernel_builds/{}", build_id))?;
+        let modules_dir = out_dir.join("modules_install");
+        std::fs::create_dir_all(&modules_dir)?;
+        let source = Path::new(&source_dir);
+        let out_arg = format!("O={}", out_dir.display());
+
+        info!("Building kernel {} from {}", build_id, source_dir);
+        let defconfig = defconfig.unwrap_or_else(|| "defconfig".to_string());
+        run_make(&app, &build_id, source, &cross_compile, &[out_arg.clone(), defconfig]).await?;
+        run_make(&app, &build_id, source, &cross_compile, &[out_arg.clone(), "Image".into(), "modules".into()]).await?;
+        run_make(
+            &app,
+            &build_id,
+            source,
+            &cross_compile,
+            &[out_arg.clone(), "modules_install".into(), format!("INSTALL_MOD_PATH={}", modules_dir.display())],
+        )
+        .await?;
+        let kernel_release = run_make(&app, &build_id, source, &cross_compile, &[out_arg, "-s".into(), "kernelrelease".into()]).await?;
+
+        anyhow::Ok(KernelArtifacts {
+            build_id,
+            l4t_version: l4t,
+            toolchain: toolchain.name,
+            source_dir: source_dir.clone(),
+            kernel_release: Some(kernel_release.trim().to_string()),
+            image: Some(out_dir.join("arch/arm64/boot/Image").to_string_lossy().to_string()),
+            modules_dir: Some(modules_dir.to_string_lossy().to_string()),
+            modules: Vec::new(),
+            patches: patches_for_dir(&app, &source_dir),
+            built_at: Utc::now(),
+        })
+    };
+    build.await.map_err(|e| format!("Kernel build failed: {}", e))
+}
+
+// Build an out-of-tree module against a prepared kernel tree (source or build output directory)
+#[command]
+pub async fn build_kernel_module(
+    l4t_version: String,
+    kernel_dir: String,
+    module_dir: String,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<KernelArtifacts, String> {
+    let build = async {
+        let l4t = resolve_l4t(&state, &l4t_version)?;
+        let toolchain = installed_toolchain(&app, &l4t)?;
+        let cross_compile = toolchain.cross_compile.clone().unwrap_or_default();
+        let build_id = Uuid::new_v4().to_string();
+        let kernel = Path::new(&kernel_dir);
+
+        info!("Building module {} against {}", module_dir, kernel_dir);
+        run_make(&app, &build_id, kernel, &cross_compile, &[format!("M={}", module_dir), "modules".into()]).await?;
+        let kernel_release = run_make(&app, &build_id, kernel, &cross_compile, &["-s".into(), "kernelrelease".into()])
+            .await
+            .ok();
+
+        let modules = std::fs::read_dir(&module_dir)?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "ko"))
+            .map(|path| path.to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        if modules.is_empty() {
+            return Err(anyhow::anyhow!("Build produced no .ko files"));
+        }
+
+        anyhow::Ok(KernelArtifacts {
+            build_id,
+            l4t_version: l4t,
+            toolchain: toolchain.name,
+            source_dir: kernel_dir.clone(),
+            kernel_release: kernel_release.map(|r| r.trim().to_string()),
+            image: None,
+            modules_dir: None,
+            modules,
+            patches: patches_for_dir(&app, &kernel_dir),
+            built_at: Utc::now(),
+        })
+    };
+    build.await.map_err(|e| format!("Module build failed: {}", e))
+}
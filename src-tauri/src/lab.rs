@@ -0,0 +1,478 @@
+// CFU - Cordatus Flash Utility - Lab Fixtures
+// Board farm slots managed by the app, with power and force-recovery hooks, exported as labgrid exporter /
+// environment files and LAVA device dictionaries whose control commands call back into the control API
+
+use crate::settings::LabFixtureSettings;
+use crate::{control_api, AppState, FlashProgress, JetsonDevice};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::info;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{command, State};
+use tokio::process::Command as TokioCommand;
+
+const HOOK_TIMEOUT: Duration = Duration::from_secs(60);
+const POWER_CYCLE_OFF_TIME: Duration = Duration::from_secs(3);
+const CERTIFICATE_FILE: &str = "cfu-control-api.pem";
+const RECENT_RESULTS_PER_SLOT: usize = 5;
+// Only this many of the newest jobs are scanned for recent results, keeping a poll cheap on long histories
+const RECENT_RESULTS_SCAN: usize = 500;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LabFixture {
+    pub name: String,
+    pub usb_port_path: String,
+    pub serial_port: Option<String>,
+    pub ssh_host: Option<String>,
+    pub power_control: bool,
+    pub recovery_control: bool,
+    pub device: Option<JetsonDevice>, // The Jetson currently enumerated on the fixture's port
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LabSlotJob {
+    pub flash_id: String,
+    pub device_module: String,
+    pub jetpack_version: String,
+    pub profile: Option<String>,
+    pub operator: Option<String>,
+    pub progress: Option<FlashProgress>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LabSlotResult {
+    pub flash_id: String,
+    pub status: String,
+    pub serial_number: Option<String>,
+    pub failed_stage: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LabSlot {
+    pub name: String, // Fixture name, or the device ID for a device on no fixture
+    pub fixture: bool,
+    pub state: String, // 'flashing' | 'recovery' | 'connected' | 'empty'
+    pub device: Option<JetsonDevice>,
+    pub current_job: Option<LabSlotJob>,
+    pub recent_results: Vec<LabSlotResult>, // Newest first
+}
+
+// Everything a wall dashboard shows, built from in-memory state only so it can be polled every second
+#[derive(Debug, Clone, Serialize)]
+pub struct LabState {
+    pub station: Option<String>,
+    pub generated_at: DateTime<Utc>,
+    pub slots: Vec<LabSlot>,
+    pub unassigned_jobs: Vec<LabSlotJob>, // Running jobs not started on a fixture
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LabExport {
+    pub files: Vec<String>,
+    pub api_url: String,
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    (!value.trim().is_empty()).then(|| value.to_string())
+}
+
+pub fn find_fixture(state: &AppState, name: &str) -> Result<LabFixtureSettings> {
+    state
+        .settings
+        .lock()
+        .unwrap()
+        .lab_fixtures
+        .iter()
+        .find(|f| f.name == name)
+        .cloned()
+        .with_context(|| format!("Lab fixture not found: {}", name))
+}
+
+pub fn fixture_device(state: &AppState, fixture: &LabFixtureSettings) -> Option<JetsonDevice> {
+    if fixture.usb_port_path.is_empty() {
+        return None;
+    }
+    state
+        .connected_devices
+        .lock()
+        .unwrap()
+        .values()
+        .find(|d| {
+            d.usb_info
+                .as_ref()
+                .and_then(|usb| usb.port_path.as_deref())
+                .is_some_and(|port| port == fixture.usb_port_path)
+        })
+        .cloned()
+}
+
+// The fixture whose device is waiting in recovery mode, which the flash script will pick up
+pub fn recovery_fixture(state: &AppState) -> Option<String> {
+    let fixtures = state.settings.lock().unwrap().lab_fixtures.clone();
+    let mut waiting = fixtures.iter().filter(|fixture| {
+        fixture_device(state, fixture)
+            .and_then(|d| d.usb_info)
+            .is_some_and(|usb| usb.is_recovery_mode)
+    });
+    match (waiting.next(), waiting.next()) {
+        (Some(fixture), None) => Some(fixture.name.clone()),
+        _ => None,
+    }
+}
+
+pub fn list_fixtures(state: &AppState) -> Vec<LabFixture> {
+    let fixtures = state.settings.lock().unwrap().lab_fixtures.clone();
+    fixtures
+        .iter()
+        .map(|fixture| LabFixture {
+            name: fixture.name.clone(),
+            usb_port_path: fixture.usb_port_path.clone(),
+            serial_port: non_empty(&fixture.serial_port),
+            ssh_host: non_empty(&fixture.ssh_host),
+            power_control: !fixture.power_on_command.is_empty() && !fixture.power_off_command.is_empty(),
+            recovery_control: crate::recovery::method(fixture).is_some(),
+            device: fixture_device(state, fixture),
+        })
+        .collect()
+}
+
+fn slot_state(device: Option<&JetsonDevice>, flashing: bool) -> &'static str {
+    if flashing {
+        return "flashing";
+    }
+    match device {
+        Some(device) if device.usb_info.as_ref().is_some_and(|usb| usb.is_recovery_mode) => "recovery",
+        Some(_) => "connected",
+        None => "empty",
+    }
+}
+
+pub fn lab_state(state: &AppState) -> LabState {
+    let fixtures = state.settings.lock().unwrap().lab_fixtures.clone();
+    let devices: Vec<JetsonDevice> = state.connected_devices.lock().unwrap().values().cloned().collect();
+    let progress = state.flash_progress.lock().unwrap().clone();
+
+    let mut running: HashMap<String, LabSlotJob> = HashMap::new();
+    let mut unassigned_jobs = Vec::new();
+    let mut recent: HashMap<String, Vec<LabSlotResult>> = HashMap::new();
+    {
+        let history = state.history.lock().unwrap();
+        for job in history.jobs.iter().rev().take(RECENT_RESULTS_SCAN) {
+            if job.status == "running" {
+                let slot_job = LabSlotJob {
+                    flash_id: job.flash_id.clone(),
+                    device_module: job.command.device_module.clone(),
+                    jetpack_version: job.command.jetpack_version.clone(),
+                    profile: job.profile.clone(),
+                    operator: job.command.operator.clone(),
+                    progress: progress.get(&job.flash_id).cloned(),
+                };
+                match &job.fixture {
+                    Some(fixture) => {
+                        running.entry(fixture.clone()).or_insert(slot_job);
+                    }
+                    None => unassigned_jobs.push(slot_job),
+                }
+                continue;
+            }
+            let Some(fixture) = &job.fixture else { continue };
+            let results = recent.entry(fixture.clone()).or_default();
+            if results.len() < RECENT_RESULTS_PER_SLOT {
+                results.push(LabSlotResult {
+                    flash_id: job.flash_id.clone(),
+                    status: job.status.clone(),
+                    serial_number: job.serial_number.clone(),
+                    failed_stage: job.failed_stage.clone(),
+                    started_at: job.started_at,
+                    finished_at: job.finished_at,
+                });
+            }
+        }
+    }
+
+    let mut on_fixture = HashSet::new();
+    let mut slots: Vec<LabSlot> = fixtures
+        .iter()
+        .map(|fixture| {
+            let device = devices
+                .iter()
+                .find(|d| {
+                    !fixture.usb_port_path.is_empty()
+                        && d.usb_info.as_ref().and_then(|usb| usb.port_path.as_deref()) == Some(fixture.usb_port_path.as_str())
+                })
+                .cloned();
+            if let Some(device) = &device {
+                on_fixture.insert(device.id.clone());
+            }
+            let current_job = running.remove(&fixture.name);
+            LabSlot {
+                name: fixture.name.clone(),
+                fixture: true,
+                state: slot_state(device.as_ref(), current_job.is_some()).to_string(),
+                device,
+                current_job,
+                recent_results: recent.remove(&fixture.name).unwrap_or_default(),
+            }
+        })
+        .collect();
+    // Jobs recorded on a fixture that has since been removed from the settings
+    unassigned_jobs.extend(running.into_values());
+    slots.extend(devices.into_iter().filter(|d| !on_fixture.contains(&d.id)).map(|device| LabSlot {
+        name: device.id.clone(),
+        fixture: false,
+        state: slot_state(Some(&device), false).to_string(),
+        device: Some(device),
+        current_job: None,
+        recent_results: Vec::new(),
+    }));
+
+    LabState {
+        station: crate::history::station_name(state),
+        generated_at: Utc::now(),
+        slots,
+        unassigned_jobs,
+    }
+}
+
+pub async fn run_hook(fixture: &LabFixtureSettings, hook: &str, script: &str) -> Result<()> {
+    if script.trim().is_empty() {
+        return Err(anyhow::anyhow!("Fixture {} has no {} command", fixture.name, hook));
+    }
+    let output = tokio::time::timeout(
+        HOOK_TIMEOUT,
+        TokioCommand::new("sh")
+            .args(["-c", script])
+            .env("CFU_FIXTURE", &fixture.name)
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .with_context(|| format!("The {} command of {} timed out", hook, fixture.name))?
+    .with_context(|| format!("Failed to run the {} command of {}", hook, fixture.name))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow::anyhow!("The {} command of {} failed: {}", hook, fixture.name, stderr.trim()));
+    }
+    Ok(())
+}
+
+// action: 'power_on' | 'power_off' | 'power_cycle' | 'recovery'. Recovery uses the fixture's
+// configured method and does not wait for the board.
+pub async fn control_fixture(state: &AppState, name: &str, action: &str) -> Result<()> {
+    let fixture = find_fixture(state, name)?;
+    match action {
+        "power_on" => run_hook(&fixture, "power on", &fixture.power_on_command).await?,
+        "power_off" => run_hook(&fixture, "power off", &fixture.power_off_command).await?,
+        "power_cycle" => {
+            run_hook(&fixture, "power off", &fixture.power_off_command).await?;
+            tokio::time::sleep(POWER_CYCLE_OFF_TIME).await;
+            run_hook(&fixture, "power on", &fixture.power_on_command).await?;
+        }
+        "recovery" => {
+            crate::recovery::trigger(&fixture).await?;
+        }
+        _ => return Err(anyhow::anyhow!("Unknown fixture action: {}", action)),
+    }
+    info!("Lab fixture {}: {}", name, action);
+    Ok(())
+}
+
+// Where lab hosts reach the control API; a wildcard bind address is replaced with this host's name
+fn control_api_url(state: &AppState) -> String {
+    let settings = state.settings.lock().unwrap().control_api.clone();
+    let host = match settings.bind_address.as_str() {
+        "0.0.0.0" | "::" | "" => sys_info::hostname().unwrap_or_else(|_| "localhost".to_string()),
+        address => address.to_string(),
+    };
+    let scheme = if settings.tls == "off" { "http" } else { "https" };
+    format!("{}://{}:{}", scheme, host, settings.port)
+}
+
+// YAML double-quoted scalars accept JSON string escapes
+fn yaml_string(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+// Jinja2 single-quoted string for LAVA device dictionaries
+fn jinja_string(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+// Shell command calling a fixture action; the token comes from CFU_API_TOKEN on the lab host
+fn action_command(api_url: &str, certificate: Option<&Path>, fixture: &str, action: &str) -> String {
+    let mut command = "curl -fsS -X POST -H \"Authorization: Bearer ${CFU_API_TOKEN}\"".to_string();
+    if let Some(certificate) = certificate {
+        command.push_str(&format!(" --cacert {}", crate::ssh::shell_quote(&certificate.display().to_string())));
+    }
+    let url = format!("{}/api/fixtures/{}/{}", api_url, fixture, action);
+    command.push_str(&format!(" {}", crate::ssh::shell_quote(&url)));
+    command
+}
+
+fn place_name(fixture: &LabFixtureSettings) -> String {
+    format!("cfu-{}", fixture.name)
+}
+
+fn render_labgrid_exporter(station: &str, fixtures: &[LabFixtureSettings]) -> String {
+    let mut yaml = String::from("# labgrid exporter configuration generated by Cordatus Flash Utility\n");
+    for fixture in fixtures {
+        yaml.push_str(&format!("{}:\n  location: {}\n", place_name(fixture), yaml_string(station)));
+        if !fixture.serial_port.is_empty() {
+            yaml.push_str(&format!(
+                "  RawSerialPort:\n    port: {}\n    speed: {}\n",
+                yaml_string(&fixture.serial_port),
+                fixture.serial_baud
+            ));
+        }
+        if !fixture.ssh_host.is_empty() {
+            yaml.push_str(&format!(
+                "  NetworkService:\n    address: {}\n    username: {}\n",
+                yaml_string(&fixture.ssh_host),
+                yaml_string(&fixture.ssh_user)
+            ));
+        }
+    }
+    yaml
+}
+
+fn render_labgrid_environment(api_url: &str, certificate: Option<&Path>, fixtures: &[LabFixtureSettings]) -> String {
+    let mut yaml = String::from(
+        "# labgrid environment generated by Cordatus Flash Utility\n\
+         # Export CFU_API_TOKEN with a control API token allowing flash access before use.\n\
+         targets:\n",
+    );
+    for fixture in fixtures {
+        let command = |action: &str| yaml_string(&action_command(api_url, certificate, &fixture.name, action));
+        yaml.push_str(&format!("  {}:\n    resources:\n      RemotePlace:\n        name: {}\n    drivers:\n", place_name(fixture), place_name(fixture)));
+        if !fixture.serial_port.is_empty() {
+            yaml.push_str("      SerialDriver: {}\n");
+        }
+        if !fixture.power_on_command.is_empty() && !fixture.power_off_command.is_empty() {
+            yaml.push_str(&format!(
+                "      ExternalPowerDriver:\n        cmd_on: {}\n        cmd_off: {}\n        cmd_cycle: {}\n",
+                command("power_on"),
+                command("power_off"),
+                command("power_cycle")
+            ));
+        }
+        if !fixture.ssh_host.is_empty() {
+            yaml.push_str("      SSHDriver: {}\n");
+        }
+        if crate::recovery::method(fixture).is_some() {
+            // labgrid has no force-recovery driver; strategies can run this through a shell
+            yaml.push_str(&format!("    options:\n      cfu_recovery_command: {}\n", command("recovery")));
+        }
+    }
+    yaml
+}
+
+fn render_lava_device(api_url: &str, certificate: Option<&Path>, fixture: &LabFixtureSettings, device_type: &str) -> String {
+    let command = |action: &str| jinja_string(&action_command(api_url, certificate, &fixture.name, action));
+    let mut dictionary = format!(
+        "{{# LAVA device dictionary for lab fixture {} generated by Cordatus Flash Utility #}}\n\
+         {{% extends '{}.jinja2' %}}\n",
+        fixture.name, device_type
+    );
+    if !fixture.serial_port.is_empty() {
+        let connection = format!("picocom -b {} {}", fixture.serial_baud, fixture.serial_port);
+        dictionary.push_str(&format!("{{% set connection_command = {} %}}\n", jinja_string(&connection)));
+    }
+    if !fixture.power_on_command.is_empty() && !fixture.power_off_command.is_empty() {
+        dictionary.push_str(&format!("{{% set power_on_command = {} %}}\n", command("power_on")));
+        dictionary.push_str(&format!("{{% set power_off_command = {} %}}\n", command("power_off")));
+        dictionary.push_str(&format!("{{% set hard_reset_command = {} %}}\n", command("power_cycle")));
+    }
+    if crate::recovery::method(fixture).is_some() {
+        dictionary.push_str(&format!("{{% set recovery_mode_command = {} %}}\n", command("recovery")));
+        dictionary.push_str(&format!("{{% set recovery_exit_command = {} %}}\n", command("power_cycle")));
+    }
+    if !fixture.ssh_host.is_empty() {
+        dictionary.push_str(&format!("{{% set ssh_host = {} %}}\n", jinja_string(&fixture.ssh_host)));
+        dictionary.push_str(&format!("{{% set ssh_user = {} %}}\n", jinja_string(&fixture.ssh_user)));
+    }
+    dictionary
+}
+
+fn export(app: &tauri::AppHandle, state: &AppState, directory: &Path, device_type: &str) -> Result<LabExport> {
+    let fixtures = state.settings.lock().unwrap().lab_fixtures.clone();
+    if fixtures.is_empty() {
+        return Err(anyhow::anyhow!("No lab fixtures are configured"));
+    }
+    let api_settings = state.settings.lock().unwrap().control_api.clone();
+    if !api_settings.enabled {
+        return Err(anyhow::anyhow!("Enable the control API so lab tooling can drive the fixtures"));
+    }
+    let api_url = control_api_url(state);
+    let station = crate::history::station_name(state).unwrap_or_else(|| "cfu".to_string());
+    std::fs::create_dir_all(directory.join("lava"))
+        .with_context(|| format!("Failed to create {}", directory.display()))?;
+
+    let mut files = Vec::new();
+    let mut write = |path: PathBuf, content: String| -> Result<()> {
+        std::fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+        files.push(path.display().to_string());
+        Ok(())
+    };
+
+    // Clients verify the API's certificate with the copy exported next to the configuration
+    let certificate = match control_api::tls_files(app, &api_settings)? {
+        Some((cert_path, _)) => {
+            let target = directory.join(CERTIFICATE_FILE);
+            let pem = std::fs::read_to_string(&cert_path).with_context(|| format!("Failed to read {}", cert_path.display()))?;
+            write(target.clone(), pem)?;
+            Some(target)
+        }
+        None => None,
+    };
+    let certificate = certificate.as_deref();
+
+    write(directory.join("labgrid-exporter.yaml"), render_labgrid_exporter(&station, &fixtures))?;
+    write(directory.join("labgrid-environment.yaml"), render_labgrid_environment(&api_url, certificate, &fixtures))?;
+    for fixture in &fixtures {
+        let path = directory.join("lava").join(format!("{}.jinja2", place_name(fixture)));
+        write(path, render_lava_device(&api_url, certificate, fixture, device_type))?;
+    }
+    info!("Exported {} lab fixtures to {}", fixtures.len(), directory.display());
+    Ok(LabExport { files, api_url })
+}
+
+// Configured fixtures with the device currently on each
+#[command]
+pub async fn list_lab_fixtures(state: State<'_, Arc<AppState>>) -> Result<Vec<LabFixture>, String> {
+    Ok(list_fixtures(&state))
+}
+
+// All slots with their device, state, running job and recent results in one payload
+#[command]
+pub async fn get_lab_state(state: State<'_, Arc<AppState>>) -> Result<LabState, String> {
+    Ok(lab_state(&state))
+}
+
+// Run a fixture's power or recovery hook
+#[command]
+pub async fn control_lab_fixture(
+    name: String,
+    action: String,
+    state: State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    control_fixture(&state, &name, &action).await.map_err(|e| format!("{:#}", e))
+}
+
+// Write labgrid exporter/environment files and LAVA device dictionaries (extending `device_type`, default
+// "cfu-jetson") for all fixtures into `directory`
+#[command]
+pub async fn export_lab_configuration(
+    directory: String,
+    device_type: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<LabExport, String> {
+    let device_type = device_type.filter(|t| !t.is_empty()).unwrap_or_else(|| "cfu-jetson".to_string());
+    export(&app, &state, Path::new(&directory), &device_type).map_err(|e| format!("{:#}", e))
+}
@@ -0,0 +1,113 @@
+// CFU - Cordatus Flash Utility - Label Printing
+// Renders ZPL labels for flashed units and sends them to network/USB label printers
+
+use crate::settings::LabelPrinterSettings;
+use crate::AppState;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tauri::{command, State};
+use tokio::io::AsyncWriteExt;
+
+const DEFAULT_PRINTER_PORT: u16 = 9100; // Raw/JetDirect port used by ZPL printers
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LabelData {
+    pub flash_id: String,
+    pub serial_number: Option<String>,
+    pub image_version: String,
+    pub flashed_at: DateTime<Utc>,
+    pub report_url: Option<String>,
+}
+
+// ZPL treats ^ and ~ as command prefixes, so they cannot appear in field data
+fn zpl_escape(value: &str) -> String {
+    value.replace(['^', '~'], "-")
+}
+
+// Render a 4x2" label: text on the left, QR code linking to the flash report on the right
+pub fn render_zpl(label: &LabelData) -> String {
+    let identity = match &label.serial_number {
+        Some(serial) => format!("S/N: {}", serial),
+        None => format!("Job: {}", label.flash_id.chars().take(8).collect::<String>()),
+    };
+
+    let mut zpl = String::from("^XA\n^CI28\n^CF0,32\n");
+    zpl.push_str(&format!("^FO30,30^FD{}^FS\n", zpl_escape(&identity)));
+    zpl.push_str(&format!("^FO30,80^FDImage: {}^FS\n", zpl_escape(&label.image_version)));
+    zpl.push_str(&format!("^FO30,130^FDFlashed: {}^FS\n", label.flashed_at.format("%Y-%m-%d %H:%M UTC")));
+    if let Some(url) = &label.report_url {
+        zpl.push_str(&format!("^FO560,20^BQN,2,5^FDQA,{}^FS\n", zpl_escape(url)));
+    }
+    zpl.push_str("^XZ\n");
+    zpl
+}
+
+// Build the report URL for a job from the configured template
+pub fn report_url(printer: &LabelPrinterSettings, flash_id: &str) -> Option<String> {
+    if printer.report_url_template.is_empty() {
+        None
+    } else {
+        Some(printer.report_url_template.replace("{flash_id}", flash_id))
+    }
+}
+
+// The printer address with its port: as given when it has one, else the default port. A bare IPv6
+// address has colons too, so addresses are parsed rather than searched for a colon.
+fn printer_socket_address(address: &str) -> String {
+    if address.parse::<SocketAddr>().is_ok() {
+        return address.to_string();
+    }
+    if let Ok(ip) = address.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        return SocketAddr::new(ip, DEFAULT_PRINTER_PORT).to_string();
+    }
+    match address.rsplit_once(':') {
+        Some((_, port)) if port.parse::<u16>().is_ok() => address.to_string(),
+        _ => format!("{}:{}", address, DEFAULT_PRINTER_PORT),
+    }
+}
+
+// Send a rendered label to the configured printer
+pub async fn send_to_printer(printer: &LabelPrinterSettings, zpl: &str) -> Result<()> {
+    if printer.address.is_empty() {
+        return Err(anyhow::anyhow!("No label printer address configured"));
+    }
+
+    match printer.connection.as_str() {
+        "network" => {
+            let address = printer_socket_address(&printer.address);
+            let mut stream = tokio::net::TcpStream::connect(&address)
+                .await
+                .with_context(|| format!("Failed to connect to label printer at {}", address))?;
+            stream.write_all(zpl.as_bytes()).await.context("Failed to send label")?;
+            stream.shutdown().await.ok();
+        }
+        "usb" => {
+            tokio::fs::write(&printer.address, zpl.as_bytes())
+                .await
+                .with_context(|| format!("Failed to write label to {}", printer.address))?;
+        }
+        other => return Err(anyhow::anyhow!("Unsupported printer connection: {}", other)),
+    }
+
+    info!("Printed label on {} printer {}", printer.connection, printer.address);
+    Ok(())
+}
+
+// Render a label without printing it
+#[command]
+pub async fn preview_device_label(label: LabelData) -> Result<String, String> {
+    Ok(render_zpl(&label))
+}
+
+// Print a label on the configured printer
+#[command]
+pub async fn print_device_label(label: LabelData, state: State<'_, Arc<AppState>>) -> Result<(), String> {
+    let printer = state.settings.lock().unwrap().label_printer.clone();
+    send_to_printer(&printer, &render_zpl(&label))
+        .await
+        .map_err(|e| format!("Label printing failed: {}", e))
+}
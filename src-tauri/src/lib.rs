@@ -0,0 +1,1747 @@
+// CFU - Cordatus Flash Utility - Core Library
+// Real USB detection, flashing process management, and container integration,
+// shared by the Tauri app and the cfu-cli binary
+// Developer: İbrahim Çoban
+
+mod acceptance;
+mod alerts;
+mod analytics;
+mod api_tokens;
+mod asset;
+mod backup;
+mod batch;
+mod benchmarks;
+mod binding;
+mod boot_check;
+mod cache;
+mod catalog;
+mod catalog_publish;
+mod checksum;
+mod chip_id;
+pub mod cli;
+mod connectivity;
+mod containers;
+mod control_api;
+mod crash;
+mod credentials;
+mod delta;
+mod device_matrix;
+mod device_spec;
+mod downloads;
+mod drift;
+mod extract;
+mod failures;
+mod fleet;
+mod fleet_sync;
+mod freeze;
+mod history;
+mod host_env;
+#[cfg(target_os = "linux")]
+mod image;
+#[cfg(unix)]
+mod instance;
+mod io_priority;
+mod job_host;
+mod joblog;
+mod kernel;
+mod lab;
+mod label;
+mod maintenance;
+mod mirrors;
+mod models;
+mod native_flash;
+mod notes;
+mod notifications;
+mod pairing;
+mod partitions;
+mod passport;
+mod peers;
+mod plan;
+mod prefetch;
+mod preflight;
+mod process_tree;
+mod profile_sync;
+mod profiles;
+mod provisioning;
+mod recovery;
+mod release_checksums;
+mod remote;
+mod reproduce;
+mod rootfs_snapshot;
+mod rootfs_sync;
+mod scheduler;
+mod serial;
+mod settings;
+mod skips;
+mod ssh;
+mod telemetry;
+mod thermal;
+mod timeline;
+mod uploads;
+mod usb_watch;
+mod version_matrix;
+mod write_target;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use job_host::JobHost;
+use log::{debug, error, info, warn};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex, OnceLock};
+use tauri::{command, generate_handler, Builder, Emitter, Manager, State};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command as TokioCommand;
+use uuid::Uuid;
+
+// Data structures matching frontend types
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JetsonDevice {
+    pub id: String,
+    pub vendor: String,
+    pub product: String,
+    pub module: String,
+    pub board_id: String,
+    pub is_connected: bool,
+    pub supported_l4t: Vec<String>,
+    pub storage_options: Vec<String>,
+    pub usb_info: Option<UsbDeviceInfo>,
+    #[serde(default)]
+    pub chip_uid: Option<String>, // BR_CID, once read in recovery mode
+    #[serde(default)]
+    pub stable_id: Option<String>, // Survives re-plugging; chip UID or USB serial based
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsbDeviceInfo {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub device_path: String,
+    pub bus_number: u8,
+    pub device_address: u8,
+    pub is_recovery_mode: bool,
+    #[serde(default)]
+    pub port_path: Option<String>, // Physical port, e.g. "1-2.3"; stable across re-enumeration
+    #[serde(default)]
+    pub serial_number: Option<String>, // iSerialNumber, when the device can be opened
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlashProgress {
+    pub stage: String, // 'preparing' | 'downloading' | 'flashing' | 'verifying' | 'booting' | 'complete' | 'error'
+    pub progress: f32,
+    pub message: String,
+    pub details: Option<String>,
+    pub start_time: Option<DateTime<Utc>>,
+    pub estimated_time_remaining: Option<u64>,
+    #[serde(default)]
+    pub bytes_done: Option<u64>, // Set while a transfer with a known byte count runs
+    #[serde(default)]
+    pub bytes_total: Option<u64>,
+    #[serde(default)]
+    pub throughput: Option<f64>, // Bytes/sec over the last progress interval
+    #[serde(default)]
+    pub verified: Option<bool>, // Whether the first boot checks passed, when the job runs them
+    #[serde(default)]
+    pub failure: Option<failures::FlashFailure>, // Known failure the job's output reported, on error
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlashCommand {
+    pub product: String,
+    pub device_module: String,
+    pub jetpack_version: String,
+    pub storage_device: String,
+    pub keep_files: bool,
+    pub user_name: String,
+    #[serde(default)]
+    pub provisioning: Option<provisioning::ProvisioningOptions>,
+    #[serde(default)]
+    pub custom_kernel: Option<kernel::KernelArtifacts>,
+    #[serde(default)]
+    pub pinned_artifacts: Vec<profiles::ArtifactPin>,
+    #[serde(default)]
+    pub operator: Option<String>, // Who started the job, for the history
+    #[serde(default)]
+    pub skip_stages: Vec<String>, // Stages the user knows are unnecessary, see skips::SKIPPABLE_STAGES
+    #[serde(default)]
+    pub wait_for_device_secs: Option<u64>, // Wait this long for a board in recovery mode instead of failing
+    #[serde(default)]
+    pub device_binding: Option<binding::DeviceBinding>, // Only flash this board
+    #[serde(default)]
+    pub delta: bool, // Only rewrite partitions changed since this board's last delta flash, see delta.rs
+    #[serde(default)]
+    pub boot_check: Option<boot_check::BootCheckOptions>, // Verify the first boot before the job counts as complete
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContainerInfo {
+    pub name: String,
+    pub tag: String,
+    pub category: String,
+    pub description: String,
+    pub size: String,
+    pub supported_devices: Vec<String>,
+    pub is_installed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemInfo {
+    pub os: String,
+    pub architecture: String,
+    pub total_memory: u64,
+    pub available_space: u64,
+    pub docker_installed: bool,
+    pub nvidia_docker_installed: bool,
+    pub jetpack_version: Option<String>,
+    pub cpu_model: Option<String>,
+    pub cpu_cores: u32,
+    pub nvidia_driver_version: Option<String>,
+    pub host_gpus: Vec<HostGpuInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostGpuInfo {
+    pub name: String,
+    pub driver_version: String,
+    pub memory_total_mb: Option<u64>,
+}
+
+// Application state
+#[derive(Debug)]
+pub struct AppState {
+    pub connected_devices: Arc<Mutex<HashMap<String, JetsonDevice>>>,
+    pub flash_progress: Arc<Mutex<HashMap<String, FlashProgress>>>,
+    pub active_flashes: Arc<Mutex<HashMap<String, tokio::process::Child>>>,
+    pub cancelled_flashes: Mutex<HashSet<String>>, // Hard-cancelled jobs, set before their process is stopped
+    pub asset_batches: Arc<Mutex<asset::AssetDb>>,
+    pub settings: Arc<Mutex<settings::AppSettings>>,
+    pub version_matrix: Arc<Mutex<version_matrix::VersionMatrix>>,
+    pub download_bandwidth: Arc<Mutex<Option<f64>>>, // Last measured bytes/sec
+    pub maintenance_sessions: Arc<Mutex<HashMap<String, maintenance::MaintenanceSession>>>,
+    pub history: Arc<Mutex<history::HistoryDb>>,
+    pub scheduled_jobs: Arc<Mutex<Vec<scheduler::ScheduledJob>>>,
+    pub usb_scan_cache: cache::TtlCache<Vec<JetsonDevice>>,
+    pub system_info_cache: cache::TtlCache<SystemInfo>,
+    pub device_matrix: Mutex<Option<Arc<device_matrix::DeviceMatrix>>>,
+    pub job_output: Arc<Mutex<HashMap<String, joblog::JobOutput>>>,
+    pub job_timelines: Arc<Mutex<HashMap<String, timeline::TimelineWriter>>>,
+    pub peer_daemon: peers::PeerDaemon,
+    pub device_arrivals: tokio::sync::Notify, // Woken by the USB watcher when a device connects
+    pub chip_uids: Mutex<HashMap<String, String>>, // Chip UID by USB device path, i.e. per enumeration
+    pub serial_consoles: Mutex<HashMap<String, serial::SerialConsole>>,
+    pub job_followers: Mutex<HashMap<String, tokio::sync::mpsc::UnboundedSender<(String, String)>>>, // See joblog::follow
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        Self {
+            connected_devices: Arc::new(Mutex::new(HashMap::new())),
+            flash_progress: Arc::new(Mutex::new(HashMap::new())),
+            active_flashes: Arc::new(Mutex::new(HashMap::new())),
+            cancelled_flashes: Mutex::new(HashSet::new()),
+            asset_batches: Arc::new(Mutex::new(asset::AssetDb::default())),
+            settings: Arc::new(Mutex::new(settings::AppSettings::default())),
+            version_matrix: Arc::new(Mutex::new(version_matrix::VersionMatrix::bundled())),
+            download_bandwidth: Arc::new(Mutex::new(None)),
+            maintenance_sessions: Arc::new(Mutex::new(HashMap::new())),
+            history: Arc::new(Mutex::new(history::HistoryDb::default())),
+            scheduled_jobs: Arc::new(Mutex::new(Vec::new())),
+            usb_scan_cache: cache::TtlCache::default(),
+            system_info_cache: cache::TtlCache::default(),
+            device_matrix: Mutex::new(None),
+            job_output: Arc::new(Mutex::new(HashMap::new())),
+            job_timelines: Arc::new(Mutex::new(HashMap::new())),
+            peer_daemon: peers::PeerDaemon::default(),
+            device_arrivals: tokio::sync::Notify::new(),
+            chip_uids: Mutex::new(HashMap::new()),
+            serial_consoles: Mutex::new(HashMap::new()),
+            job_followers: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+// Load CSV data from bundled resources
+#[command]
+async fn load_csv_data(app: tauri::AppHandle) -> Result<String, String> {
+    let content = device_matrix::matrix_path(&app)
+        .and_then(|path| std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display())));
+    match content {
+        Ok(content) => {
+            info!("Loaded CSV data: {} bytes", content.len());
+            Ok(content)
+        }
+        Err(e) => {
+            error!("Failed to load CSV data: {}", e);
+            Err(format!("Could not load device configuration data: {}", e))
+        }
+    }
+}
+
+// USB Device Detection, rescanning at most once per configured interval
+#[command]
+async fn detect_usb_devices(state: State<'_, Arc<AppState>>) -> Result<Vec<JetsonDevice>, String> {
+    let ttl = state.settings.lock().unwrap().command_cache.usb_detection_ttl();
+    state.usb_scan_cache.get_or_refresh(ttl, || scan_usb_devices(&state)).await
+}
+
+async fn scan_usb_devices(state: &AppState) -> Result<Vec<JetsonDevice>, String> {
+    info!("Starting USB device detection...");
+    
+    let mut devices = Vec::new();
+    let jetson_vendor_id = 0x0955; // NVIDIA vendor ID
+    
+    // Known Jetson device product IDs
+    let jetson_products = vec![
+        (0x7c18, "AGX Orin", "AGX Orin"),
+        (0x7e19, "Orin NX", "Orin NX"), 
+        (0x7f21, "Orin Nano", "Orin Nano"),
+        (0x7019, "AGX Xavier", "AGX Xavier"),
+        (0x7e19, "Xavier NX", "Xavier NX"),
+        (0x7f21, "Nano", "Nano - 4GB"),
+    ];
+    
+    match rusb::devices() {
+        Ok(device_list) => {
+            for device in device_list.iter() {
+                if let Ok(device_desc) = device.device_descriptor() {
+                    if device_desc.vendor_id() == jetson_vendor_id {
+                        // Found a potential Jetson device
+                        if let Some((_, product, module)) = jetson_products.iter()
+                            .find(|(pid, _, _)| *pid == device_desc.product_id()) {
+                            
+                            let bus_number = device.bus_number();
+                            let device_address = device.address();
+                            let device_path = format!("/dev/bus/usb/{:03}/{:03}", bus_number, device_address);
+                            
+                            // Check if device is in recovery mode
+                            let is_recovery_mode = check_recovery_mode(&device).unwrap_or(false);
+                            
+                            let usb_info = UsbDeviceInfo {
+                                vendor_id: device_desc.vendor_id(),
+                                product_id: device_desc.product_id(),
+                                device_path: device_path.clone(),
+                                bus_number,
+                                device_address,
+                                is_recovery_mode,
+                                port_path: device.port_numbers().ok().filter(|ports| !ports.is_empty()).map(|ports| {
+                                    let ports: Vec<String> = ports.iter().map(|p| p.to_string()).collect();
+                                    format!("{}-{}", bus_number, ports.join("."))
+                                }),
+                                serial_number: read_serial_number(&device, &device_desc),
+                            };
+                            
+                            let mut jetson_device = JetsonDevice {
+                                id: format!("jetson-{:04x}-{:03}-{:03}", device_desc.product_id(), bus_number, device_address),
+                                vendor: "NVIDIA".to_string(),
+                                product: product.to_string(),
+                                module: module.to_string(),
+                                board_id: get_board_id_from_module(module),
+                                is_connected: true,
+                                supported_l4t: get_supported_l4t_versions(module),
+                                storage_options: get_storage_options(module),
+                                usb_info: Some(usb_info),
+                                chip_uid: None,
+                                stable_id: None,
+                            };
+                            chip_id::annotate(state, &mut jetson_device);
+                            
+                            devices.push(jetson_device);
+                            info!("Found Jetson device: {} {} (Recovery: {})", product, module, is_recovery_mode);
+                        }
+                    }
+                }
+            }
+        }
+        Err(e) => {
+            error!("Failed to enumerate USB devices: {}", e);
+            // Inside a sandbox the real cause is a missing permission, say which one
+            let sandbox = host_env::detect_sandbox_info();
+            if sandbox.is_restricted() {
+                return Err(format!("USB enumeration failed: {}", sandbox.describe()));
+            }
+            return Err(format!("USB enumeration failed: {}", e));
+        }
+    }
+    
+    if devices.is_empty() {
+        let sandbox = host_env::detect_sandbox_info();
+        if sandbox.is_restricted() {
+            warn!("No Jetson devices visible, likely due to sandboxing: {}", sandbox.describe());
+        }
+    }
+    
+    // Update state
+    {
+        let mut connected_devices = state.connected_devices.lock().unwrap();
+        connected_devices.clear();
+        for device in &devices {
+            connected_devices.insert(device.id.clone(), device.clone());
+        }
+    }
+    
+    info!("Found {} Jetson devices", devices.len());
+    Ok(devices)
+}
+
+// Check if device is in recovery mode
+fn check_recovery_mode(device: &rusb::Device<rusb::GlobalContext>) -> Result<bool> {
+    // In recovery mode, Jetson devices typically have specific interface configurations
+    // This is a simplified check - more sophisticated detection could be implemented
+    if let Ok(config_desc) = device.active_config_descriptor() {
+        // Recovery mode devices typically have a single interface with specific characteristics
+        if config_desc.num_interfaces() == 1 {
+            if let Some(interface) = config_desc.interfaces().next() {
+                if let Some(interface_desc) = interface.descriptors().next() {
+                    // Check for recovery mode interface characteristics
+                    return Ok(interface_desc.class_code() == 0xFF && 
+                             interface_desc.sub_class_code() == 0x00);
+                }
+            }
+        }
+    }
+    Ok(false)
+}
+
+fn read_serial_number(device: &rusb::Device<rusb::GlobalContext>, device_desc: &rusb::DeviceDescriptor) -> Option<String> {
+    device_desc.serial_number_string_index()?;
+    let handle = device.open().ok()?;
+    handle.read_serial_number_string_ascii(device_desc).ok().filter(|serial| !serial.is_empty())
+}
+
+// Get board ID mapping for modules
+fn get_board_id_from_module(module: &str) -> String {
+    match module {
+        "AGX Orin" => "3701-0000".to_string(),
+        "Orin NX" => "3767-0000".to_string(),
+        "Orin Nano" => "3767-0003".to_string(),
+        "AGX Xavier" => "2888-0001".to_string(),
+        "Xavier NX" => "3668-0000".to_string(),
+        "Nano - 4GB" => "3448-0002".to_string(),
+        _ => "0000-0000".to_string(),
+    }
+}
+
+// Get supported L4T versions for modules
+fn get_supported_l4t_versions(module: &str) -> Vec<String> {
+    match module {
+        "AGX Orin" | "Orin NX" | "Orin Nano" => vec![
+            "36.4.4".to_string(), "36.4.3".to_string(), "36.4.0".to_string(),
+            "36.3.0".to_string(), "36.2.0".to_string(), "35.5.0".to_string(),
+            "35.4.1".to_string(), "35.3.1".to_string(), "35.2.1".to_string(),
+        ],
+        "AGX Xavier" | "Xavier NX" => vec![
+            "35.5.0".to_string(), "35.4.1".to_string(), "35.3.1".to_string(),
+            "35.2.1".to_string(), "32.7.5".to_string(), "32.7.4".to_string(),
+            "32.7.3".to_string(), "32.7.2".to_string(), "32.7.1".to_string(),
+        ],
+        "Nano - 4GB" => vec![
+            "32.7.5".to_string(), "32.7.4".to_string(), "32.7.3".to_string(),
+            "32.7.2".to_string(), "32.7.1".to_string(),
+        ],
+        _ => vec![],
+    }
+}
+
+// Get storage options for modules
+fn get_storage_options(module: &str) -> Vec<String> {
+    match module {
+        "AGX Orin" | "Orin NX" | "AGX Xavier" | "Xavier NX" => vec![
+            "nvme".to_string(), "sd".to_string(), "emmc".to_string(),
+        ],
+        "Orin Nano" => vec![
+            "nvme".to_string(), "sd".to_string(),
+        ],
+        "Nano - 4GB" => vec![
+            "sd".to_string(),
+        ],
+        _ => vec!["sd".to_string()],
+    }
+}
+
+// Real flashing process
+#[command]
+async fn start_flash_process(
+    command: FlashCommand,
+    state: State<'_, Arc<AppState>>,
+    window: tauri::Window,
+) -> Result<String, String> {
+    launch_flash(command, Arc::clone(state.inner()), window.app_handle().clone()).await
+}
+
+// Validate a flash command and run it in the background, returning its ID.
+// Also used where no window is involved, e.g. scheduled jobs.
+pub async fn launch_flash(
+    command: FlashCommand,
+    state: Arc<AppState>,
+    app: tauri::AppHandle,
+) -> Result<String, String> {
+    let (flash_id, _) = begin_flash(&app, &state, &command)?;
+    tokio::spawn(run_flash(command, flash_id.clone(), state, app));
+    Ok(flash_id)
+}
+
+// Validate a flash command and record it as a running job; returns its ID and the compatibility report.
+// The GUI and cfu-cli both start jobs here, then run them with run_flash.
+pub fn begin_flash<H: JobHost>(
+    app: &H,
+    state: &AppState,
+    command: &FlashCommand,
+) -> Result<(String, version_matrix::CompatibilityReport), String> {
+    let flash_id = Uuid::new_v4().to_string();
+    info!("Starting flash process with ID: {}", flash_id);
+    
+    let compatibility = validate_flash(app, state, command)?;
+    for warning in &compatibility.warnings {
+        warn!("Flash {}: {}", flash_id, warning);
+    }
+    
+    // Initialize progress
+    let progress = FlashProgress {
+        stage: "preparing".to_string(),
+        progress: 0.0,
+        message: "Preparing flash process...".to_string(),
+        details: None,
+        start_time: Some(Utc::now()),
+        estimated_time_remaining: None,
+        bytes_done: None,
+        bytes_total: None,
+        throughput: None,
+        verified: None,
+        failure: None,
+    };
+    
+    {
+        let mut flash_progress = state.flash_progress.lock().unwrap();
+        flash_progress.insert(flash_id.clone(), progress);
+    }
+    
+    // Emit initial progress
+    app.send("flash-progress", &flash_id).map_err(|e| e.to_string())?;
+    
+    if !compatibility.warnings.is_empty() {
+        app.send("flash-compatibility-warning", serde_json::json!({
+            "flash_id": flash_id,
+            "warnings": compatibility.warnings
+        })).map_err(|e| e.to_string())?;
+    }
+    
+    history::record_started(app, state, &flash_id, command);
+    timeline::open(app, state, &flash_id);
+    timeline::record(state, &flash_id, "preparing", 0.0);
+    Ok((flash_id, compatibility))
+}
+
+// Run a started job to its end and record how it ended
+pub async fn run_flash<H: JobHost>(command: FlashCommand, flash_id: String, state: Arc<AppState>, app: H) {
+    match execute_flash_process(command, flash_id.clone(), Arc::clone(&state), app.clone()).await {
+        Ok(_) => {
+            info!("Flash process completed successfully: {}", flash_id);
+            history::record_finished(&app, &state, &flash_id, "success", None);
+        }
+        Err(e) if e.is::<SoftCancelled>() => {
+            info!("Flash process stopped by soft cancel: {}", flash_id);
+            history::record_cancelled(&app, &state, &flash_id, "soft");
+            state.flash_progress.lock().unwrap().remove(&flash_id);
+        }
+        // Whatever the job failed with once it was cancelled, e.g. its process going away, is the cancel
+        Err(e) if e.is::<HardCancelled>() || is_hard_cancelled(&state, &flash_id) => {
+            info!("Flash process stopped by hard cancel: {} ({})", flash_id, e);
+            history::record_cancelled(&app, &state, &flash_id, "hard");
+            state.flash_progress.lock().unwrap().remove(&flash_id);
+        }
+        Err(e) => {
+            error!("Flash process failed: {} - {}", flash_id, e);
+            history::record_finished(&app, &state, &flash_id, "failed", Some(e.to_string()));
+            
+            // Update progress with error, typed when the output showed why
+            let failure = joblog::failure(&state, &flash_id);
+            let details = match &failure {
+                Some(failure) => format!("{}: {} ({})", failure.code, failure.summary, e),
+                None => e.to_string(),
+            };
+            let error_progress = FlashProgress {
+                stage: "error".to_string(),
+                progress: 0.0,
+                message: "Flash process failed".to_string(),
+                details: Some(details),
+                start_time: None,
+                estimated_time_remaining: None,
+                bytes_done: None,
+                bytes_total: None,
+                throughput: None,
+                verified: None,
+                failure,
+            };
+            
+            if let Ok(mut flash_progress) = state.flash_progress.lock() {
+                flash_progress.insert(flash_id.clone(), error_progress);
+            }
+        }
+    }
+    state.cancelled_flashes.lock().unwrap().remove(&flash_id);
+    joblog::close(&state, &flash_id);
+    timeline::close(&state, &flash_id);
+    notifications::notify_job_finished(&state, &flash_id).await;
+    alerts::check_station_failure_rate(&app, &state, &flash_id).await;
+    telemetry::report_job(&app, &state, &flash_id).await;
+    uploads::upload_finished_job(&app, &state, &flash_id).await;
+}
+
+// Checks a flash must pass before it starts; returns the compatibility report with its warnings
+pub fn validate_flash<H: JobHost>(
+    app: &H,
+    state: &AppState,
+    command: &FlashCommand,
+) -> Result<version_matrix::CompatibilityReport, String> {
+    // Refuse combinations the version matrix marks as impossible
+    let mut compatibility = {
+        let host = version_matrix::host_ubuntu_version();
+        let matrix = state.version_matrix.lock().unwrap();
+        matrix.check(&command.device_module, &command.jetpack_version, host.as_deref())
+    };
+    if compatibility.is_blocked() {
+        return Err(format!("Unsupported configuration: {}", compatibility.blocks.join("; ")));
+    }
+    if let Some(options) = &command.provisioning {
+        options.validate().map_err(|e| format!("Invalid provisioning options: {}", e))?;
+    }
+    if let Some(artifacts) = &command.custom_kernel {
+        kernel::validate_artifacts(artifacts).map_err(|e| e.to_string())?;
+    }
+    if let Some(target) = command.boot_check.as_ref().and_then(|options| options.ssh.as_ref()) {
+        target.validate().map_err(|e| format!("Invalid boot check target: {}", e))?;
+    }
+    profiles::verify_overlay_pins(&command.pinned_artifacts).map_err(|e| e.to_string())?;
+    skips::validate(command).map_err(|e| format!("Invalid stage skips: {}", e))?;
+    delta::validate(command).map_err(|e| e.to_string())?;
+    if let Some(binding) = &command.device_binding {
+        binding.validate()?;
+    }
+    freeze::check_flash(app, command)?;
+    // Flashing from a VM fails in well-known ways, warn up front
+    compatibility.warnings.extend(host_env::detect_virtualization_info().warnings);
+    Ok(compatibility)
+}
+
+// The flash script invocation for a job, writing the rootfs hook and pins files it refers to.
+// Returns the command and the manifest the script records the used archives in.
+pub async fn flash_script_command<H: JobHost>(
+    app: &H,
+    state: &AppState,
+    flash_id: &str,
+    command: &FlashCommand,
+) -> Result<(TokioCommand, std::path::PathBuf)> {
+    let script_path = get_script_path().await.map_err(|e| anyhow::anyhow!(e))?;
+    let working_dir = get_working_directory().await.map_err(|e| anyhow::anyhow!(e))?;
+    
+    let mut cmd = TokioCommand::new("bash");
+    cmd.arg(&script_path)
+       .arg(&command.product)
+       .arg(&command.device_module)
+       .arg(&command.jetpack_version)
+       .arg(&command.storage_device)
+       .arg(if command.keep_files { "true" } else { "false" })
+       .arg(&command.user_name)
+       .current_dir(&working_dir)
+       .stdout(Stdio::piped())
+       .stderr(Stdio::piped());
+    
+    // Provisioning options and a custom kernel are applied to the rootfs by the script right before flashing
+    let has_provisioning = command.provisioning.as_ref().is_some_and(|o| !o.is_empty());
+    if has_provisioning || command.custom_kernel.is_some() {
+        let hook = provisioning::write_rootfs_hook(app, flash_id, command)?;
+        cmd.env("CFU_ROOTFS_HOOK", hook);
+    }
+    
+    // The script records the checksums of the archives it used and verifies pinned ones
+    let manifest = app_data_file(app, &format!("artifacts_{}.txt", flash_id))?;
+    cmd.env("CFU_ARTIFACT_MANIFEST", &manifest);
+    if let Some(pins) = profiles::write_pins_file(app, flash_id, &command.pinned_artifacts)? {
+        cmd.env("CFU_ARTIFACT_PINS", pins);
+    }
+    
+    cmd.env("CFU_SOFT_CANCEL_FILE", soft_cancel_file(app, flash_id)?);
+    if !command.skip_stages.is_empty() {
+        cmd.env("CFU_SKIP_STAGES", skips::env_value(command));
+    }
+    // Archives the script downloads come from the site's mirrors first
+    let mirror_urls = mirrors::env_value(&state.settings.lock().unwrap().mirrors);
+    if !mirror_urls.is_empty() {
+        cmd.env("CFU_MIRRORS", mirror_urls);
+    }
+    match release_checksums::prepare(app, state).await {
+        Ok(checksums) => {
+            cmd.env("CFU_PUBLISHED_CHECKSUMS", checksums);
+        }
+        Err(e) => warn!("Downloads are not checked against published checksums: {}", e),
+    }
+    
+    // Archives are extracted by this binary, with progress and cancellation
+    if let Ok(exe) = std::env::current_exe() {
+        let cancel = extract::cancel_file(app, flash_id)?;
+        cmd.env("CFU_EXTRACTOR", exe).env("CFU_CANCEL_FILE", cancel);
+    }
+    Ok((cmd, manifest))
+}
+
+// Exit code of the flash script when a soft cancel stopped it between steps
+const SOFT_CANCEL_EXIT_CODE: i32 = 75;
+
+// Flag file asking the flash script to stop at its next checkpoint
+fn soft_cancel_file(app: &impl JobHost, flash_id: &str) -> Result<std::path::PathBuf> {
+    app_data_file(app, &format!("soft_cancel_{}", flash_id))
+}
+
+#[derive(Debug)]
+struct SoftCancelled;
+
+impl std::fmt::Display for SoftCancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Stopped by a soft cancel before the next destructive step")
+    }
+}
+
+impl std::error::Error for SoftCancelled {}
+
+#[derive(Debug)]
+pub(crate) struct HardCancelled;
+
+impl std::fmt::Display for HardCancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Flash process was cancelled")
+    }
+}
+
+impl std::error::Error for HardCancelled {}
+
+pub(crate) fn is_hard_cancelled(state: &AppState, flash_id: &str) -> bool {
+    state.cancelled_flashes.lock().unwrap().contains(flash_id)
+}
+
+// Execute the actual flashing process
+async fn execute_flash_process<H: JobHost>(
+    command: FlashCommand,
+    flash_id: String,
+    state: Arc<AppState>,
+    app: H,
+) -> Result<()> {
+    if let Some(timeout_secs) = command.wait_for_device_secs {
+        wait_for_recovery_device(&state, &app, &flash_id, &command, timeout_secs).await?;
+    }
+    if let Some(binding) = &command.device_binding {
+        binding::enforce(&app, &state, &flash_id, binding).await?;
+    }
+    
+    // Use the measured bandwidth from the last connectivity check when available
+    let download_eta = state.download_bandwidth.lock().unwrap()
+        .and_then(|rate| connectivity::download_eta_seconds(connectivity::TYPICAL_JETPACK_DOWNLOAD_BYTES, rate))
+        .unwrap_or(300); // 5 minutes estimated
+    
+    // Update progress: downloading
+    update_flash_progress(&state, &app, &flash_id, FlashProgress {
+        stage: "downloading".to_string(),
+        progress: 10.0,
+        message: "Downloading JetPack files...".to_string(),
+        details: Some(format!("Downloading {} for {}", command.jetpack_version, command.device_module)),
+        start_time: None,
+        estimated_time_remaining: Some(download_eta),
+        bytes_done: None,
+        bytes_total: None,
+        throughput: None,
+        verified: None,
+        failure: None,
+    }).await?;
+    
+    // Archives another station already downloaded are copied over the LAN first
+    peers::seed_artifacts(&app, &state, &flash_id, &command).await?;
+    
+    if native_flash::selected(&state, &command).await {
+        if let Ok(cancel) = extract::cancel_file(&app, &flash_id) {
+            std::fs::remove_file(cancel).ok();
+        }
+        if let Ok(soft_cancel) = soft_cancel_file(&app, &flash_id) {
+            std::fs::remove_file(soft_cancel).ok();
+        }
+        let result = native_flash::run(&app, &state, &flash_id, &command).await;
+        if let Ok(cancel) = extract::cancel_file(&app, &flash_id) {
+            std::fs::remove_file(cancel).ok();
+        }
+        if let Ok(soft_cancel) = soft_cancel_file(&app, &flash_id) {
+            std::fs::remove_file(soft_cancel).ok();
+        }
+        return complete_flash(&state, &app, &flash_id, &command, result?).await;
+    }
+    
+    // Prepare flash command with proper paths
+    let (mut cmd, manifest) = flash_script_command(&app, &state, &flash_id, &command).await?;
+    if let Ok(cancel) = extract::cancel_file(&app, &flash_id) {
+        std::fs::remove_file(cancel).ok();
+    }
+    if let Ok(soft_cancel) = soft_cancel_file(&app, &flash_id) {
+        std::fs::remove_file(soft_cancel).ok();
+    }
+    io_priority::apply(&state, &mut cmd);
+    process_tree::new_session(&mut cmd);
+    
+    info!("Executing flash command: {:?}", cmd);
+    
+    let mut child = cmd.spawn().context("Failed to start flash process")?;
+    joblog::open(&app, &state, &flash_id);
+    
+    // Take stdout and stderr before storing the child
+    let stdout = child.stdout.take();
+    if let Some(stderr) = child.stderr.take() {
+        let state = Arc::clone(&state);
+        let app = app.clone();
+        let flash_id = flash_id.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            let mut meter = TransferMeter::default();
+            while let Ok(Some(line)) = lines.next_line().await {
+                joblog::append_stream(&state, &flash_id, "stderr", &line);
+                // The script downloads with wget, which reports on stderr
+                if let Some(bytes_done) = meter.update(&line) {
+                    timeline::record_bytes(&state, &flash_id, bytes_done);
+                    update_transfer_progress(&state, &app, &flash_id, &meter);
+                }
+            }
+        });
+    }
+    
+    // Store the child process
+    {
+        let mut active_flashes = state.active_flashes.lock().unwrap();
+        active_flashes.insert(flash_id.clone(), child);
+    }
+    
+    // Read stdout and stderr for progress updates
+    if let Some(stdout) = stdout {
+        let reader = BufReader::new(stdout);
+        let mut lines = reader.lines();
+        
+        while let Ok(Some(line)) = lines.next_line().await {
+            debug!("Flash output: {}", line);
+            joblog::append(&state, &flash_id, &line);
+            
+            // Parse progress from output
+            if let Some(progress_info) = parse_flash_output(&line) {
+                update_flash_progress(&state, &app, &flash_id, progress_info).await?;
+            }
+        }
+    }
+    
+    // Retrieve and wait for process completion; without a child a hard cancel has taken it to stop it
+    let child = state.active_flashes.lock().unwrap().remove(&flash_id);
+    let mut child = match child {
+        Some(mut child) if is_hard_cancelled(&state, &flash_id) => {
+            process_tree::terminate(&mut child).await;
+            return Err(HardCancelled.into());
+        }
+        Some(child) => child,
+        None => return Err(HardCancelled.into()),
+    };
+    
+    let output = child.wait().await.context("Flash process failed")?;
+    if is_hard_cancelled(&state, &flash_id) {
+        return Err(HardCancelled.into());
+    }
+    
+    if let Ok(cancel) = extract::cancel_file(&app, &flash_id) {
+        std::fs::remove_file(cancel).ok();
+    }
+    let soft_cancelled = soft_cancel_file(&app, &flash_id).is_ok_and(|file| std::fs::remove_file(file).is_ok());
+    if soft_cancelled && output.code() == Some(SOFT_CANCEL_EXIT_CODE) {
+        return Err(SoftCancelled.into());
+    }
+    
+    if output.success() {
+        let artifacts = profiles::read_manifest(&manifest);
+        std::fs::remove_file(&manifest).ok();
+        complete_flash(&state, &app, &flash_id, &command, artifacts).await?;
+    } else {
+        return Err(anyhow::anyhow!("Flash process exited with error code: {}", output.code().unwrap_or(-1)));
+    }
+    
+    // Clean up
+    {
+        let mut active_flashes = state.active_flashes.lock().unwrap();
+        active_flashes.remove(&flash_id);
+    }
+    
+    Ok(())
+}
+
+// Hold a job in the waiting-for-device stage until a board in recovery mode is connected;
+// it can be cancelled while waiting like at any other stage
+async fn wait_for_recovery_device(
+    state: &Arc<AppState>,
+    app: &impl JobHost,
+    flash_id: &str,
+    command: &FlashCommand,
+    timeout_secs: u64,
+) -> Result<()> {
+    // A bound job waits for its own board, not any board
+    let wanted = |device: &JetsonDevice| command.device_binding.as_ref().is_none_or(|b| b.matches_location(device));
+    if scan_usb_devices(state).await.is_ok_and(|devices| devices.iter().any(|d| usb_watch::in_recovery(d) && wanted(d))) {
+        return Ok(());
+    }
+    update_flash_progress(state, app, flash_id, FlashProgress {
+        stage: "waiting-for-device".to_string(),
+        progress: 0.0,
+        message: "Waiting for a device in recovery mode...".to_string(),
+        details: Some("The flash starts automatically when the board appears".to_string()),
+        start_time: None,
+        estimated_time_remaining: Some(timeout_secs),
+        bytes_done: None,
+        bytes_total: None,
+        throughput: None,
+        verified: None,
+        failure: None,
+    }).await?;
+    
+    let cancel = extract::cancel_file(app, flash_id)?;
+    let soft_cancel = soft_cancel_file(app, flash_id)?;
+    let timeout = std::time::Duration::from_secs(timeout_secs);
+    let found = usb_watch::wait_for_device(state, timeout, wanted, || cancel.exists() || soft_cancel.exists()).await;
+    if std::fs::remove_file(&soft_cancel).is_ok() {
+        return Err(SoftCancelled.into());
+    }
+    if std::fs::remove_file(&cancel).is_ok() {
+        return Err(HardCancelled.into());
+    }
+    if !found {
+        return Err(anyhow::anyhow!("Cannot find a force recovery device, none appeared within {}s", timeout_secs));
+    }
+    info!("Recovery device appeared, starting flash {}", flash_id);
+    Ok(())
+}
+
+// Mark a flash complete and record the archives it used, for the script and the native pipeline alike
+async fn complete_flash(
+    state: &Arc<AppState>,
+    app: &impl JobHost,
+    flash_id: &str,
+    command: &FlashCommand,
+    mut artifacts: Vec<profiles::ArtifactPin>,
+) -> Result<()> {
+    let verified = match &command.boot_check {
+        Some(options) => Some(verify_first_boot(state, app, flash_id, command, options).await?),
+        None => None,
+    };
+    update_flash_progress(state, app, flash_id, FlashProgress {
+        stage: "complete".to_string(),
+        progress: 100.0,
+        message: "Flash process completed successfully!".to_string(),
+        details: Some(if verified.is_some() { "Device booted and passed the first boot checks" } else { "Device is ready to use" }.to_string()),
+        start_time: None,
+        estimated_time_remaining: None,
+        bytes_done: None,
+        bytes_total: None,
+        throughput: None,
+        verified,
+        failure: None,
+    }).await?;
+    
+    // Before the label, which shows the serial the capture links to the job
+    asset::capture_flashed(app, state, flash_id, command).await;
+    print_completion_label(state, flash_id, command).await;
+    
+    if let Some(kernel) = &command.custom_kernel {
+        artifacts.extend(profiles::overlay_pins(kernel).unwrap_or_default());
+    }
+    history::record_artifacts(app, state, flash_id, artifacts);
+    Ok(())
+}
+
+// Wait for the flashed board to boot and check it; failed checks fail the job
+async fn verify_first_boot(
+    state: &Arc<AppState>,
+    app: &impl JobHost,
+    flash_id: &str,
+    command: &FlashCommand,
+    options: &boot_check::BootCheckOptions,
+) -> Result<bool> {
+    update_flash_progress(state, app, flash_id, FlashProgress {
+        stage: "booting".to_string(),
+        progress: 99.0,
+        message: "Waiting for the first boot...".to_string(),
+        details: None,
+        start_time: None,
+        estimated_time_remaining: None,
+        bytes_done: None,
+        bytes_total: None,
+        throughput: None,
+        verified: None,
+        failure: None,
+    }).await?;
+    let verification = boot_check::verify(app, state, flash_id, command, options)
+        .await
+        .context("First boot verification failed")?;
+    if !verification.verified {
+        let failed: Vec<&str> = verification.checks.iter().filter(|c| !c.passed).map(|c| c.name.as_str()).collect();
+        return Err(anyhow::anyhow!("The flashed board failed the first boot checks: {}", failed.join(", ")));
+    }
+    Ok(true)
+}
+
+// Print a label for the flashed unit if enabled in settings
+async fn print_completion_label(state: &Arc<AppState>, flash_id: &str, command: &FlashCommand) {
+    let printer = state.settings.lock().unwrap().label_printer.clone();
+    if !printer.enabled || !printer.print_on_success {
+        return;
+    }
+    
+    // The serial the unit was identified by (see asset.rs), else the ID of its chip
+    let serial_number = state
+        .history
+        .lock()
+        .unwrap()
+        .jobs
+        .iter()
+        .rev()
+        .find(|job| job.flash_id == flash_id)
+        .and_then(|job| job.serial_number.clone().or_else(|| job.board_id.clone()));
+    let label = label::LabelData {
+        flash_id: flash_id.to_string(),
+        serial_number,
+        image_version: format!("{} {}", command.device_module, command.jetpack_version),
+        flashed_at: Utc::now(),
+        report_url: label::report_url(&printer, flash_id),
+    };
+    
+    // A failed print must not turn a successful flash into an error
+    if let Err(e) = label::send_to_printer(&printer, &label::render_zpl(&label)).await {
+        warn!("Failed to print label for {}: {}", flash_id, e);
+    }
+}
+
+// Patterns of the flash output, compiled once since every line of a flash log goes through them
+struct OutputPatterns {
+    download: Regex,
+    flash: Regex,
+    verify: Regex,
+    extract: Regex,
+    checksum: Regex,
+    length: Regex,   // wget starting a file
+    transfer: Regex, // wget progress
+}
+
+fn output_patterns() -> Option<&'static OutputPatterns> {
+    static PATTERNS: OnceLock<Option<OutputPatterns>> = OnceLock::new();
+    PATTERNS
+        .get_or_init(|| {
+            Some(OutputPatterns {
+                download: Regex::new(r"Downloading.*?(\d+)%").ok()?,
+                flash: Regex::new(r"Flashing.*?(\d+)%").ok()?,
+                verify: Regex::new(r"Verifying.*?(\d+)%").ok()?,
+                extract: Regex::new(r"^Extracting (.+): (\d+)%").ok()?,
+                checksum: Regex::new(r"^Checking download (.+) against published checksums").ok()?,
+                length: Regex::new(r"^Length: (\d+)").ok()?,
+                transfer: Regex::new(r"^\s*(\d+)K[ .]+\d+%").ok()?,
+            })
+        })
+        .as_ref()
+}
+
+// Parse flash output for progress information
+fn parse_flash_output(line: &str) -> Option<FlashProgress> {
+    let patterns = output_patterns()?;
+    
+    if let Some(caps) = patterns.download.captures(line) {
+        if let Ok(progress) = caps[1].parse::<f32>() {
+            return Some(FlashProgress {
+                stage: "downloading".to_string(),
+                progress: progress * 0.3, // Downloading is 0-30%
+                message: line.to_string(),
+                details: None,
+                start_time: None,
+                estimated_time_remaining: Some(((100.0 - progress) * 2.0) as u64), // Rough estimate
+                bytes_done: None,
+                bytes_total: None,
+                throughput: None,
+                verified: None,
+                failure: None,
+            });
+        }
+    }
+    
+    if let Some(caps) = patterns.checksum.captures(line) {
+        return Some(FlashProgress {
+            stage: "verifying-download".to_string(),
+            progress: 30.0, // Between downloading and extraction
+            message: format!("Checking {} against published checksums", &caps[1]),
+            details: Some(line.to_string()),
+            start_time: None,
+            estimated_time_remaining: None,
+            bytes_done: None,
+            bytes_total: None,
+            throughput: None,
+            verified: None,
+            failure: None,
+        });
+    }
+    
+    if let Some(caps) = patterns.extract.captures(line) {
+        if let Ok(progress) = caps[2].parse::<f32>() {
+            return Some(FlashProgress {
+                stage: "preparing".to_string(),
+                progress: 30.0, // Extraction sits between downloading and flashing
+                message: format!("Extracting {}", &caps[1]),
+                details: Some(format!("{}% extracted", progress)),
+                start_time: None,
+                estimated_time_remaining: None,
+                bytes_done: None,
+                bytes_total: None,
+                throughput: None,
+                verified: None,
+                failure: None,
+            });
+        }
+    }
+    
+    if let Some(caps) = patterns.flash.captures(line) {
+        if let Ok(progress) = caps[1].parse::<f32>() {
+            return Some(FlashProgress {
+                stage: "flashing".to_string(),
+                progress: 30.0 + (progress * 0.6), // Flashing is 30-90%
+                message: line.to_string(),
+                details: None,
+                start_time: None,
+                estimated_time_remaining: Some(((100.0 - progress) * 1.5) as u64),
+                bytes_done: None,
+                bytes_total: None,
+                throughput: None,
+                verified: None,
+                failure: None,
+            });
+        }
+    }
+    
+    if let Some(caps) = patterns.verify.captures(line) {
+        if let Ok(progress) = caps[1].parse::<f32>() {
+            return Some(FlashProgress {
+                stage: "verifying".to_string(),
+                progress: 90.0 + (progress * 0.1), // Verifying is 90-100%
+                message: line.to_string(),
+                details: None,
+                start_time: None,
+                estimated_time_remaining: Some(((100.0 - progress) * 0.5) as u64),
+                bytes_done: None,
+                bytes_total: None,
+                throughput: None,
+                verified: None,
+                failure: None,
+            });
+        }
+    }
+    
+    None
+}
+
+// Transfer state of the download running in a flash job, read from wget output:
+// "Length: 1234567 (1.2M) [...]" when a file starts, then "  51200K .......... .......... 45% 2.1M 30s"
+#[derive(Debug, Default)]
+struct TransferMeter {
+    bytes_total: Option<u64>,
+    bytes_done: u64,
+    throughput: Option<f64>,
+    last_sample: Option<(std::time::Instant, u64)>,
+}
+
+impl TransferMeter {
+    // Bytes done when the line reported progress and a new throughput sample is due
+    fn update(&mut self, line: &str) -> Option<u64> {
+        let patterns = output_patterns()?;
+        if let Some(caps) = patterns.length.captures(line) {
+            self.bytes_total = caps[1].parse().ok();
+            self.bytes_done = 0;
+            self.throughput = None;
+            self.last_sample = None;
+            return None;
+        }
+        let kilobytes: u64 = patterns.transfer.captures(line)?[1].parse().ok()?;
+        let now = std::time::Instant::now();
+        self.bytes_done = kilobytes * 1024;
+        match self.last_sample {
+            Some((at, _)) if now.duration_since(at).as_millis() < 500 => None,
+            Some((at, bytes)) => {
+                let seconds = now.duration_since(at).as_secs_f64();
+                self.throughput = Some(self.bytes_done.saturating_sub(bytes) as f64 / seconds);
+                self.last_sample = Some((now, self.bytes_done));
+                Some(self.bytes_done)
+            }
+            None => {
+                self.last_sample = Some((now, self.bytes_done));
+                Some(self.bytes_done)
+            }
+        }
+    }
+}
+
+// Add the transfer figures to a downloading job's progress
+fn update_transfer_progress(state: &AppState, app: &impl JobHost, flash_id: &str, meter: &TransferMeter) {
+    let progress = {
+        let mut flash_progress = state.flash_progress.lock().unwrap();
+        let Some(progress) = flash_progress.get_mut(flash_id).filter(|p| p.stage == "downloading") else {
+            return;
+        };
+        progress.bytes_done = Some(meter.bytes_done);
+        progress.bytes_total = meter.bytes_total;
+        progress.throughput = meter.throughput;
+        progress.clone()
+    };
+    let _ = app.send("flash-progress-update", serde_json::json!({
+        "flash_id": flash_id,
+        "progress": progress
+    }));
+}
+
+// Update flash progress and emit to frontend
+async fn update_flash_progress(
+    state: &Arc<AppState>,
+    app: &impl JobHost,
+    flash_id: &str,
+    progress: FlashProgress,
+) -> Result<()> {
+    {
+        let mut flash_progress = state.flash_progress.lock().unwrap();
+        flash_progress.insert(flash_id.to_string(), progress.clone());
+    }
+    timeline::record(state, flash_id, &progress.stage, progress.progress);
+    
+    // Emit progress update to frontend
+    app.send("flash-progress-update", serde_json::json!({
+        "flash_id": flash_id,
+        "progress": progress
+    })).context("Failed to emit progress update")?;
+    
+    Ok(())
+}
+
+// Get flash progress
+#[command]
+async fn get_flash_progress(flash_id: String, state: State<'_, Arc<AppState>>) -> Result<Option<FlashProgress>, String> {
+    let flash_progress = state.flash_progress.lock().unwrap();
+    Ok(flash_progress.get(&flash_id).cloned())
+}
+
+// Cancel flash process; mode 'hard' (default) kills it, 'soft' lets it finish the current step
+#[command]
+async fn cancel_flash_process(
+    flash_id: String,
+    mode: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    match mode.as_deref().unwrap_or("hard") {
+        "hard" => {
+            cancel_flash(&app, &state, &flash_id).await;
+            Ok(())
+        }
+        "soft" => soft_cancel_flash(&app, &state, &flash_id),
+        other => Err(format!("Unknown cancel mode: {}", other)),
+    }
+}
+
+// Ask a running flash to stop before its next destructive step (removing prepared files or writing the
+// device); the job ends as cancelled once the script reaches that point. Also used by the control API.
+pub fn soft_cancel_flash(app: &tauri::AppHandle, state: &AppState, flash_id: &str) -> Result<(), String> {
+    // The native pipeline has no child process while it downloads, so a running job is one with live progress
+    let progress = {
+        let mut flash_progress = state.flash_progress.lock().unwrap();
+        let progress = flash_progress
+            .get_mut(flash_id)
+            .filter(|p| p.stage != "error")
+            .ok_or(format!("No running flash {}", flash_id))?;
+        // Nothing checks the flag from the write on, the first boot check included
+        if matches!(progress.stage.as_str(), "flashing" | "verifying" | "booting" | "complete") {
+            return Err("The device is already written or being written; only a hard cancel stops it now".to_string());
+        }
+        progress.details = Some("Soft cancel requested, stopping after the current step".to_string());
+        progress.clone()
+    };
+    let file = soft_cancel_file(app, flash_id).map_err(|e| e.to_string())?;
+    std::fs::write(&file, b"").map_err(|e| format!("Failed to request soft cancel: {}", e))?;
+    info!("Soft cancel requested for flash process: {}", flash_id);
+    let _ = app.emit("flash-progress-update", serde_json::json!({
+        "flash_id": flash_id,
+        "progress": progress
+    }));
+    Ok(())
+}
+
+// Stop a running flash; also used by the control API
+pub async fn cancel_flash(app: &impl JobHost, state: &AppState, flash_id: &str) {
+    info!("Cancelling flash process: {}", flash_id);
+    
+    // Stop an extraction the script is running; killing bash does not reach it
+    if let Ok(cancel) = extract::cancel_file(app, flash_id) {
+        std::fs::write(&cancel, b"").ok();
+    }
+    
+    // Recorded before the process is stopped, so the job does not end as failed when its process goes away
+    state.cancelled_flashes.lock().unwrap().insert(flash_id.to_string());
+    history::record_cancelled(app, state, flash_id, "hard");
+    
+    let mut child = {
+        let mut active_flashes = state.active_flashes.lock().unwrap();
+        active_flashes.remove(flash_id)
+    };
+    
+    if let Some(ref mut child) = child {
+        process_tree::terminate(child).await;
+        reset_cancelled_device(state, flash_id).await;
+    }
+    
+    // Update progress to cancelled
+    let mut flash_progress = state.flash_progress.lock().unwrap();
+    flash_progress.remove(flash_id);
+}
+
+// Reset the board a cancelled flash was talking to, so it can be flashed again right away. Without a
+// device binding that is only known when no other flash is running.
+async fn reset_cancelled_device(state: &AppState, flash_id: &str) {
+    let binding = {
+        let history = state.history.lock().unwrap();
+        history.jobs.iter().find(|job| job.flash_id == flash_id).and_then(|job| job.command.device_binding.clone())
+    };
+    let others_running = !state.active_flashes.lock().unwrap().is_empty();
+    let reset = match binding {
+        Some(binding) => usb_watch::reset_recovery_devices(state, |device| binding.matches_location(device)).await,
+        None if !others_running => usb_watch::reset_recovery_devices(state, |_| true).await,
+        None => {
+            info!("Not resetting USB devices after cancelling {}: other flashes are running", flash_id);
+            0
+        }
+    };
+    if reset > 0 {
+        info!("Reset {} board(s) after cancelling {}", reset, flash_id);
+    }
+}
+
+// Get system information, cached for the configured interval
+#[command]
+async fn get_system_info(state: State<'_, Arc<AppState>>) -> Result<SystemInfo, String> {
+    let ttl = state.settings.lock().unwrap().command_cache.system_info_ttl();
+    state.system_info_cache.get_or_refresh(ttl, collect_system_info).await
+}
+
+async fn collect_system_info() -> Result<SystemInfo, String> {
+    let os = std::env::consts::OS.to_string();
+    let arch = std::env::consts::ARCH.to_string();
+    
+    let memory_info = sys_info::mem_info().map_err(|e| e.to_string())?;
+    let disk_info = sys_info::disk_info().map_err(|e| e.to_string())?;
+    
+    // Check Docker installation
+    let docker_installed = Command::new("docker").arg("--version").output().is_ok();
+    let nvidia_docker_installed = Command::new("nvidia-container-cli").arg("--version").output().is_ok();
+    
+    // Try to detect JetPack version
+    let jetpack_version = detect_jetpack_version().await;
+    
+    let host_gpus = detect_host_gpus();
+    let nvidia_driver_version = host_gpus.first().map(|gpu| gpu.driver_version.clone());
+    
+    Ok(SystemInfo {
+        os,
+        architecture: arch,
+        total_memory: memory_info.total * 1024, // Convert to bytes
+        available_space: disk_info.free,
+        docker_installed,
+        nvidia_docker_installed,
+        jetpack_version,
+        cpu_model: detect_cpu_model(),
+        cpu_cores: sys_info::cpu_num().unwrap_or(1),
+        nvidia_driver_version,
+        host_gpus,
+    })
+}
+
+// Detect NVIDIA GPUs on the host via nvidia-smi (empty when no driver is installed)
+fn detect_host_gpus() -> Vec<HostGpuInfo> {
+    let output = match Command::new("nvidia-smi")
+        .args(["--query-gpu=name,driver_version,memory.total", "--format=csv,noheader,nounits"])
+        .output()
+    {
+        Ok(output) if output.status.success() => output,
+        _ => return Vec::new(),
+    };
+    
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+            match fields.as_slice() {
+                [name, driver_version, memory] => Some(HostGpuInfo {
+                    name: name.to_string(),
+                    driver_version: driver_version.to_string(),
+                    memory_total_mb: memory.parse().ok(),
+                }),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+// Detect the host CPU model name
+fn detect_cpu_model() -> Option<String> {
+    if let Ok(cpuinfo) = std::fs::read_to_string("/proc/cpuinfo") {
+        return cpuinfo
+            .lines()
+            .find(|line| line.starts_with("model name"))
+            .and_then(|line| line.split_once(':'))
+            .map(|(_, model)| model.trim().to_string());
+    }
+    
+    // macOS
+    let output = Command::new("sysctl").args(["-n", "machdep.cpu.brand_string"]).output().ok()?;
+    let model = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Some(model).filter(|m| !m.is_empty())
+}
+
+// Detect JetPack version
+async fn detect_jetpack_version() -> Option<String> {
+    // Try to read L4T version
+    let contents = tokio::fs::read_to_string("/etc/nv_tegra_release").await.ok()?;
+    parse_nv_tegra_release(&contents)
+}
+
+// Parse the contents of /etc/nv_tegra_release into "L4T <major>.<revision>"
+fn parse_nv_tegra_release(contents: &str) -> Option<String> {
+    let line = contents.lines().find(|line| line.contains("R"))?;
+    // Parse version like "# R36 , REVISION: 4.3"
+    let version_regex = Regex::new(r"R(\d+)\s*,\s*REVISION:\s*([\d.]+)").ok()?;
+    let caps = version_regex.captures(line)?;
+    Some(format!("L4T {}.{}", &caps[1], &caps[2]))
+}
+
+// Jetson-containers integration
+#[command]
+async fn list_available_containers() -> Result<Vec<ContainerInfo>, String> {
+    info!("Listing available jetson-containers...");
+    Ok(known_containers())
+}
+
+// This would typically query the jetson-containers registry or local cache
+// For now, a static list of popular containers; device specs are checked against it too
+fn known_containers() -> Vec<ContainerInfo> {
+    vec![
+        ContainerInfo {
+            name: "l4t-pytorch".to_string(),
+            tag: "r36.2.0".to_string(),
+            category: "ML".to_string(),
+            description: "PyTorch with CUDA support for L4T".to_string(),
+            size: "2.1 GB".to_string(),
+            supported_devices: vec!["AGX Orin".to_string(), "Orin NX".to_string(), "Orin Nano".to_string()],
+            is_installed: false,
+        },
+        ContainerInfo {
+            name: "text-generation-webui".to_string(),
+            tag: "latest".to_string(),
+            category: "LLM".to_string(),
+            description: "Web UI for running Large Language Models".to_string(),
+            size: "8.5 GB".to_string(),
+            supported_devices: vec!["AGX Orin".to_string(), "Orin NX".to_string()],
+            is_installed: false,
+        },
+        ContainerInfo {
+            name: "nanollm".to_string(),
+            tag: "latest".to_string(),
+            category: "LLM".to_string(),
+            description: "Optimized LLM inference for Jetson".to_string(),
+            size: "3.2 GB".to_string(),
+            supported_devices: vec!["AGX Orin".to_string(), "Orin NX".to_string(), "Orin Nano".to_string()],
+            is_installed: false,
+        },
+    ]
+}
+
+// Pull jetson-container
+#[command]
+async fn pull_container(container_name: String, tag: String) -> Result<String, String> {
+    info!("Pulling container: {}:{}", container_name, tag);
+    
+    // Use jetson-containers command to pull
+    let output = Command::new("jetson-containers")
+        .arg("run")
+        .arg(format!("{}:{}", container_name, tag))
+        .output()
+        .map_err(|e| format!("Failed to pull container: {}", e))?;
+    
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).to_string())
+    }
+}
+
+// Main Tauri application, also serving the helper modes the flash script runs it in
+pub fn run() {
+    // Helper modes for the flash script and sudo, before anything GUI-related starts. They come before
+    // the single-instance check because the running instance's own flash script runs them.
+    if let Some(code) = extract::run_from_args() {
+        std::process::exit(code);
+    }
+    if let Some(code) = release_checksums::run_from_args() {
+        std::process::exit(code);
+    }
+    if let Some(code) = rootfs_snapshot::run_from_args() {
+        std::process::exit(code);
+    }
+    // Headless flashing for CI; handed to the running instance if there is one, see cli::run
+    if let Some(code) = cli::run_from_args() {
+        std::process::exit(code);
+    }
+    crash::init_logging();
+    info!("Starting CFU - Cordatus Flash Utility");
+    #[cfg(unix)]
+    if instance::hand_off_to_running_instance() {
+        return;
+    }
+    
+    Builder::default()
+        .manage(Arc::new(AppState::default()))
+        .setup(|app| {
+            crash::install_panic_hook(app.handle());
+            let state = app.state::<Arc<AppState>>();
+            *state.settings.lock().unwrap() = settings::load_settings(app.handle());
+            *state.version_matrix.lock().unwrap() = version_matrix::load_version_matrix(app.handle());
+            *state.history.lock().unwrap() = history::load_history(app.handle());
+            *state.asset_batches.lock().unwrap() = asset::load_assets(app.handle());
+            *state.scheduled_jobs.lock().unwrap() = scheduler::load_schedule(app.handle());
+            scheduler::start_scheduler(app.handle().clone());
+            #[cfg(unix)]
+            instance::listen_for_activations(app.handle().clone());
+            peers::start(app.handle().clone());
+            control_api::start(app.handle().clone());
+            fleet_sync::start(app.handle().clone());
+            usb_watch::start(app.handle().clone());
+            Ok(())
+        })
+        .invoke_handler(generate_handler![
+            load_csv_data,
+            device_matrix::get_boards,
+            device_matrix::get_versions_for_board,
+            device_matrix::get_pending_device_matrix,
+            device_matrix::apply_device_matrix_update,
+            device_matrix::discard_device_matrix_update,
+            detect_usb_devices,
+            chip_id::read_chip_id,
+            start_flash_process,
+            preflight::run_preflight_checks,
+            preflight::estimate_required_space,
+            plan::plan_flash,
+            device_spec::validate_device_spec,
+            device_spec::validate_spec,
+            device_spec::run_device_spec,
+            device_spec::apply_device_spec,
+            device_spec::export_profile_spec,
+            reproduce::export_reproduction_script,
+            lab::list_lab_fixtures,
+            lab::get_lab_state,
+            lab::control_lab_fixture,
+            recovery::enter_recovery_mode,
+            lab::export_lab_configuration,
+            release_checksums::verify_artifacts,
+            get_flash_progress,
+            joblog::get_recent_output,
+            joblog::get_flash_log,
+            joblog::export_flash_log,
+            joblog::get_boot_log,
+            serial::list_serial_ports,
+            serial::open_serial_console,
+            serial::write_serial_console,
+            serial::list_serial_consoles,
+            serial::close_serial_console,
+            timeline::get_job_timeline,
+            uploads::upload_job_artifacts,
+            cancel_flash_process,
+            get_system_info,
+            list_available_containers,
+            pull_container,
+            asset::capture_asset_record,
+            asset::get_asset_batch,
+            asset::export_asset_batch,
+            asset::set_active_asset_batch,
+            asset::get_active_asset_batch,
+            settings::get_settings,
+            settings::update_settings,
+            api_tokens::create_api_token,
+            api_tokens::list_api_tokens,
+            api_tokens::revoke_api_token,
+            control_api::get_control_api_certificate,
+            label::preview_device_label,
+            label::print_device_label,
+            pairing::generate_pairing_qr,
+            version_matrix::get_version_matrix,
+            version_matrix::resolve_release,
+            version_matrix::update_version_matrix,
+            version_matrix::check_version_compatibility,
+            remote::detect_remote_jetpack,
+            connectivity::check_connectivity,
+            host_env::detect_virtualization,
+            host_env::detect_sandbox,
+            host_env::request_sandbox_device_access,
+            maintenance::start_maintenance_session,
+            maintenance::list_maintenance_sessions,
+            maintenance::end_maintenance_session,
+            maintenance::push_file,
+            maintenance::pull_file,
+            maintenance::run_fsck,
+            maintenance::repair_device,
+            partitions::get_partition_layout,
+            partitions::grow_app_partition,
+            provisioning::preview_provisioning_script,
+            provisioning::apply_provisioning,
+            history::get_flash_history,
+            history::get_flash_job,
+            history::query_flash_history,
+            history::rollback_device,
+            notes::add_job_note,
+            notes::delete_job_note,
+            notes::attach_job_file,
+            notes::remove_job_attachment,
+            acceptance::list_acceptance_checklists,
+            acceptance::save_acceptance_checklist,
+            acceptance::delete_acceptance_checklist,
+            acceptance::start_acceptance,
+            acceptance::confirm_acceptance_item,
+            profiles::list_profiles,
+            profiles::save_profile,
+            profiles::delete_profile,
+            profiles::pin_profile_artifacts,
+            profiles::flash_profile,
+            profile_sync::sync_profiles,
+            fleet_sync::sync_fleet_server,
+            freeze::get_station_freeze,
+            freeze::freeze_station,
+            freeze::unfreeze_station,
+            drift::capture_profile_baseline,
+            drift::detect_drift,
+            rootfs_sync::sync_rootfs,
+            rootfs_snapshot::get_rootfs_workspace,
+            rootfs_snapshot::customize_rootfs,
+            rootfs_snapshot::create_rootfs_snapshot,
+            rootfs_snapshot::list_rootfs_snapshots,
+            rootfs_snapshot::delete_rootfs_snapshot,
+            rootfs_snapshot::export_rootfs_snapshot,
+            rootfs_snapshot::import_rootfs_snapshot,
+            rootfs_snapshot::rebuild_rootfs_snapshot,
+            fleet::list_devices,
+            fleet::set_device_tags,
+            fleet::export_ansible_inventory,
+            fleet::export_fleet_state,
+            notifications::send_test_email,
+            scheduler::schedule_flash,
+            scheduler::list_scheduled_jobs,
+            scheduler::cancel_scheduled_job,
+            prefetch::prefetch_jetpack,
+            downloads::read_release_manifest,
+            downloads::download_release,
+            peers::list_cache_peers,
+            analytics::get_flash_time_stats,
+            analytics::get_failure_stats,
+            analytics::get_station_throughput,
+            backup::export_app_data,
+            backup::import_app_data,
+            telemetry::preview_telemetry_event,
+            crash::list_crash_reports,
+            crash::submit_crash_report,
+            crash::delete_crash_report,
+            #[cfg(target_os = "linux")]
+            image::write_image,
+            write_target::describe_write_target,
+            write_target::list_write_targets,
+            catalog::get_image_catalog,
+            catalog::download_catalog_image,
+            catalog::flash_catalog_image,
+            catalog_publish::publish_catalog_image,
+            catalog_publish::generate_catalog_signing_key,
+            catalog_publish::get_catalog_public_key,
+            #[cfg(target_os = "linux")]
+            image::backup_device,
+            kernel::list_toolchains,
+            kernel::list_kernel_sources,
+            kernel::fetch_kernel_sources,
+            kernel::apply_kernel_patches,
+            kernel::install_toolchain,
+            kernel::build_kernel,
+            kernel::build_kernel_module,
+            containers::build_container,
+            containers::list_container_presets,
+            containers::save_container_preset,
+            containers::delete_container_preset,
+            containers::deploy_container,
+            models::deploy_preset_assets,
+            models::clear_model_cache,
+            credentials::set_credential,
+            credentials::delete_credential,
+            credentials::list_credentials,
+            benchmarks::run_benchmark,
+            benchmarks::get_device_benchmarks,
+            thermal::run_thermal_report,
+            thermal::get_thermal_reports,
+            passport::export_device_passport
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
+
+// Resolve a file in the app data directory, creating the directory if needed
+fn app_data_file(app: &impl JobHost, name: &str) -> Result<std::path::PathBuf> {
+    let dir = app.data_dir()?;
+    std::fs::create_dir_all(&dir).context("Failed to create app data directory")?;
+    Ok(dir.join(name))
+}
+
+// Helper functions for proper script execution
+async fn get_script_path() -> Result<String, String> {
+    // Try bundled resource first
+    if let Ok(exe_dir) = std::env::current_exe() {
+        if let Some(parent) = exe_dir.parent() {
+            let bundled_script = parent.join("flash_cordatus.sh");
+            if bundled_script.exists() {
+                return Ok(bundled_script.to_string_lossy().to_string());
+            }
+        }
+    }
+    
+    // Fallback to development paths
+    let dev_scripts = vec![
+        ("./flash_cordatus.sh", "./flash_cordatus.sh"),
+        ("../flash_cordatus.sh", "../flash_cordatus.sh"),
+    ];
+    
+    for (path, result) in dev_scripts {
+        let script_path = std::path::PathBuf::from(path);
+        if script_path.exists() {
+            return Ok(result.to_string());
+        }
+    }
+    
+    Err("flash_cordatus.sh script not found".to_string())
+}
+
+async fn get_working_directory() -> Result<String, String> {
+    // For development, check multiple possible paths
+    if std::path::Path::new("./data/template.csv").exists() {
+        return Ok(".".to_string());
+    }
+    
+    if std::path::Path::new("../data/template.csv").exists() {
+        return Ok("..".to_string());
+    }
+    
+    // For bundled app, use app directory where resources are located
+    if let Ok(exe_dir) = std::env::current_exe() {
+        if let Some(parent) = exe_dir.parent() {
+            return Ok(parent.to_string_lossy().to_string());
+        }
+    }
+    
+    Ok("..".to_string()) // Default to parent directory for development
+}
\ No newline at end of file
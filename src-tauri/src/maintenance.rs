@@ -0,0 +1,556 @@
+// CFU - Cordatus Flash Utility - Maintenance Sessions
+// RCM-boots the L4T flashing initrd without flashing so the target storage can be
+// inspected, exported to the host as USB mass storage, or repaired
+
+use crate::ssh::{run_remote, shell_quote, spawn_remote, SshTarget};
+use crate::AppState;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{command, Emitter, State};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::Command as TokioCommand;
+use uuid::Uuid;
+
+// Address of the device on the USB network set up by the flashing initrd
+const INITRD_DEVICE_ADDRESS: &str = "fc00:1:1:0::2";
+const SSH_WAIT_ATTEMPTS: u32 = 60;
+const BLOCK_DEVICE_WAIT_ATTEMPTS: u32 = 30;
+const TRANSFER_CHUNK_SIZE: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceSession {
+    pub session_id: String,
+    pub board_config: String,
+    pub l4t_dir: String,
+    pub device_address: String,
+    pub storage_device: String, // e.g. "nvme0n1", "mmcblk0"
+    pub mass_storage: bool,
+    pub host_block_device: Option<String>,
+    pub started_at: DateTime<Utc>,
+}
+
+impl MaintenanceSession {
+    // The flashing initrd accepts root logins without a password
+    pub fn ssh_target(&self) -> SshTarget {
+        SshTarget {
+            host: self.device_address.clone(),
+            user: "root".to_string(),
+            port: None,
+            identity_file: None,
+        }
+    }
+}
+
+// Default L4T workspace created by flash_cordatus.sh
+pub fn default_l4t_dir() -> String {
+    let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+    format!("{}/openzeka/Linux_for_Tegra", home)
+}
+
+fn emit_status(app: &tauri::AppHandle, session_id: &str, message: &str) {
+    info!("Maintenance {}: {}", session_id, message);
+    let _ = app.emit("maintenance-status", serde_json::json!({
+        "session_id": session_id,
+        "message": message
+    }));
+}
+
+// Boot the flashing initrd and stop before anything is written
+async fn boot_maintenance_initrd(l4t_dir: &str, board_config: &str) -> Result<()> {
+    let output = TokioCommand::new("sudo")
+        .arg("./tools/kernel_flash/l4t_initrd_flash.sh")
+        .args(["--initrd", board_config, "internal"])
+        .current_dir(l4t_dir)
+        .output()
+        .await
+        .context("Failed to start l4t_initrd_flash.sh")?;
+
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "Initrd boot failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+async fn wait_for_ssh(target: &SshTarget) -> Result<()> {
+    for _ in 0..SSH_WAIT_ATTEMPTS {
+        if run_remote(target, "true").await.is_ok() {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+    Err(anyhow::anyhow!("Device did not come up on {} after initrd boot", target.host))
+}
+
+// Storage devices are bare kernel names (e.g. "mmcblk0", "nvme0n1") that end up in a root shell script
+fn validate_storage_device(storage_device: &str) -> Result<()> {
+    if storage_device.is_empty()
+        || !storage_device.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+    {
+        return Err(anyhow::anyhow!(
+            "Invalid storage device {:?}, expected a kernel device name such as mmcblk0 or nvme0n1",
+            storage_device
+        ));
+    }
+    Ok(())
+}
+
+// Add a mass-storage function to the initrd's USB gadget. The UDC has to be
+// rebound, which drops the ssh connection, so the change runs detached.
+async fn export_mass_storage(target: &SshTarget, storage_device: &str) -> Result<()> {
+    let script = format!(
+        r#"(sleep 1
+G=$(ls -d /sys/kernel/config/usb_gadget/* | head -n 1)
+C=$(ls -d $G/configs/* | head -n 1)
+UDC=$(cat $G/UDC)
+echo "" > $G/UDC
+mkdir -p $G/functions/mass_storage.cfu
+echo 0 > $G/functions/mass_storage.cfu/lun.0/removable
+echo {} > $G/functions/mass_storage.cfu/lun.0/file
+ln -s $G/functions/mass_storage.cfu $C/
+echo $UDC > $G/UDC) >/dev/null 2>&1 &"#,
+        shell_quote(&format!("/dev/{}", storage_device))
+    );
+    run_remote(target, &script).await?;
+    Ok(())
+}
+
+// The Linux mass-storage gadget identifies itself as "File-Stor Gadget"
+async fn find_gadget_block_device() -> Result<Option<String>> {
+    let output = TokioCommand::new("lsblk")
+        .args(["-J", "-d", "-o", "NAME,MODEL,TRAN"])
+        .output()
+        .await
+        .context("Failed to run lsblk")?;
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+
+    let device = json["blockdevices"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|dev| {
+            dev["tran"].as_str() == Some("usb")
+                && dev["model"].as_str().is_some_and(|model| model.contains("File-Stor"))
+        })
+        .and_then(|dev| dev["name"].as_str())
+        .map(|name| format!("/dev/{}", name));
+    Ok(device)
+}
+
+async fn wait_for_gadget_block_device() -> Result<String> {
+    for _ in 0..BLOCK_DEVICE_WAIT_ATTEMPTS {
+        if let Some(device) = find_gadget_block_device().await? {
+            return Ok(device);
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    Err(anyhow::anyhow!("Target storage did not appear on the host as a USB disk"))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileTransferResult {
+    pub transfer_id: String,
+    pub bytes: u64,
+}
+
+fn get_session(state: &AppState, session_id: &str) -> Result<MaintenanceSession, String> {
+    let session = state
+        .maintenance_sessions
+        .lock()
+        .unwrap()
+        .get(session_id)
+        .cloned()
+        .ok_or_else(|| format!("Maintenance session not found: {}", session_id))?;
+
+    // While exported as a USB disk the host owns the filesystem; mounting it
+    // on the device at the same time would corrupt it
+    if session.mass_storage {
+        return Err("Storage is exported to the host, mount it there instead".to_string());
+    }
+    Ok(session)
+}
+
+// Shell prefix that mounts a partition (device name like "nvme0n1p1" or a GPT
+// label like "APP") at a per-partition mount point inside the initrd
+fn mount_prefix(partition: &str) -> (String, String) {
+    let safe_name: String = partition
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .collect();
+    let mount_point = format!("/mnt/cfu-{}", safe_name);
+    let prefix = format!(
+        "set -e; P={p}; DEV=/dev/$P; [ -b \"$DEV\" ] || DEV=/dev/disk/by-partlabel/$P; \
+         mkdir -p {m}; mountpoint -q {m} || mount \"$DEV\" {m}; ",
+        p = shell_quote(partition),
+        m = mount_point
+    );
+    (prefix, mount_point)
+}
+
+fn emit_transfer_progress(app: &tauri::AppHandle, transfer_id: &str, bytes_done: u64, bytes_total: u64) {
+    let _ = app.emit("file-transfer-progress", serde_json::json!({
+        "transfer_id": transfer_id,
+        "bytes_done": bytes_done,
+        "bytes_total": bytes_total
+    }));
+}
+
+async fn push_file_inner(
+    app: &tauri::AppHandle,
+    target: &SshTarget,
+    transfer_id: &str,
+    partition: &str,
+    src: &str,
+    dest: &str,
+) -> Result<u64> {
+    let mut file = tokio::fs::File::open(src).await.with_context(|| format!("Failed to open {}", src))?;
+    let total = file.metadata().await?.len();
+
+    // Write to a temporary name first so an interrupted push never leaves a
+    // truncated config file (e.g. fstab) behind
+    let (prefix, mount_point) = mount_prefix(partition);
+    let remote_path = shell_quote(&format!("{}/{}", mount_point, dest.trim_start_matches('/')));
+    let script = format!(
+        "{}mkdir -p \"$(dirname {path})\"; cat > {path}.cfu-tmp; mv {path}.cfu-tmp {path}; sync; umount {m}",
+        prefix,
+        path = remote_path,
+        m = mount_point
+    );
+
+    let mut child = spawn_remote(target, &script)?;
+    let mut stdin = child.stdin.take().context("ssh stdin unavailable")?;
+    let mut buffer = vec![0u8; TRANSFER_CHUNK_SIZE];
+    let mut sent: u64 = 0;
+
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        stdin.write_all(&buffer[..read]).await.context("Connection to device lost")?;
+        sent += read as u64;
+        emit_transfer_progress(app, transfer_id, sent, total);
+    }
+    drop(stdin);
+
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("Push failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(sent)
+}
+
+async fn pull_file_inner(
+    app: &tauri::AppHandle,
+    target: &SshTarget,
+    transfer_id: &str,
+    partition: &str,
+    src: &str,
+    dest: &str,
+) -> Result<u64> {
+    let (prefix, mount_point) = mount_prefix(partition);
+    let remote_path = shell_quote(&format!("{}/{}", mount_point, src.trim_start_matches('/')));
+
+    let size_output = run_remote(target, &format!("{}stat -c %s {}", prefix, remote_path)).await?;
+    let total: u64 = size_output.trim().parse().context("Unexpected file size")?;
+
+    let mut child = spawn_remote(target, &format!("{}cat {}", prefix, remote_path))?;
+    let mut stdout = child.stdout.take().context("ssh stdout unavailable")?;
+    let mut file = tokio::fs::File::create(dest).await.with_context(|| format!("Failed to create {}", dest))?;
+    let mut buffer = vec![0u8; TRANSFER_CHUNK_SIZE];
+    let mut received: u64 = 0;
+
+    loop {
+        let read = stdout.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        file.write_all(&buffer[..read]).await?;
+        received += read as u64;
+        emit_transfer_progress(app, transfer_id, received, total);
+    }
+    file.flush().await?;
+
+    let status = child.wait().await?;
+    let _ = run_remote(target, &format!("umount {}", mount_point)).await;
+    if !status.success() || received != total {
+        return Err(anyhow::anyhow!("Pull incomplete: received {} of {} bytes", received, total));
+    }
+    Ok(received)
+}
+
+// Copy a host file into a target partition through a maintenance session
+#[command]
+pub async fn push_file(
+    session_id: String,
+    target_partition: String,
+    src: String,
+    dest: String,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<FileTransferResult, String> {
+    let session = get_session(&state, &session_id)?;
+    let transfer_id = Uuid::new_v4().to_string();
+    info!("Pushing {} to {}:{} ({})", src, target_partition, dest, transfer_id);
+
+    let bytes = push_file_inner(&app, &session.ssh_target(), &transfer_id, &target_partition, &src, &dest)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(FileTransferResult { transfer_id, bytes })
+}
+
+// Copy a file from a target partition to the host through a maintenance session
+#[command]
+pub async fn pull_file(
+    session_id: String,
+    target_partition: String,
+    src: String,
+    dest: String,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<FileTransferResult, String> {
+    let session = get_session(&state, &session_id)?;
+    let transfer_id = Uuid::new_v4().to_string();
+    info!("Pulling {}:{} to {} ({})", target_partition, src, dest, transfer_id);
+
+    let bytes = pull_file_inner(&app, &session.ssh_target(), &transfer_id, &target_partition, &src, &dest)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(FileTransferResult { transfer_id, bytes })
+}
+
+async fn open_session(
+    app: &tauri::AppHandle,
+    board_config: String,
+    storage_device: String,
+    expose_mass_storage: bool,
+    l4t_dir: Option<String>,
+) -> Result<MaintenanceSession> {
+    validate_storage_device(&storage_device)?;
+    let mut session = MaintenanceSession {
+        session_id: Uuid::new_v4().to_string(),
+        board_config,
+        l4t_dir: l4t_dir.unwrap_or_else(default_l4t_dir),
+        device_address: INITRD_DEVICE_ADDRESS.to_string(),
+        storage_device,
+        mass_storage: expose_mass_storage,
+        host_block_device: None,
+        started_at: Utc::now(),
+    };
+
+    emit_status(app, &session.session_id, "Booting maintenance initrd...");
+    boot_maintenance_initrd(&session.l4t_dir, &session.board_config).await?;
+
+    let target = session.ssh_target();
+    emit_status(app, &session.session_id, "Waiting for device network...");
+    wait_for_ssh(&target).await?;
+
+    if expose_mass_storage {
+        emit_status(app, &session.session_id, "Exporting target storage as USB mass storage...");
+        export_mass_storage(&target, &session.storage_device)
+            .await
+            .context("Failed to export storage")?;
+        let block_device = wait_for_gadget_block_device().await?;
+        emit_status(app, &session.session_id, &format!("Target storage available as {}", block_device));
+        session.host_block_device = Some(block_device);
+    }
+
+    Ok(session)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FsckReport {
+    pub partition: String,
+    pub exit_code: i32,
+    pub status: String, // 'clean' | 'repaired' | 'errors-remaining' | 'failed'
+    pub output: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepairReport {
+    pub fsck: FsckReport,
+    pub bootloader_reinstalled: bool,
+}
+
+// fsck exit codes are a bit mask: 1/2 = errors corrected, 4 = errors left, 8+ = fsck failed
+fn fsck_status(exit_code: i32) -> &'static str {
+    match exit_code {
+        0 => "clean",
+        code if code >= 8 => "failed",
+        code if code & 4 != 0 => "errors-remaining",
+        _ => "repaired",
+    }
+}
+
+async fn run_fsck_inner(target: &SshTarget, partition: &str, repair: bool) -> Result<FsckReport> {
+    let (_, mount_point) = mount_prefix(partition);
+    let script = format!(
+        "P={p}; DEV=/dev/$P; [ -b \"$DEV\" ] || DEV=/dev/disk/by-partlabel/$P; \
+         umount {m} 2>/dev/null; fsck.ext4 -f {mode} \"$DEV\" 2>&1; echo \"CFU_FSCK_EXIT=$?\"",
+        p = shell_quote(partition),
+        m = mount_point,
+        mode = if repair { "-y" } else { "-n" }
+    );
+    let output = run_remote(target, &script).await?;
+
+    let exit_code = output
+        .lines()
+        .find_map(|line| line.strip_prefix("CFU_FSCK_EXIT="))
+        .and_then(|code| code.trim().parse().ok())
+        .context("fsck did not report an exit status")?;
+
+    Ok(FsckReport {
+        partition: partition.to_string(),
+        exit_code,
+        status: fsck_status(exit_code).to_string(),
+        output: output
+            .lines()
+            .filter(|line| !line.starts_with("CFU_FSCK_EXIT="))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    })
+}
+
+// Put the device back into recovery mode and rewrite the QSPI bootloader
+async fn reinstall_bootloader_inner(app: &tauri::AppHandle, session: &MaintenanceSession) -> Result<()> {
+    emit_status(app, &session.session_id, "Rebooting into recovery mode...");
+    run_remote(&session.ssh_target(), "(sync; sleep 1; reboot forced-recovery) >/dev/null 2>&1 &").await?;
+
+    // Wait for the board to disappear from the USB network and come back in RCM
+    let mut in_recovery = false;
+    for _ in 0..BLOCK_DEVICE_WAIT_ATTEMPTS {
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        let status = TokioCommand::new("lsusb").args(["-d", "0955:"]).output().await?;
+        if status.status.success() && run_remote(&session.ssh_target(), "true").await.is_err() {
+            in_recovery = true;
+            break;
+        }
+    }
+    if !in_recovery {
+        return Err(anyhow::anyhow!("Device did not re-enter recovery mode"));
+    }
+
+    emit_status(app, &session.session_id, "Reinstalling bootloader...");
+    let output = TokioCommand::new("sudo")
+        .arg("./tools/kernel_flash/l4t_initrd_flash.sh")
+        .args(["--qspi-only", &session.board_config, "internal"])
+        .current_dir(&session.l4t_dir)
+        .output()
+        .await
+        .context("Failed to start l4t_initrd_flash.sh")?;
+
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "Bootloader reinstall failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+// Check (and optionally repair) a partition inside a maintenance session
+#[command]
+pub async fn run_fsck(
+    session_id: String,
+    partition: Option<String>,
+    repair: bool,
+    state: State<'_, Arc<AppState>>,
+) -> Result<FsckReport, String> {
+    let session = get_session(&state, &session_id)?;
+    let partition = partition.unwrap_or_else(|| "APP".to_string());
+    info!("Running fsck on {} (repair: {})", partition, repair);
+
+    run_fsck_inner(&session.ssh_target(), &partition, repair)
+        .await
+        .map_err(|e| format!("fsck failed: {}", e))
+}
+
+// Guided repair: boot the maintenance initrd, fsck the APP partition, then
+// either reinstall the bootloader or reboot the device normally
+#[command]
+pub async fn repair_device(
+    board_config: String,
+    storage_device: String,
+    reinstall_bootloader: bool,
+    l4t_dir: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<RepairReport, String> {
+    let session = open_session(&app, board_config, storage_device, false, l4t_dir)
+        .await
+        .map_err(|e| format!("{:#}", e))?;
+
+    emit_status(&app, &session.session_id, "Checking APP filesystem...");
+    let fsck = run_fsck_inner(&session.ssh_target(), "APP", true)
+        .await
+        .map_err(|e| format!("fsck failed: {}", e))?;
+    emit_status(&app, &session.session_id, &format!("Filesystem check result: {}", fsck.status));
+
+    if reinstall_bootloader {
+        reinstall_bootloader_inner(&app, &session)
+            .await
+            .map_err(|e| e.to_string())?;
+    } else if let Err(e) = run_remote(&session.ssh_target(), "(sync; sleep 1; reboot -f) >/dev/null 2>&1 &").await {
+        warn!("Failed to reboot device after repair: {}", e);
+    }
+
+    Ok(RepairReport {
+        fsck,
+        bootloader_reinstalled: reinstall_bootloader,
+    })
+}
+
+// Boot a device in recovery mode into the maintenance initrd, optionally
+// exporting its storage to the host as a USB disk
+#[command]
+pub async fn start_maintenance_session(
+    board_config: String,
+    storage_device: String,
+    expose_mass_storage: bool,
+    l4t_dir: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<MaintenanceSession, String> {
+    let session = open_session(&app, board_config, storage_device, expose_mass_storage, l4t_dir)
+        .await
+        .map_err(|e| format!("{:#}", e))?;
+
+    state
+        .maintenance_sessions
+        .lock()
+        .unwrap()
+        .insert(session.session_id.clone(), session.clone());
+    Ok(session)
+}
+
+// List active maintenance sessions
+#[command]
+pub async fn list_maintenance_sessions(state: State<'_, Arc<AppState>>) -> Result<Vec<MaintenanceSession>, String> {
+    Ok(state.maintenance_sessions.lock().unwrap().values().cloned().collect())
+}
+
+// Reboot the device out of the maintenance initrd
+#[command]
+pub async fn end_maintenance_session(session_id: String, state: State<'_, Arc<AppState>>) -> Result<(), String> {
+    let session = state
+        .maintenance_sessions
+        .lock()
+        .unwrap()
+        .remove(&session_id)
+        .ok_or_else(|| format!("Maintenance session not found: {}", session_id))?;
+
+    // Flush host writes to the exported disk before the device goes away
+    if session.mass_storage {
+        let _ = TokioCommand::new("sync").status().await;
+    }
+
+    if let Err(e) = run_remote(&session.ssh_target(), "(sync; sleep 1; reboot -f) >/dev/null 2>&1 &").await {
+        warn!("Failed to reboot device for session {}: {}", session_id, e);
+    }
+    Ok(())
+}
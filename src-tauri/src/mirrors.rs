@@ -0,0 +1,129 @@
+// CFU - Cordatus Flash Utility - Download Mirrors
+// Local HTTP, IPFS and BitTorrent sources for release files, tried before the original URL so
+// sites with many stations fetch large archives over the WAN only once. The flash pipelines, which
+// know an archive only by its URL, use the HTTP mirrors.
+
+use crate::downloads::ManifestFile;
+use crate::settings::MirrorSettings;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use tokio::process::Command as TokioCommand;
+
+pub enum Source {
+    Http { name: String, url: String },
+    Torrent { name: String, tracker: String, magnet: String },
+}
+
+impl Source {
+    pub fn name(&self) -> &str {
+        match self {
+            Source::Http { name, .. } | Source::Torrent { name, .. } => name,
+        }
+    }
+}
+
+// Enabled mirrors able to serve the file, in settings order, followed by its original URL.
+// IPFS gateways and torrents need the file's CID or magnet link in the manifest.
+pub fn sources(mirrors: &[MirrorSettings], file: &ManifestFile) -> Vec<Source> {
+    let file_name = file.url.rsplit('/').next().unwrap_or_default();
+    let mut sources: Vec<Source> = mirrors
+        .iter()
+        .filter(|mirror| mirror.enabled && !mirror.endpoint.trim().is_empty())
+        .filter_map(|mirror| {
+            let endpoint = mirror.endpoint.trim().trim_end_matches('/');
+            let name = mirror.name.clone();
+            match mirror.transport.as_str() {
+                "http" => Some(Source::Http { name, url: format!("{}/{}", endpoint, file_name) }),
+                "ipfs" => file.ipfs_cid.as_ref().map(|cid| Source::Http { name, url: format!("{}/ipfs/{}", endpoint, cid) }),
+                "torrent" => file.magnet.as_ref().map(|magnet| Source::Torrent {
+                    name,
+                    tracker: endpoint.to_string(),
+                    magnet: magnet.clone(),
+                }),
+                _ => None,
+            }
+        })
+        .collect();
+    sources.push(Source::Http { name: "origin".to_string(), url: file.url.clone() });
+    sources
+}
+
+// Sources of a release archive known only by its URL, as the flash pipelines know them: the HTTP
+// mirrors, then the URL itself
+pub fn archive_sources(mirrors: &[MirrorSettings], url: &str) -> Vec<Source> {
+    let file = ManifestFile {
+        url: url.to_string(),
+        size: None,
+        sha256: None,
+        destination: String::new(),
+        ipfs_cid: None,
+        magnet: None,
+    };
+    sources(mirrors, &file)
+}
+
+// CFU_MIRRORS for the flash script: the base URLs of the enabled HTTP mirrors, space separated
+pub fn env_value(mirrors: &[MirrorSettings]) -> String {
+    mirrors
+        .iter()
+        .filter(|mirror| mirror.enabled && mirror.transport == "http" && !mirror.endpoint.trim().is_empty())
+        .map(|mirror| mirror.endpoint.trim().trim_end_matches('/'))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn largest_file(dir: &Path, depth: usize) -> Option<(u64, PathBuf)> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let meta = entry.metadata().ok()?;
+            if meta.is_file() {
+                Some((meta.len(), path))
+            } else if meta.is_dir() && depth > 0 {
+                largest_file(&path, depth - 1)
+            } else {
+                None
+            }
+        })
+        .max_by_key(|(len, _)| *len)
+}
+
+// Download a single-file torrent with aria2c, announcing only to the site tracker and local
+// peer discovery, then move the payload to `part`
+pub async fn fetch_torrent(tracker: &str, magnet: &str, part: &Path) -> Result<()> {
+    let mut dir = part.as_os_str().to_owned();
+    dir.push(".d");
+    let dir = PathBuf::from(dir);
+    tokio::fs::create_dir_all(&dir).await?;
+
+    let output = TokioCommand::new("aria2c")
+        .arg("--dir")
+        .arg(&dir)
+        .args([
+            "--seed-time=0",
+            "--enable-dht=false",
+            "--enable-peer-exchange=false",
+            "--bt-enable-lpd=true",
+            "--follow-torrent=mem",
+            "--summary-interval=0",
+            "--console-log-level=warn",
+        ])
+        .arg(format!("--bt-tracker={}", tracker))
+        .arg(magnet)
+        .output()
+        .await
+        .context("Failed to run aria2c, which torrent mirrors need")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "Torrent download failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let (_, payload) = largest_file(&dir, 2).context("Torrent download produced no file")?;
+    tokio::fs::rename(&payload, part).await?;
+    tokio::fs::remove_dir_all(&dir).await.ok();
+    Ok(())
+}
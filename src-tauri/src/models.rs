@@ -0,0 +1,337 @@
+// CFU - Cordatus Flash Utility - Model Assets
+// Download, checksum and placement of model weights and other large assets listed in container presets.
+// Assets come from plain HTTP(S) URLs or, with tokens from the keyring, Hugging Face and NGC. The NGC API key
+// is only ever sent to NGC's token service, which exchanges it for short-lived bearer tokens.
+
+use crate::checksum;
+use crate::containers;
+use crate::credentials;
+use crate::ssh::{run_remote, shell_quote, spawn_remote, SshTarget, SUDO};
+use anyhow::{Context, Result};
+use log::info;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tauri::{command, Emitter};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const CHUNK_SIZE: usize = 1024 * 1024;
+const NGC_AUTH_URL: &str = "https://authn.nvidia.com/token";
+const NGC_AUTH_TIMEOUT: Duration = Duration::from_secs(15);
+const NGC_TOKEN_MARGIN: Duration = Duration::from_secs(60); // Renewed this long before it expires
+
+// One file a container needs on the device, e.g. model weights mounted into it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelAsset {
+    pub name: String,
+    pub url: String, // https://..., hf://org/repo[@revision]/path or ngc://org[/team]/model:version/path
+    pub sha256: Option<String>,
+    pub target_path: String, // Absolute path on the device
+    #[serde(default)]
+    pub size_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssetPlacement {
+    pub name: String,
+    pub target_path: String,
+    pub bytes: u64,
+    pub sha256: String,
+    pub from_cache: bool,    // Already downloaded on this host
+    pub already_present: bool, // Identical file already on the device, upload skipped
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct AssetProgress<'a> {
+    preset: &'a str,
+    asset: &'a str,
+    phase: &'a str, // 'download' | 'upload'
+    transferred: u64,
+    total: Option<u64>,
+}
+
+fn emit_progress(app: &tauri::AppHandle, preset: &str, asset: &str, phase: &str, transferred: u64, total: Option<u64>) {
+    let _ = app.emit("model-asset-progress", AssetProgress { preset, asset, phase, transferred, total });
+}
+
+// Map registry URLs to their download endpoints and the keyring account holding the token
+fn resolve_source(url: &str) -> Result<(String, Option<&'static str>)> {
+    if let Some(rest) = url.strip_prefix("hf://") {
+        let parts: Vec<&str> = rest.splitn(3, '/').collect();
+        if parts.len() < 3 {
+            return Err(anyhow::anyhow!("Expected hf://org/repo/path, got {}", url));
+        }
+        let (repo, revision) = parts[1].split_once('@').unwrap_or((parts[1], "main"));
+        return Ok((
+            format!("https://huggingface.co/{}/{}/resolve/{}/{}", parts[0], repo, revision, parts[2]),
+            Some("huggingface"),
+        ));
+    }
+
+    if let Some(rest) = url.strip_prefix("ngc://") {
+        let parts: Vec<&str> = rest.split('/').collect();
+        let model_index = parts
+            .iter()
+            .position(|part| part.contains(':'))
+            .with_context(|| format!("Expected ngc://org[/team]/model:version/path, got {}", url))?;
+        let (model, version) = parts[model_index].split_once(':').unwrap_or_default();
+        let scope = match &parts[..model_index] {
+            [org] => format!("org/{}", org),
+            [org, team] => format!("org/{}/team/{}", org, team),
+            _ => return Err(anyhow::anyhow!("Invalid NGC model path: {}", url)),
+        };
+        let file = parts[model_index + 1..].join("/");
+        return Ok((
+            format!("https://api.ngc.nvidia.com/v2/{}/models/{}/versions/{}/files/{}", scope, model, version, file),
+            Some("ngc"),
+        ));
+    }
+
+    Ok((url.to_string(), None))
+}
+
+#[derive(Debug, Deserialize)]
+struct NgcToken {
+    token: String,
+    #[serde(default)]
+    expires_in: Option<u64>, // Seconds
+}
+
+// The organization of an ngc:// URL, which the token is scoped to
+fn ngc_org(url: &str) -> Option<&str> {
+    url.strip_prefix("ngc://")?.split('/').next().filter(|org| !org.is_empty())
+}
+
+// NGC takes the API key only at its token service, as the password of the user "$oauthtoken", and answers
+// with a bearer token for the API that expires after minutes; it is cached until shortly before that
+async fn ngc_token(api_key: &str, org: Option<&str>) -> Result<String> {
+    static TOKENS: OnceLock<Mutex<HashMap<String, (String, Instant)>>> = OnceLock::new();
+    let tokens = TOKENS.get_or_init(Default::default);
+    let key = format!("{:x}/{}", Sha256::digest(api_key.as_bytes()), org.unwrap_or_default());
+    if let Some((token, _)) = tokens.lock().unwrap().get(&key).filter(|(_, renew_at)| Instant::now() < *renew_at) {
+        return Ok(token.clone());
+    }
+
+    let mut request = reqwest::Client::new()
+        .get(NGC_AUTH_URL)
+        .query(&[("service", "ngc")])
+        .basic_auth("$oauthtoken", Some(api_key))
+        .timeout(NGC_AUTH_TIMEOUT);
+    if let Some(org) = org {
+        request = request.query(&[("scope", format!("group/ngc:{}", org))]);
+    }
+    let answer: NgcToken = request
+        .send()
+        .await
+        .context("Failed to reach the NGC token service")?
+        .error_for_status()
+        .context("NGC did not accept the API key")?
+        .json()
+        .await
+        .context("Invalid answer from the NGC token service")?;
+    let lifetime = Duration::from_secs(answer.expires_in.unwrap_or(300)).saturating_sub(NGC_TOKEN_MARGIN);
+    tokens.lock().unwrap().insert(key, (answer.token.clone(), Instant::now() + lifetime));
+    Ok(answer.token)
+}
+
+// Cached per host, so one download serves every device provisioned afterwards
+fn cache_path(app: &tauri::AppHandle, asset: &ModelAsset) -> Result<PathBuf> {
+    let dir = crate::app_data_file(app, "model_cache")?;
+    std::fs::create_dir_all(&dir)?;
+    let key = match &asset.sha256 {
+        Some(sha256) => sha256.to_lowercase(),
+        None => format!("{:x}", Sha256::digest(asset.url.as_bytes())),
+    };
+    Ok(dir.join(key))
+}
+
+async fn sha256_file(path: &Path) -> Result<String> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || checksum::sha256_file(&path)).await?
+}
+
+async fn hash_existing(path: &Path, hasher: &mut Sha256) -> Result<u64> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    let mut total: u64 = 0;
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        total += read as u64;
+    }
+    Ok(total)
+}
+
+// Download into the host cache, hashing on the fly and resuming an interrupted
+// .part file with a Range request; returns (path, sha256, from_cache)
+async fn fetch_to_cache(app: &tauri::AppHandle, preset: &str, asset: &ModelAsset) -> Result<(PathBuf, String, bool)> {
+    let path = cache_path(app, asset)?;
+    if path.exists() {
+        let sha256 = sha256_file(&path).await?;
+        if asset.sha256.as_deref().is_none_or(|expected| expected.eq_ignore_ascii_case(&sha256)) {
+            return Ok((path, sha256, true));
+        }
+    }
+
+    let (url, account) = resolve_source(&asset.url)?;
+    let mut request = reqwest::Client::new().get(&url);
+    if let Some(account) = account {
+        match credentials::get_secret(account)? {
+            Some(api_key) if account == "ngc" => request = request.bearer_auth(ngc_token(&api_key, ngc_org(&asset.url)).await?),
+            Some(token) => request = request.bearer_auth(token),
+            None => info!("No {} token configured, trying anonymous download of {}", account, asset.name),
+        }
+    }
+
+    let partial = path.with_extension("part");
+    let mut hasher = Sha256::new();
+    let resume_from = if partial.exists() { hash_existing(&partial, &mut hasher).await? } else { 0 };
+    if resume_from > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", resume_from));
+    }
+
+    let mut response = request.send().await?.error_for_status()?;
+    let resumed = resume_from > 0 && response.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+    let mut downloaded = if resumed { resume_from } else { 0 };
+    if !resumed {
+        hasher = Sha256::new();
+    }
+
+    // Hugging Face and its CDN report the sha256 of LFS files as the ETag, use it
+    // when the preset has none
+    let from_huggingface = account == Some("huggingface");
+    let expected = asset.sha256.clone().or_else(|| {
+        let headers = response.headers();
+        headers
+            .get("x-linked-etag")
+            .or_else(|| headers.get(reqwest::header::ETAG))
+            .and_then(|value| value.to_str().ok())
+            .map(|etag| etag.trim_matches('"').to_string())
+            .filter(|etag| from_huggingface && etag.len() == 64 && etag.chars().all(|c| c.is_ascii_hexdigit()))
+    });
+
+    let total = response.content_length().map(|length| length + downloaded).or(asset.size_bytes);
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(&partial)
+        .await?;
+
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk).await?;
+        hasher.update(&chunk);
+        downloaded += chunk.len() as u64;
+        emit_progress(app, preset, &asset.name, "download", downloaded, total);
+    }
+    file.flush().await?;
+
+    let sha256 = format!("{:x}", hasher.finalize());
+    if let Some(expected) = &expected {
+        if !expected.eq_ignore_ascii_case(&sha256) {
+            tokio::fs::remove_file(&partial).await.ok();
+            return Err(anyhow::anyhow!("Checksum mismatch for {}: expected {}, got {}", asset.name, expected, sha256));
+        }
+    }
+    tokio::fs::rename(&partial, &path).await?;
+    Ok((path, sha256, false))
+}
+
+async fn remote_sha256(target: &SshTarget, path: &str) -> Option<String> {
+    let script = format!("{}$S sha256sum {} 2>/dev/null", SUDO, shell_quote(path));
+    let output = run_remote(target, &script).await.ok()?;
+    output.split_whitespace().next().map(str::to_string)
+}
+
+// Stream a cached file to the device through ssh, replacing the destination atomically
+async fn upload(app: &tauri::AppHandle, preset: &str, asset: &ModelAsset, target: &SshTarget, src: &Path) -> Result<u64> {
+    let mut file = tokio::fs::File::open(src).await?;
+    let total = file.metadata().await?.len();
+    let dest = shell_quote(&asset.target_path);
+    let script = format!(
+        "{}set -e; $S mkdir -p \"$(dirname {dest})\"; $S tee {dest}.cfu-tmp >/dev/null; $S mv {dest}.cfu-tmp {dest}",
+        SUDO,
+        dest = dest
+    );
+
+    let mut child = spawn_remote(target, &script)?;
+    let mut stdin = child.stdin.take().context("ssh stdin unavailable")?;
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    let mut sent: u64 = 0;
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        stdin.write_all(&buffer[..read]).await.context("Connection to device lost")?;
+        sent += read as u64;
+        emit_progress(app, preset, &asset.name, "upload", sent, Some(total));
+    }
+    drop(stdin);
+
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("Upload failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(sent)
+}
+
+pub async fn place_asset(app: &tauri::AppHandle, preset: &str, asset: &ModelAsset, target: &SshTarget) -> Result<AssetPlacement> {
+    let (cached, sha256, from_cache) = fetch_to_cache(app, preset, asset).await?;
+    let bytes = std::fs::metadata(&cached)?.len();
+
+    let already_present = remote_sha256(target, &asset.target_path).await.as_deref() == Some(sha256.as_str());
+    if !already_present {
+        upload(app, preset, asset, target, &cached).await?;
+    }
+
+    Ok(AssetPlacement {
+        name: asset.name.clone(),
+        target_path: asset.target_path.clone(),
+        bytes,
+        sha256,
+        from_cache,
+        already_present,
+    })
+}
+
+// Download (or reuse from cache) every asset of a container preset and place it on the device
+#[command]
+pub async fn deploy_preset_assets(
+    preset_name: String,
+    target: SshTarget,
+    app: tauri::AppHandle,
+) -> Result<Vec<AssetPlacement>, String> {
+    let preset = containers::find_preset(&app, &preset_name).map_err(|e| e.to_string())?;
+    let mut placements = Vec::new();
+
+    for asset in &preset.assets {
+        info!("Placing asset {} for {} on {}", asset.name, preset.name, target.host);
+        let placement = place_asset(&app, &preset.name, asset, &target)
+            .await
+            .map_err(|e| format!("Failed to place {}: {}", asset.name, e))?;
+        placements.push(placement);
+    }
+    Ok(placements)
+}
+
+// Remove cached downloads, returning the number of bytes freed
+#[command]
+pub async fn clear_model_cache(app: tauri::AppHandle) -> Result<u64, String> {
+    let dir = crate::app_data_file(&app, "model_cache").map_err(|e| e.to_string())?;
+    let mut freed = 0;
+    if let Ok(entries) = std::fs::read_dir(&dir) {
+        for entry in entries.flatten() {
+            freed += entry.metadata().map(|m| m.len()).unwrap_or(0);
+            std::fs::remove_file(entry.path()).ok();
+        }
+    }
+    Ok(freed)
+}
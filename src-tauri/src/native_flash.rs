@@ -0,0 +1,552 @@
+// CFU - Cordatus Flash Utility - Native Flash Pipeline
+// Flashes NVIDIA developer kits without flash_cordatus.sh: the release archives are downloaded, verified and
+// extracted here, and NVIDIA's flash tools are run with arguments built from the flash command
+
+use crate::job_host::JobHost;
+use crate::profiles::ArtifactPin;
+use crate::{
+    checksum, delta, downloads, extract, history, joblog, mirrors, peers, provisioning, release_checksums, skips, timeline, usb_watch,
+    AppState, FlashCommand, FlashProgress,
+};
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command as TokioCommand;
+
+const PREPARED_STAMP: &str = ".cfu_prepared";
+// How often a running download checks for a hard cancel
+const CANCEL_POLL: Duration = Duration::from_secs(1);
+// How long a delta flash waits for the board to come back in recovery mode between partitions
+const DELTA_RECOVERY_WAIT: Duration = Duration::from_secs(120);
+
+// Board and release resolved from a flash command, named as in flash_cordatus.sh
+struct Target {
+    device_flashed: String, // Release family in data/urls.sh; "ALL" from JetPack 5 on
+    device_name: String,    // Board configuration passed to flash.sh
+    jetpack_code: String,   // "5.1.2 - L4T 35.4.1" -> "5_1_2"
+}
+
+struct Archive {
+    file_name: String,
+    url: String,
+    destination: PathBuf, // Where the archive is extracted
+    label: &'static str,
+}
+
+// Developer kits only; carrier boards need vendor setup steps that only the script knows
+fn board(command: &FlashCommand) -> Option<(&'static str, &'static str)> {
+    match (command.product.as_str(), command.device_module.as_str()) {
+        ("Orin", "AGX Orin") => Some(("agx_orin_devkit", "jetson-agx-orin-devkit")),
+        ("Xavier", "AGX Xavier") => Some(("agx_xavier_xavier_nx", "jetson-agx-xavier-devkit")),
+        ("Xavier", "Xavier NX") => Some(("agx_xavier_xavier_nx", "jetson-xavier-nx-devkit")),
+        ("Nano", _) => Some(("Nano", "jetson-nano-devkit")),
+        _ => None,
+    }
+}
+
+pub fn is_supported(command: &FlashCommand) -> bool {
+    board(command).is_some() && matches!(command.storage_device.as_str(), "Micro SD" | "NVMe SSD")
+}
+
+fn jetpack_major(command: &FlashCommand) -> u32 {
+    command.jetpack_version.chars().next().and_then(|c| c.to_digit(10)).unwrap_or(0)
+}
+
+fn resolve(command: &FlashCommand) -> Result<Target> {
+    let (device_flashed, device_name) = board(command).with_context(|| {
+        format!("{} {} is only supported by flash_cordatus.sh", command.product, command.device_module)
+    })?;
+    let jetpack_code = command.jetpack_version.split_whitespace().next().unwrap_or_default().replace('.', "_");
+    // From JetPack 5 on one release covers every developer kit
+    let device_flashed = if jetpack_major(command) != 4 { "ALL" } else { device_flashed };
+    Ok(Target {
+        device_flashed: device_flashed.to_string(),
+        device_name: device_name.to_string(),
+        jetpack_code,
+    })
+}
+
+// Release URL arrays of data/urls.sh: readonly NAME=("url" \ "url" ...)
+fn load_urls(path: &Path) -> Result<HashMap<String, Vec<String>>> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let array_regex = Regex::new(r"(?s)readonly\s+(\w+)=\((.*?)\)")?;
+    let url_regex = Regex::new(r#""([^"]+)""#)?;
+    Ok(array_regex
+        .captures_iter(&text)
+        .map(|caps| {
+            let urls = url_regex.captures_iter(&caps[2]).map(|url| url[1].to_string()).collect();
+            (caps[1].to_string(), urls)
+        })
+        .collect())
+}
+
+// Xavier releases 4.6.3 to 4.6.5 need the separately shipped secure boot files
+fn needs_secure_boot(command: &FlashCommand, target: &Target) -> bool {
+    command.product == "Xavier" && matches!(target.jetpack_code.as_str(), "4_6_3" | "4_6_4" | "4_6_5")
+}
+
+fn archives(command: &FlashCommand, target: &Target, urls: &HashMap<String, Vec<String>>, openzeka: &Path) -> Result<Vec<Archive>> {
+    let key = format!("{}_{}", target.device_flashed.to_uppercase(), target.jetpack_code);
+    let links = urls.get(&key).with_context(|| format!("No release files listed for {}", key))?;
+    let link = |index: usize| links.get(index).cloned().with_context(|| format!("Release {} lists too few files", key));
+    let suffix = format!("{}_{}", target.device_flashed, target.jetpack_code);
+
+    let mut archives = vec![
+        Archive {
+            file_name: format!("bsp_files_{}.tbz2", suffix),
+            url: link(0)?,
+            destination: openzeka.to_path_buf(),
+            label: "BSP files",
+        },
+        Archive {
+            file_name: format!("sample_root_files_{}.tbz2", suffix),
+            url: link(1)?,
+            destination: openzeka.join("Linux_for_Tegra").join("rootfs"),
+            label: "Sample Root Filesystem",
+        },
+    ];
+    if needs_secure_boot(command, target) {
+        archives.push(Archive {
+            file_name: format!("secure_boot_{}.tbz2", suffix),
+            url: link(2)?,
+            destination: openzeka.to_path_buf(),
+            label: "Secure Boot Files",
+        });
+    }
+    Ok(archives)
+}
+
+// Same rule as the script: 18.04 and 20.04 for every release, 22.04 from JetPack 5 on
+fn check_host(command: &FlashCommand) -> Result<()> {
+    let os_release = std::fs::read_to_string("/etc/os-release").unwrap_or_default();
+    let version = os_release
+        .lines()
+        .find_map(|line| line.strip_prefix("VERSION_ID="))
+        .map(|v| v.trim_matches('"').to_string())
+        .unwrap_or_default();
+    match version.as_str() {
+        "18.04" | "20.04" => Ok(()),
+        "22.04" if jetpack_major(command) >= 5 => Ok(()),
+        _ => Err(anyhow::anyhow!(
+            "Your host computer Ubuntu version is {}, please use Ubuntu 20.04 or 18.04",
+            if version.is_empty() { "unknown" } else { &version }
+        )),
+    }
+}
+
+struct Job<'a, H: JobHost> {
+    app: &'a H,
+    state: &'a Arc<AppState>,
+    flash_id: &'a str,
+    cancel: PathBuf,
+    soft_cancel: PathBuf,
+}
+
+impl<H: JobHost> Job<'_, H> {
+    async fn progress(&self, stage: &str, percent: f32, message: &str, details: Option<String>) -> Result<()> {
+        self.log(message);
+        crate::update_flash_progress(self.state, self.app, self.flash_id, FlashProgress {
+            stage: stage.to_string(),
+            progress: percent,
+            message: message.to_string(),
+            details,
+            start_time: None,
+            estimated_time_remaining: None,
+            bytes_done: None,
+            bytes_total: None,
+            throughput: None,
+            verified: None,
+            failure: None,
+        }).await
+    }
+
+    fn log(&self, line: &str) {
+        debug!("Flash output: {}", line);
+        joblog::append(self.state, self.flash_id, line);
+    }
+
+    // A hard cancel flags the job and leaves the extractor's cancel file behind
+    fn check_cancelled(&self) -> Result<()> {
+        if crate::is_hard_cancelled(self.state, self.flash_id) || self.cancel.exists() {
+            return Err(crate::HardCancelled.into());
+        }
+        Ok(())
+    }
+
+    // Stop here when a soft cancel was requested, like soft_cancel_checkpoint in the script
+    fn checkpoint(&self, next_step: &str) -> Result<()> {
+        self.check_cancelled()?;
+        if self.soft_cancel.exists() {
+            self.log(&format!("Soft cancel requested, stopping before {}", next_step));
+            return Err(crate::SoftCancelled.into());
+        }
+        Ok(())
+    }
+
+    // Run one step under sudo, streaming its output to the job log and progress. The child is kept
+    // in active_flashes so a hard cancel stops its process tree as it would stop the script's.
+    async fn run(&self, program: &str, args: &[&str], current_dir: &Path) -> Result<()> {
+        self.check_cancelled()?;
+        self.log(&format!("sudo {} {}", program, args.join(" ")));
+        let mut cmd = TokioCommand::new("sudo");
+        cmd.arg(program)
+            .args(args)
+            .current_dir(current_dir)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        crate::io_priority::apply(self.state, &mut cmd);
+        crate::process_tree::new_session(&mut cmd);
+        let mut child = cmd.spawn().with_context(|| format!("Failed to start {}", program))?;
+
+        let stdout = child.stdout.take();
+        if let Some(stderr) = child.stderr.take() {
+            let state = Arc::clone(self.state);
+            let flash_id = self.flash_id.to_string();
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    joblog::append_stream(&state, &flash_id, "stderr", &line);
+                }
+            });
+        }
+        self.state.active_flashes.lock().unwrap().insert(self.flash_id.to_string(), child);
+
+        if let Some(stdout) = stdout {
+            let mut lines = BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                self.log(&line);
+                if let Some(progress) = crate::parse_flash_output(&line) {
+                    crate::update_flash_progress(self.state, self.app, self.flash_id, progress).await?;
+                }
+            }
+        }
+
+        // Without a child a hard cancel has taken it to stop it
+        let child = self.state.active_flashes.lock().unwrap().remove(self.flash_id);
+        let mut child = match child {
+            Some(mut child) if crate::is_hard_cancelled(self.state, self.flash_id) => {
+                crate::process_tree::terminate(&mut child).await;
+                return Err(crate::HardCancelled.into());
+            }
+            Some(child) => child,
+            None => return Err(crate::HardCancelled.into()),
+        };
+        let status = child.wait().await.with_context(|| format!("{} failed", program))?;
+        self.check_cancelled()?;
+        if !status.success() {
+            return Err(anyhow::anyhow!("{} exited with error code: {}", program, status.code().unwrap_or(-1)));
+        }
+        Ok(())
+    }
+
+    // Download into a .part file next to the archive, resuming an earlier attempt
+    async fn download(&self, archive: &Archive, path: &Path, percent: f32) -> Result<()> {
+        self.progress("downloading", percent, &format!("downloading file {}", archive.file_name), Some(archive.label.to_string())).await?;
+        let part = path.with_extension("tbz2.part");
+        let started = Instant::now();
+        let mut bytes_done: u64 = 0;
+        let mut last_sample = (Instant::now(), 0u64);
+        let mut on_chunk = |bytes: u64| {
+            bytes_done += bytes;
+            let now = Instant::now();
+            let seconds = now.duration_since(last_sample.0).as_secs_f64();
+            if seconds < 0.5 {
+                return;
+            }
+            let throughput = (bytes_done - last_sample.1) as f64 / seconds;
+            last_sample = (now, bytes_done);
+            timeline::record_bytes(self.state, self.flash_id, bytes_done);
+            self.transfer_progress(bytes_done, throughput);
+        };
+
+        // Mirrors first, then NVIDIA's URL; a failed transfer leaves the partial file for the next source.
+        // What a mirror served is checked against the published checksums like any download.
+        let mirror_settings = self.state.settings.lock().unwrap().mirrors.clone();
+        let transfer = async {
+            let mut result = Err(anyhow::anyhow!("No source for {}", archive.file_name));
+            for source in mirrors::archive_sources(&mirror_settings, &archive.url) {
+                self.log(&format!("Downloading {} from {}", archive.file_name, source.name()));
+                result = downloads::fetch_from(&source, &part, &mut on_chunk).await;
+                match &result {
+                    Ok(()) => break,
+                    Err(e) => warn!("Download of {} from {} failed: {:#}", archive.file_name, source.name(), e),
+                }
+            }
+            result
+        };
+        tokio::pin!(transfer);
+        loop {
+            tokio::select! {
+                result = &mut transfer => {
+                    result.with_context(|| format!("Unable to download {}", archive.label))?;
+                    break;
+                }
+                _ = tokio::time::sleep(CANCEL_POLL) => self.check_cancelled()?,
+            }
+        }
+        tokio::fs::rename(&part, path).await.context("Failed to move downloaded archive into place")?;
+        info!("Downloaded {} in {}s", archive.file_name, started.elapsed().as_secs());
+        Ok(())
+    }
+
+    fn transfer_progress(&self, bytes_done: u64, throughput: f64) {
+        let progress = {
+            let mut flash_progress = self.state.flash_progress.lock().unwrap();
+            let Some(progress) = flash_progress.get_mut(self.flash_id).filter(|p| p.stage == "downloading") else {
+                return;
+            };
+            progress.bytes_done = Some(bytes_done);
+            progress.throughput = Some(throughput);
+            progress.clone()
+        };
+        let _ = self.app.send("flash-progress-update", serde_json::json!({
+            "flash_id": self.flash_id,
+            "progress": progress
+        }));
+    }
+}
+
+// Whether this job goes through the native pipeline: when selected in settings, or when the script is
+// missing and the board is one the pipeline knows
+pub async fn selected(state: &AppState, command: &FlashCommand) -> bool {
+    let enabled = state.settings.lock().unwrap().native_flash;
+    // Delta flashing exists only here
+    enabled || command.delta || (crate::get_script_path().await.is_err() && is_supported(command))
+}
+
+// The release URL arrays of the flash script's data/urls.sh, by array name
+async fn release_urls() -> Result<HashMap<String, Vec<String>>> {
+    let working_dir = crate::get_working_directory().await.map_err(|e| anyhow::anyhow!(e))?;
+    load_urls(&Path::new(&working_dir).join("data").join("urls.sh"))
+}
+
+// Archive names in ~/openzeka and the URLs they are downloaded from, for a developer kit command
+pub async fn release_files(command: &FlashCommand) -> Result<Vec<(String, String)>> {
+    let target = resolve(command)?;
+    let archives = archives(command, &target, &release_urls().await?, &peers::artifact_dir())?;
+    Ok(archives.into_iter().map(|archive| (archive.file_name, archive.url)).collect())
+}
+
+// Build the images without flashing, then write only the partitions that differ from this board's last
+// delta flash. A board that cannot be identified, or has no earlier delta flash, gets every partition.
+async fn flash_delta(job: &Job<'_, impl JobHost>, command: &FlashCommand, target: &Target, boot_dev: &str, l4t: &Path) -> Result<()> {
+    let board = delta::board(job.state, command).await;
+    let board_id = board.as_ref().and_then(|b| b.stable_id.clone());
+    job.progress("flashing", 35.0, "Building partition images", None).await?;
+    job.run("./flash.sh", &["--no-flash", &target.device_name, boot_dev], l4t).await.context("Unable to build the partition images")?;
+    job.progress("flashing", 40.0, "Comparing partition images with the last flash", None).await?;
+    let layout_dir = l4t.to_path_buf();
+    let checksums = tokio::task::spawn_blocking(move || delta::checksums(&layout_dir)).await??;
+    let previous = board_id.as_deref().and_then(|id| delta::previous(job.state, id, &command.device_module));
+
+    match previous.map(|previous| delta::changed(&checksums, &previous)) {
+        None => {
+            job.log("No earlier delta flash of this board, writing every partition");
+            job.progress("flashing", 50.0, &format!("sudo ./flash.sh -r {} {}", target.device_name, boot_dev), None).await?;
+            job.run("./flash.sh", &["-r", &target.device_name, boot_dev], l4t).await.context("Unable to flash the device")?;
+        }
+        Some(changed) if changed.is_empty() => job.log("No partition changed since the last flash, nothing to write"),
+        Some(changed) => {
+            job.log(&format!("Writing changed partitions: {}", changed.join(", ")));
+            for (index, name) in changed.iter().enumerate() {
+                if index > 0 {
+                    // flash.sh reboots the board after each partition
+                    let board = board.as_ref().context("Lost track of the board being flashed")?;
+                    delta::reenter_recovery(job.state, board).await;
+                    job.progress("waiting-for-device", 50.0, &format!("Waiting for the board in recovery mode to write {}", name), None).await?;
+                    let wanted = |device: &crate::JetsonDevice| {
+                        device.stable_id == board.stable_id && device.usb_info.as_ref().is_some_and(|usb| usb.is_recovery_mode)
+                    };
+                    if !usb_watch::wait_for_device(job.state, DELTA_RECOVERY_WAIT, wanted, || job.cancel.exists()).await {
+                        job.check_cancelled()?;
+                        return Err(anyhow::anyhow!("The board did not return to recovery mode to write {}", name));
+                    }
+                }
+                let percent = 50.0 + 40.0 * index as f32 / changed.len() as f32;
+                job.progress("flashing", percent, &format!("sudo ./flash.sh -r -k {} {} {}", name, target.device_name, boot_dev), None).await?;
+                job.run("./flash.sh", &["-r", "-k", name, &target.device_name, boot_dev], l4t)
+                    .await
+                    .with_context(|| format!("Unable to write partition {}", name))?;
+            }
+        }
+    }
+    match board_id {
+        Some(board_id) => history::record_partitions(job.app, job.state, job.flash_id, &board_id, checksums),
+        None => job.log("The board has no serial number or chip UID, the next delta flash will write every partition"),
+    }
+    Ok(())
+}
+
+// Flash a developer kit end to end; returns the checksums of the archives used
+pub async fn run(app: &impl JobHost, state: &Arc<AppState>, flash_id: &str, command: &FlashCommand) -> Result<Vec<ArtifactPin>> {
+    let target = resolve(command)?;
+    if !is_supported(command) {
+        return Err(anyhow::anyhow!("{} storage is only supported by flash_cordatus.sh", command.storage_device));
+    }
+    let urls = release_urls().await?;
+    let openzeka = peers::artifact_dir();
+    let archives = archives(command, &target, &urls, &openzeka)?;
+    let l4t = openzeka.join("Linux_for_Tegra");
+
+    let job = Job {
+        app,
+        state,
+        flash_id,
+        cancel: extract::cancel_file(app, flash_id)?,
+        soft_cancel: crate::soft_cancel_file(app, flash_id)?,
+    };
+    joblog::open(app, state, flash_id);
+    info!("Flashing {} {} natively: {:?}", command.device_module, command.jetpack_version, target.device_name);
+
+    check_host(command)?;
+    job.log("Ubuntu version is compatible, processing with flashing");
+    // Like the script's lsusb check, any NVIDIA device on USB is a board in recovery mode
+    let devices = crate::scan_usb_devices(state).await.map_err(|e| anyhow::anyhow!(e))?;
+    if devices.is_empty() {
+        return Err(anyhow::anyhow!("Cannot find a force recovery device"));
+    }
+
+    // Downloads: 10-30%
+    std::fs::create_dir_all(&openzeka).context("Unable to create openzeka folder")?;
+    for (index, archive) in archives.iter().enumerate() {
+        let path = openzeka.join(&archive.file_name);
+        if !path.exists() && skips::skips(command, "download") {
+            return Err(anyhow::anyhow!("Download was skipped but {} is not in {}", archive.file_name, openzeka.display()));
+        }
+        if !path.exists() {
+            job.download(archive, &path, 10.0 + 20.0 * index as f32 / archives.len() as f32).await?;
+        }
+    }
+    job.log("Downloading has been finished!");
+
+    // Against NVIDIA's published checksums, before anything is extracted
+    if !skips::skips(command, "verification") {
+        job.progress("verifying-download", 30.0, "Checking downloads against published checksums", None).await?;
+        let checksums = release_checksums::prepare(app, state).await.and_then(|path| release_checksums::load(&path))?;
+        for archive in &archives {
+            let path = openzeka.join(&archive.file_name);
+            let url = archive.url.clone();
+            let checksums = checksums.clone();
+            let result = tokio::task::spawn_blocking(move || release_checksums::verify(&path, &url, &checksums)).await??;
+            job.log(&format!("Checking download {} against published checksums: {}", result.file_name, result.status));
+            if result.status == "mismatch" {
+                return Err(anyhow::anyhow!("{} does not match its published checksum, delete it to download it again", archive.file_name));
+            }
+        }
+    }
+
+    // Checksums of the archives used, verified against the profile's pins
+    let paths: Vec<PathBuf> = if skips::skips(command, "verification") {
+        job.log("Skipping checksum verification");
+        Vec::new()
+    } else {
+        archives.iter().map(|a| openzeka.join(&a.file_name)).collect()
+    };
+    let sums = tokio::task::spawn_blocking(move || checksum::sha256_files(&paths)).await?;
+    let mut artifacts = Vec::new();
+    for (archive, sha256) in archives.iter().zip(sums) {
+        artifacts.push(ArtifactPin {
+            file_name: archive.file_name.clone(),
+            sha256: sha256.with_context(|| format!("Failed to checksum {}", archive.file_name))?,
+        });
+    }
+    if !command.pinned_artifacts.is_empty() {
+        job.log("Verifying pinned artifact checksums ...");
+    }
+    for pin in &command.pinned_artifacts {
+        if let Some(actual) = artifacts.iter().find(|a| a.file_name == pin.file_name) {
+            if !actual.sha256.eq_ignore_ascii_case(&pin.sha256) {
+                return Err(anyhow::anyhow!("Downloaded or cached artifacts differ from the pinned checksums: {}", pin.file_name));
+            }
+        }
+    }
+
+    job.checkpoint("removing old files")?;
+
+    // Files prepared by an earlier prefetch of the same configuration are used as they are
+    let stamp = openzeka.join(PREPARED_STAMP);
+    let prepared_config = format!("{}_{}_{}", command.product, target.device_flashed, target.jetpack_code);
+    let reuse_prepared = l4t.is_dir() && std::fs::read_to_string(&stamp).is_ok_and(|s| s.trim() == prepared_config);
+    std::fs::remove_file(&stamp).ok();
+
+    if reuse_prepared {
+        job.log(&format!("Using files prepared in advance for {}", prepared_config));
+    } else if skips::skips(command, "extraction") {
+        if !l4t.is_dir() {
+            return Err(anyhow::anyhow!("Extraction was skipped but {} does not exist", l4t.display()));
+        }
+        job.log("Skipping extraction, using the existing Linux_for_Tegra");
+    } else {
+        if l4t.is_dir() {
+            job.progress("preparing", 30.0, "Removing old files...", None).await?;
+            job.run("rm", &["-r", "Linux_for_Tegra"], &openzeka).await?;
+        }
+        let exe = std::env::current_exe().context("Failed to locate the extractor")?;
+        let exe = exe.to_string_lossy();
+        let cancel = job.cancel.to_string_lossy();
+        for archive in &archives {
+            let path = openzeka.join(&archive.file_name);
+            job.progress("preparing", 30.0, &format!("Extracting {}, this may take a while...", archive.file_name), Some(archive.label.to_string())).await?;
+            std::fs::create_dir_all(&archive.destination).ok();
+            let destination = archive.destination.to_string_lossy();
+            job.run(&exe, &["--extract", &path.to_string_lossy(), &destination, &cancel], &openzeka)
+                .await
+                .with_context(|| format!("Unable to extract {}", archive.label))?;
+        }
+
+        job.progress("preparing", 30.0, "Applying binaries ...", None).await?;
+        job.run("./apply_binaries.sh", &[], &l4t).await.context("Unable to apply binaries")?;
+        job.run("./tools/l4t_flash_prerequisites.sh", &[], &l4t).await.context("Unable to complete flash prerequisites")?;
+    }
+
+    // Prepared files are kept for the next flash when a soft cancel stops here
+    if job.soft_cancel.exists() {
+        std::fs::write(&stamp, &prepared_config).ok();
+    }
+    job.checkpoint("flashing the device")?;
+
+    let has_provisioning = command.provisioning.as_ref().is_some_and(|o| !o.is_empty());
+    if has_provisioning || command.custom_kernel.is_some() {
+        let hook = provisioning::write_rootfs_hook(app, flash_id, command)?;
+        let root = format!("ROOT={}", l4t.join("rootfs").display());
+        job.progress("preparing", 30.0, "Applying provisioning to the root filesystem", None).await?;
+        job.run("env", &[&root, "bash", &hook.to_string_lossy()], &l4t).await.context("Unable to apply provisioning to the root filesystem")?;
+    }
+
+    // The bound board may have been swapped while the files were prepared
+    if let Some(binding) = &command.device_binding {
+        crate::binding::enforce(app, state, flash_id, binding).await?;
+    }
+
+    // Flashing: 30-90%
+    match command.storage_device.as_str() {
+        "Micro SD" => {
+            let internal = command.device_module == "AGX Orin"
+                && matches!(command.jetpack_version.as_str(), "6.0.DP - L4T 36.2" | "6.2 - L4T 36.4.3");
+            let boot_dev = if internal { "internal" } else { "mmcblk0p1" };
+            if command.delta {
+                flash_delta(&job, command, &target, boot_dev, &l4t).await?;
+            } else {
+                job.progress("flashing", 50.0, &format!("sudo ./flash.sh {} {}", target.device_name, boot_dev), None).await?;
+                job.run("./flash.sh", &[&target.device_name, boot_dev], &l4t).await.context("Unable to flash the device")?;
+            }
+        }
+        _ => {
+            job.progress("flashing", 50.0, "./nvsdkmanager_flash.sh --storage nvme0n1p1", None).await?;
+            job.run("./nvsdkmanager_flash.sh", &["--storage", "nvme0n1p1"], &l4t).await.context("Unable to flash the device")?;
+        }
+    }
+
+    if !command.keep_files {
+        job.progress("verifying", 90.0, "Deleting installation files", None).await?;
+        job.run("rm", &["-r", &openzeka.to_string_lossy()], Path::new("/")).await.context("Unable to delete installation files")?;
+    }
+    job.log("Your device has been flashed successfully...");
+    Ok(artifacts)
+}
@@ -0,0 +1,154 @@
+// CFU - Cordatus Flash Utility - Job Notes
+// Free-text notes and attached photos or files on flash job records, for documenting rework and anomalies
+
+use crate::history;
+use crate::AppState;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tauri::{command, State};
+use uuid::Uuid;
+
+// Attachments are copied under the app data directory, one folder per job
+const ATTACHMENTS_DIR: &str = "attachments";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobNote {
+    pub note_id: String,
+    pub text: String,
+    pub author: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobAttachment {
+    pub attachment_id: String,
+    pub file_name: String, // Original name, for display
+    pub path: String,      // Stored copy
+    pub size_bytes: u64,
+    pub added_at: DateTime<Utc>,
+}
+
+fn job_exists(state: &AppState, flash_id: &str) -> bool {
+    state.history.lock().unwrap().jobs.iter().any(|r| r.flash_id == flash_id)
+}
+
+fn attachment_dir(app: &tauri::AppHandle, flash_id: &str) -> Result<PathBuf> {
+    let dir = crate::app_data_file(app, ATTACHMENTS_DIR)?.join(flash_id);
+    std::fs::create_dir_all(&dir).context("Failed to create attachment directory")?;
+    Ok(dir)
+}
+
+fn store_attachment(app: &tauri::AppHandle, flash_id: &str, source: &Path) -> Result<JobAttachment> {
+    let file_name = source
+        .file_name()
+        .and_then(|name| name.to_str())
+        .context("Attachment path has no file name")?
+        .to_string();
+    let attachment_id = Uuid::new_v4().to_string();
+    // Prefix the ID so two photos named "IMG_0001.jpg" do not collide
+    let path = attachment_dir(app, flash_id)?.join(format!("{}_{}", &attachment_id[..8], file_name));
+    let size_bytes = std::fs::copy(source, &path).with_context(|| format!("Failed to copy {}", source.display()))?;
+    Ok(JobAttachment {
+        attachment_id,
+        file_name,
+        path: path.to_string_lossy().to_string(),
+        size_bytes,
+        added_at: Utc::now(),
+    })
+}
+
+// Add a note to a flash job
+#[command]
+pub async fn add_job_note(
+    flash_id: String,
+    text: String,
+    author: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<JobNote, String> {
+    if text.trim().is_empty() {
+        return Err("Note is empty".to_string());
+    }
+    if !job_exists(&state, &flash_id) {
+        return Err(format!("Flash job not found: {}", flash_id));
+    }
+
+    let note = JobNote {
+        note_id: Uuid::new_v4().to_string(),
+        text: text.trim().to_string(),
+        author: author.filter(|a| !a.trim().is_empty()),
+        created_at: Utc::now(),
+    };
+    history::update_history(&app, &state, |history| {
+        if let Some(record) = history.jobs.iter_mut().find(|r| r.flash_id == flash_id) {
+            record.notes.push(note.clone());
+        }
+    });
+    Ok(note)
+}
+
+// Remove a note from a flash job
+#[command]
+pub async fn delete_job_note(
+    flash_id: String,
+    note_id: String,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    history::update_history(&app, &state, |history| {
+        if let Some(record) = history.jobs.iter_mut().find(|r| r.flash_id == flash_id) {
+            record.notes.retain(|n| n.note_id != note_id);
+        }
+    });
+    Ok(())
+}
+
+// Copy a photo or file into the job's attachment folder and link it to the job
+#[command]
+pub async fn attach_job_file(
+    flash_id: String,
+    path: String,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<JobAttachment, String> {
+    if !job_exists(&state, &flash_id) {
+        return Err(format!("Flash job not found: {}", flash_id));
+    }
+    let attachment = store_attachment(&app, &flash_id, Path::new(&path)).map_err(|e| e.to_string())?;
+    history::update_history(&app, &state, |history| {
+        if let Some(record) = history.jobs.iter_mut().find(|r| r.flash_id == flash_id) {
+            record.attachments.push(attachment.clone());
+        }
+    });
+
+    info!("Attached {} to flash {}", attachment.file_name, flash_id);
+    Ok(attachment)
+}
+
+// Unlink an attachment and delete its stored copy
+#[command]
+pub async fn remove_job_attachment(
+    flash_id: String,
+    attachment_id: String,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    let mut removed = None;
+    history::update_history(&app, &state, |history| {
+        if let Some(record) = history.jobs.iter_mut().find(|r| r.flash_id == flash_id) {
+            if let Some(index) = record.attachments.iter().position(|a| a.attachment_id == attachment_id) {
+                removed = Some(record.attachments.remove(index));
+            }
+        }
+    });
+
+    let attachment = removed.ok_or_else(|| format!("Attachment not found: {}", attachment_id))?;
+    if let Err(e) = std::fs::remove_file(&attachment.path) {
+        warn!("Failed to delete attachment {}: {}", attachment.path, e);
+    }
+    Ok(())
+}
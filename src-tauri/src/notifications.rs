@@ -0,0 +1,121 @@
+// CFU - Cordatus Flash Utility - Email Notifications
+// Optional SMTP mail on flash job completion or failure, with the job report attached, for unattended batch runs
+
+use crate::credentials;
+use crate::history::FlashJobRecord;
+use crate::settings::EmailSettings;
+use crate::AppState;
+use anyhow::{Context, Result};
+use lettre::message::header::ContentType;
+use lettre::message::{Attachment, Mailbox, MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use log::{info, warn};
+use tauri::command;
+
+fn job_summary(job: &FlashJobRecord) -> String {
+    let duration = job
+        .finished_at
+        .map(|finished| format!("{} min", (finished - job.started_at).num_minutes()))
+        .unwrap_or_else(|| "unknown".to_string());
+    let mut body = format!(
+        "Flash job {} finished with status: {}\n\n\
+         Module:     {} ({})\n\
+         JetPack:    {}\n\
+         Storage:    {}\n\
+         Serial:     {}\n\
+         Started:    {}\n\
+         Duration:   {}\n",
+        job.flash_id,
+        job.status,
+        job.command.device_module,
+        job.command.product,
+        job.command.jetpack_version,
+        job.command.storage_device,
+        job.serial_number.as_deref().unwrap_or("not captured"),
+        job.started_at.format("%Y-%m-%d %H:%M UTC"),
+        duration
+    );
+    if let Some(profile) = &job.profile {
+        body.push_str(&format!("Profile:    {}\n", profile));
+    }
+    if let Some(error) = &job.error {
+        body.push_str(&format!("\nError:\n{}\n", error));
+    }
+    body.push_str("\nThe full job record is attached.\n");
+    body
+}
+
+async fn send_email(settings: &EmailSettings, subject: &str, body: String, attachment: Option<(String, String)>) -> Result<()> {
+    if settings.smtp_host.is_empty() || settings.from.is_empty() || settings.to.is_empty() {
+        return Err(anyhow::anyhow!("SMTP host, sender and recipients must be configured"));
+    }
+
+    let mut builder = Message::builder()
+        .from(settings.from.parse::<Mailbox>().context("Invalid sender address")?)
+        .subject(subject);
+    for to in &settings.to {
+        builder = builder.to(to.parse::<Mailbox>().with_context(|| format!("Invalid recipient address: {}", to))?);
+    }
+    let mut parts = MultiPart::mixed().singlepart(SinglePart::plain(body));
+    if let Some((file_name, content)) = attachment {
+        parts = parts.singlepart(Attachment::new(file_name).body(content, ContentType::parse("application/json")?));
+    }
+    let message = builder.multipart(parts)?;
+
+    let mut transport = match settings.security.as_str() {
+        "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(&settings.smtp_host)?,
+        "starttls" => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&settings.smtp_host)?,
+        "none" => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&settings.smtp_host),
+        other => return Err(anyhow::anyhow!("Unknown SMTP security mode: {}", other)),
+    }
+    .port(settings.smtp_port);
+    if !settings.username.is_empty() {
+        let password = credentials::get_secret("smtp")?.unwrap_or_default();
+        transport = transport.credentials(Credentials::new(settings.username.clone(), password));
+    }
+
+    transport.build().send(message).await.context("Failed to send email")?;
+    Ok(())
+}
+
+// Mail the outcome of a finished job if enabled; failures are only logged
+pub async fn notify_job_finished(state: &AppState, flash_id: &str) {
+    let settings = state.settings.lock().unwrap().email.clone();
+    if !settings.enabled {
+        return;
+    }
+    let Some(job) = state.history.lock().unwrap().jobs.iter().find(|r| r.flash_id == flash_id).cloned() else {
+        return;
+    };
+    if job.status == "success" && settings.notify_on == "failure" {
+        return;
+    }
+
+    let subject = format!(
+        "[CFU] {} {} flash {}",
+        job.command.device_module,
+        job.command.jetpack_version,
+        job.status
+    );
+    let report = serde_json::to_string_pretty(&job).unwrap_or_default();
+    let attachment = (format!("flash-{}.json", job.flash_id), report);
+    match send_email(&settings, &subject, job_summary(&job), Some(attachment)).await {
+        Ok(()) => info!("Sent {} notification for flash {}", job.status, flash_id),
+        Err(e) => warn!("Failed to send notification for flash {}: {}", flash_id, e),
+    }
+}
+
+// Send a test message with the given settings, before saving them
+#[command]
+pub async fn send_test_email(settings: EmailSettings) -> Result<(), String> {
+    send_email(
+        &settings,
+        "[CFU] Test notification",
+        "Email notifications from Cordatus Flash Utility are working.\n".to_string(),
+        None,
+    )
+    .await
+    .map_err(|e| e.to_string())
+}
+
@@ -0,0 +1,96 @@
+// CFU - Cordatus Flash Utility - Field Pairing
+// QR codes carrying hostname/IP/SSH fingerprint so field technicians can pair with a unit
+
+use crate::ssh::{run_remote, SshTarget};
+use anyhow::{Context, Result};
+use log::info;
+use qrcode::QrCode;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tauri::command;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PairingPayload {
+    pub kind: String,
+    pub hostname: String,
+    pub ip: String,
+    pub port: u16,
+    pub user: String,
+    pub ssh_fingerprint: String,
+}
+
+// Fingerprint the device's ed25519 host key as the host sees it, the same
+// value ssh shows a technician when connecting for the first time
+fn host_key_fingerprint(target: &SshTarget) -> Result<String> {
+    target.validate()?;
+    let port = target.port.unwrap_or(22).to_string();
+    let keyscan = Command::new("ssh-keyscan")
+        .args(["-t", "ed25519", "-p", &port, "--", &target.host])
+        .output()
+        .context("Failed to run ssh-keyscan")?;
+
+    if keyscan.stdout.is_empty() {
+        return Err(anyhow::anyhow!("No SSH host key returned by {}", target.host));
+    }
+
+    let mut keygen = Command::new("ssh-keygen")
+        .args(["-l", "-f", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Failed to run ssh-keygen")?;
+    keygen
+        .stdin
+        .take()
+        .context("ssh-keygen stdin unavailable")?
+        .write_all(&keyscan.stdout)?;
+    let output = keygen.wait_with_output()?;
+
+    // Output looks like "256 SHA256:abc... host (ED25519)"
+    String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .nth(1)
+        .map(|s| s.to_string())
+        .context("Unexpected ssh-keygen output")
+}
+
+async fn build_payload(target: &SshTarget) -> Result<PairingPayload> {
+    let hostname = run_remote(target, "hostname").await?.trim().to_string();
+
+    let fingerprint_target = target.clone();
+    let ssh_fingerprint = tokio::task::spawn_blocking(move || host_key_fingerprint(&fingerprint_target)).await??;
+
+    Ok(PairingPayload {
+        kind: "cfu-pair".to_string(),
+        hostname,
+        ip: target.host.clone(),
+        port: target.port.unwrap_or(22),
+        user: target.user.clone(),
+        ssh_fingerprint,
+    })
+}
+
+fn render_png(data: &str) -> Result<Vec<u8>> {
+    let code = QrCode::new(data.as_bytes()).context("Failed to encode QR code")?;
+    let image = code.render::<image::Luma<u8>>().min_dimensions(320, 320).build();
+
+    let mut png = Vec::new();
+    image::DynamicImage::ImageLuma8(image)
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .context("Failed to encode PNG")?;
+    Ok(png)
+}
+
+// Generate a pairing QR code for a provisioned device, returned as PNG bytes
+#[command]
+pub async fn generate_pairing_qr(target: SshTarget) -> Result<Vec<u8>, String> {
+    info!("Generating pairing QR code for {}", target.host);
+
+    let payload = build_payload(&target)
+        .await
+        .map_err(|e| format!("Failed to collect pairing information: {}", e))?;
+    let json = serde_json::to_string(&payload).map_err(|e| e.to_string())?;
+
+    render_png(&json).map_err(|e| e.to_string())
+}
@@ -0,0 +1,154 @@
+// CFU - Cordatus Flash Utility - Partition Tools
+// GPT layout inspection and post-flash growth of the APP partition on the target
+
+use crate::ssh::{run_remote, shell_quote, SshTarget, SUDO};
+use anyhow::{Context, Result};
+use log::info;
+use serde::{Deserialize, Serialize};
+use tauri::command;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartitionEntry {
+    pub node: String,
+    pub number: u32,
+    pub name: Option<String>,
+    pub type_guid: String,
+    pub start_bytes: u64,
+    pub size_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartitionLayout {
+    pub device: String,
+    pub label: String, // 'gpt' | 'dos'
+    pub sector_size: u64,
+    pub disk_size_bytes: u64,
+    pub partitions: Vec<PartitionEntry>,
+    pub unallocated_tail_bytes: u64, // Free space after the last partition
+}
+
+#[derive(Debug, Deserialize)]
+struct SfdiskDump {
+    partitiontable: SfdiskTable,
+}
+
+#[derive(Debug, Deserialize)]
+struct SfdiskTable {
+    label: String,
+    device: String,
+    #[serde(default = "default_sector_size")]
+    sectorsize: u64,
+    #[serde(default)]
+    partitions: Vec<SfdiskPartition>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SfdiskPartition {
+    node: String,
+    start: u64,
+    size: u64,
+    #[serde(rename = "type")]
+    type_guid: String,
+    name: Option<String>,
+}
+
+fn default_sector_size() -> u64 {
+    512
+}
+
+// "/dev/nvme0n1p12" -> 12, "/dev/sda3" -> 3
+fn partition_number(node: &str) -> Option<u32> {
+    let digits: String = node.chars().rev().take_while(|c| c.is_ascii_digit()).collect();
+    digits.chars().rev().collect::<String>().parse().ok()
+}
+
+async fn read_layout(target: &SshTarget, disk: &str) -> Result<PartitionLayout> {
+    let device = shell_quote(&format!("/dev/{}", disk.trim_start_matches("/dev/")));
+    let output = run_remote(
+        target,
+        &format!("{}$S blockdev --getsize64 {dev}; $S sfdisk -J {dev}", SUDO, dev = device),
+    )
+    .await?;
+
+    let (size_line, json) = output.split_once('\n').context("Unexpected sfdisk output")?;
+    let disk_size_bytes: u64 = size_line.trim().parse().context("Unexpected disk size")?;
+    let dump: SfdiskDump = serde_json::from_str(json).context("Failed to parse partition table")?;
+    let table = dump.partitiontable;
+    let sector = table.sectorsize;
+
+    let partitions: Vec<PartitionEntry> = table
+        .partitions
+        .into_iter()
+        .map(|p| PartitionEntry {
+            number: partition_number(&p.node).unwrap_or(0),
+            node: p.node,
+            name: p.name,
+            type_guid: p.type_guid,
+            start_bytes: p.start * sector,
+            size_bytes: p.size * sector,
+        })
+        .collect();
+
+    // GPT keeps a 33-sector backup header at the end of the disk
+    let usable_end = disk_size_bytes.saturating_sub(if table.label == "gpt" { 33 * sector } else { 0 });
+    let last_end = partitions.iter().map(|p| p.start_bytes + p.size_bytes).max().unwrap_or(0);
+
+    Ok(PartitionLayout {
+        device: table.device,
+        label: table.label,
+        sector_size: sector,
+        disk_size_bytes,
+        unallocated_tail_bytes: usable_end.saturating_sub(last_end),
+        partitions,
+    })
+}
+
+// Read the partition table of a disk on the target
+#[command]
+pub async fn get_partition_layout(target: SshTarget, disk: String) -> Result<PartitionLayout, String> {
+    read_layout(&target, &disk)
+        .await
+        .map_err(|e| format!("Failed to read partition layout: {}", e))
+}
+
+// Grow the APP partition and its ext4 filesystem to fill the rest of the disk.
+// Works on a booted device (online resize) or inside a maintenance session.
+#[command]
+pub async fn grow_app_partition(target: SshTarget, disk: String) -> Result<PartitionLayout, String> {
+    let layout = read_layout(&target, &disk).await.map_err(|e| e.to_string())?;
+
+    let app = layout
+        .partitions
+        .iter()
+        .find(|p| p.name.as_deref() == Some("APP"))
+        .ok_or("No APP partition found on this disk")?;
+
+    let last_start = layout.partitions.iter().map(|p| p.start_bytes).max().unwrap_or(0);
+    if app.start_bytes != last_start {
+        return Err("APP is not the last partition on the disk and cannot be grown in place".to_string());
+    }
+    if layout.unallocated_tail_bytes < 1024 * 1024 {
+        return Err("APP partition already fills the disk".to_string());
+    }
+
+    info!("Growing {} by {} bytes", app.node, layout.unallocated_tail_bytes);
+
+    // Flashed images are smaller than the disk, so the backup GPT header has to
+    // move to the real end of the disk before the partition can grow
+    let device = shell_quote(&layout.device);
+    let script = format!(
+        "{sudo}set -e; \
+         if command -v growpart >/dev/null; then $S growpart {dev} {num}; \
+         else $S sfdisk --relocate gpt-bak-std {dev}; echo ', +' | $S sfdisk --no-reread -N {num} {dev}; $S partprobe {dev} || true; fi; \
+         $S resize2fs {node}",
+        sudo = SUDO,
+        dev = device,
+        num = app.number,
+        node = shell_quote(&app.node)
+    );
+    run_remote(&target, &script)
+        .await
+        .map_err(|e| format!("Failed to grow APP partition: {}", e))?;
+
+    read_layout(&target, &disk).await.map_err(|e| e.to_string())
+}
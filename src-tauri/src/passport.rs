@@ -0,0 +1,321 @@
+// CFU - Cordatus Flash Utility - Device Passport
+// Single JSON or PDF document per device for customer hand-off: flash history, configuration, containers and test results
+
+use crate::asset::{read_device_identity, AssetRecord};
+use crate::benchmarks::BenchmarkResult;
+use crate::history::FlashJobRecord;
+use crate::kernel::KernelArtifacts;
+use crate::provisioning::ProvisioningOptions;
+use crate::ssh::{run_remote, SshTarget, SUDO};
+use crate::thermal::ThermalReport;
+use crate::AppState;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tauri::{command, State};
+
+// US Letter in points, Helvetica 9pt
+const PAGE_WIDTH: u32 = 612;
+const PAGE_HEIGHT: u32 = 792;
+const MARGIN: u32 = 50;
+const LINE_HEIGHT: u32 = 12;
+const MAX_LINE_CHARS: usize = 110;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstalledContainer {
+    pub name: String,
+    pub image: String,
+    pub status: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DevicePassport {
+    pub serial_number: String,
+    pub device_model: Option<String>,
+    pub l4t_version: Option<String>,
+    pub asset_record: Option<AssetRecord>,
+    pub flash_jobs: Vec<FlashJobRecord>,
+    pub provisioning: Option<ProvisioningOptions>, // From the last successful flash
+    pub custom_kernel: Option<KernelArtifacts>,
+    pub installed_containers: Option<Vec<InstalledContainer>>, // None when the device was not reachable
+    pub benchmarks: Vec<BenchmarkResult>,
+    pub thermal_reports: Vec<ThermalReport>,
+    pub generated_at: DateTime<Utc>,
+}
+
+pub async fn list_containers(target: &SshTarget) -> Result<Vec<InstalledContainer>> {
+    let script = format!(
+        "{}$S docker ps -a --format '{{{{.Names}}}}\\t{{{{.Image}}}}\\t{{{{.Status}}}}'",
+        SUDO
+    );
+    let output = run_remote(target, &script).await?;
+    Ok(output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            Some(InstalledContainer {
+                name: fields.next()?.to_string(),
+                image: fields.next()?.to_string(),
+                status: fields.next().unwrap_or_default().to_string(),
+            })
+        })
+        .collect())
+}
+
+fn collect_passport(state: &AppState, serial_number: &str) -> DevicePassport {
+    let history = state.history.lock().unwrap();
+    let flash_jobs: Vec<FlashJobRecord> = history
+        .jobs
+        .iter()
+        .filter(|job| job.serial_number.as_deref() == Some(serial_number))
+        .cloned()
+        .collect();
+    let benchmarks: Vec<BenchmarkResult> = history
+        .benchmarks
+        .iter()
+        .filter(|b| b.serial_number == serial_number)
+        .cloned()
+        .collect();
+    let thermal_reports: Vec<ThermalReport> = history
+        .thermal_reports
+        .iter()
+        .filter(|r| r.serial_number == serial_number)
+        .cloned()
+        .collect();
+    drop(history);
+
+    let asset_record = state
+        .asset_batches
+        .lock()
+        .unwrap()
+        .batches
+        .values()
+        .flatten()
+        .filter(|r| r.serial_number == serial_number)
+        .max_by_key(|r| r.captured_at)
+        .cloned();
+
+    let last_success = flash_jobs.iter().rev().find(|job| job.status == "success");
+    DevicePassport {
+        serial_number: serial_number.to_string(),
+        device_model: asset_record
+            .as_ref()
+            .and_then(|r| r.model.clone())
+            .or_else(|| benchmarks.iter().find_map(|b| b.device_model.clone()))
+            .or_else(|| thermal_reports.iter().find_map(|r| r.device_model.clone())),
+        l4t_version: asset_record.as_ref().and_then(|r| r.l4t_version.clone()),
+        provisioning: last_success.and_then(|job| job.command.provisioning.clone()),
+        custom_kernel: last_success.and_then(|job| job.command.custom_kernel.clone()),
+        asset_record,
+        installed_containers: None,
+        flash_jobs,
+        benchmarks,
+        thermal_reports,
+        generated_at: Utc::now(),
+    }
+}
+
+fn passport_lines(passport: &DevicePassport) -> Vec<String> {
+    let mut lines = vec![
+        "Cordatus Device Passport".to_string(),
+        String::new(),
+        format!("Serial number: {}", passport.serial_number),
+        format!("Model: {}", passport.device_model.as_deref().unwrap_or("unknown")),
+        format!("L4T: {}", passport.l4t_version.as_deref().unwrap_or("unknown")),
+        format!("Generated: {}", passport.generated_at.format("%Y-%m-%d %H:%M UTC")),
+    ];
+    if let Some(record) = &passport.asset_record {
+        lines.push(format!("Hostname: {}", record.hostname));
+        for mac in &record.mac_addresses {
+            lines.push(format!("MAC {}: {}", mac.interface, mac.mac));
+        }
+    }
+
+    lines.push(String::new());
+    lines.push("Flash history".to_string());
+    if passport.flash_jobs.is_empty() {
+        lines.push("  No recorded flash jobs".to_string());
+    }
+    for job in &passport.flash_jobs {
+        lines.push(format!(
+            "  {}  {} / JetPack {} on {}  {}",
+            job.started_at.format("%Y-%m-%d %H:%M"),
+            job.command.device_module,
+            job.command.jetpack_version,
+            job.command.storage_device,
+            job.status
+        ));
+    }
+
+    lines.push(String::new());
+    lines.push("Provisioning".to_string());
+    match &passport.provisioning {
+        Some(options) => {
+            let json = serde_json::to_string_pretty(options).unwrap_or_default();
+            lines.extend(json.lines().map(|line| format!("  {}", line)));
+        }
+        None => lines.push("  Stock configuration".to_string()),
+    }
+    if let Some(kernel) = &passport.custom_kernel {
+        lines.push(format!(
+            "  Custom kernel {} (build {})",
+            kernel.kernel_release.as_deref().unwrap_or("modules only"),
+            kernel.build_id
+        ));
+        for patch in &kernel.patches {
+            lines.push(format!("    patch {} sha256 {}", patch.name, patch.sha256));
+        }
+    }
+
+    lines.push(String::new());
+    lines.push("Installed containers".to_string());
+    match &passport.installed_containers {
+        Some(containers) if containers.is_empty() => lines.push("  None".to_string()),
+        Some(containers) => {
+            for container in containers {
+                lines.push(format!("  {}  {}  {}", container.name, container.image, container.status));
+            }
+        }
+        None => lines.push("  Not queried".to_string()),
+    }
+
+    lines.push(String::new());
+    lines.push("Benchmarks".to_string());
+    if passport.benchmarks.is_empty() {
+        lines.push("  None recorded".to_string());
+    }
+    for benchmark in &passport.benchmarks {
+        let metrics = benchmark
+            .metrics
+            .iter()
+            .map(|m| format!("{} {:.2} {}", m.name, m.value, m.unit))
+            .collect::<Vec<_>>()
+            .join(", ");
+        lines.push(format!(
+            "  {}  {} {}: {}{}",
+            benchmark.ran_at.format("%Y-%m-%d"),
+            benchmark.suite,
+            benchmark.model,
+            metrics,
+            if benchmark.underperforming { "  (below fleet median)" } else { "" }
+        ));
+    }
+
+    lines.push(String::new());
+    lines.push("Thermal and power".to_string());
+    if passport.thermal_reports.is_empty() {
+        lines.push("  None recorded".to_string());
+    }
+    for report in &passport.thermal_reports {
+        lines.push(format!("  {}  {}", report.recorded_at.format("%Y-%m-%d"), report.summary()));
+        for warning in &report.warnings {
+            lines.push(format!("    {}", warning));
+        }
+    }
+    lines
+}
+
+// PDF strings are Latin-1 with ( ) and \ escaped
+fn pdf_text(line: &str) -> String {
+    line.chars()
+        .take(MAX_LINE_CHARS)
+        .map(|c| match c {
+            '(' | ')' | '\\' => format!("\\{}", c),
+            ' '..='~' => c.to_string(),
+            _ => "?".to_string(),
+        })
+        .collect()
+}
+
+// Minimal text-only PDF: one Helvetica font, as many pages as the lines need
+fn render_pdf(lines: &[String]) -> Vec<u8> {
+    let per_page = ((PAGE_HEIGHT - 2 * MARGIN) / LINE_HEIGHT) as usize;
+    let pages: Vec<&[String]> = lines.chunks(per_page).collect();
+
+    // Objects: 1 catalog, 2 page tree, 3 font, then a page and its content stream per page
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            (0..pages.len()).map(|i| format!("{} 0 R", 4 + i * 2)).collect::<Vec<_>>().join(" "),
+            pages.len()
+        ),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_string(),
+    ];
+    for (index, page) in pages.iter().enumerate() {
+        let mut stream = format!("BT /F1 9 Tf {} TL {} {} Td\n", LINE_HEIGHT, MARGIN, PAGE_HEIGHT - MARGIN);
+        for line in *page {
+            stream.push_str(&format!("({}) '\n", pdf_text(line)));
+        }
+        stream.push_str("ET");
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+            PAGE_WIDTH,
+            PAGE_HEIGHT,
+            5 + index * 2
+        ));
+        objects.push(format!("<< /Length {} >>\nstream\n{}\nendstream", stream.len(), stream));
+    }
+
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::new();
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend(format!("{} 0 obj\n{}\nendobj\n", index + 1, object).into_bytes());
+    }
+    let xref = pdf.len();
+    pdf.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).into_bytes());
+    for offset in offsets {
+        pdf.extend(format!("{:010} 00000 n \n", offset).into_bytes());
+    }
+    pdf.extend(
+        format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref).into_bytes(),
+    );
+    pdf
+}
+
+// Export everything recorded about a device as JSON or PDF. With a target the
+// installed containers are read from the device, after checking it is the same unit.
+#[command]
+pub async fn export_device_passport(
+    serial_number: String,
+    path: String,
+    format: String,
+    target: Option<SshTarget>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<DevicePassport, String> {
+    let mut passport = collect_passport(&state, &serial_number);
+
+    if let Some(target) = &target {
+        let identity = read_device_identity(target, "")
+            .await
+            .map_err(|e| format!("Failed to identify device: {}", e))?;
+        if identity.serial_number != serial_number {
+            return Err(format!(
+                "Device at {} is {}, not {}",
+                target.host, identity.serial_number, serial_number
+            ));
+        }
+        passport.device_model = identity.model.or(passport.device_model);
+        passport.l4t_version = identity.l4t_version.or(passport.l4t_version);
+        passport.installed_containers = Some(
+            list_containers(target)
+                .await
+                .map_err(|e| format!("Failed to list containers: {}", e))?,
+        );
+    }
+
+    match format.as_str() {
+        "json" => serde_json::to_vec_pretty(&passport)
+            .context("Failed to serialize passport")
+            .and_then(|json| std::fs::write(&path, json).context("Failed to write JSON file")),
+        "pdf" => std::fs::write(&path, render_pdf(&passport_lines(&passport))).context("Failed to write PDF file"),
+        other => return Err(format!("Unsupported export format: {}", other)),
+    }
+    .map_err(|e| format!("Failed to export passport: {}", e))?;
+
+    info!("Exported device passport for {} to {}", serial_number, path);
+    Ok(passport)
+}
@@ -0,0 +1,271 @@
+// CFU - Cordatus Flash Utility - Peer Cache
+// Stations on the same LAN announce themselves over mDNS and serve their downloaded BSP archives,
+// so only one machine per lab downloads a release from NVIDIA; fetched files are checksum-verified
+// against their pin or NVIDIA's published checksum
+
+use crate::job_host::JobHost;
+use crate::{checksum, native_flash, release_checksums, AppState, FlashCommand};
+use anyhow::{Context, Result};
+use log::{info, warn};
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{command, Manager, State};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+const SERVICE_TYPE: &str = "_cfu-cache._tcp.local.";
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(2);
+const FETCH_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const FETCH_STALL_TIMEOUT: Duration = Duration::from_secs(30); // Longest wait for the next chunk of an archive
+const ARCHIVE_EXTENSIONS: &[&str] = &[".tbz2", ".tar", ".tar.gz", ".tgz", ".tar.bz2", ".tar.zst", ".zip"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CachePeer {
+    pub station: String,
+    pub address: String,
+    pub port: u16,
+}
+
+impl CachePeer {
+    pub fn artifact_url(&self, file_name: &str) -> String {
+        format!("http://{}:{}/artifacts/{}", self.address, self.port, file_name)
+    }
+}
+
+// Where flash_cordatus.sh keeps downloaded archives
+pub fn artifact_dir() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+    PathBuf::from(home).join("openzeka")
+}
+
+// Only plain archive names directly in the artifact directory are shared
+fn is_archive_name(name: &str) -> bool {
+    !name.starts_with('.') && !name.contains(['/', '\\']) && ARCHIVE_EXTENSIONS.iter().any(|ext| name.ends_with(ext))
+}
+
+// The mDNS daemon, started on first use and shared by announcing and browsing
+#[derive(Default)]
+pub struct PeerDaemon(Mutex<Option<ServiceDaemon>>);
+
+impl std::fmt::Debug for PeerDaemon {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("PeerDaemon")
+    }
+}
+
+fn daemon(state: &AppState) -> Result<ServiceDaemon> {
+    let mut daemon = state.peer_daemon.0.lock().unwrap();
+    if let Some(daemon) = daemon.as_ref() {
+        return Ok(daemon.clone());
+    }
+    let created = ServiceDaemon::new().context("Failed to start mDNS")?;
+    *daemon = Some(created.clone());
+    Ok(created)
+}
+
+// Serve cached archives and announce this station, when enabled in settings
+pub fn start(app: tauri::AppHandle) {
+    let state = Arc::clone(app.state::<Arc<AppState>>().inner());
+    let settings = state.settings.lock().unwrap().peer_cache.clone();
+    if !settings.enabled {
+        return;
+    }
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = serve(&state, settings.port).await {
+            warn!("Peer cache unavailable: {:#}", e);
+        }
+    });
+}
+
+async fn serve(state: &AppState, port: u16) -> Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", port))
+        .await
+        .with_context(|| format!("Failed to listen on port {}", port))?;
+
+    let station = crate::history::station_name(state).unwrap_or_else(|| "cfu".to_string());
+    let host_name = format!("{}.local.", sys_info::hostname().unwrap_or_else(|_| "cfu".to_string()));
+    let instance = station.replace('.', "-");
+    let info = ServiceInfo::new(SERVICE_TYPE, &instance, &host_name, "", port, &[("station", station.as_str())][..])?
+        .enable_addr_auto();
+    daemon(state)?.register(info).context("Failed to announce peer cache")?;
+    info!("Sharing cached artifacts with peers on port {}", port);
+
+    loop {
+        let (stream, remote) = listener.accept().await?;
+        tokio::spawn(async move {
+            if let Err(e) = handle_request(stream).await {
+                warn!("Peer cache request from {} failed: {}", remote, e);
+            }
+        });
+    }
+}
+
+// Minimal HTTP/1.0 responder for `GET /artifacts/<file name>`
+async fn handle_request(stream: TcpStream) -> Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    // Headers are not needed, but must be consumed before replying
+    let mut header = String::new();
+    while reader.read_line(&mut header).await? > 2 {
+        header.clear();
+    }
+
+    let mut stream = reader.into_inner();
+    let mut parts = request_line.split_whitespace();
+    let path = match (parts.next(), parts.next()) {
+        (Some("GET"), Some(target)) => target
+            .strip_prefix("/artifacts/")
+            .filter(|name| is_archive_name(name))
+            .map(|name| artifact_dir().join(name))
+            .filter(|path| path.is_file()),
+        _ => None,
+    };
+    let Some(path) = path else {
+        stream.write_all(b"HTTP/1.0 404 Not Found\r\nContent-Length: 0\r\n\r\n").await?;
+        return Ok(());
+    };
+
+    let mut file = tokio::fs::File::open(&path).await?;
+    let length = file.metadata().await?.len();
+    let header = format!(
+        "HTTP/1.0 200 OK\r\nContent-Type: application/octet-stream\r\nContent-Length: {}\r\n\r\n",
+        length
+    );
+    stream.write_all(header.as_bytes()).await?;
+    tokio::io::copy(&mut file, &mut stream).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+// Stations announcing a peer cache, other than this one
+pub async fn discover(state: &AppState, timeout: Duration) -> Result<Vec<CachePeer>> {
+    let own_station = crate::history::station_name(state).unwrap_or_default();
+    let daemon = daemon(state)?;
+    let receiver = daemon.browse(SERVICE_TYPE).context("Failed to browse for peers")?;
+
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut peers: Vec<CachePeer> = Vec::new();
+    while let Ok(Ok(event)) = tokio::time::timeout_at(deadline, receiver.recv_async()).await {
+        let ServiceEvent::ServiceResolved(info) = event else {
+            continue;
+        };
+        let station = info.get_property_val_str("station").unwrap_or(info.get_fullname()).to_string();
+        let Some(address) = info.get_addresses_v4().into_iter().next() else {
+            continue;
+        };
+        if station != own_station && !peers.iter().any(|p| p.station == station) {
+            peers.push(CachePeer { station, address: address.to_string(), port: info.get_port() });
+        }
+    }
+    daemon.stop_browse(SERVICE_TYPE).ok();
+    Ok(peers)
+}
+
+// Archives take minutes over the LAN, so only connecting and stalls are timed out, and the job's hard
+// cancel is checked between chunks
+async fn fetch_verified(client: &reqwest::Client, url: &str, sha256: &str, dest: &Path, state: &AppState, flash_id: &str) -> Result<()> {
+    let mut partial = dest.as_os_str().to_owned();
+    partial.push(".peer");
+    let partial = PathBuf::from(partial);
+
+    let stalled = || anyhow::anyhow!("no data for {}s", FETCH_STALL_TIMEOUT.as_secs());
+    let mut response = tokio::time::timeout(FETCH_STALL_TIMEOUT, client.get(url).send())
+        .await
+        .map_err(|_| stalled())??
+        .error_for_status()?;
+    let mut file = tokio::fs::File::create(&partial).await?;
+    while let Some(chunk) = tokio::time::timeout(FETCH_STALL_TIMEOUT, response.chunk()).await.map_err(|_| stalled())?? {
+        if crate::is_hard_cancelled(state, flash_id) {
+            drop(file);
+            tokio::fs::remove_file(&partial).await.ok();
+            return Err(crate::HardCancelled.into());
+        }
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    drop(file);
+
+    let hashed = partial.clone();
+    let actual = tokio::task::spawn_blocking(move || checksum::sha256_file(&hashed)).await??;
+    if !actual.eq_ignore_ascii_case(sha256) {
+        tokio::fs::remove_file(&partial).await.ok();
+        return Err(anyhow::anyhow!("checksum mismatch"));
+    }
+    tokio::fs::rename(&partial, dest).await?;
+    Ok(())
+}
+
+// Archives of the command's release that can be verified after fetching them from a peer, as
+// (file name, sha256): its pinned archives, and the release's BSP and rootfs archives that NVIDIA
+// publishes a SHA-256 for
+async fn verifiable_archives(app: &impl JobHost, state: &AppState, command: &FlashCommand) -> Vec<(String, String)> {
+    let mut archives: Vec<(String, String)> =
+        command.pinned_artifacts.iter().map(|pin| (pin.file_name.clone(), pin.sha256.clone())).collect();
+    let published = match release_checksums::prepare(app, state).await.and_then(|path| release_checksums::load(&path)) {
+        Ok(published) => published,
+        Err(e) => {
+            warn!("No published checksums for the peer cache: {:#}", e);
+            return archives;
+        }
+    };
+    // Only developer kit releases have a file list; other commands bring their archives as pins
+    for (file_name, url) in native_flash::release_files(command).await.unwrap_or_default() {
+        let sha256 = published.files.iter().find(|file| file.url == url).and_then(|file| file.sha256.clone());
+        if let Some(sha256) = sha256.filter(|_| !archives.iter().any(|(name, _)| *name == file_name)) {
+            archives.push((file_name, sha256));
+        }
+    }
+    archives
+}
+
+// Copy archives this station lacks from a peer before a flash, so the flash finds them and skips its
+// own download. Archives without a checksum to verify against are left to the flash, as is anything
+// no peer could provide. Fails only when the job is cancelled meanwhile.
+pub async fn seed_artifacts(app: &impl JobHost, state: &AppState, flash_id: &str, command: &FlashCommand) -> Result<()> {
+    if !state.settings.lock().unwrap().peer_cache.enabled {
+        return Ok(());
+    }
+    let dir = artifact_dir();
+    let missing: Vec<(String, String)> = verifiable_archives(app, state, command)
+        .await
+        .into_iter()
+        .filter(|(file_name, _)| is_archive_name(file_name) && !dir.join(file_name).exists())
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    let peers = match discover(state, DISCOVERY_TIMEOUT).await {
+        Ok(peers) => peers,
+        Err(e) => {
+            warn!("Peer discovery failed: {:#}", e);
+            return Ok(());
+        }
+    };
+    let client = reqwest::Client::builder().connect_timeout(FETCH_CONNECT_TIMEOUT).build()?;
+    std::fs::create_dir_all(&dir).ok();
+    for (file_name, sha256) in missing {
+        for peer in &peers {
+            let url = peer.artifact_url(&file_name);
+            match fetch_verified(&client, &url, &sha256, &dir.join(&file_name), state, flash_id).await {
+                Ok(()) => {
+                    info!("Fetched {} from peer {}", file_name, peer.station);
+                    break;
+                }
+                Err(e) if e.is::<crate::HardCancelled>() => return Err(e),
+                Err(e) => warn!("Fetching {} from peer {} failed: {:#}", file_name, peer.station, e),
+            }
+        }
+    }
+    Ok(())
+}
+
+// Stations on the LAN sharing their artifact cache
+#[command]
+pub async fn list_cache_peers(state: State<'_, Arc<AppState>>) -> Result<Vec<CachePeer>, String> {
+    discover(&state, DISCOVERY_TIMEOUT).await.map_err(|e| e.to_string())
+}
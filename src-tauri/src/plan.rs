@@ -0,0 +1,245 @@
+// CFU - Cordatus Flash Utility - Flash Plans
+// Dry run of a flash: the exact script invocation, the archives it would download or reuse and the stages it
+// would go through with estimated durations, resolved by the flash script itself without touching the device
+
+use crate::{connectivity, AppState, FlashCommand};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tauri::{command, State};
+use tokio::process::Command as TokioCommand;
+use uuid::Uuid;
+
+// Stage durations used until the history has a successful job of the same configuration
+const DEFAULT_DOWNLOAD_SECS: u64 = 300;
+const DEFAULT_EXTRACT_SECS: u64 = 240;
+const DEFAULT_FLASH_SECS: u64 = 900;
+const DEFAULT_VERIFY_SECS: u64 = 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedArtifact {
+    pub file_name: String, // In ~/openzeka
+    pub url: String,
+    pub cached: bool, // Already downloaded, so it is reused as is
+    pub pinned_sha256: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedStage {
+    pub stage: String, // Progress stage names: 'downloading' | 'preparing' | 'flashing' | 'verifying'
+    pub description: String,
+    pub estimated_secs: u64,
+}
+
+// How the flash script is started for a command
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptInvocation {
+    pub program: String,
+    pub arguments: Vec<String>,
+    pub working_directory: String,
+    pub environment: BTreeMap<String, String>, // Variables set for the script; file paths are per job
+    pub rootfs_hook: Option<String>, // Provisioning / kernel script applied to the rootfs
+    pub artifact_pins: Option<String>, // sha256sum check list the archives must match
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlashPlan {
+    pub command: FlashCommand,
+    #[serde(flatten)]
+    pub invocation: ScriptInvocation,
+    pub device_name: Option<String>, // Board configuration passed to the NVIDIA flash tools
+    pub host_version: Option<String>,
+    pub recovery_device_connected: bool,
+    pub reuses_prepared_files: bool,
+    pub artifacts: Vec<PlannedArtifact>,
+    pub stages: Vec<PlannedStage>,
+    pub estimated_total_secs: u64,
+    pub warnings: Vec<String>,
+}
+
+// What the script reported in CFU_PLAN_ONLY mode
+#[derive(Debug, Default)]
+struct ScriptPlan {
+    values: BTreeMap<String, String>,
+    archives: Vec<(String, String, bool)>, // File name, URL, cached
+}
+
+fn parse_script_plan(output: &str) -> ScriptPlan {
+    let mut plan = ScriptPlan::default();
+    for (key, value) in output
+        .lines()
+        .filter_map(|line| line.strip_prefix("CFU_PLAN "))
+        .filter_map(|line| line.split_once('='))
+    {
+        if key == "archive" {
+            let mut parts = value.splitn(3, '|');
+            let (name, url, status) = (parts.next(), parts.next(), parts.next());
+            if let (Some(name), Some(url)) = (name, url) {
+                plan.archives.push((name.to_string(), url.to_string(), status == Some("cached")));
+            }
+        } else {
+            plan.values.insert(key.to_string(), value.to_string());
+        }
+    }
+    plan
+}
+
+// Average duration of successful jobs with the same module, release and storage
+fn historical_secs(state: &AppState, command: &FlashCommand) -> Option<u64> {
+    let history = state.history.lock().unwrap();
+    let times: Vec<i64> = history
+        .jobs
+        .iter()
+        .filter(|job| job.status == "success")
+        .filter(|job| {
+            job.command.device_module == command.device_module
+                && job.command.jetpack_version == command.jetpack_version
+                && job.command.storage_device == command.storage_device
+        })
+        .filter_map(|job| job.finished_at.map(|finished| (finished - job.started_at).num_seconds()))
+        .collect();
+    (!times.is_empty()).then(|| (times.iter().sum::<i64>() / times.len() as i64).max(0) as u64)
+}
+
+fn plan_stages(state: &AppState, command: &FlashCommand, script: &ScriptPlan) -> Vec<PlannedStage> {
+    let downloads = script.archives.iter().filter(|(_, _, cached)| !cached).count() as u64;
+    let download_secs = if downloads == 0 {
+        0
+    } else {
+        state
+            .download_bandwidth
+            .lock()
+            .unwrap()
+            .and_then(|rate| connectivity::download_eta_seconds(connectivity::TYPICAL_JETPACK_DOWNLOAD_BYTES, rate))
+            .unwrap_or(DEFAULT_DOWNLOAD_SECS)
+    };
+    let prepared = script.values.get("prepared").is_some_and(|v| v == "true");
+    let extract_secs = if prepared { 0 } else { DEFAULT_EXTRACT_SECS };
+    // Earlier jobs mostly ran with cached archives, so their time is attributed to the later stages
+    let flash_secs = historical_secs(state, command)
+        .map(|total| total.saturating_sub(extract_secs + DEFAULT_VERIFY_SECS).max(DEFAULT_VERIFY_SECS))
+        .unwrap_or(DEFAULT_FLASH_SECS);
+
+    vec![
+        PlannedStage {
+            stage: "downloading".to_string(),
+            description: format!("Download {} of {} archives", downloads, script.archives.len()),
+            estimated_secs: download_secs,
+        },
+        PlannedStage {
+            stage: "preparing".to_string(),
+            description: if prepared {
+                "Reuse the files prepared by a prefetch".to_string()
+            } else {
+                "Extract the BSP and sample rootfs and apply binaries".to_string()
+            },
+            estimated_secs: extract_secs,
+        },
+        PlannedStage {
+            stage: "flashing".to_string(),
+            description: format!("Flash {} to {}", command.jetpack_version, command.storage_device),
+            estimated_secs: flash_secs,
+        },
+        PlannedStage {
+            stage: "verifying".to_string(),
+            description: "Verify the written image".to_string(),
+            estimated_secs: DEFAULT_VERIFY_SECS,
+        },
+    ]
+}
+
+// Built exactly like a job's invocation; the files it writes are read back and removed
+pub async fn script_invocation(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    command: &FlashCommand,
+) -> Result<(ScriptInvocation, TokioCommand)> {
+    let plan_id = format!("plan-{}", Uuid::new_v4());
+    let (cmd, _) = crate::flash_script_command(app, state, &plan_id, command).await?;
+    let std_cmd = cmd.as_std();
+    let program = std_cmd.get_program().to_string_lossy().to_string();
+    let arguments: Vec<String> = std_cmd.get_args().map(|a| a.to_string_lossy().to_string()).collect();
+    let working_directory = std_cmd
+        .get_current_dir()
+        .map(|dir| dir.display().to_string())
+        .unwrap_or_default();
+    let environment: BTreeMap<String, String> = std_cmd
+        .get_envs()
+        .filter_map(|(key, value)| Some((key.to_string_lossy().to_string(), value?.to_string_lossy().to_string())))
+        .collect();
+    let read_and_remove = |key: &str| {
+        let path = environment.get(key)?;
+        let content = std::fs::read_to_string(path).ok();
+        std::fs::remove_file(path).ok();
+        content
+    };
+    let rootfs_hook = read_and_remove("CFU_ROOTFS_HOOK");
+    let artifact_pins = read_and_remove("CFU_ARTIFACT_PINS");
+    let invocation = ScriptInvocation {
+        program,
+        arguments,
+        working_directory,
+        environment,
+        rootfs_hook,
+        artifact_pins,
+    };
+    Ok((invocation, cmd))
+}
+
+async fn plan(app: &tauri::AppHandle, state: &AppState, command: FlashCommand) -> Result<FlashPlan> {
+    let compatibility = crate::validate_flash(app, state, &command).map_err(anyhow::Error::msg)?;
+    let (invocation, mut cmd) = script_invocation(app, state, &command).await?;
+
+    let output = cmd
+        .env("CFU_PLAN_ONLY", "1")
+        .output()
+        .await
+        .context("Failed to run the flash script")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow::anyhow!("The flash script cannot run this configuration: {}", stderr.trim()));
+    }
+    let script = parse_script_plan(&String::from_utf8_lossy(&output.stdout));
+
+    let pins: BTreeMap<&str, &str> = command
+        .pinned_artifacts
+        .iter()
+        .map(|pin| (pin.file_name.as_str(), pin.sha256.as_str()))
+        .collect();
+    let artifacts = script
+        .archives
+        .iter()
+        .map(|(file_name, url, cached)| PlannedArtifact {
+            file_name: file_name.clone(),
+            url: url.clone(),
+            cached: *cached,
+            pinned_sha256: pins.get(file_name.as_str()).map(|sha| sha.to_string()),
+        })
+        .collect();
+    let stages = plan_stages(state, &command, &script);
+    let value = |key: &str| script.values.get(key).filter(|v| !v.is_empty()).cloned();
+
+    Ok(FlashPlan {
+        invocation,
+        device_name: value("device_name"),
+        host_version: value("host_version"),
+        recovery_device_connected: script.values.get("recovery_device").is_some_and(|v| v == "true"),
+        reuses_prepared_files: script.values.get("prepared").is_some_and(|v| v == "true"),
+        artifacts,
+        estimated_total_secs: stages.iter().map(|s| s.estimated_secs).sum(),
+        stages,
+        warnings: compatibility.warnings,
+        command,
+    })
+}
+
+// Resolve everything a flash would do and return it without running it
+#[command]
+pub async fn plan_flash(
+    command: FlashCommand,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<FlashPlan, String> {
+    plan(&app, &state, command).await.map_err(|e| format!("Flash plan failed: {:#}", e))
+}
@@ -0,0 +1,120 @@
+// CFU - Cordatus Flash Utility - Prefetch
+// Download and prepare the BSP for a configuration ahead of time, so a later flash starts as soon as the device is in recovery
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use log::{debug, info};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::process::Stdio;
+use std::sync::Arc;
+use tauri::{command, Emitter, Manager};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Command as TokioCommand;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrefetchProgress {
+    pub prefetch_id: String,
+    pub line: String,
+    pub download_percent: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrefetchResult {
+    pub prefetch_id: String,
+    pub product: String,
+    pub device_module: String,
+    pub jetpack_version: String,
+    pub duration_secs: i64,
+}
+
+// Emit every line as progress, returning the last few for error reports
+async fn forward_lines(app: tauri::AppHandle, prefetch_id: String, stream: impl AsyncRead + Unpin) -> Vec<String> {
+    let percent_regex = Regex::new(r"(\d+)%").ok();
+    let mut lines = BufReader::new(stream).lines();
+    let mut tail = Vec::new();
+    while let Ok(Some(line)) = lines.next_line().await {
+        debug!("Prefetch output: {}", line);
+        let download_percent = percent_regex
+            .as_ref()
+            .and_then(|re| re.captures(&line))
+            .and_then(|caps| caps[1].parse().ok());
+        let _ = app.emit("prefetch-progress", PrefetchProgress {
+            prefetch_id: prefetch_id.clone(),
+            line: line.clone(),
+            download_percent,
+        });
+        tail.push(line);
+        if tail.len() > 20 {
+            tail.remove(0);
+        }
+    }
+    tail
+}
+
+async fn run_prefetch(
+    app: &tauri::AppHandle,
+    prefetch_id: &str,
+    product: &str,
+    module: &str,
+    version: &str,
+    user_name: &str,
+) -> Result<()> {
+    let script_path = crate::get_script_path().await.map_err(|e| anyhow::anyhow!(e))?;
+    let working_dir = crate::get_working_directory().await.map_err(|e| anyhow::anyhow!(e))?;
+
+    // Same arguments as a flash; storage is irrelevant and the files are kept for the flash
+    let mut cmd = TokioCommand::new("bash");
+    cmd.arg(&script_path)
+        .args([product, module, version, "", "true", user_name])
+        .env("CFU_PREFETCH_ONLY", "1")
+        .current_dir(&working_dir)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    crate::io_priority::apply(&app.state::<Arc<crate::AppState>>(), &mut cmd);
+    let mut child = cmd.spawn().context("Failed to start prefetch")?;
+
+    // wget reports its progress on stderr, so both streams are forwarded
+    let stderr = child.stderr.take().context("Prefetch stderr unavailable")?;
+    let stderr_task = tokio::spawn(forward_lines(app.clone(), prefetch_id.to_string(), stderr));
+    if let Some(stdout) = child.stdout.take() {
+        forward_lines(app.clone(), prefetch_id.to_string(), stdout).await;
+    }
+    let tail = stderr_task.await.unwrap_or_default();
+
+    let status = child.wait().await?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("Prefetch failed:\n{}", tail.join("\n")));
+    }
+    Ok(())
+}
+
+// Download and extract everything a flash of this configuration needs. The next
+// flash of the same product/module/version then skips straight to flashing.
+#[command]
+pub async fn prefetch_jetpack(
+    product: String,
+    module: String,
+    version: String,
+    user_name: String,
+    app: tauri::AppHandle,
+) -> Result<PrefetchResult, String> {
+    let prefetch_id = Uuid::new_v4().to_string();
+    let started = Utc::now();
+    info!("Prefetching {} {} ({})", module, version, product);
+
+    run_prefetch(&app, &prefetch_id, &product, &module, &version, &user_name)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let duration_secs = (Utc::now() - started).num_seconds();
+    info!("Prefetched {} {} in {}s", module, version, duration_secs);
+    Ok(PrefetchResult {
+        prefetch_id,
+        product,
+        device_module: module,
+        jetpack_version: version,
+        duration_secs,
+    })
+}
@@ -0,0 +1,325 @@
+// CFU - Cordatus Flash Utility - Pre-flight Checklist
+// Everything a flash needs from the host and the board, checked before the job starts: a board in
+// recovery mode, free disk space for the release, sudo and USB permissions and the host tools the
+// flash scripts call, reported item by item with what to do about each failure
+
+use crate::{host_env, peers, AppState, FlashCommand, JetsonDevice};
+use chrono::{DateTime, Utc};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::ffi::CString;
+use std::path::Path;
+use std::sync::Arc;
+use tauri::{command, State};
+use tokio::process::Command as TokioCommand;
+
+const MB: u64 = 1024 * 1024;
+const GB: u64 = 1024 * MB;
+// Disk use of a flash in MB by L4T release, first matching prefix: (l4t prefix, downloaded archives,
+// extracted BSP with the sample rootfs, images flash.sh writes next to it). Measured on the largest
+// module of each release; the last entry stands in for releases not listed.
+const RELEASE_SIZES: &[(&str, u64, u64, u64)] = &[
+    ("32.7", 1_900, 6_800, 16_000),
+    ("35.1", 2_400, 8_200, 30_000),
+    ("35.2", 2_500, 8_500, 30_000),
+    ("35.3", 2_500, 8_500, 30_000),
+    ("35.4", 2_600, 9_000, 30_000),
+    ("35.5", 2_600, 9_000, 30_000),
+    ("35.6", 2_600, 9_000, 30_000),
+    ("36.2", 2_900, 10_500, 32_000),
+    ("36.3", 3_000, 11_000, 32_000),
+    ("36.4", 3_200, 11_500, 34_000),
+];
+// Below this much headroom over the estimate the disk check warns
+const SPACE_MARGIN_PERCENT: u64 = 15;
+// Host tools the flash scripts call: (binary, Debian/Ubuntu package)
+const HOST_TOOLS: &[(&str, &str)] = &[("qemu-aarch64-static", "qemu-user-static"), ("xmllint", "libxml2-utils")];
+const UDEV_RULE_DIRS: &[&str] = &["/etc/udev/rules.d", "/lib/udev/rules.d", "/usr/lib/udev/rules.d"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreflightCheck {
+    pub name: String, // 'device' | 'configuration' | 'disk_space' | 'sudo' | 'usb_permissions' | 'host_packages' | 'flash_script'
+    pub status: String, // 'pass' | 'warn' | 'fail'
+    pub message: String,
+    pub remediation: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpaceEstimate {
+    pub l4t: Option<String>, // Release the sizes are for; None when the version did not resolve
+    pub download_bytes: u64,
+    pub extracted_bytes: u64,
+    pub images_bytes: u64,
+    pub required_bytes: u64, // Peak use while flashing
+    pub retained_bytes: u64, // Still used after the job: everything with keep_files, nothing otherwise
+    pub path: String, // Where the flash works, ~/openzeka
+    pub available_bytes: Option<u64>,
+    pub status: String, // 'ok' | 'low' (less than the safety margin left) | 'insufficient' | 'unknown'
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreflightReport {
+    pub passed: bool, // No check failed; warnings do not block the flash
+    pub checks: Vec<PreflightCheck>,
+    pub checked_at: DateTime<Utc>,
+}
+
+impl PreflightCheck {
+    fn new(name: &str, status: &str, message: impl Into<String>, remediation: Vec<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status: status.to_string(),
+            message: message.into(),
+            remediation,
+        }
+    }
+
+    fn pass(name: &str, message: impl Into<String>) -> Self {
+        Self::new(name, "pass", message, Vec::new())
+    }
+
+    // Name the board a device check found
+    fn with_device(mut self, device: &JetsonDevice) -> Self {
+        let found = format!("{} {} in recovery mode", device.product, device.module);
+        self.message = if self.message.is_empty() { found } else { format!("{}: {}", self.message, found) };
+        self
+    }
+}
+
+async fn check_device(state: &AppState, command: &FlashCommand) -> PreflightCheck {
+    let devices = match crate::scan_usb_devices(state).await {
+        Ok(devices) => devices,
+        Err(e) => return PreflightCheck::new("device", "fail", format!("USB scan failed: {}", e), Vec::new()),
+    };
+    let in_recovery: Vec<_> = devices
+        .iter()
+        .filter(|d| d.usb_info.as_ref().is_some_and(|usb| usb.is_recovery_mode))
+        .collect();
+    let device = match &command.device_binding {
+        Some(binding) => in_recovery.iter().find(|d| binding.matches_location(d)),
+        None => in_recovery.first(),
+    };
+    match device {
+        Some(device) if command.device_binding.is_none() && in_recovery.len() > 1 => PreflightCheck::new(
+            "device",
+            "warn",
+            format!("{} boards are in recovery mode, the first one found is flashed", in_recovery.len()),
+            vec!["Bind the job to a board by serial number or port, or disconnect the others".to_string()],
+        )
+        .with_device(device),
+        Some(device) => PreflightCheck::pass("device", "").with_device(device),
+        None => PreflightCheck::new(
+            "device",
+            "fail",
+            if command.device_binding.is_some() {
+                "The bound board is not in recovery mode"
+            } else {
+                "No Jetson board in recovery mode found"
+            },
+            vec![
+                "Hold the REC (FC_REC) button, press and release RESET, then release REC".to_string(),
+                "Check that `lsusb` lists an NVIDIA Corp. device (ID 0955:7xxx)".to_string(),
+            ],
+        ),
+    }
+}
+
+// The compatibility and option checks launch_flash runs, warnings included
+fn check_configuration(app: &tauri::AppHandle, state: &AppState, command: &FlashCommand) -> PreflightCheck {
+    match crate::validate_flash(app, state, command) {
+        Ok(report) if report.warnings.is_empty() => PreflightCheck::pass(
+            "configuration",
+            format!("{} with JetPack {} on {}", command.device_module, command.jetpack_version, command.storage_device),
+        ),
+        Ok(report) => PreflightCheck::new("configuration", "warn", report.warnings.join("; "), Vec::new()),
+        Err(e) => PreflightCheck::new("configuration", "fail", e, Vec::new()),
+    }
+}
+
+// Free bytes on the filesystem holding a path, from its nearest existing ancestor
+fn free_space(path: &Path) -> Option<u64> {
+    let existing = path.ancestors().find(|p| p.exists())?;
+    let path = CString::new(existing.to_string_lossy().as_bytes()).ok()?;
+    // Safety: statvfs only writes into the zeroed struct we pass, the path is NUL-terminated
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+// Space a flash of the release needs in the flash working directory. Conservative: archives already
+// downloaded and the previous BSP, which the script removes first, are counted as needed anyway.
+pub fn estimate(state: &AppState, jetpack_version: &str, keep_files: bool) -> SpaceEstimate {
+    let l4t = {
+        let matrix = state.version_matrix.lock().unwrap();
+        matrix.resolve(jetpack_version).map(|release| release.l4t.clone())
+    };
+    let (_, download, extracted, images) = l4t
+        .as_deref()
+        .and_then(|l4t| RELEASE_SIZES.iter().find(|(prefix, ..)| l4t.starts_with(prefix)))
+        .or(RELEASE_SIZES.last())
+        .copied()
+        .unwrap_or_default();
+    let required = (download + extracted + images) * MB;
+    let dir = peers::artifact_dir();
+    let available = free_space(&dir);
+    let status = match available {
+        None => "unknown",
+        Some(free) if free < required => "insufficient",
+        Some(free) if free < required + required * SPACE_MARGIN_PERCENT / 100 => "low",
+        Some(_) => "ok",
+    };
+    SpaceEstimate {
+        l4t,
+        download_bytes: download * MB,
+        extracted_bytes: extracted * MB,
+        images_bytes: images * MB,
+        required_bytes: required,
+        retained_bytes: if keep_files { required } else { 0 },
+        path: dir.display().to_string(),
+        available_bytes: available,
+        status: status.to_string(),
+    }
+}
+
+fn check_disk_space(state: &AppState, command: &FlashCommand) -> PreflightCheck {
+    let estimate = estimate(state, &command.jetpack_version, command.keep_files);
+    let required = estimate.required_bytes.div_ceil(GB);
+    let Some(free) = estimate.available_bytes.map(|free| free / GB) else {
+        return PreflightCheck::new("disk_space", "warn", format!("Could not read the free space at {}", estimate.path), Vec::new());
+    };
+    let mut message = format!(
+        "{} GB free at {}, JetPack {} needs about {} GB",
+        free, estimate.path, command.jetpack_version, required
+    );
+    if command.keep_files {
+        message.push_str(", kept after the flash");
+    }
+    let free_up = vec![
+        format!("Free at least {} GB on the disk holding {}", required.saturating_sub(free).max(1), estimate.path),
+        "Delete BSP directories of releases that are no longer flashed".to_string(),
+    ];
+    match estimate.status.as_str() {
+        "insufficient" => PreflightCheck::new("disk_space", "fail", message, free_up),
+        "low" => PreflightCheck::new("disk_space", "warn", format!("{}, little headroom left", message), Vec::new()),
+        _ => PreflightCheck::pass("disk_space", message),
+    }
+}
+
+// The flash scripts run sudo without a terminal
+async fn check_sudo() -> PreflightCheck {
+    let output = TokioCommand::new("sudo").args(["-n", "true"]).output().await;
+    match output {
+        Ok(output) if output.status.success() => PreflightCheck::pass("sudo", "sudo works without a password"),
+        Ok(output) => PreflightCheck::new(
+            "sudo",
+            "fail",
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            vec!["Allow the flashing user to run sudo without a password, e.g. a NOPASSWD rule in /etc/sudoers.d".to_string()],
+        ),
+        Err(e) => PreflightCheck::new("sudo", "fail", format!("sudo is not available: {}", e), vec!["Install sudo".to_string()]),
+    }
+}
+
+fn has_nvidia_udev_rule() -> bool {
+    UDEV_RULE_DIRS
+        .iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.flatten())
+        .filter_map(|entry| std::fs::read_to_string(entry.path()).ok())
+        .any(|rules| rules.contains("0955"))
+}
+
+// Sandbox restrictions block flashing; a missing udev rule only affects scanning without root
+fn check_usb_permissions() -> PreflightCheck {
+    let sandbox = host_env::detect_sandbox_info();
+    if sandbox.is_restricted() {
+        return PreflightCheck::new("usb_permissions", "fail", sandbox.describe(), sandbox.remediation);
+    }
+    if !has_nvidia_udev_rule() {
+        return PreflightCheck::new(
+            "usb_permissions",
+            "warn",
+            "No udev rule for NVIDIA USB devices, boards are only visible to root",
+            vec![
+                "Add /etc/udev/rules.d/99-jetson.rules with: SUBSYSTEM==\"usb\", ATTR{idVendor}==\"0955\", MODE=\"0666\"".to_string(),
+                "Reload the rules: sudo udevadm control --reload-rules && sudo udevadm trigger".to_string(),
+            ],
+        );
+    }
+    PreflightCheck::pass("usb_permissions", "USB devices are accessible")
+}
+
+fn on_path(binary: &str) -> bool {
+    std::env::var_os("PATH").is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(binary).is_file()))
+}
+
+fn check_host_packages() -> PreflightCheck {
+    let missing: Vec<&str> = HOST_TOOLS
+        .iter()
+        .filter(|(binary, _)| !on_path(binary))
+        .map(|(_, package)| *package)
+        .collect();
+    if missing.is_empty() {
+        return PreflightCheck::pass("host_packages", "Required host packages are installed");
+    }
+    PreflightCheck::new(
+        "host_packages",
+        "fail",
+        format!("Missing host packages: {}", missing.join(", ")),
+        vec![format!("sudo apt install {}", missing.join(" "))],
+    )
+}
+
+async fn check_flash_script() -> PreflightCheck {
+    match crate::get_script_path().await {
+        Ok(path) => PreflightCheck::pass("flash_script", path),
+        Err(e) => PreflightCheck::new("flash_script", "fail", e, Vec::new()),
+    }
+}
+
+pub async fn run(app: &tauri::AppHandle, state: &AppState, command: &FlashCommand) -> PreflightReport {
+    let checks = vec![
+        check_device(state, command).await,
+        check_configuration(app, state, command),
+        check_disk_space(state, command),
+        check_sudo().await,
+        check_usb_permissions(),
+        check_host_packages(),
+        check_flash_script().await,
+    ];
+    let report = PreflightReport {
+        passed: checks.iter().all(|check| check.status != "fail"),
+        checks,
+        checked_at: Utc::now(),
+    };
+    let failed: Vec<&str> = report.checks.iter().filter(|c| c.status == "fail").map(|c| c.name.as_str()).collect();
+    info!(
+        "Pre-flight checks for {} with JetPack {}: {}",
+        command.device_module,
+        command.jetpack_version,
+        if failed.is_empty() { "passed".to_string() } else { format!("failed {}", failed.join(", ")) }
+    );
+    report
+}
+
+// Disk space a flash of the release needs, against what is free where it runs
+#[command]
+pub async fn estimate_required_space(
+    jetpack_version: String,
+    keep_files: bool,
+    state: State<'_, Arc<AppState>>,
+) -> Result<SpaceEstimate, String> {
+    Ok(estimate(&state, &jetpack_version, keep_files))
+}
+
+// Check everything a flash needs before starting it
+#[command]
+pub async fn run_preflight_checks(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    command: FlashCommand,
+) -> Result<PreflightReport, String> {
+    Ok(run(&app, &state, &command).await)
+}
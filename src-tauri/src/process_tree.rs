@@ -0,0 +1,115 @@
+// CFU - Cordatus Flash Utility - Process Trees
+// Flash jobs run in a session of their own, so a hard cancel stops the whole tree (sudo, flash.sh,
+// tegraflash.py, dd) and not only the bash at its top, whose orphans kept holding the USB device
+
+use log::{info, warn};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::process::{Child, Command as TokioCommand};
+
+// How long the tree gets to exit on SIGTERM before it is killed
+const TERM_GRACE: Duration = Duration::from_secs(5);
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+// Start the command as the leader of a new session and process group
+pub fn new_session(cmd: &mut TokioCommand) {
+    // Safety: only the async-signal-safe setsid syscall runs between fork and exec
+    unsafe {
+        cmd.pre_exec(|| {
+            libc::setsid();
+            Ok(())
+        });
+    }
+}
+
+// All processes below `root`, from the parent links in /proc. Collected before signalling anything:
+// once their parent is gone, orphans are reparented and can no longer be found this way.
+fn descendants(root: i32) -> Vec<i32> {
+    let mut children: HashMap<i32, Vec<i32>> = HashMap::new();
+    for entry in std::fs::read_dir("/proc").into_iter().flatten().flatten() {
+        let Ok(pid) = entry.file_name().to_string_lossy().parse::<i32>() else {
+            continue;
+        };
+        let Ok(stat) = std::fs::read_to_string(entry.path().join("stat")) else {
+            continue;
+        };
+        // The command name may contain spaces and parentheses, the fields after it do not
+        let Some(ppid) = stat.rsplit_once(')').and_then(|(_, rest)| rest.split_whitespace().nth(1)?.parse::<i32>().ok()) else {
+            continue;
+        };
+        children.entry(ppid).or_default().push(pid);
+    }
+    let mut found = Vec::new();
+    let mut pending = vec![root];
+    while let Some(pid) = pending.pop() {
+        for &child in children.get(&pid).map(Vec::as_slice).unwrap_or_default() {
+            found.push(child);
+            pending.push(child);
+        }
+    }
+    found
+}
+
+fn alive(pid: i32) -> bool {
+    // Safety: signal 0 only checks that the process exists
+    unsafe { libc::kill(pid, 0) == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM) }
+}
+
+// Signal each process, returning those we may not signal: the ones sudo started as root
+fn signal(pids: &[i32], sig: libc::c_int) -> Vec<i32> {
+    pids.iter()
+        .copied()
+        .filter(|&pid| {
+            // Safety: plain kill(2) on a process ID
+            let refused = unsafe { libc::kill(pid, sig) } != 0;
+            refused && std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+        })
+        .collect()
+}
+
+// Root processes are signalled through sudo, if it does not need to ask for a password
+async fn signal_as_root(pids: &[i32], signal: &str) {
+    if pids.is_empty() {
+        return;
+    }
+    let pids: Vec<String> = pids.iter().map(i32::to_string).collect();
+    let result = TokioCommand::new("sudo").args(["-n", "kill", signal]).args(&pids).output().await;
+    if !result.is_ok_and(|output| output.status.success()) {
+        warn!("Could not signal root processes {} of the cancelled job", pids.join(" "));
+    }
+}
+
+// Stop a job and everything it started: SIGTERM to the process group and every descendant, then
+// SIGKILL to whatever is still running after the grace period
+pub async fn terminate(child: &mut Child) {
+    let Some(pid) = child.id().map(|pid| pid as i32) else {
+        return; // Already exited and reaped
+    };
+    let mut tree = vec![pid];
+    tree.extend(descendants(pid));
+    info!("Stopping process tree of {} ({} processes)", pid, tree.len());
+
+    // Safety: negative PID signals the process group the child leads
+    unsafe { libc::kill(-pid, libc::SIGTERM) };
+    signal_as_root(&signal(&tree, libc::SIGTERM), "-TERM").await;
+
+    let started = Instant::now();
+    while started.elapsed() < TERM_GRACE {
+        let _ = child.try_wait();
+        if !tree.iter().any(|&pid| alive(pid)) {
+            break;
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+
+    let remaining: Vec<i32> = tree.iter().copied().filter(|&pid| alive(pid)).collect();
+    if !remaining.is_empty() {
+        warn!("{} processes ignored SIGTERM, killing them", remaining.len());
+        // Safety: as above
+        unsafe { libc::kill(-pid, libc::SIGKILL) };
+        signal_as_root(&signal(&remaining, libc::SIGKILL), "-KILL").await;
+    }
+    if let Err(e) = child.wait().await {
+        warn!("Failed to reap process {}: {}", pid, e);
+    }
+}
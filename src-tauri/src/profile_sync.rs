@@ -0,0 +1,239 @@
+// CFU - Cordatus Flash Utility - Profile Sync
+// Keeps flash profiles consistent across the stations of a line through a shared folder or a plain
+// HTTP endpoint (GET/PUT of one JSON document); the newest edit wins and overwritten versions are kept
+
+use crate::profiles::{self, FlashProfile};
+use crate::AppState;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use reqwest::header::{ETAG, IF_MATCH};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tauri::{command, State};
+
+const SYNC_STATE_FILE: &str = "profile_sync.json";
+const SHARED_FILE: &str = "cfu-profiles.json";
+const CONFLICTS_DIR: &str = "sync_conflicts";
+
+// What every station reads and writes
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SharedProfiles {
+    pub profiles: Vec<FlashProfile>,
+    pub deleted: BTreeMap<String, DateTime<Utc>>, // Profile name -> deletion time
+    pub updated_by: String,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+// This station's side: when it last synced and which profiles it deleted since
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SyncState {
+    last_synced: Option<DateTime<Utc>>,
+    deleted: BTreeMap<String, DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncConflict {
+    pub name: String,
+    pub kept: String, // 'local' | 'remote'
+    pub saved_copy: String, // Where the overwritten version was written
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncResult {
+    pub profiles: usize,
+    pub updated_locally: usize,
+    pub conflicts: Vec<SyncConflict>,
+    pub synced_at: DateTime<Utc>,
+}
+
+fn load_state(app: &tauri::AppHandle) -> SyncState {
+    crate::app_data_file(app, SYNC_STATE_FILE)
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_state(app: &tauri::AppHandle, sync_state: &SyncState) -> Result<()> {
+    let path = crate::app_data_file(app, SYNC_STATE_FILE)?;
+    std::fs::write(path, serde_json::to_string_pretty(sync_state)?).context("Failed to save sync state")
+}
+
+// Remember a deletion so the next sync removes the profile on other stations too
+pub fn record_deletion(app: &tauri::AppHandle, name: &str) {
+    let mut sync_state = load_state(app);
+    sync_state.deleted.insert(name.to_string(), Utc::now());
+    if let Err(e) = save_state(app, &sync_state) {
+        warn!("Deletion of profile {} will not sync: {}", name, e);
+    }
+}
+
+enum Store {
+    Folder(PathBuf),
+    Server(String),
+}
+
+impl Store {
+    fn from_settings(settings: &crate::settings::ProfileSyncSettings) -> Result<Self> {
+        match settings.target.as_str() {
+            "folder" if !settings.folder.trim().is_empty() => Ok(Store::Folder(PathBuf::from(settings.folder.trim()).join(SHARED_FILE))),
+            "server" if !settings.server_url.trim().is_empty() => Ok(Store::Server(settings.server_url.trim().to_string())),
+            _ => Err(anyhow::anyhow!("Profile sync needs a shared folder or server URL")),
+        }
+    }
+
+    // The shared document, plus the server's ETag so a concurrent write is detected
+    async fn read(&self) -> Result<(SharedProfiles, Option<String>)> {
+        match self {
+            Store::Folder(path) => match std::fs::read_to_string(path) {
+                Ok(content) => Ok((serde_json::from_str(&content).with_context(|| format!("Invalid {}", path.display()))?, None)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok((SharedProfiles::default(), None)),
+                Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+            },
+            Store::Server(url) => {
+                let response = reqwest::get(url).await?;
+                if response.status() == StatusCode::NOT_FOUND {
+                    return Ok((SharedProfiles::default(), None));
+                }
+                let response = response.error_for_status()?;
+                let etag = response.headers().get(ETAG).and_then(|v| v.to_str().ok()).map(str::to_string);
+                Ok((response.json().await?, etag))
+            }
+        }
+    }
+
+    async fn write(&self, shared: &SharedProfiles, etag: Option<String>) -> Result<()> {
+        let json = serde_json::to_string_pretty(shared)?;
+        match self {
+            Store::Folder(path) => {
+                // Written beside the target and renamed, so other stations never read half a file
+                let temp = path.with_extension("json.tmp");
+                std::fs::write(&temp, json).with_context(|| format!("Failed to write {}", temp.display()))?;
+                std::fs::rename(&temp, path).with_context(|| format!("Failed to replace {}", path.display()))
+            }
+            Store::Server(url) => {
+                let mut request = reqwest::Client::new()
+                    .put(url)
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .body(json);
+                if let Some(etag) = etag {
+                    request = request.header(IF_MATCH, etag);
+                }
+                let response = request.send().await?;
+                if response.status() == StatusCode::PRECONDITION_FAILED {
+                    return Err(anyhow::anyhow!("Another station synced at the same time; sync again"));
+                }
+                response.error_for_status()?;
+                Ok(())
+            }
+        }
+    }
+}
+
+fn same_content(a: &FlashProfile, b: &FlashProfile) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+fn save_conflict_copy(app: &tauri::AppHandle, profile: &FlashProfile) -> Result<PathBuf> {
+    let dir = crate::app_data_file(app, CONFLICTS_DIR)?;
+    std::fs::create_dir_all(&dir)?;
+    let safe_name: String = profile.name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+    let path = dir.join(format!("{}-{}.json", safe_name, profile.updated_at.format("%Y%m%dT%H%M%S")));
+    std::fs::write(&path, serde_json::to_string_pretty(profile)?)?;
+    Ok(path)
+}
+
+async fn sync(app: &tauri::AppHandle, state: &AppState) -> Result<SyncResult> {
+    let settings = state.settings.lock().unwrap().profile_sync.clone();
+    if !settings.enabled {
+        return Err(anyhow::anyhow!("Profile sync is not enabled"));
+    }
+    crate::freeze::ensure_not_frozen(app, "Profile syncs").map_err(anyhow::Error::msg)?;
+    let store = Store::from_settings(&settings)?;
+    let (remote, etag) = store.read().await?;
+    let mut sync_state = load_state(app);
+    let local = profiles::load_profiles(app);
+
+    // Deletions from both sides; a profile edited after its deletion comes back
+    let mut deleted = remote.deleted.clone();
+    for (name, at) in &sync_state.deleted {
+        let entry = deleted.entry(name.clone()).or_insert(*at);
+        *entry = (*entry).max(*at);
+    }
+
+    let names: BTreeSet<&String> = local.iter().chain(&remote.profiles).map(|p| &p.name).collect();
+    let mut merged: Vec<FlashProfile> = Vec::new();
+    let mut conflicts = Vec::new();
+    for name in names {
+        let ours = local.iter().find(|p| &p.name == name);
+        let theirs = remote.profiles.iter().find(|p| &p.name == name);
+        let chosen = match (ours, theirs) {
+            (Some(ours), Some(theirs)) => {
+                let (winner, loser, kept) = if ours.updated_at >= theirs.updated_at {
+                    (ours, theirs, "local")
+                } else {
+                    (theirs, ours, "remote")
+                };
+                // Both sides edited since the last sync: the newer edit wins, the other is saved aside
+                let changed_since = |p: &FlashProfile| sync_state.last_synced.is_none_or(|at| p.updated_at > at);
+                if changed_since(ours) && changed_since(theirs) && !same_content(ours, theirs) {
+                    let saved = save_conflict_copy(app, loser)?;
+                    warn!("Profile {} changed on this and another station; kept the {} version", name, kept);
+                    conflicts.push(SyncConflict {
+                        name: name.clone(),
+                        kept: kept.to_string(),
+                        saved_copy: saved.display().to_string(),
+                    });
+                }
+                winner
+            }
+            (Some(profile), None) | (None, Some(profile)) => profile,
+            (None, None) => continue,
+        };
+        if deleted.get(name).is_some_and(|at| *at >= chosen.updated_at) {
+            continue;
+        }
+        merged.push(chosen.clone());
+    }
+    // Tombstones of profiles that came back are no longer needed
+    deleted.retain(|name, _| !merged.iter().any(|p| &p.name == name));
+
+    let updated_locally = merged
+        .iter()
+        .filter(|p| !local.iter().any(|l| same_content(l, p)))
+        .count()
+        + local.iter().filter(|l| !merged.iter().any(|p| p.name == l.name)).count();
+
+    let now = Utc::now();
+    let shared = SharedProfiles {
+        profiles: merged.clone(),
+        deleted,
+        updated_by: crate::history::station_name(state).unwrap_or_default(),
+        updated_at: Some(now),
+    };
+    store.write(&shared, etag).await?;
+    profiles::save_profiles(app, &merged)?;
+    sync_state.last_synced = Some(now);
+    sync_state.deleted.clear();
+    save_state(app, &sync_state)?;
+
+    info!("Synced {} profiles ({} updated locally, {} conflicts)", merged.len(), updated_locally, conflicts.len());
+    Ok(SyncResult {
+        profiles: merged.len(),
+        updated_locally,
+        conflicts,
+        synced_at: now,
+    })
+}
+
+// Merge profiles with the shared copy and write the result to both sides
+#[command]
+pub async fn sync_profiles(app: tauri::AppHandle, state: State<'_, Arc<AppState>>) -> Result<SyncResult, String> {
+    sync(&app, &state).await.map_err(|e| format!("Profile sync failed: {:#}", e))
+}
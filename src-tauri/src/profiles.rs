@@ -0,0 +1,239 @@
+// CFU - Cordatus Flash Utility - Flash Profiles
+// Saved flash configurations with optional checksum pins, so re-flashing a profile later uses byte-identical artifacts
+
+use crate::batch::{self, JobVariables};
+use crate::checksum;
+use crate::history;
+use crate::job_host::JobHost;
+use crate::kernel::KernelArtifacts;
+use crate::{AppState, FlashCommand};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tauri::{command, State};
+
+pub const PROFILES_FILE: &str = "profiles.json";
+
+// One artifact a flash used. BSP, sample rootfs and secure boot archives are
+// named relative to ~/openzeka; kernel overlays by absolute path.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArtifactPin {
+    pub file_name: String,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlashProfile {
+    pub name: String,
+    pub command: FlashCommand,
+    #[serde(default)]
+    pub pins: Vec<ArtifactPin>, // Empty until pinned from a successful flash
+    #[serde(default)]
+    pub packages: BTreeMap<String, String>, // dpkg package -> version baseline for drift checks
+    #[serde(default)]
+    pub containers: Vec<String>, // Container names expected on provisioned devices
+    pub updated_at: DateTime<Utc>,
+}
+
+pub fn load_profiles(app: &impl JobHost) -> Vec<FlashProfile> {
+    crate::app_data_file(app, PROFILES_FILE)
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_profiles(app: &impl JobHost, profiles: &[FlashProfile]) -> Result<()> {
+    let path = crate::app_data_file(app, PROFILES_FILE)?;
+    std::fs::write(path, serde_json::to_string_pretty(profiles)?).context("Failed to save profiles")
+}
+
+pub fn find_profile(app: &impl JobHost, name: &str) -> Result<FlashProfile> {
+    load_profiles(app)
+        .into_iter()
+        .find(|profile| profile.name == name)
+        .with_context(|| format!("Profile not found: {}", name))
+}
+
+// Checksums of the kernel image and out-of-tree modules a job installs
+pub fn overlay_pins(artifacts: &KernelArtifacts) -> Result<Vec<ArtifactPin>> {
+    let paths: Vec<&String> = artifacts.image.iter().chain(&artifacts.modules).collect();
+    paths
+        .iter()
+        .zip(checksum::sha256_files(&paths))
+        .map(|(path, sha256)| {
+            Ok(ArtifactPin {
+                file_name: path.to_string(),
+                sha256: sha256?,
+            })
+        })
+        .collect()
+}
+
+// Overlays are checked here, before anything is flashed
+pub fn verify_overlay_pins(pins: &[ArtifactPin]) -> Result<()> {
+    let overlays: Vec<&ArtifactPin> = pins.iter().filter(|pin| Path::new(&pin.file_name).is_absolute()).collect();
+    let paths: Vec<&str> = overlays.iter().map(|pin| pin.file_name.as_str()).collect();
+    for (pin, actual) in overlays.iter().zip(checksum::sha256_files(&paths)) {
+        let actual = actual?;
+        if actual != pin.sha256 {
+            return Err(anyhow::anyhow!(
+                "Pinned artifact {} changed: expected {}, got {}",
+                pin.file_name,
+                pin.sha256,
+                actual
+            ));
+        }
+    }
+    Ok(())
+}
+
+// BSP archives are checked by the flash script after download, with `sha256sum --check`
+pub fn write_pins_file(app: &impl JobHost, flash_id: &str, pins: &[ArtifactPin]) -> Result<Option<PathBuf>> {
+    let lines: String = pins
+        .iter()
+        .filter(|pin| !Path::new(&pin.file_name).is_absolute())
+        .map(|pin| format!("{}  {}\n", pin.sha256, pin.file_name))
+        .collect();
+    if lines.is_empty() {
+        return Ok(None);
+    }
+    let path = crate::app_data_file(app, &format!("artifact_pins_{}.txt", flash_id))?;
+    std::fs::write(&path, lines).context("Failed to write artifact pins")?;
+    Ok(Some(path))
+}
+
+// Read the `sha256sum` output the flash script leaves behind
+pub fn read_manifest(path: &Path) -> Vec<ArtifactPin> {
+    std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let (sha256, file_name) = line.split_once("  ")?;
+            Some(ArtifactPin {
+                file_name: file_name.trim().to_string(),
+                sha256: sha256.to_string(),
+            })
+        })
+        .collect()
+}
+
+// List saved flash profiles
+#[command]
+pub async fn list_profiles(app: tauri::AppHandle) -> Result<Vec<FlashProfile>, String> {
+    Ok(load_profiles(&app))
+}
+
+// Create or replace a profile's flash configuration, keeping its pins and drift baseline
+#[command]
+pub async fn save_profile(name: String, command: FlashCommand, app: tauri::AppHandle) -> Result<(), String> {
+    crate::freeze::ensure_not_frozen(&app, "Profile changes")?;
+    let mut profiles = load_profiles(&app);
+    match profiles.iter_mut().find(|p| p.name == name) {
+        Some(profile) => {
+            profile.command = command;
+            profile.updated_at = Utc::now();
+        }
+        None => profiles.push(FlashProfile {
+            name,
+            command,
+            pins: Vec::new(),
+            packages: BTreeMap::new(),
+            containers: Vec::new(),
+            updated_at: Utc::now(),
+        }),
+    }
+    save_profiles(&app, &profiles).map_err(|e| e.to_string())
+}
+
+// Delete a flash profile
+#[command]
+pub async fn delete_profile(name: String, app: tauri::AppHandle) -> Result<(), String> {
+    crate::freeze::ensure_not_frozen(&app, "Profile changes")?;
+    let mut profiles = load_profiles(&app);
+    profiles.retain(|p| p.name != name);
+    save_profiles(&app, &profiles).map_err(|e| e.to_string())?;
+    crate::profile_sync::record_deletion(&app, &name);
+    Ok(())
+}
+
+// Pin a profile to the exact artifacts a successful flash used
+#[command]
+pub async fn pin_profile_artifacts(
+    name: String,
+    flash_id: String,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<ArtifactPin>, String> {
+    crate::freeze::ensure_not_frozen(&app, "Profile changes")?;
+    let pins = {
+        let history = state.history.lock().unwrap();
+        let job = history
+            .jobs
+            .iter()
+            .find(|r| r.flash_id == flash_id)
+            .ok_or_else(|| format!("Flash job not found: {}", flash_id))?;
+        if job.status != "success" || job.artifacts.is_empty() {
+            return Err(format!("Flash job {} has no verified artifacts to pin", flash_id));
+        }
+        job.artifacts.clone()
+    };
+
+    let mut profiles = load_profiles(&app);
+    let profile = profiles
+        .iter_mut()
+        .find(|p| p.name == name)
+        .ok_or_else(|| format!("Profile not found: {}", name))?;
+    profile.pins = pins.clone();
+    profile.updated_at = Utc::now();
+    save_profiles(&app, &profiles).map_err(|e| e.to_string())?;
+
+    info!("Pinned {} artifacts for profile {}", pins.len(), name);
+    Ok(pins)
+}
+
+// The profile's flash command for one unit of a batch: overrides applied and variables filled in
+pub(crate) fn resolve_command(command: FlashCommand, vars: &JobVariables) -> Result<(FlashCommand, BTreeMap<String, String>)> {
+    let mut vars = vars.clone();
+    if vars.serial.is_none() {
+        vars.serial = command.device_binding.as_ref().and_then(|binding| binding.serial_number.clone());
+    }
+    let operator = command.operator.clone();
+    let mut value = serde_json::to_value(command)?;
+    let variables = batch::resolve(&mut value, &vars)?;
+    let mut command: FlashCommand = serde_json::from_value(value).context("Invalid flash command after batch overrides")?;
+    command.operator = vars.operator.or(operator);
+    Ok((command, variables))
+}
+
+// Flash a device from a saved profile (its frozen copy on a frozen station), failing if a pinned artifact differs.
+// `variables` identify the unit within its batch, see batch.rs.
+#[command]
+pub async fn flash_profile(
+    name: String,
+    variables: Option<JobVariables>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    window: tauri::Window,
+) -> Result<String, String> {
+    let vars = variables.unwrap_or_default();
+    if vars.batch_file.is_some() {
+        // Frozen stations flash their profiles exactly as frozen
+        crate::freeze::ensure_not_frozen(&app, "Batch overrides")?;
+    }
+    let profile = crate::freeze::profile_for_flash(&app, &name).map_err(|e| e.to_string())?;
+    let (mut command, variables) = resolve_command(profile.command, &vars).map_err(|e| e.to_string())?;
+    command.pinned_artifacts = profile.pins;
+    let flash_id = crate::start_flash_process(command, state.clone(), window).await?;
+    history::update_history(&app, &state, |history| {
+        if let Some(record) = history.jobs.iter_mut().find(|r| r.flash_id == flash_id) {
+            record.profile = Some(name.clone());
+            record.variables = variables;
+        }
+    });
+    Ok(flash_id)
+}
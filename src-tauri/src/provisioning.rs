@@ -0,0 +1,358 @@
+// CFU - Cordatus Flash Utility - Provisioning
+// Post-flash system configuration, applied to the rootfs before flashing or over SSH afterwards
+
+use crate::job_host::JobHost;
+use crate::kernel;
+use crate::ssh::{run_remote, shell_quote, SshTarget, SUDO};
+use crate::FlashCommand;
+use anyhow::{Context, Result};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tauri::command;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProvisioningOptions {
+    pub swap: Option<SwapOptions>,
+    pub desktop: Option<DesktopOptions>,
+    pub regional: Option<RegionalOptions>,
+    pub kernel_cmdline: Option<KernelCmdlineOptions>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SwapOptions {
+    pub kind: String, // 'swapfile' | 'zram' | 'none'
+    pub size_mb: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DesktopOptions {
+    pub default_target: String, // 'graphical' | 'multi-user'
+    pub remove_desktop: bool,   // Purge the desktop stack, frees ~1 GB of RAM
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RegionalOptions {
+    pub timezone: Option<String>, // e.g. "Europe/Istanbul"
+    pub locale: Option<String>,   // e.g. "tr_TR.UTF-8"
+    pub ntp_servers: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KernelCmdlineOptions {
+    pub set: Vec<String>,    // "key=value" or "flag"; replaces an existing parameter with the same key
+    pub remove: Vec<String>, // Keys to drop from the command line
+}
+
+// Set by the flashing tools from the partition layout and must not be overridden
+const PROTECTED_CMDLINE_KEYS: &[&str] = &["root", "rootfstype"];
+
+// Desktop packages shipped in the JetPack sample rootfs
+const DESKTOP_PACKAGES: &str = "ubuntu-desktop gdm3 gnome-shell gnome-session nautilus 'libreoffice*' thunderbird chromium-browser";
+
+impl ProvisioningOptions {
+    pub fn is_empty(&self) -> bool {
+        self.swap.is_none() && self.desktop.is_none() && self.regional.is_none() && self.kernel_cmdline.is_none()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if let Some(swap) = &self.swap {
+            match swap.kind.as_str() {
+                "swapfile" | "zram" if swap.size_mb == 0 => {
+                    return Err(anyhow::anyhow!("Swap size must be greater than zero"))
+                }
+                "swapfile" | "zram" | "none" => {}
+                other => return Err(anyhow::anyhow!("Unknown swap type: {}", other)),
+            }
+        }
+        if let Some(desktop) = &self.desktop {
+            if desktop.default_target != "graphical" && desktop.default_target != "multi-user" {
+                return Err(anyhow::anyhow!("Unknown default target: {}", desktop.default_target));
+            }
+            if desktop.remove_desktop && desktop.default_target == "graphical" {
+                return Err(anyhow::anyhow!("Cannot boot to the desktop after removing it"));
+            }
+        }
+        if let Some(regional) = &self.regional {
+            let values = regional.timezone.iter().chain(&regional.locale).chain(&regional.ntp_servers);
+            for value in values {
+                if !is_safe_value(value) {
+                    return Err(anyhow::anyhow!("Invalid regional setting: {}", value));
+                }
+            }
+        }
+        if let Some(cmdline) = &self.kernel_cmdline {
+            validate_cmdline(cmdline)?;
+        }
+        Ok(())
+    }
+}
+
+// Values interpolated into the script verbatim: zone names, locales, hostnames
+fn is_safe_value(value: &str) -> bool {
+    !value.is_empty()
+        && !value.contains("..")
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || "._-+/@:".contains(c))
+}
+
+fn cmdline_key(param: &str) -> &str {
+    param.split('=').next().unwrap_or(param)
+}
+
+fn validate_cmdline(cmdline: &KernelCmdlineOptions) -> Result<()> {
+    for param in cmdline.set.iter().chain(&cmdline.remove) {
+        let key = cmdline_key(param);
+        let valid = !key.is_empty()
+            && param.chars().all(|c| c.is_ascii_alphanumeric() || "._-,:=/@".contains(c));
+        if !valid {
+            return Err(anyhow::anyhow!("Invalid kernel parameter: {:?}", param));
+        }
+        if PROTECTED_CMDLINE_KEYS.contains(&key) {
+            return Err(anyhow::anyhow!("Kernel parameter '{}' is managed by the flashing tools", key));
+        }
+    }
+    let total: usize = cmdline.set.iter().map(|p| p.len() + 1).sum();
+    if total > 1024 {
+        return Err(anyhow::anyhow!("Kernel command line additions are too long ({} bytes)", total));
+    }
+    Ok(())
+}
+
+// Shared preamble: $ROOT is the rootfs directory when customizing before
+// flashing and empty when running on the booted device
+const SCRIPT_HEADER: &str = r#"#!/bin/bash
+# Generated by Cordatus Flash Utility
+set -e
+ROOT="${ROOT:-}"
+UNITS="$ROOT/etc/systemd/system"
+enable_unit() { mkdir -p "$UNITS/multi-user.target.wants"; ln -sf "/etc/systemd/system/$1" "$UNITS/multi-user.target.wants/$1"; }
+mask_unit() { ln -sf /dev/null "$UNITS/$1"; }
+live() { if [ -z "$ROOT" ]; then "$@"; fi; }
+"#;
+
+// The swapfile is created on first boot so it does not inflate the system image
+fn swap_section(swap: &SwapOptions) -> String {
+    match swap.kind.as_str() {
+        "swapfile" => format!(
+            r#"
+# Swap: {size} MB swapfile
+cat > "$UNITS/cfu-swapfile.service" <<'EOF'
+[Unit]
+Description=CFU swapfile
+ConditionPathExists=!/swapfile
+
+[Service]
+Type=oneshot
+ExecStart=/bin/sh -c 'fallocate -l {size}M /swapfile && chmod 600 /swapfile && mkswap /swapfile && swapon /swapfile'
+
+[Install]
+WantedBy=multi-user.target
+EOF
+enable_unit cfu-swapfile.service
+grep -q '^/swapfile ' "$ROOT/etc/fstab" || echo '/swapfile none swap sw,nofail 0 0' >> "$ROOT/etc/fstab"
+live systemctl daemon-reload
+live systemctl start cfu-swapfile.service
+"#,
+            size = swap.size_mb
+        ),
+        // Replace NVIDIA's per-core zram setup with a single device of the chosen size
+        "zram" => format!(
+            r#"
+# Swap: {size} MB zram
+mask_unit nvzramconfig.service
+cat > "$UNITS/cfu-zram.service" <<'EOF'
+[Unit]
+Description=CFU zram swap
+
+[Service]
+Type=oneshot
+RemainAfterExit=yes
+ExecStart=/bin/sh -c 'modprobe zram && DEV=$$(zramctl --find --size {size}M) && mkswap $$DEV && swapon -p 5 $$DEV'
+
+[Install]
+WantedBy=multi-user.target
+EOF
+enable_unit cfu-zram.service
+live systemctl daemon-reload
+live systemctl start cfu-zram.service
+"#,
+            size = swap.size_mb
+        ),
+        _ => r#"
+# Swap: disabled
+mask_unit nvzramconfig.service
+rm -f "$UNITS/multi-user.target.wants/cfu-swapfile.service" "$UNITS/multi-user.target.wants/cfu-zram.service"
+sed -i '/^\/swapfile /d' "$ROOT/etc/fstab"
+live swapoff -a
+"#
+        .to_string(),
+    }
+}
+
+// Package removal needs the target's own apt, so in the rootfs it runs once on first boot
+fn desktop_section(desktop: &DesktopOptions) -> String {
+    let mut section = format!(
+        r#"
+# Desktop: boot to {target}.target
+ln -sf /lib/systemd/system/{target}.target "$UNITS/default.target"
+"#,
+        target = desktop.default_target
+    );
+    if desktop.remove_desktop {
+        section.push_str(&format!(
+            r#"
+# Desktop: remove desktop stack
+if [ -z "$ROOT" ]; then
+  DEBIAN_FRONTEND=noninteractive apt-get purge -y {packages} && apt-get autoremove -y
+else
+cat > "$UNITS/cfu-remove-desktop.service" <<'EOF'
+[Unit]
+Description=CFU desktop removal
+ConditionPathExists=!/var/lib/cfu/desktop-removed
+
+[Service]
+Type=oneshot
+Environment=DEBIAN_FRONTEND=noninteractive
+ExecStart=/bin/sh -c "apt-get purge -y {packages} && apt-get autoremove -y && mkdir -p /var/lib/cfu && touch /var/lib/cfu/desktop-removed"
+
+[Install]
+WantedBy=multi-user.target
+EOF
+enable_unit cfu-remove-desktop.service
+fi
+"#,
+            packages = DESKTOP_PACKAGES
+        ));
+    }
+    section
+}
+
+// Locales are generated inside the rootfs through qemu-user-static, which
+// l4t_flash_prerequisites.sh installs on the host
+fn regional_section(regional: &RegionalOptions) -> String {
+    let mut section = String::new();
+    if let Some(timezone) = &regional.timezone {
+        section.push_str(&format!(
+            r#"
+# Timezone: {tz}
+[ -f "$ROOT/usr/share/zoneinfo/{tz}" ] || {{ echo "Unknown timezone {tz}" >&2; exit 1; }}
+ln -sf /usr/share/zoneinfo/{tz} "$ROOT/etc/localtime"
+echo {tz} > "$ROOT/etc/timezone"
+"#,
+            tz = timezone
+        ));
+    }
+    if let Some(locale) = &regional.locale {
+        section.push_str(&format!(
+            r#"
+# Locale: {locale}
+grep -q "^{locale} " "$ROOT/etc/locale.gen" || echo "{locale} {charset}" >> "$ROOT/etc/locale.gen"
+sed -i "s/^# *\({locale} \)//" "$ROOT/etc/locale.gen"
+if [ -z "$ROOT" ]; then locale-gen; else chroot "$ROOT" locale-gen; fi
+printf 'LANG={locale}
+LC_ALL={locale}
+' > "$ROOT/etc/default/locale"
+"#,
+            locale = locale,
+            charset = locale.split('.').nth(1).unwrap_or("UTF-8")
+        ));
+    }
+    if !regional.ntp_servers.is_empty() {
+        section.push_str(&format!(
+            r#"
+# NTP servers
+mkdir -p "$ROOT/etc/systemd/timesyncd.conf.d"
+printf '[Time]
+NTP={servers}
+' > "$ROOT/etc/systemd/timesyncd.conf.d/cfu.conf"
+live systemctl restart systemd-timesyncd
+"#,
+            servers = regional.ntp_servers.join(" ")
+        ));
+    }
+    section
+}
+
+// Edits the APPEND lines of extlinux.conf, which flash.sh keeps when it
+// fills in the rootfs device
+fn kernel_cmdline_section(cmdline: &KernelCmdlineOptions) -> String {
+    let mut section = String::from(
+        r#"
+# Kernel command line
+EXTLINUX="$ROOT/boot/extlinux/extlinux.conf"
+[ -f "$EXTLINUX" ] || { echo "extlinux.conf not found" >&2; exit 1; }
+"#,
+    );
+    let keys = cmdline.remove.iter().map(String::as_str).chain(cmdline.set.iter().map(|p| cmdline_key(p)));
+    for key in keys {
+        let key = key.replace('.', "\\.").replace('/', "\\/");
+        section.push_str(&format!(
+            "sed -i -E '/^[[:space:]]*APPEND /s/ {key}(=[^ ]*)?( |$)/\\2/g' \"$EXTLINUX\"\n",
+            key = key
+        ));
+    }
+    if !cmdline.set.is_empty() {
+        section.push_str(&format!(
+            "sed -i -E '/^[[:space:]]*APPEND /s/$/ {}/' \"$EXTLINUX\"\n",
+            cmdline.set.join(" ").replace('/', "\\/")
+        ));
+    }
+    section
+}
+
+pub fn render_script(options: &ProvisioningOptions) -> String {
+    let mut script = SCRIPT_HEADER.to_string();
+    if let Some(swap) = &options.swap {
+        script.push_str(&swap_section(swap));
+    }
+    if let Some(desktop) = &options.desktop {
+        script.push_str(&desktop_section(desktop));
+    }
+    if let Some(regional) = &options.regional {
+        script.push_str(&regional_section(regional));
+    }
+    if let Some(cmdline) = &options.kernel_cmdline {
+        script.push_str(&kernel_cmdline_section(cmdline));
+    }
+    script
+}
+
+// Write the rootfs hook picked up by flash_cordatus.sh (CFU_ROOTFS_HOOK)
+pub fn write_rootfs_hook(app: &impl JobHost, flash_id: &str, command: &FlashCommand) -> Result<PathBuf> {
+    let mut script = match &command.provisioning {
+        Some(options) => {
+            options.validate()?;
+            render_script(options)
+        }
+        None => SCRIPT_HEADER.to_string(),
+    };
+    if let Some(artifacts) = &command.custom_kernel {
+        script.push_str(&kernel::install_section(artifacts));
+    }
+
+    let path = crate::app_data_file(app, &format!("rootfs_hook_{}.sh", flash_id))?;
+    std::fs::write(&path, script).context("Failed to write provisioning hook")?;
+    Ok(path)
+}
+
+// Show the script that would be applied for the given options
+#[command]
+pub async fn preview_provisioning_script(options: ProvisioningOptions) -> Result<String, String> {
+    options.validate().map_err(|e| e.to_string())?;
+    Ok(render_script(&options))
+}
+
+// Apply provisioning options to an already flashed, booted device
+#[command]
+pub async fn apply_provisioning(target: SshTarget, options: ProvisioningOptions) -> Result<String, String> {
+    options.validate().map_err(|e| e.to_string())?;
+    info!("Applying provisioning options to {}", target.host);
+
+    let script = format!("{}$S bash -c {}", SUDO, shell_quote(&render_script(&options)));
+    run_remote(&target, &script)
+        .await
+        .map_err(|e| format!("Provisioning failed: {}", e))
+}
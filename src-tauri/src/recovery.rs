@@ -0,0 +1,220 @@
+// CFU - Cordatus Flash Utility - Recovery Mode Entry
+// Puts a lab fixture's board into force recovery without touching jumpers: `sudo reboot forced-recovery`
+// typed on its debug UART, a USB relay board across the REC and RESET pins, or the fixture's own
+// recovery command (e.g. gpioset for boards wired to host GPIOs)
+
+use crate::settings::LabFixtureSettings;
+use crate::{credentials, lab, usb_watch, AppState, JetsonDevice};
+use anyhow::{Context, Result};
+use log::info;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{command, State};
+
+// How long a board takes to show up in recovery mode after the trigger
+const RECOVERY_WAIT: Duration = Duration::from_secs(30);
+const UART_READ_TIMEOUT: Duration = Duration::from_millis(200);
+// A console that stays silent this long has finished answering
+const UART_QUIET_TIME: Duration = Duration::from_secs(2);
+const RELAY_BAUD: u32 = 9600;
+// REC is held while RESET is pulsed, and a little after so the boot ROM samples it
+const RESET_PULSE: Duration = Duration::from_millis(300);
+const RECOVERY_HOLD: Duration = Duration::from_secs(2);
+const LOGIN_ACCOUNT: &str = "uart_login";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecoveryEntry {
+    pub fixture: String,
+    pub method: String, // 'command' | 'relay' | 'uart'
+    pub device: JetsonDevice, // The board as it enumerated in recovery mode
+}
+
+// The method a fixture uses, or None when it has nothing configured to enter recovery with
+pub fn method(fixture: &LabFixtureSettings) -> Option<&'static str> {
+    match fixture.recovery_method.as_str() {
+        "command" if !fixture.recovery_command.trim().is_empty() => Some("command"),
+        "relay" if !fixture.relay_port.is_empty() => Some("relay"),
+        "uart" if !fixture.serial_port.is_empty() => Some("uart"),
+        "auto" | "" => {
+            if !fixture.recovery_command.trim().is_empty() {
+                Some("command")
+            } else if !fixture.relay_port.is_empty() {
+                Some("relay")
+            } else if !fixture.serial_port.is_empty() {
+                Some("uart")
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+// Switch one channel of an LCUS-type relay board: A0, channel, state, checksum
+fn set_relay(port: &mut dyn serialport::SerialPort, channel: u8, closed: bool) -> Result<()> {
+    let state = closed as u8;
+    let frame = [0xA0, channel, state, 0xA0u8.wrapping_add(channel).wrapping_add(state)];
+    port.write_all(&frame).context("Failed to switch relay")?;
+    port.flush()?;
+    Ok(())
+}
+
+fn relay_recovery(fixture: &LabFixtureSettings) -> Result<()> {
+    let mut port = serialport::new(&fixture.relay_port, RELAY_BAUD)
+        .open()
+        .with_context(|| format!("Failed to open relay board {}", fixture.relay_port))?;
+    set_relay(port.as_mut(), fixture.relay_recovery_channel, true)?;
+    if fixture.relay_reset_channel > 0 {
+        set_relay(port.as_mut(), fixture.relay_reset_channel, true)?;
+        std::thread::sleep(RESET_PULSE);
+        set_relay(port.as_mut(), fixture.relay_reset_channel, false)?;
+    }
+    std::thread::sleep(RECOVERY_HOLD);
+    set_relay(port.as_mut(), fixture.relay_recovery_channel, false)
+}
+
+// Everything the console prints until it goes quiet
+fn read_console(port: &mut dyn serialport::SerialPort) -> String {
+    let mut output = Vec::new();
+    let mut buffer = [0u8; 1024];
+    let mut last_data = Instant::now();
+    while last_data.elapsed() < UART_QUIET_TIME {
+        match port.read(&mut buffer) {
+            Ok(read) if read > 0 => {
+                output.extend_from_slice(&buffer[..read]);
+                last_data = Instant::now();
+            }
+            _ => {}
+        }
+    }
+    String::from_utf8_lossy(&output).to_string()
+}
+
+pub fn send_line(port: &mut dyn serialport::SerialPort, line: &str) -> Result<String> {
+    port.write_all(format!("{}\r\n", line).as_bytes()).context("Failed to write to the UART")?;
+    Ok(read_console(port))
+}
+
+pub fn open_console(path: &str, baud: u32) -> Result<Box<dyn serialport::SerialPort>> {
+    serialport::new(path, baud)
+        .timeout(UART_READ_TIMEOUT)
+        .open()
+        .with_context(|| format!("Failed to open {}", path))
+}
+
+// Log in if `output`, what the console last printed, is a login prompt; the password is the
+// "uart_login" credential
+pub fn console_login(port: &mut dyn serialport::SerialPort, output: &str, user: &str, password: &Option<String>) -> Result<()> {
+    if !output.trim_end().ends_with("login:") {
+        return Ok(());
+    }
+    let mut output = send_line(port, user)?;
+    if output.trim_end().ends_with("Password:") {
+        let password = password.as_deref().context("The console asks for a password; store it as the uart_login credential")?;
+        output = send_line(port, password)?;
+    }
+    if output.contains("Login incorrect") {
+        return Err(anyhow::anyhow!("Login as {} on the console failed", user));
+    }
+    Ok(())
+}
+
+pub fn login_password() -> Result<Option<String>> {
+    credentials::get_secret(LOGIN_ACCOUNT)
+}
+
+// Log in on the console if it asks, then reboot into recovery
+fn uart_recovery(fixture: &LabFixtureSettings, password: Option<String>) -> Result<()> {
+    let mut port = open_console(&fixture.serial_port, fixture.serial_baud)?;
+    let port = port.as_mut();
+
+    let output = send_line(port, "")?;
+    console_login(port, &output, &fixture.ssh_user, &password)?;
+    let output = send_line(port, "sudo reboot forced-recovery")?;
+    if output.contains("password for") {
+        let password = password.as_deref().context("sudo asks for a password; store it as the uart_login credential")?;
+        send_line(port, password)?;
+    }
+    Ok(())
+}
+
+// Fire the fixture's recovery method without waiting for the board
+pub async fn trigger(fixture: &LabFixtureSettings) -> Result<&'static str> {
+    let method = method(fixture).with_context(|| format!("Fixture {} has no way to enter recovery mode configured", fixture.name))?;
+    match method {
+        "command" => lab::run_hook(fixture, "recovery", &fixture.recovery_command).await?,
+        "relay" => {
+            // Without a reset relay the board is powered up with REC held instead
+            if fixture.relay_reset_channel == 0 {
+                lab::run_hook(fixture, "power off", &fixture.power_off_command).await?;
+            }
+            let relay_fixture = fixture.clone();
+            let hold = tokio::task::spawn_blocking(move || relay_recovery(&relay_fixture));
+            if fixture.relay_reset_channel == 0 {
+                lab::run_hook(fixture, "power on", &fixture.power_on_command).await?;
+            }
+            hold.await??;
+        }
+        _ => {
+            let password = login_password()?;
+            let uart_fixture = fixture.clone();
+            tokio::task::spawn_blocking(move || uart_recovery(&uart_fixture, password)).await??;
+        }
+    }
+    info!("Triggered recovery mode on {} via {}", fixture.name, method);
+    Ok(method)
+}
+
+// A fixture by name, or the fixture a connected device is plugged into
+fn resolve_fixture(state: &AppState, device_id: &str) -> Result<LabFixtureSettings> {
+    if let Ok(fixture) = lab::find_fixture(state, device_id) {
+        return Ok(fixture);
+    }
+    let port = state
+        .connected_devices
+        .lock()
+        .unwrap()
+        .values()
+        .find(|d| d.id == device_id || d.stable_id.as_deref() == Some(device_id))
+        .and_then(|d| d.usb_info.as_ref()?.port_path.clone())
+        .with_context(|| format!("{} is neither a lab fixture nor a connected device", device_id))?;
+    let fixtures = state.settings.lock().unwrap().lab_fixtures.clone();
+    fixtures
+        .into_iter()
+        .find(|f| f.usb_port_path == port)
+        .with_context(|| format!("No lab fixture is configured for port {}", port))
+}
+
+pub async fn enter(state: &AppState, device_id: &str) -> Result<RecoveryEntry> {
+    let fixture = resolve_fixture(state, device_id)?;
+    let on_fixture = |d: &JetsonDevice| {
+        d.usb_info.as_ref().is_some_and(|usb| {
+            usb.is_recovery_mode && !fixture.usb_port_path.is_empty() && usb.port_path.as_deref() == Some(fixture.usb_port_path.as_str())
+        })
+    };
+    let method = trigger(&fixture).await?;
+    if fixture.usb_port_path.is_empty() {
+        return Err(anyhow::anyhow!("Recovery was triggered, but {} has no USB port to watch for the board", fixture.name));
+    }
+    if !usb_watch::wait_for_device(state, RECOVERY_WAIT, on_fixture, || false).await {
+        return Err(anyhow::anyhow!(
+            "{} did not appear in recovery mode within {} seconds",
+            fixture.name,
+            RECOVERY_WAIT.as_secs()
+        ));
+    }
+    let device = lab::fixture_device(state, &fixture).context("The board left recovery mode again")?;
+    Ok(RecoveryEntry {
+        fixture: fixture.name,
+        method: method.to_string(),
+        device,
+    })
+}
+
+// Put a board into recovery mode and wait for it to enumerate; `device_id` is a lab fixture name or
+// the ID of a device connected to a fixture's port
+#[command]
+pub async fn enter_recovery_mode(device_id: String, state: State<'_, Arc<AppState>>) -> Result<RecoveryEntry, String> {
+    enter(&state, &device_id).await.map_err(|e| format!("{:#}", e))
+}
@@ -0,0 +1,176 @@
+// CFU - Cordatus Flash Utility - Published Checksums
+// Checks downloaded BSP and rootfs archives against the checksums NVIDIA publishes for them before anything
+// is extracted, so a corrupted download fails in seconds instead of halfway through a 40 minute flash
+
+use crate::job_host::JobHost;
+use crate::{checksum, native_flash, peers, AppState, FlashCommand};
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{command, State};
+
+const BUNDLED_CHECKSUMS: &str = include_str!("../../data/published_checksums.json");
+const CACHE_FILE: &str = "published_checksums.json";
+const FETCH_TIMEOUT: Duration = Duration::from_secs(15);
+
+// Checksums of release files by download URL; sha256 is preferred where both are listed
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PublishedChecksums {
+    pub schema: u32,
+    pub files: Vec<PublishedChecksum>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublishedChecksum {
+    pub url: String,
+    #[serde(default)]
+    pub sha256: Option<String>,
+    #[serde(default)]
+    pub md5: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArtifactVerification {
+    pub file_name: String,
+    pub url: String,
+    pub status: String, // 'verified' | 'mismatch' | 'unlisted' | 'missing'
+    pub algorithm: Option<String>, // 'sha256' | 'md5'
+}
+
+impl PublishedChecksums {
+    fn bundled() -> Self {
+        serde_json::from_str(BUNDLED_CHECKSUMS).unwrap_or_default()
+    }
+
+    fn find(&self, url: &str) -> Option<&PublishedChecksum> {
+        self.files.iter().find(|file| file.url == url)
+    }
+
+    // Entries of `other` replace ours for the same URL
+    fn merge(mut self, other: PublishedChecksums) -> Self {
+        self.files.retain(|file| other.find(&file.url).is_none());
+        self.files.extend(other.files);
+        self
+    }
+}
+
+pub fn load(path: &Path) -> Result<PublishedChecksums> {
+    let content = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&content).context("Invalid published checksums document")
+}
+
+async fn fetch(url: &str) -> Result<PublishedChecksums> {
+    reqwest::Client::new()
+        .get(url)
+        .timeout(FETCH_TIMEOUT)
+        .send()
+        .await
+        .context("Failed to download published checksums")?
+        .error_for_status()?
+        .json()
+        .await
+        .context("Invalid published checksums document")
+}
+
+// Refresh the checksum list from the configured URL and return the file the flash script reads.
+// An unreachable URL keeps the last list that was fetched.
+pub async fn prepare(app: &impl JobHost, state: &AppState) -> Result<PathBuf> {
+    let path = crate::app_data_file(app, CACHE_FILE)?;
+    let url = state.settings.lock().unwrap().published_checksums_url.clone();
+    let remote = if url.is_empty() {
+        None
+    } else {
+        match fetch(&url).await {
+            Ok(remote) => Some(remote),
+            Err(e) => {
+                warn!("Using cached published checksums: {}", e);
+                load(&path).ok()
+            }
+        }
+    };
+    let checksums = match remote {
+        Some(remote) => PublishedChecksums::bundled().merge(remote),
+        None => PublishedChecksums::bundled(),
+    };
+    std::fs::write(&path, serde_json::to_string_pretty(&checksums)?).context("Failed to write published checksums")?;
+    Ok(path)
+}
+
+pub fn verify(archive: &Path, url: &str, checksums: &PublishedChecksums) -> Result<ArtifactVerification> {
+    let file_name = archive.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let mut result = ArtifactVerification {
+        file_name,
+        url: url.to_string(),
+        status: "unlisted".to_string(),
+        algorithm: None,
+    };
+    if !archive.exists() {
+        result.status = "missing".to_string();
+        return Ok(result);
+    }
+    let Some(published) = checksums.find(url) else {
+        return Ok(result);
+    };
+    let (algorithm, expected, actual) = match (&published.sha256, &published.md5) {
+        // The flash script may be downloading the archive meanwhile, so it is read rather than mapped
+        (Some(expected), _) => ("sha256", expected, checksum::sha256_file_streamed(archive)?),
+        (None, Some(expected)) => ("md5", expected, checksum::md5_file_streamed(archive)?),
+        (None, None) => return Ok(result),
+    };
+    result.algorithm = Some(algorithm.to_string());
+    result.status = if actual.eq_ignore_ascii_case(expected) { "verified" } else { "mismatch" }.to_string();
+    Ok(result)
+}
+
+// Helper mode for the flash script: `cfu --verify-download <archive> <url> <checksums>`.
+// Exits 1 only on a mismatch; archives without a published checksum pass.
+pub fn run_from_args() -> Option<i32> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) != Some("--verify-download") {
+        return None;
+    }
+    let (Some(archive), Some(url), Some(list)) = (args.get(2), args.get(3), args.get(4)) else {
+        eprintln!("Usage: {} --verify-download <archive> <url> <checksums>", args[0]);
+        return Some(2);
+    };
+    let result = load(Path::new(list)).and_then(|checksums| verify(Path::new(archive), url, &checksums));
+    match result {
+        Ok(verification) => {
+            println!("Checking download {} against published checksums: {}", verification.file_name, verification.status);
+            Some(if verification.status == "mismatch" { 1 } else { 0 })
+        }
+        Err(e) => {
+            eprintln!("{:#}", e);
+            Some(1)
+        }
+    }
+}
+
+// Check the archives a flash command would use, as far as they are already downloaded
+#[command]
+pub async fn verify_artifacts(
+    command: FlashCommand,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<ArtifactVerification>, String> {
+    let files = native_flash::release_files(&command).await.map_err(|e| e.to_string())?;
+    let path = prepare(&app, &state).await.map_err(|e| e.to_string())?;
+    let checksums = load(&path).map_err(|e| e.to_string())?;
+    let directory = peers::artifact_dir();
+    let results = tokio::task::spawn_blocking(move || {
+        files
+            .iter()
+            .map(|(file_name, url)| verify(&directory.join(file_name), url, &checksums))
+            .collect::<Result<Vec<_>>>()
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())?;
+    for result in &results {
+        info!("Published checksum check of {}: {}", result.file_name, result.status);
+    }
+    Ok(results)
+}
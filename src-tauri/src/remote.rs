@@ -0,0 +1,99 @@
+// CFU - Cordatus Flash Utility - Remote Device Inspection
+// Queries a booted Jetson over SSH for its L4T/JetPack, CUDA and installed SDK components
+
+use crate::ssh::{run_remote, SshTarget};
+use crate::version_matrix::ReleaseInfo;
+use crate::AppState;
+use log::info;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tauri::{command, State};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstalledPackage {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteJetpackInfo {
+    pub host: String,
+    pub l4t_version: Option<String>,
+    pub jetpack_version: Option<String>,
+    pub cuda_version: Option<String>,
+    pub release: Option<ReleaseInfo>, // Expected component versions from the matrix
+    pub packages: Vec<InstalledPackage>,
+}
+
+// Sections are separated by "---"; dpkg-query exits non-zero when a pattern
+// has no match, so its status is ignored
+const DETECT_SCRIPT: &str = r#"cat /etc/nv_tegra_release 2>/dev/null
+echo ---
+(/usr/local/cuda/bin/nvcc --version 2>/dev/null || cat /usr/local/cuda/version.txt 2>/dev/null) | tail -n 2
+echo ---
+dpkg-query -W -f='${Package} ${Version}\n' nvidia-jetpack nvidia-l4t-core 'cuda-toolkit-*' 'libcudnn*' tensorrt 'libnvinfer[0-9]*' 'vpi*' 'deepstream*' 2>/dev/null || true"#;
+
+fn parse_cuda_version(output: &str) -> Option<String> {
+    // nvcc: "Cuda compilation tools, release 12.6, V12.6.68"; version.txt: "CUDA Version 10.2.300"
+    let regex = Regex::new(r"(?:release|CUDA Version)\s+(\d+\.\d+)").ok()?;
+    regex.captures(output).map(|caps| caps[1].to_string())
+}
+
+fn parse_packages(output: &str) -> Vec<InstalledPackage> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (name, version) = line.split_once(' ')?;
+            let version = version.trim();
+            // Removed packages are still listed by dpkg-query with an empty version
+            if version.is_empty() {
+                return None;
+            }
+            Some(InstalledPackage {
+                name: name.to_string(),
+                version: version.to_string(),
+            })
+        })
+        .collect()
+}
+
+// Inspect a booted device over SSH
+#[command]
+pub async fn detect_remote_jetpack(
+    target: SshTarget,
+    state: State<'_, Arc<AppState>>,
+) -> Result<RemoteJetpackInfo, String> {
+    info!("Detecting JetPack on remote device {}", target.host);
+
+    let output = run_remote(&target, DETECT_SCRIPT)
+        .await
+        .map_err(|e| format!("Remote detection failed: {}", e))?;
+    let sections: Vec<&str> = output.split("---\n").collect();
+    let section = |index: usize| sections.get(index).copied().unwrap_or("");
+
+    let l4t_version = crate::parse_nv_tegra_release(section(0))
+        .map(|v| v.trim_start_matches("L4T ").to_string());
+    let packages = parse_packages(section(2));
+
+    let release = l4t_version.as_ref().and_then(|l4t| {
+        let matrix = state.version_matrix.lock().unwrap();
+        matrix.resolve(&format!("L4T {}", l4t)).cloned()
+    });
+
+    // Prefer the installed meta-package ("6.2+b77"), fall back to the matrix mapping
+    let jetpack_version = packages
+        .iter()
+        .find(|p| p.name == "nvidia-jetpack")
+        .map(|p| p.version.split(['+', '-']).next().unwrap_or(&p.version).to_string())
+        .or_else(|| release.as_ref().map(|r| r.jetpack.clone()));
+
+    Ok(RemoteJetpackInfo {
+        host: target.host.clone(),
+        l4t_version,
+        jetpack_version,
+        cuda_version: parse_cuda_version(section(1)),
+        release,
+        packages,
+    })
+}
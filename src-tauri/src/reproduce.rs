@@ -0,0 +1,127 @@
+// CFU - Cordatus Flash Utility - Reproduction Scripts
+// Standalone shell scripts that rerun a planned or finished job with the same arguments, rootfs hook and
+// artifact checksums, for debugging outside the app or embedding in other automation
+
+use crate::plan::{self, ScriptInvocation};
+use crate::ssh::shell_quote;
+use crate::{AppState, FlashCommand};
+use anyhow::{Context, Result};
+use chrono::Utc;
+use log::info;
+use std::sync::Arc;
+use tauri::{command, State};
+
+const HEREDOC_END: &str = "CFU_REPRODUCE_EOF";
+
+// Variables pointing at per-job files of this station; the script writes its own copies
+const PER_JOB_VARIABLES: &[&str] = &["CFU_ROOTFS_HOOK", "CFU_ARTIFACT_PINS", "CFU_ARTIFACT_MANIFEST", "CFU_CANCEL_FILE", "CFU_SOFT_CANCEL_FILE"];
+
+fn heredoc(path: &str, content: &str) -> String {
+    format!("cat > {} <<'{}'\n{}\n{}\n", path, HEREDOC_END, content.trim_end_matches('\n'), HEREDOC_END)
+}
+
+fn render_script(description: &str, invocation: &ScriptInvocation) -> String {
+    let mut script = format!(
+        "#!/usr/bin/env bash\n\
+         # Reproduces {}\n\
+         # Generated by Cordatus Flash Utility {} on {}\n\
+         # Run it from a Cordatus Flash Utility checkout or installation, or set CFU_DIR to one.\n\
+         set -euo pipefail\n\n\
+         CFU_DIR=\"${{CFU_DIR:-{}}}\"\n\
+         work=\"$(mktemp -d)\"\n\
+         trap 'rm -rf \"${{work}}\"' EXIT\n\n",
+        description,
+        env!("CARGO_PKG_VERSION"),
+        Utc::now().format("%Y-%m-%d %H:%M UTC"),
+        invocation.working_directory.replace('"', "\\\""),
+    );
+
+    if let Some(pins) = &invocation.artifact_pins {
+        script.push_str("# Archives in ~/openzeka must match these checksums\n");
+        script.push_str(&heredoc("\"${work}/artifact_pins.txt\"", pins));
+        script.push_str("export CFU_ARTIFACT_PINS=\"${work}/artifact_pins.txt\"\n\n");
+    }
+    if let Some(hook) = &invocation.rootfs_hook {
+        script.push_str("# Provisioning applied to the rootfs before flashing\n");
+        script.push_str(&heredoc("\"${work}/rootfs_hook.sh\"", hook));
+        script.push_str("export CFU_ROOTFS_HOOK=\"${work}/rootfs_hook.sh\"\n\n");
+    }
+    script.push_str("export CFU_ARTIFACT_MANIFEST=\"${work}/artifacts.txt\"\n");
+    for (key, value) in &invocation.environment {
+        // The extractor is this app's binary; without it the flash script falls back to tar
+        if !PER_JOB_VARIABLES.contains(&key.as_str()) && key != "CFU_EXTRACTOR" {
+            script.push_str(&format!("export {}={}\n", key, shell_quote(value)));
+        }
+    }
+
+    let arguments: Vec<String> = invocation.arguments.iter().map(|a| shell_quote(a)).collect();
+    script.push_str(&format!(
+        "\ncd \"${{CFU_DIR}}\"\n{} {}\n",
+        shell_quote(&invocation.program),
+        arguments.join(" ")
+    ));
+    script
+}
+
+async fn reproduction_script(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    flash_id: Option<String>,
+    command: Option<FlashCommand>,
+) -> Result<String> {
+    let (description, command) = match (flash_id, command) {
+        (Some(flash_id), _) => {
+            let record = state
+                .history
+                .lock()
+                .unwrap()
+                .jobs
+                .iter()
+                .find(|r| r.flash_id == flash_id)
+                .cloned()
+                .with_context(|| format!("Flash job not found: {}", flash_id))?;
+            let mut command = record.command;
+            // The archives a finished job actually used are pinned, so a rerun with different files stops early
+            if !record.artifacts.is_empty() {
+                command.pinned_artifacts = record.artifacts;
+            }
+            let description = format!(
+                "flash job {} ({}, {} at {})",
+                flash_id,
+                record.status,
+                command.device_module,
+                record.started_at.format("%Y-%m-%d %H:%M UTC")
+            );
+            (description, command)
+        }
+        (None, Some(command)) => (format!("a planned flash of {}", command.device_module), command),
+        (None, None) => return Err(anyhow::anyhow!("Give a flash job or a flash command")),
+    };
+    let description = format!("{}: {} {} on {}", description, command.product, command.jetpack_version, command.storage_device);
+    let (invocation, _) = plan::script_invocation(app, state, &command).await?;
+    Ok(render_script(&description, &invocation))
+}
+
+// Shell script reproducing a finished job (`flash_id`) or a planned one (`command`), written to `path` when given
+#[command]
+pub async fn export_reproduction_script(
+    flash_id: Option<String>,
+    command: Option<FlashCommand>,
+    path: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<String, String> {
+    let script = reproduction_script(&app, &state, flash_id, command)
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Some(path) = &path {
+        std::fs::write(path, &script).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).map_err(|e| e.to_string())?;
+        }
+        info!("Exported reproduction script to {}", path);
+    }
+    Ok(script)
+}
@@ -0,0 +1,492 @@
+// CFU - Cordatus Flash Utility - Rootfs Snapshots
+// Customizations of the workspace rootfs (packages, SSH keys, overlays, scripts) are applied through the
+// app and journaled. A snapshot freezes the journal with exact package versions and the resulting tree
+// hash, so the same customized rootfs can be rebuilt from a freshly extracted one, here or on another station.
+
+use crate::{checksum, peers, AppState};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::info;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use tauri::{command, Emitter, State};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command as TokioCommand;
+
+const SNAPSHOTS_FILE: &str = "rootfs_snapshots.json";
+// Paths whose contents differ between otherwise identical builds: runtime directories, logs and caches.
+// Only their names are hashed.
+const VOLATILE_PATHS: [&str; 14] = [
+    "proc",
+    "sys",
+    "dev",
+    "run",
+    "tmp",
+    "var/tmp",
+    "var/log",
+    "var/cache/apt",
+    "var/cache/debconf",
+    "var/cache/ldconfig",
+    "var/lib/apt/lists",
+    "var/lib/dpkg/status-old",
+    "etc/ld.so.cache",
+    "var/backups",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomizationStep {
+    pub kind: String, // 'packages' | 'ssh_key' | 'overlay' | 'script'
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub packages: Vec<String>, // "name" or "name=version"; snapshots pin the installed versions
+    #[serde(default)]
+    pub user: Option<String>, // SSH key owner, "root" or a user; a user not created yet gets it from /etc/skel
+    #[serde(default)]
+    pub public_key: Option<String>,
+    #[serde(default)]
+    pub archive: Option<String>, // Overlay tarball extracted over the rootfs, by absolute path
+    #[serde(default)]
+    pub sha256: Option<String>, // Of the overlay, recorded when it is applied
+    #[serde(default)]
+    pub script: Option<String>, // Run with bash inside the rootfs
+}
+
+// The customizations applied to the current workspace rootfs since it was extracted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Workspace {
+    pub rootfs: String,
+    pub rootfs_inode: u64, // A re-extracted rootfs is a new directory, which starts a new journal
+    pub l4t_version: Option<String>,
+    pub base_tree_hash: String,
+    pub steps: Vec<CustomizationStep>,
+    pub started_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RootfsSnapshot {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub l4t_version: Option<String>,
+    pub base_tree_hash: String, // The stock rootfs the steps start from
+    pub steps: Vec<CustomizationStep>,
+    pub tree_hash: String,
+    pub station: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SnapshotDb {
+    workspace: Option<Workspace>,
+    snapshots: Vec<RootfsSnapshot>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RebuildReport {
+    pub snapshot: String,
+    pub tree_hash: String,
+    pub matches: bool, // Whether the rebuilt tree is identical to the snapshot's
+}
+
+fn load_db(app: &tauri::AppHandle) -> SnapshotDb {
+    crate::app_data_file(app, SNAPSHOTS_FILE)
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_db(app: &tauri::AppHandle, db: &SnapshotDb) -> Result<()> {
+    let path = crate::app_data_file(app, SNAPSHOTS_FILE)?;
+    std::fs::write(path, serde_json::to_string_pretty(db)?).context("Failed to save rootfs snapshots")
+}
+
+fn rootfs_dir() -> PathBuf {
+    peers::artifact_dir().join("Linux_for_Tegra").join("rootfs")
+}
+
+fn emit_status(app: &tauri::AppHandle, message: &str) {
+    info!("Rootfs snapshot: {}", message);
+    let _ = app.emit("rootfs-snapshot-status", serde_json::json!({ "message": message }));
+}
+
+// Hash of a directory tree: every path with its type, mode, owner and symlink target or content hash,
+// in path order. Timestamps are left out, they differ on every build.
+pub fn tree_hash(root: &Path) -> Result<String> {
+    let device = std::fs::symlink_metadata(root).with_context(|| format!("Failed to read {}", root.display()))?.dev();
+    let mut entries: Vec<(String, Option<std::fs::Metadata>)> = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative) = pending.pop() {
+        let directory = root.join(&relative);
+        for entry in std::fs::read_dir(&directory).with_context(|| format!("Failed to read {}", directory.display()))? {
+            let entry = entry?;
+            let path = relative.join(entry.file_name());
+            let name = path.to_string_lossy().to_string();
+            let metadata = entry.path().symlink_metadata()?;
+            if VOLATILE_PATHS.contains(&name.as_str()) {
+                entries.push((name, None));
+                continue;
+            }
+            // Other filesystems mounted into the rootfs are not part of it
+            if metadata.is_dir() && metadata.dev() == device {
+                pending.push(path);
+            }
+            entries.push((name, Some(metadata)));
+        }
+    }
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let is_file = |metadata: &Option<std::fs::Metadata>| metadata.as_ref().is_some_and(|m| m.is_file());
+    let files: Vec<PathBuf> = entries.iter().filter(|(_, m)| is_file(m)).map(|(name, _)| root.join(name)).collect();
+    let mut sums = checksum::sha256_files(&files).into_iter();
+    let mut hasher = Sha256::new();
+    for (name, metadata) in &entries {
+        let Some(metadata) = metadata else {
+            hasher.update(format!("{}\n", name).as_bytes());
+            continue;
+        };
+        let content = if metadata.is_file() {
+            sums.next().context("Missing file checksum")??
+        } else if metadata.file_type().is_symlink() {
+            std::fs::read_link(root.join(name))?.to_string_lossy().to_string()
+        } else {
+            String::new()
+        };
+        let line = format!(
+            "{:o} {} {} {} {}\n",
+            metadata.permissions().mode(),
+            metadata.uid(),
+            metadata.gid(),
+            name,
+            content
+        );
+        hasher.update(line.as_bytes());
+    }
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+// Helper mode: `cfu --tree-hash <dir>`, run under sudo since a rootfs has files only root may read
+pub fn run_from_args() -> Option<i32> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) != Some("--tree-hash") {
+        return None;
+    }
+    let Some(dir) = args.get(2) else {
+        eprintln!("Usage: {} --tree-hash <directory>", args[0]);
+        return Some(2);
+    };
+    match tree_hash(Path::new(dir)) {
+        Ok(hash) => {
+            println!("{}", hash);
+            Some(0)
+        }
+        Err(e) => {
+            eprintln!("{:#}", e);
+            Some(1)
+        }
+    }
+}
+
+async fn hash_rootfs(rootfs: &Path) -> Result<String> {
+    let exe = std::env::current_exe().context("Failed to locate the app binary")?;
+    let output = TokioCommand::new("sudo")
+        .arg("-n")
+        .arg(exe)
+        .arg("--tree-hash")
+        .arg(rootfs)
+        .output()
+        .await
+        .context("Failed to start sudo")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("Failed to hash {}: {}", rootfs.display(), String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// Run a script as root, with `root` as its first argument, or inside the rootfs with `chroot`
+async fn run_root(script: &str, root: &Path, chroot: bool) -> Result<()> {
+    let mut cmd = TokioCommand::new("sudo");
+    cmd.arg("-n");
+    if chroot {
+        cmd.arg("chroot").arg(root).args(["/usr/bin/env", "DEBIAN_FRONTEND=noninteractive", "/bin/bash", "-s"]);
+    } else {
+        cmd.args(["/bin/bash", "-s"]).arg(root);
+    }
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to start sudo")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(script.as_bytes()).await?;
+    }
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let lines: Vec<&str> = stderr.lines().collect();
+        let last = lines[lines.len().saturating_sub(5)..].join("\n");
+        return Err(anyhow::anyhow!("Step failed ({}): {}", output.status.code().unwrap_or(-1), last));
+    }
+    Ok(())
+}
+
+fn step_script(step: &CustomizationStep) -> Result<(String, bool)> {
+    let quote = crate::ssh::shell_quote;
+    match step.kind.as_str() {
+        "packages" => {
+            if step.packages.is_empty() {
+                return Err(anyhow::anyhow!("No packages to install"));
+            }
+            let packages: Vec<String> = step.packages.iter().map(|p| quote(p)).collect();
+            // Foreign-architecture chroots need qemu-user-static registered with binfmt on the host
+            Ok((format!("set -e\napt-get update\napt-get install -y --no-install-recommends {}\napt-get clean\n", packages.join(" ")), true))
+        }
+        "ssh_key" => {
+            let user = step.user.as_deref().unwrap_or("root");
+            let key = step.public_key.as_deref().map(str::trim).filter(|k| !k.is_empty()).context("No public key given")?;
+            if user.contains('/') || user.is_empty() {
+                return Err(anyhow::anyhow!("Invalid user name: {}", user));
+            }
+            let home = if user == "root" { "root".to_string() } else { format!("home/{}", user) };
+            Ok((
+                format!(
+                    "set -e\nhome=\"$1\"/{home}\n[ -d \"$home\" ] || home=\"$1\"/etc/skel\nmkdir -p \"$home/.ssh\"\n\
+                     grep -qxF {key} \"$home/.ssh/authorized_keys\" 2>/dev/null || echo {key} >> \"$home/.ssh/authorized_keys\"\n\
+                     chmod 700 \"$home/.ssh\"\nchmod 600 \"$home/.ssh/authorized_keys\"\n\
+                     chown -R --reference=\"$home\" \"$home/.ssh\"\n",
+                    home = quote(&home),
+                    key = quote(key)
+                ),
+                false,
+            ))
+        }
+        "overlay" => {
+            let archive = step.archive.as_deref().context("No overlay archive given")?;
+            Ok((format!("set -e\ntar -xpf {} -C \"$1\"\n", quote(archive)), false))
+        }
+        "script" => Ok((step.script.clone().context("No script given")?, true)),
+        other => Err(anyhow::anyhow!("Unknown customization step: {}", other)),
+    }
+}
+
+// Apply one step; overlays are checked against the recorded checksum, or have it recorded
+async fn apply_step(rootfs: &Path, step: &mut CustomizationStep) -> Result<()> {
+    if step.kind == "overlay" {
+        let archive = PathBuf::from(step.archive.as_deref().context("No overlay archive given")?);
+        let hashed = archive.clone();
+        let sha256 = tokio::task::spawn_blocking(move || checksum::sha256_file(&hashed)).await??;
+        match &step.sha256 {
+            Some(expected) if !expected.eq_ignore_ascii_case(&sha256) => {
+                return Err(anyhow::anyhow!("{} differs from the overlay the snapshot was made with", archive.display()))
+            }
+            _ => step.sha256 = Some(sha256),
+        }
+    }
+    let (script, chroot) = step_script(step)?;
+    run_root(&script, rootfs, chroot).await
+}
+
+fn installed_packages(rootfs: &Path) -> BTreeMap<String, String> {
+    let status = std::fs::read_to_string(rootfs.join("var/lib/dpkg/status")).unwrap_or_default();
+    status
+        .split("\n\n")
+        .filter_map(|stanza| {
+            let field = |name: &str| stanza.lines().find_map(|line| line.strip_prefix(name)).map(str::trim);
+            Some((field("Package:")?.to_string(), field("Version:")?.to_string()))
+        })
+        .collect()
+}
+
+// Package steps with the versions now installed, so a rebuild installs exactly those
+fn pin_versions(rootfs: &Path, steps: &[CustomizationStep]) -> Vec<CustomizationStep> {
+    let installed = installed_packages(rootfs);
+    steps
+        .iter()
+        .cloned()
+        .map(|mut step| {
+            if step.kind == "packages" {
+                step.packages = step
+                    .packages
+                    .iter()
+                    .map(|package| match installed.get(package.as_str()) {
+                        Some(version) if !package.contains('=') => format!("{}={}", package, version),
+                        _ => package.clone(),
+                    })
+                    .collect();
+            }
+            step
+        })
+        .collect()
+}
+
+fn rootfs_inode(rootfs: &Path) -> Result<u64> {
+    Ok(std::fs::metadata(rootfs)
+        .with_context(|| format!("No extracted rootfs at {}; prepare a flash of the release first", rootfs.display()))?
+        .ino())
+}
+
+// The journal of the current rootfs, started anew when it was re-extracted
+async fn current_workspace(app: &tauri::AppHandle, rootfs: &Path) -> Result<Workspace> {
+    let inode = rootfs_inode(rootfs)?;
+    if let Some(workspace) = load_db(app).workspace.filter(|w| w.rootfs_inode == inode) {
+        return Ok(workspace);
+    }
+    emit_status(app, "Hashing the stock rootfs");
+    Ok(Workspace {
+        rootfs: rootfs.to_string_lossy().to_string(),
+        rootfs_inode: inode,
+        l4t_version: std::fs::read_to_string(rootfs.join("etc/nv_tegra_release"))
+            .ok()
+            .and_then(|content| crate::parse_nv_tegra_release(&content)),
+        base_tree_hash: hash_rootfs(rootfs).await?,
+        steps: Vec::new(),
+        started_at: Utc::now(),
+    })
+}
+
+// Customizations applied to the workspace rootfs so far
+#[command]
+pub async fn get_rootfs_workspace(app: tauri::AppHandle) -> Result<Option<Workspace>, String> {
+    let inode = rootfs_inode(&rootfs_dir()).ok();
+    Ok(load_db(&app).workspace.filter(|w| Some(w.rootfs_inode) == inode))
+}
+
+// Apply a customization to the workspace rootfs and add it to the journal
+#[command]
+pub async fn customize_rootfs(mut step: CustomizationStep, app: tauri::AppHandle) -> Result<Workspace, String> {
+    let customize = async {
+        let rootfs = rootfs_dir();
+        let mut workspace = current_workspace(&app, &rootfs).await?;
+        emit_status(&app, &format!("Applying {} step", step.kind));
+        apply_step(&rootfs, &mut step).await?;
+        workspace.steps.push(step);
+        let mut db = load_db(&app);
+        db.workspace = Some(workspace.clone());
+        save_db(&app, &db)?;
+        Ok::<_, anyhow::Error>(workspace)
+    };
+    customize.await.map_err(|e| format!("{:#}", e))
+}
+
+// Snapshot the workspace: its steps with pinned package versions and the resulting tree hash
+#[command]
+pub async fn create_rootfs_snapshot(
+    name: String,
+    description: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<RootfsSnapshot, String> {
+    let create = async {
+        let rootfs = rootfs_dir();
+        let workspace = current_workspace(&app, &rootfs).await?;
+        if workspace.steps.is_empty() {
+            return Err(anyhow::anyhow!("The rootfs has no customizations to snapshot"));
+        }
+        emit_status(&app, "Hashing the customized rootfs");
+        let snapshot = RootfsSnapshot {
+            name: name.clone(),
+            description: description.unwrap_or_default(),
+            l4t_version: workspace.l4t_version.clone(),
+            base_tree_hash: workspace.base_tree_hash.clone(),
+            steps: pin_versions(&rootfs, &workspace.steps),
+            tree_hash: hash_rootfs(&rootfs).await?,
+            station: crate::history::station_name(&state),
+            created_at: Utc::now(),
+        };
+        let mut db = load_db(&app);
+        db.snapshots.retain(|s| s.name != name);
+        db.snapshots.push(snapshot.clone());
+        save_db(&app, &db)?;
+        info!("Created rootfs snapshot {} ({})", name, snapshot.tree_hash);
+        Ok(snapshot)
+    };
+    create.await.map_err(|e| format!("{:#}", e))
+}
+
+#[command]
+pub async fn list_rootfs_snapshots(app: tauri::AppHandle) -> Result<Vec<RootfsSnapshot>, String> {
+    Ok(load_db(&app).snapshots)
+}
+
+#[command]
+pub async fn delete_rootfs_snapshot(name: String, app: tauri::AppHandle) -> Result<(), String> {
+    let mut db = load_db(&app);
+    let before = db.snapshots.len();
+    db.snapshots.retain(|s| s.name != name);
+    if db.snapshots.len() == before {
+        return Err(format!("Rootfs snapshot not found: {}", name));
+    }
+    save_db(&app, &db).map_err(|e| e.to_string())
+}
+
+// Write a snapshot to a file, to rebuild it on another station
+#[command]
+pub async fn export_rootfs_snapshot(name: String, path: String, app: tauri::AppHandle) -> Result<(), String> {
+    let snapshot = load_db(&app)
+        .snapshots
+        .into_iter()
+        .find(|s| s.name == name)
+        .ok_or_else(|| format!("Rootfs snapshot not found: {}", name))?;
+    let content = serde_json::to_string_pretty(&snapshot).map_err(|e| e.to_string())?;
+    std::fs::write(&path, content).map_err(|e| format!("Failed to write {}: {}", path, e))
+}
+
+// Add an exported snapshot, replacing one with the same name
+#[command]
+pub async fn import_rootfs_snapshot(path: String, app: tauri::AppHandle) -> Result<RootfsSnapshot, String> {
+    let content = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let snapshot: RootfsSnapshot = serde_json::from_str(&content).map_err(|e| format!("Invalid rootfs snapshot: {}", e))?;
+    let mut db = load_db(&app);
+    db.snapshots.retain(|s| s.name != snapshot.name);
+    db.snapshots.push(snapshot.clone());
+    save_db(&app, &db).map_err(|e| e.to_string())?;
+    Ok(snapshot)
+}
+
+// Replay a snapshot on the freshly extracted stock rootfs it was made from, then compare tree hashes
+#[command]
+pub async fn rebuild_rootfs_snapshot(name: String, app: tauri::AppHandle) -> Result<RebuildReport, String> {
+    let rebuild = async {
+        let snapshot = load_db(&app)
+            .snapshots
+            .into_iter()
+            .find(|s| s.name == name)
+            .with_context(|| format!("Rootfs snapshot not found: {}", name))?;
+        let rootfs = rootfs_dir();
+        let mut workspace = current_workspace(&app, &rootfs).await?;
+        if !workspace.steps.is_empty() || workspace.base_tree_hash != snapshot.base_tree_hash {
+            return Err(anyhow::anyhow!(
+                "The workspace rootfs is not the stock {} rootfs the snapshot starts from; extract it again first",
+                snapshot.l4t_version.as_deref().unwrap_or("L4T")
+            ));
+        }
+        for (index, step) in snapshot.steps.iter().enumerate() {
+            emit_status(&app, &format!("Replaying step {} of {}: {}", index + 1, snapshot.steps.len(), step.kind));
+            let mut step = step.clone();
+            apply_step(&rootfs, &mut step).await?;
+            workspace.steps.push(step);
+        }
+        let mut db = load_db(&app);
+        db.workspace = Some(workspace);
+        save_db(&app, &db)?;
+
+        emit_status(&app, "Hashing the rebuilt rootfs");
+        let tree_hash = hash_rootfs(&rootfs).await?;
+        let matches = tree_hash == snapshot.tree_hash;
+        info!("Rebuilt rootfs snapshot {}: {}", name, if matches { "identical" } else { "differs" });
+        Ok::<_, anyhow::Error>(RebuildReport {
+            snapshot: name.clone(),
+            tree_hash,
+            matches,
+        })
+    };
+    rebuild.await.map_err(|e| format!("{:#}", e))
+}
@@ -0,0 +1,160 @@
+// CFU - Cordatus Flash Utility - Rootfs Sync
+// For day-to-day application development: rsyncs the changes in a local Linux_for_Tegra rootfs to the
+// APP partition of a booted device over SSH instead of reflashing it. A dry run lists what would change.
+// Files that are per device or only exist at runtime are never synced, see BUILTIN_EXCLUDES.
+
+use crate::ssh::SshTarget;
+use crate::{maintenance, AppState};
+use anyhow::{Context, Result};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Arc;
+use tauri::{command, State};
+use tokio::process::Command as TokioCommand;
+
+// Anchored at the rootfs: runtime filesystems, mount points and the device's own identity and state
+const BUILTIN_EXCLUDES: [&str; 17] = [
+    "/proc/*",
+    "/sys/*",
+    "/dev/*",
+    "/run/*",
+    "/tmp/*",
+    "/mnt/*",
+    "/media/*",
+    "/lost+found",
+    "/home/*",
+    "/var/log/*",
+    "/var/tmp/*",
+    "/etc/hostname",
+    "/etc/hosts",
+    "/etc/machine-id",
+    "/etc/ssh/ssh_host_*",
+    "/etc/NetworkManager/system-connections/*",
+    "/etc/nv_boot_control.conf",
+];
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SyncRequest {
+    pub target: SshTarget,
+    #[serde(default)]
+    pub source: Option<String>, // Defaults to the rootfs of the default L4T workspace
+    #[serde(default)]
+    pub excludes: Vec<String>, // Added to the built-in and configured excludes
+    #[serde(default)]
+    pub delete: bool, // Also remove files from the device that are gone from the source
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncChange {
+    pub path: String,
+    pub change: String, // 'create' | 'update' | 'attributes' | 'delete'
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncReport {
+    pub host: String,
+    pub source: String,
+    pub dry_run: bool,
+    pub changes: Vec<SyncChange>,
+}
+
+// One line of rsync --itemize-changes output, e.g. ">f.st...... usr/bin/app" or "*deleting etc/old.conf";
+// directories whose only change is their timestamp are left out
+fn parse_change(line: &str) -> Option<SyncChange> {
+    if let Some(path) = line.strip_prefix("*deleting") {
+        return Some(SyncChange {
+            path: path.trim().to_string(),
+            change: "delete".to_string(),
+        });
+    }
+    let (flags, path) = line.split_once(' ')?;
+    let flags: Vec<char> = flags.chars().collect();
+    if flags.len() < 9 || !matches!(flags[0], '<' | '>' | 'c' | 'h' | '.') {
+        return None;
+    }
+    let change = if flags[2..].iter().all(|&flag| flag == '+') {
+        "create"
+    } else if flags[2] == 'c' || flags[3] == 's' {
+        "update"
+    } else if flags[1] == 'd' {
+        return None;
+    } else {
+        "attributes"
+    };
+    Some(SyncChange {
+        path: path.trim().to_string(),
+        change: change.to_string(),
+    })
+}
+
+fn source_dir(source: Option<&str>) -> Result<PathBuf> {
+    let source = source
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(maintenance::default_l4t_dir()).join("rootfs"));
+    // Syncing anything but a Jetson rootfs to / would wreck the device
+    if !source.join("etc/nv_tegra_release").is_file() {
+        return Err(anyhow::anyhow!("{} is not an L4T rootfs", source.display()));
+    }
+    Ok(source)
+}
+
+pub async fn sync(state: &AppState, request: &SyncRequest) -> Result<SyncReport> {
+    let source = source_dir(request.source.as_deref())?;
+    let configured = state.settings.lock().unwrap().rootfs_sync_excludes.clone();
+
+    // The rootfs is owned by root, so rsync runs through sudo; ssh then authenticates with the
+    // configured identity or the user's agent, whose socket sudo is asked to keep
+    let mut cmd = TokioCommand::new("sudo");
+    cmd.args(["-n", "--preserve-env=SSH_AUTH_SOCK", "rsync"])
+        .args(["--archive", "--acls", "--xattrs", "--hard-links", "--numeric-ids", "--itemize-changes"])
+        .arg("--rsync-path=sudo -n rsync")
+        .arg("-e")
+        .arg(request.target.ssh_command());
+    if request.dry_run {
+        cmd.arg("--dry-run");
+    }
+    if request.delete {
+        cmd.arg("--delete");
+    }
+    for pattern in BUILTIN_EXCLUDES.iter().copied().chain(configured.iter().chain(&request.excludes).map(String::as_str)) {
+        cmd.arg(format!("--exclude={}", pattern));
+    }
+    cmd.arg("--")
+        .arg(format!("{}/", source.display()))
+        .arg(format!("{}:/", request.target.destination()?))
+        .stdin(Stdio::null());
+
+    info!(
+        "Syncing {} to {}{}",
+        source.display(),
+        request.target.host,
+        if request.dry_run { " (dry run)" } else { "" }
+    );
+    let output = cmd.output().await.context("Failed to start rsync")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "rsync to {} failed ({}): {}; rsync must be installed on both sides and sudo must not ask for a password",
+            request.target.host,
+            output.status.code().unwrap_or(-1),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let changes: Vec<SyncChange> = String::from_utf8_lossy(&output.stdout).lines().filter_map(parse_change).collect();
+    info!("{} changes {} on {}", changes.len(), if request.dry_run { "pending" } else { "synced" }, request.target.host);
+    Ok(SyncReport {
+        host: request.target.host.clone(),
+        source: source.to_string_lossy().to_string(),
+        dry_run: request.dry_run,
+        changes,
+    })
+}
+
+// Sync rootfs changes to a booted device, or only list them with dry_run
+#[command]
+pub async fn sync_rootfs(request: SyncRequest, state: State<'_, Arc<AppState>>) -> Result<SyncReport, String> {
+    sync(&state, &request).await.map_err(|e| format!("{:#}", e))
+}
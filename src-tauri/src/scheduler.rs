@@ -0,0 +1,207 @@
+// CFU - Cordatus Flash Utility - Scheduled Jobs
+// Flash jobs queued to start at a given time (e.g. overnight when bandwidth is free), persisted across restarts.
+// A due job waits for its board in recovery mode before it starts; if none appears it is skipped, not failed.
+
+use crate::{usb_watch, AppState, FlashCommand, JetsonDevice};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{command, Emitter, Manager, State};
+use uuid::Uuid;
+
+const SCHEDULE_FILE: &str = "scheduled_jobs.json";
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+const DEVICE_WAIT: Duration = Duration::from_secs(30 * 60); // Unless the job sets wait_for_device_secs
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledJob {
+    pub schedule_id: String,
+    pub command: FlashCommand,
+    pub start_at: DateTime<Utc>,
+    pub status: String, // 'pending' | 'waiting' | 'started' | 'skipped' | 'failed' | 'cancelled'
+    pub flash_id: Option<String>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+// Jobs that were waiting for their board when the app closed wait again
+pub fn load_schedule(app: &tauri::AppHandle) -> Vec<ScheduledJob> {
+    let mut jobs: Vec<ScheduledJob> = crate::app_data_file(app, SCHEDULE_FILE)
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default();
+    for job in jobs.iter_mut().filter(|job| job.status == "waiting") {
+        job.status = "pending".to_string();
+    }
+    jobs
+}
+
+fn save_schedule(app: &tauri::AppHandle, jobs: &[ScheduledJob]) -> Result<()> {
+    let path = crate::app_data_file(app, SCHEDULE_FILE)?;
+    std::fs::write(path, serde_json::to_string_pretty(jobs)?).context("Failed to save scheduled jobs")
+}
+
+fn update_schedule(app: &tauri::AppHandle, state: &AppState, change: impl FnOnce(&mut Vec<ScheduledJob>)) {
+    let mut jobs = state.scheduled_jobs.lock().unwrap();
+    change(&mut jobs);
+    if let Err(e) = save_schedule(app, &jobs) {
+        warn!("Failed to save scheduled jobs: {}", e);
+    }
+}
+
+fn set_status(app: &tauri::AppHandle, state: &AppState, schedule_id: &str, change: impl FnOnce(&mut ScheduledJob)) {
+    update_schedule(app, state, |jobs| {
+        if let Some(entry) = jobs.iter_mut().find(|j| j.schedule_id == schedule_id) {
+            change(entry);
+        }
+    });
+}
+
+fn is_cancelled(state: &AppState, schedule_id: &str) -> bool {
+    state
+        .scheduled_jobs
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|job| job.schedule_id == schedule_id && job.status == "cancelled")
+}
+
+// Hand every pending job that is due to its own waiter; jobs that came due while the app was closed start right away
+fn start_due_jobs(app: &tauri::AppHandle, state: &Arc<AppState>) {
+    let now = Utc::now();
+    let due: Vec<ScheduledJob> = state
+        .scheduled_jobs
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|job| job.status == "pending" && job.start_at <= now)
+        .cloned()
+        .collect();
+
+    for job in due {
+        set_status(app, state, &job.schedule_id, |entry| entry.status = "waiting".to_string());
+        tauri::async_runtime::spawn(start_job(job, app.clone(), Arc::clone(state)));
+    }
+}
+
+// Wait for the job's board in recovery mode, then start it. Nobody may be around at the scheduled time
+// to put the board in recovery, so a board that never appears skips the job instead of failing it.
+async fn start_job(job: ScheduledJob, app: tauri::AppHandle, state: Arc<AppState>) {
+    let wait = job.command.wait_for_device_secs.map(Duration::from_secs).unwrap_or(DEVICE_WAIT);
+    info!("Scheduled job {} is due, waiting up to {}s for a device in recovery mode", job.schedule_id, wait.as_secs());
+    // A bound job waits for its own board, not any board; a booted board never starts the job
+    let binding = job.command.device_binding.as_ref();
+    let wanted = |device: &JetsonDevice| binding.is_none_or(|b| b.matches_location(device));
+    let found = usb_watch::wait_for_device(&state, wait, wanted, || is_cancelled(&state, &job.schedule_id)).await;
+    if is_cancelled(&state, &job.schedule_id) {
+        info!("Scheduled job {} was cancelled while waiting for a device", job.schedule_id);
+        return;
+    }
+    if !found {
+        let reason = format!("No device in recovery mode appeared within {}s", wait.as_secs());
+        warn!("Skipping scheduled job {}: {}", job.schedule_id, reason);
+        set_status(&app, &state, &job.schedule_id, |entry| {
+            entry.status = "skipped".to_string();
+            entry.error = Some(reason.clone());
+        });
+        let _ = app.emit("scheduled-job-skipped", serde_json::json!({
+            "schedule_id": job.schedule_id,
+            "reason": reason
+        }));
+        return;
+    }
+
+    info!("Starting scheduled job {}", job.schedule_id);
+    let result = crate::launch_flash(job.command.clone(), Arc::clone(&state), app.clone()).await;
+    if let Err(e) = &result {
+        error!("Scheduled job {} failed to start: {}", job.schedule_id, e);
+    }
+    set_status(&app, &state, &job.schedule_id, |entry| match &result {
+        Ok(flash_id) => {
+            entry.status = "started".to_string();
+            entry.flash_id = Some(flash_id.clone());
+        }
+        Err(e) => {
+            entry.status = "failed".to_string();
+            entry.error = Some(e.clone());
+        }
+    });
+    let _ = app.emit("scheduled-job-started", serde_json::json!({
+        "schedule_id": job.schedule_id,
+        "flash_id": result.as_ref().ok(),
+        "error": result.as_ref().err()
+    }));
+}
+
+// Poll for due jobs for the lifetime of the app
+pub fn start_scheduler(app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let state = Arc::clone(app.state::<Arc<AppState>>().inner());
+        loop {
+            start_due_jobs(&app, &state);
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    });
+}
+
+// Queue a flash job to start at the given time
+#[command]
+pub async fn schedule_flash(
+    command: FlashCommand,
+    start_at: DateTime<Utc>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<ScheduledJob, String> {
+    if start_at <= Utc::now() {
+        return Err("Start time must be in the future".to_string());
+    }
+    let job = ScheduledJob {
+        schedule_id: Uuid::new_v4().to_string(),
+        command,
+        start_at,
+        status: "pending".to_string(),
+        flash_id: None,
+        error: None,
+        created_at: Utc::now(),
+    };
+    info!("Scheduled job {} for {}", job.schedule_id, start_at);
+    let entry = job.clone();
+    update_schedule(&app, &state, |jobs| jobs.push(entry));
+    Ok(job)
+}
+
+// List scheduled jobs, soonest first
+#[command]
+pub async fn list_scheduled_jobs(state: State<'_, Arc<AppState>>) -> Result<Vec<ScheduledJob>, String> {
+    let mut jobs = state.scheduled_jobs.lock().unwrap().clone();
+    jobs.sort_by_key(|job| job.start_at);
+    Ok(jobs)
+}
+
+// Cancel a job that has not started yet, including one waiting for its device
+#[command]
+pub async fn cancel_scheduled_job(
+    schedule_id: String,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    let pending = state
+        .scheduled_jobs
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|job| job.schedule_id == schedule_id && (job.status == "pending" || job.status == "waiting"));
+    if !pending {
+        return Err(format!("No pending scheduled job: {}", schedule_id));
+    }
+    update_schedule(&app, &state, |jobs| {
+        if let Some(job) = jobs.iter_mut().find(|job| job.schedule_id == schedule_id) {
+            job.status = "cancelled".to_string();
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,237 @@
+// CFU - Cordatus Flash Utility - Serial Console
+// Debug UART viewer: lists USB serial adapters, streams what the board prints as serial-console-line
+// events and, when the console is opened for a flash job, saves it as that job's boot log next to the
+// flash log. The console is read-write, so U-Boot or the login prompt can be used from the app.
+
+use crate::{joblog, AppState};
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{ErrorKind, LineWriter, Read, Write};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{command, Emitter, Manager, State};
+use uuid::Uuid;
+
+const DEFAULT_BAUD: u32 = 115_200;
+// Also how long a closed console takes to release its port
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+// A partial line (e.g. a prompt waiting for input) is shown once it is this long
+const MAX_LINE_BYTES: usize = 4096;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SerialPortEntry {
+    pub path: String,
+    pub kind: String, // 'usb' | 'acm'
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub serial_number: Option<String>,
+}
+
+// An open console; the reader thread owns the port and stops once this is dropped
+#[derive(Debug)]
+pub struct SerialConsole {
+    pub session_id: String,
+    pub port: String,
+    pub baud: u32,
+    pub flash_id: Option<String>,
+    input: Sender<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SerialConsoleInfo {
+    pub session_id: String,
+    pub port: String,
+    pub baud: u32,
+    pub flash_id: Option<String>,
+}
+
+impl From<&SerialConsole> for SerialConsoleInfo {
+    fn from(console: &SerialConsole) -> Self {
+        Self {
+            session_id: console.session_id.clone(),
+            port: console.port.clone(),
+            baud: console.baud,
+            flash_id: console.flash_id.clone(),
+        }
+    }
+}
+
+// USB serial adapters (ttyUSB) and CDC ACM devices such as the devkits' own debug port (ttyACM)
+pub fn list_ports() -> Result<Vec<SerialPortEntry>> {
+    let mut ports: Vec<SerialPortEntry> = serialport::available_ports()
+        .context("Failed to list serial ports")?
+        .into_iter()
+        .filter_map(|port| {
+            let kind = if port.port_name.contains("ttyUSB") {
+                "usb"
+            } else if port.port_name.contains("ttyACM") {
+                "acm"
+            } else {
+                return None;
+            };
+            let (manufacturer, product, serial_number) = match port.port_type {
+                serialport::SerialPortType::UsbPort(usb) => (usb.manufacturer, usb.product, usb.serial_number),
+                _ => (None, None, None),
+            };
+            Some(SerialPortEntry {
+                path: port.port_name,
+                kind: kind.to_string(),
+                manufacturer,
+                product,
+                serial_number,
+            })
+        })
+        .collect();
+    ports.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(ports)
+}
+
+fn emit_line(app: &tauri::AppHandle, session_id: &str, port: &str, line: &str) {
+    let _ = app.emit("serial-console-line", serde_json::json!({
+        "session_id": session_id,
+        "port": port,
+        "line": line
+    }));
+}
+
+fn open_boot_log(app: &tauri::AppHandle, flash_id: &str) -> Option<LineWriter<File>> {
+    let file = joblog::boot_log_path(app, flash_id)
+        .and_then(|path| File::create(&path).with_context(|| format!("Failed to create {}", path.display())));
+    match file {
+        Ok(file) => Some(LineWriter::new(file)),
+        Err(e) => {
+            warn!("Boot log for {} is not saved: {:#}", flash_id, e);
+            None
+        }
+    }
+}
+
+// Read until the console is closed or the adapter disappears, forwarding typed input in between
+fn run_console(
+    app: tauri::AppHandle,
+    mut port: Box<dyn serialport::SerialPort>,
+    session_id: String,
+    port_name: String,
+    mut boot_log: Option<LineWriter<File>>,
+    input: Receiver<Vec<u8>>,
+) {
+    let mut buffer = [0u8; 1024];
+    let mut pending: Vec<u8> = Vec::new();
+    let mut flush_line = |line: &[u8]| {
+        let line = String::from_utf8_lossy(line).trim_end_matches('\r').to_string();
+        if let Some(log) = boot_log.as_mut() {
+            if let Err(e) = writeln!(log, "{}", line) {
+                warn!("Failed to write boot log from {}, streaming only: {}", port_name, e);
+                boot_log = None;
+            }
+        }
+        emit_line(&app, &session_id, &port_name, &line);
+    };
+    loop {
+        match port.read(&mut buffer) {
+            Ok(read) => pending.extend_from_slice(&buffer[..read]),
+            Err(e) if e.kind() == ErrorKind::TimedOut => {}
+            Err(e) => {
+                warn!("Serial console on {} stopped: {}", port_name, e);
+                break;
+            }
+        }
+        while let Some(end) = pending.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            flush_line(&line[..line.len() - 1]);
+        }
+        if pending.len() >= MAX_LINE_BYTES {
+            flush_line(&std::mem::take(&mut pending));
+        }
+        match input.try_recv() {
+            Ok(data) => {
+                if let Err(e) = port.write_all(&data).and_then(|_| port.flush()) {
+                    warn!("Failed to write to {}: {}", port_name, e);
+                }
+            }
+            Err(TryRecvError::Empty) => {}
+            Err(TryRecvError::Disconnected) => break,
+        }
+    }
+    if !pending.is_empty() {
+        flush_line(&pending);
+    }
+    app.state::<Arc<AppState>>().serial_consoles.lock().unwrap().remove(&session_id);
+    let _ = app.emit("serial-console-closed", serde_json::json!({ "session_id": session_id, "port": port_name }));
+    info!("Closed serial console {} on {}", session_id, port_name);
+}
+
+pub fn open(app: &tauri::AppHandle, state: &AppState, port: &str, baud: u32, flash_id: Option<String>) -> Result<SerialConsoleInfo> {
+    if state.serial_consoles.lock().unwrap().values().any(|console| console.port == port) {
+        return Err(anyhow::anyhow!("A console is already open on {}", port));
+    }
+    let serial = serialport::new(port, baud)
+        .timeout(READ_TIMEOUT)
+        .open()
+        .with_context(|| format!("Failed to open {}", port))?;
+    let session_id = Uuid::new_v4().to_string();
+    let boot_log = flash_id.as_deref().and_then(|flash_id| open_boot_log(app, flash_id));
+    let (input, received) = mpsc::channel();
+    let console = SerialConsole {
+        session_id: session_id.clone(),
+        port: port.to_string(),
+        baud,
+        flash_id,
+        input,
+    };
+    let info = SerialConsoleInfo::from(&console);
+    state.serial_consoles.lock().unwrap().insert(session_id.clone(), console);
+
+    let app = app.clone();
+    let port_name = port.to_string();
+    std::thread::spawn(move || run_console(app, serial, session_id, port_name, boot_log, received));
+    info!("Opened serial console on {} at {} baud", port, baud);
+    Ok(info)
+}
+
+// Serial ports a debug UART can be on
+#[command]
+pub async fn list_serial_ports() -> Result<Vec<SerialPortEntry>, String> {
+    list_ports().map_err(|e| e.to_string())
+}
+
+// Open a console and stream its lines as serial-console-line events; with `flash_id` the output is
+// also saved as that job's boot log
+#[command]
+pub async fn open_serial_console(
+    port: String,
+    baud: Option<u32>,
+    flash_id: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<SerialConsoleInfo, String> {
+    open(&app, &state, &port, baud.unwrap_or(DEFAULT_BAUD), flash_id).map_err(|e| format!("{:#}", e))
+}
+
+// Send keystrokes or a line (with its own line ending) to the board
+#[command]
+pub async fn write_serial_console(session_id: String, data: String, state: State<'_, Arc<AppState>>) -> Result<(), String> {
+    let consoles = state.serial_consoles.lock().unwrap();
+    let console = consoles.get(&session_id).ok_or("Serial console not found")?;
+    console.input.send(data.into_bytes()).map_err(|_| "The serial console is closed".to_string())
+}
+
+#[command]
+pub async fn list_serial_consoles(state: State<'_, Arc<AppState>>) -> Result<Vec<SerialConsoleInfo>, String> {
+    Ok(state.serial_consoles.lock().unwrap().values().map(SerialConsoleInfo::from).collect())
+}
+
+// Close a console; serial-console-closed follows once its port is released
+#[command]
+pub async fn close_serial_console(session_id: String, state: State<'_, Arc<AppState>>) -> Result<(), String> {
+    state
+        .serial_consoles
+        .lock()
+        .unwrap()
+        .remove(&session_id)
+        .map(|_| ())
+        .ok_or_else(|| "Serial console not found".to_string())
+}
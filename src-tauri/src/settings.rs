@@ -0,0 +1,401 @@
+// CFU - Cordatus Flash Utility - Settings
+// Persistent application settings stored as JSON in the app config directory
+
+use crate::job_host::JobHost;
+use crate::AppState;
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{command, State};
+
+const SETTINGS_FILE: &str = "settings.json";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    pub label_printer: LabelPrinterSettings,
+    pub version_matrix_url: String,
+    pub published_checksums_url: String, // Checksums of release files added to the bundled list; empty uses only that
+    pub image_catalog_url: String, // Feed of prebuilt images added to the bundled catalog; empty uses only that
+    pub image_catalog_public_key: String, // Hex ed25519 key the feed must be signed with; empty accepts unsigned feeds
+    pub bandwidth_probe_url: String, // Empty uses the built-in NVIDIA CDN probe
+    pub station_name: String,        // Recorded with each job; empty uses the host name
+    pub email: EmailSettings,
+    pub failure_alerts: FailureAlertSettings,
+    pub telemetry: TelemetrySettings,
+    pub crash_report_url: String, // Where submitted crash reports are posted; empty disables submission
+    pub command_cache: CommandCacheSettings,
+    pub maximum_io_speed: bool, // Run flashes and extraction at normal I/O priority instead of below the desktop
+    pub native_flash: bool, // Flash developer kits with the built-in pipeline instead of flash_cordatus.sh
+    pub denied_block_devices: Vec<String>, // Host disks never written or backed up, by path or serial; the system disk always is
+    pub rootfs_sync_excludes: Vec<String>, // rsync patterns never synced to a device, on top of the built-in ones
+    pub mirrors: Vec<MirrorSettings>, // Tried in order before the original URL of each release file
+    pub peer_cache: PeerCacheSettings,
+    pub profile_sync: ProfileSyncSettings,
+    pub control_api: ControlApiSettings,
+    pub artifact_upload: ArtifactUploadSettings,
+    pub catalog_publish: CatalogPublishSettings,
+    pub fleet_server: FleetServerSettings,
+    pub lab_fixtures: Vec<LabFixtureSettings>, // Board farm slots exported to labgrid / LAVA
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LabelPrinterSettings {
+    pub enabled: bool,
+    pub print_on_success: bool,
+    pub connection: String, // 'network' | 'usb'
+    pub address: String,    // "host:port" for network printers, device path for USB
+    pub report_url_template: String, // "{flash_id}" is replaced with the job ID
+}
+
+impl Default for LabelPrinterSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            print_on_success: true,
+            connection: "network".to_string(),
+            address: String::new(),
+            report_url_template: String::new(),
+        }
+    }
+}
+
+// SMTP notifications; the password is kept in the keyring under "smtp"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmailSettings {
+    pub enabled: bool,
+    pub notify_on: String, // 'all' | 'failure'
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub security: String, // 'starttls' | 'tls' | 'none'
+    pub username: String,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+impl Default for EmailSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            notify_on: "all".to_string(),
+            smtp_host: String::new(),
+            smtp_port: 587,
+            security: "starttls".to_string(),
+            username: String::new(),
+            from: String::new(),
+            to: Vec::new(),
+        }
+    }
+}
+
+// Alert when a station's recent jobs fail too often, e.g. 3 failures in the last 10
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FailureAlertSettings {
+    pub enabled: bool,
+    pub window_jobs: usize,
+    pub max_failures: usize,
+    pub webhook_url: String, // Empty only emits the in-app event
+}
+
+impl Default for FailureAlertSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window_jobs: 10,
+            max_failures: 3,
+            webhook_url: String::new(),
+        }
+    }
+}
+
+// Anonymous flash outcome reporting, off unless the user opts in
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TelemetrySettings {
+    pub enabled: bool,
+    pub endpoint: String,
+    pub installation_id: String, // Random, generated on the first report
+}
+
+// A local source for large release archives, e.g. a seed station on the site network
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MirrorSettings {
+    pub name: String,
+    pub enabled: bool,
+    pub transport: String, // 'http' | 'ipfs' | 'torrent'
+    pub endpoint: String,  // Base URL for http, gateway URL for ipfs, tracker announce URL for torrent
+}
+
+impl Default for MirrorSettings {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            enabled: true,
+            transport: "http".to_string(),
+            endpoint: String::new(),
+        }
+    }
+}
+
+// Sharing downloaded archives with other stations on the LAN, discovered over mDNS
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PeerCacheSettings {
+    pub enabled: bool,
+    pub port: u16,
+}
+
+impl Default for PeerCacheSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 47810,
+        }
+    }
+}
+
+// Sharing flash profiles between the stations of a line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfileSyncSettings {
+    pub enabled: bool,
+    pub target: String,     // 'folder' | 'server'
+    pub folder: String,     // Shared (network) folder holding cfu-profiles.json
+    pub server_url: String, // URL of the JSON document, read with GET and written with PUT
+}
+
+impl Default for ProfileSyncSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target: "folder".to_string(),
+            folder: String::new(),
+            server_url: String::new(),
+        }
+    }
+}
+
+// REST/WebSocket control server; requests need an API token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControlApiSettings {
+    pub enabled: bool,
+    pub bind_address: String, // 127.0.0.1 keeps it local; 0.0.0.0 exposes it to the network
+    pub port: u16,
+    pub observer_only: bool, // Dashboards only: no token can start or cancel jobs
+    pub tls: String, // 'off' | 'provided' | 'self_signed'
+    pub certificate_path: String, // PEM files used with 'provided'
+    pub private_key_path: String,
+}
+
+impl Default for ControlApiSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind_address: "127.0.0.1".to_string(),
+            port: 47800,
+            observer_only: false,
+            tls: "off".to_string(),
+            certificate_path: String::new(),
+            private_key_path: String::new(),
+        }
+    }
+}
+
+// Copies of job logs, reports and backups in an S3-compatible bucket; the secret key is kept in the keyring under "s3"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArtifactUploadSettings {
+    pub enabled: bool,
+    pub endpoint: String, // e.g. "https://s3.eu-central-1.amazonaws.com" or "http://minio.local:9000"
+    pub region: String,
+    pub bucket: String,
+    pub access_key_id: String,
+    pub prefix: String, // Objects go under <prefix>/<station>/
+    pub path_style: bool, // MinIO and most self-hosted stores need path-style URLs
+    pub upload_backups: bool,
+    pub retention_days: u32, // Tagged on each object for lifecycle rules; 0 adds no hint
+}
+
+impl Default for ArtifactUploadSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: String::new(),
+            region: "us-east-1".to_string(),
+            bucket: String::new(),
+            access_key_id: String::new(),
+            prefix: "cfu".to_string(),
+            path_style: true,
+            upload_backups: false,
+            retention_days: 0,
+        }
+    }
+}
+
+// Where this station publishes images to the shared catalog. 's3' uses the artifact upload bucket;
+// 'http' PUTs to a server, with the bearer token kept in the keyring under "catalog_publish".
+// The signing key is kept under "catalog_signing".
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CatalogPublishSettings {
+    pub destination: String, // 's3' | 'http'
+    pub prefix: String,      // Object key or path prefix; the catalog is <prefix>/catalog.json
+    pub http_url: String,    // Base URL accepting PUT, for 'http'
+    pub public_url: String,  // Base URL stations download <prefix>/... from
+}
+
+impl Default for CatalogPublishSettings {
+    fn default() -> Self {
+        Self {
+            destination: "s3".to_string(),
+            prefix: "catalog".to_string(),
+            http_url: String::new(),
+            public_url: String::new(),
+        }
+    }
+}
+
+// Central Cordatus server shared by several sites; the API token is kept in the keyring under "fleet_server"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FleetServerSettings {
+    pub enabled: bool,
+    pub url: String,
+    pub interval_minutes: u32, // Background sync period; 0 only syncs on request
+}
+
+impl Default for FleetServerSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: String::new(),
+            interval_minutes: 15,
+        }
+    }
+}
+
+// One board farm slot: which USB port its device enumerates on and the shell hooks that switch it.
+// Hooks run with `sh -c` and get CFU_FIXTURE set to the slot name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LabFixtureSettings {
+    pub name: String,
+    pub usb_port_path: String, // "bus-port.port", e.g. "1-2.3", as listed in /sys/bus/usb/devices
+    pub serial_port: String,   // Debug UART, e.g. "/dev/ttyUSB0"; empty if none
+    pub serial_baud: u32,
+    pub ssh_host: String, // Address of the flashed device; empty if not reachable
+    pub ssh_user: String,
+    pub power_on_command: String,
+    pub power_off_command: String,
+    pub recovery_command: String, // Puts a powered device into force recovery, e.g. gpioset on the REC pin
+    pub recovery_method: String,  // 'auto' | 'command' | 'relay' | 'uart'; auto uses the first one configured
+    pub relay_port: String,       // USB relay board (LCUS serial protocol), e.g. "/dev/ttyUSB1"
+    pub relay_recovery_channel: u8, // Relay wired across the REC pin
+    pub relay_reset_channel: u8,    // Relay wired across the RESET pin; 0 power-cycles with the power commands
+}
+
+impl Default for LabFixtureSettings {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            usb_port_path: String::new(),
+            serial_port: String::new(),
+            serial_baud: 115_200,
+            ssh_host: String::new(),
+            ssh_user: "nvidia".to_string(),
+            power_on_command: String::new(),
+            power_off_command: String::new(),
+            recovery_command: String::new(),
+            recovery_method: "auto".to_string(),
+            relay_port: String::new(),
+            relay_recovery_channel: 1,
+            relay_reset_channel: 2,
+        }
+    }
+}
+
+// How long expensive command results are reused; 0 always recomputes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CommandCacheSettings {
+    pub usb_detection_ttl_ms: u64,
+    pub system_info_ttl_ms: u64,
+}
+
+impl Default for CommandCacheSettings {
+    fn default() -> Self {
+        Self {
+            usb_detection_ttl_ms: 1500,
+            system_info_ttl_ms: 10_000,
+        }
+    }
+}
+
+impl CommandCacheSettings {
+    pub fn usb_detection_ttl(&self) -> Duration {
+        Duration::from_millis(self.usb_detection_ttl_ms)
+    }
+
+    pub fn system_info_ttl(&self) -> Duration {
+        Duration::from_millis(self.system_info_ttl_ms)
+    }
+}
+
+fn settings_path(app: &impl JobHost) -> Result<PathBuf> {
+    let dir = app.config_dir()?;
+    std::fs::create_dir_all(&dir).context("Failed to create config directory")?;
+    Ok(dir.join(SETTINGS_FILE))
+}
+
+// Load settings from disk, falling back to defaults
+pub fn load_settings(app: &impl JobHost) -> AppSettings {
+    let path = match settings_path(app) {
+        Ok(path) => path,
+        Err(e) => {
+            warn!("Using default settings: {}", e);
+            return AppSettings::default();
+        }
+    };
+
+    match std::fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            warn!("Invalid settings file {}, using defaults: {}", path.display(), e);
+            AppSettings::default()
+        }),
+        Err(_) => AppSettings::default(),
+    }
+}
+
+pub fn save_settings(app: &impl JobHost, settings: &AppSettings) -> Result<()> {
+    let path = settings_path(app)?;
+    let json = serde_json::to_string_pretty(settings)?;
+    std::fs::write(&path, json).context("Failed to write settings file")?;
+    info!("Saved settings to {}", path.display());
+    Ok(())
+}
+
+// Get current settings
+#[command]
+pub async fn get_settings(state: State<'_, Arc<AppState>>) -> Result<AppSettings, String> {
+    Ok(state.settings.lock().unwrap().clone())
+}
+
+// Replace and persist settings
+#[command]
+pub async fn update_settings(
+    settings: AppSettings,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    save_settings(&app, &settings).map_err(|e| e.to_string())?;
+    *state.settings.lock().unwrap() = settings;
+    Ok(())
+}
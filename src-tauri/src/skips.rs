@@ -0,0 +1,39 @@
+// CFU - Cordatus Flash Utility - Stage Skipping
+// Expert option to leave out flash stages known to be unnecessary, e.g. the download when the archives
+// are already in ~/openzeka; the script and the native pipeline read the same list
+
+use crate::FlashCommand;
+use anyhow::Result;
+
+// 'download': archives must already be in place | 'verification': no checksums are taken or checked |
+// 'extraction': the existing Linux_for_Tegra is flashed as it is
+pub const SKIPPABLE_STAGES: [&str; 3] = ["download", "verification", "extraction"];
+
+pub fn validate(command: &FlashCommand) -> Result<()> {
+    for (index, stage) in command.skip_stages.iter().enumerate() {
+        if !SKIPPABLE_STAGES.contains(&stage.as_str()) {
+            return Err(anyhow::anyhow!(
+                "Unknown stage to skip: {} (expected one of {})",
+                stage,
+                SKIPPABLE_STAGES.join(", ")
+            ));
+        }
+        if command.skip_stages[..index].contains(stage) {
+            return Err(anyhow::anyhow!("Stage {} is listed twice", stage));
+        }
+    }
+    // Pins exist to be checked; skipping the check would silently void them
+    if skips(command, "verification") && !command.pinned_artifacts.is_empty() {
+        return Err(anyhow::anyhow!("Verification cannot be skipped for a command with pinned artifacts"));
+    }
+    Ok(())
+}
+
+pub fn skips(command: &FlashCommand, stage: &str) -> bool {
+    command.skip_stages.iter().any(|s| s == stage)
+}
+
+// Value of CFU_SKIP_STAGES for the flash script
+pub fn env_value(command: &FlashCommand) -> String {
+    command.skip_stages.join(" ")
+}
@@ -0,0 +1,118 @@
+// CFU - Cordatus Flash Utility - SSH Helpers
+// Thin wrapper around the system ssh client for talking to booted Jetson devices
+
+use anyhow::{Context, Result};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::process::Stdio;
+use tokio::process::Command as TokioCommand;
+
+// Script prefix setting $S to "sudo -n" on a booted device and to nothing
+// when already root (maintenance initrd)
+pub const SUDO: &str = r#"S=""; [ "$(id -u)" -ne 0 ] && S="sudo -n"; "#;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SshTarget {
+    pub host: String,
+    pub user: String,
+    pub port: Option<u16>,
+    pub identity_file: Option<String>,
+}
+
+// A host or user ssh cannot mistake for an option (e.g. "-oProxyCommand=...") or split into several words
+fn check_word(what: &str, value: &str) -> Result<()> {
+    if value.is_empty() {
+        return Err(anyhow::anyhow!("The SSH {} is empty", what));
+    }
+    if value.starts_with('-') || value.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err(anyhow::anyhow!("Invalid SSH {}: {:?}", what, value));
+    }
+    Ok(())
+}
+
+impl SshTarget {
+    // Targets also arrive over the control API and in profiles, so they are checked before any use
+    pub fn validate(&self) -> Result<()> {
+        check_word("host", &self.host)?;
+        check_word("user", &self.user)
+    }
+
+    // user@host; never passed to ssh without a preceding "--"
+    pub fn destination(&self) -> Result<String> {
+        self.validate()?;
+        Ok(format!("{}@{}", self.user, self.host))
+    }
+
+    // Common ssh options: never prompt, fail fast on unreachable hosts
+    fn base_args(&self) -> Vec<String> {
+        let mut args = vec![
+            "-o".to_string(), "BatchMode=yes".to_string(),
+            "-o".to_string(), "ConnectTimeout=10".to_string(),
+            "-o".to_string(), "StrictHostKeyChecking=accept-new".to_string(),
+        ];
+        if let Some(port) = self.port {
+            args.push("-p".to_string());
+            args.push(port.to_string());
+        }
+        if let Some(identity) = &self.identity_file {
+            args.push("-i".to_string());
+            args.push(identity.clone());
+        }
+        args
+    }
+
+    // The ssh invocation for tools taking a remote shell, e.g. rsync -e
+    pub fn ssh_command(&self) -> String {
+        let mut command = vec!["ssh".to_string()];
+        command.extend(self.base_args().iter().map(|arg| shell_quote(arg)));
+        command.join(" ")
+    }
+}
+
+// Quote a value for safe interpolation into a remote shell command
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+// Start a shell snippet on the target with piped stdin/stdout for streaming
+pub fn spawn_remote(target: &SshTarget, script: &str) -> Result<tokio::process::Child> {
+    debug!("Spawning remote command on {}: {}", target.host, script);
+
+    let destination = target.destination()?;
+    TokioCommand::new("ssh")
+        .args(target.base_args())
+        .arg("--")
+        .arg(destination)
+        .arg(script)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to start ssh")
+}
+
+// Run a shell snippet on the target and return its stdout
+pub async fn run_remote(target: &SshTarget, script: &str) -> Result<String> {
+    debug!("Running remote command on {}: {}", target.host, script);
+
+    let output = TokioCommand::new("ssh")
+        .args(target.base_args())
+        .arg("--")
+        .arg(target.destination()?)
+        .arg(script)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .context("Failed to start ssh")?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(anyhow::anyhow!(
+            "Remote command on {} failed ({}): {}",
+            target.host,
+            output.status.code().unwrap_or(-1),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
@@ -0,0 +1,123 @@
+// CFU - Cordatus Flash Utility - Telemetry
+// Opt-in anonymous flash outcome reports (module, release, duration, failure stage and code) to a configurable endpoint
+
+use crate::history::FlashJobRecord;
+use crate::job_host::JobHost;
+use crate::settings;
+use crate::AppState;
+use anyhow::Result;
+use log::{debug, warn};
+use regex::Regex;
+use serde::Serialize;
+use std::sync::Arc;
+use tauri::{command, State};
+use uuid::Uuid;
+
+// Only these fields leave the machine: no serial numbers, hostnames, user
+// names, paths or error text
+#[derive(Debug, Clone, Serialize)]
+pub struct TelemetryEvent {
+    pub installation_id: String,
+    pub app_version: String,
+    pub product: String,
+    pub device_module: String,
+    pub jetpack_version: String,
+    pub storage_device: String,
+    pub status: String,
+    pub failed_stage: Option<String>,
+    pub error_code: Option<String>,
+    pub duration_secs: Option<i64>,
+}
+
+// Reduce an error message to a code that cannot carry personal data
+fn error_code(error: &str) -> String {
+    let exit_code = Regex::new(r"exited with error code: (-?\d+)").ok();
+    if let Some(caps) = exit_code.and_then(|re| re.captures(error)) {
+        return format!("script_exit_{}", &caps[1]);
+    }
+    let known = [
+        ("Pinned artifact", "artifact_pin_mismatch"),
+        ("Unsupported configuration", "unsupported_configuration"),
+        ("script not found", "script_missing"),
+        ("USB enumeration failed", "usb_enumeration"),
+    ];
+    known
+        .iter()
+        .find(|(pattern, _)| error.contains(pattern))
+        .map(|(_, code)| code.to_string())
+        .unwrap_or_else(|| "other".to_string())
+}
+
+fn build_event(job: &FlashJobRecord, installation_id: &str) -> TelemetryEvent {
+    TelemetryEvent {
+        installation_id: installation_id.to_string(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        product: job.command.product.clone(),
+        device_module: job.command.device_module.clone(),
+        jetpack_version: job.command.jetpack_version.clone(),
+        storage_device: job.command.storage_device.clone(),
+        status: job.status.clone(),
+        failed_stage: job.failed_stage.clone(),
+        error_code: job
+            .failure
+            .as_ref()
+            .map(|failure| failure.code.clone())
+            .or_else(|| job.error.as_deref().map(error_code)),
+        duration_secs: job.finished_at.map(|finished| (finished - job.started_at).num_seconds()),
+    }
+}
+
+// The installation ID, generated and saved on first use
+fn installation_id(app: &impl JobHost, state: &AppState) -> String {
+    let mut settings = state.settings.lock().unwrap();
+    if settings.telemetry.installation_id.is_empty() {
+        settings.telemetry.installation_id = Uuid::new_v4().to_string();
+        if let Err(e) = settings::save_settings(app, &settings) {
+            warn!("Failed to save telemetry installation ID: {}", e);
+        }
+    }
+    settings.telemetry.installation_id.clone()
+}
+
+async fn send_event(endpoint: &str, event: &TelemetryEvent) -> Result<()> {
+    reqwest::Client::new()
+        .post(endpoint)
+        .json(event)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+// Report a finished job if the user opted in; failures are only logged
+pub async fn report_job(app: &impl JobHost, state: &AppState, flash_id: &str) {
+    let telemetry = state.settings.lock().unwrap().telemetry.clone();
+    if !telemetry.enabled || telemetry.endpoint.is_empty() {
+        return;
+    }
+    let Some(job) = state.history.lock().unwrap().jobs.iter().find(|r| r.flash_id == flash_id).cloned() else {
+        return;
+    };
+
+    let event = build_event(&job, &installation_id(app, state));
+    match send_event(&telemetry.endpoint, &event).await {
+        Ok(()) => debug!("Sent telemetry for flash {}", flash_id),
+        Err(e) => warn!("Failed to send telemetry: {}", e),
+    }
+}
+
+// Show exactly what would be reported for a job, for the opt-in dialog
+#[command]
+pub async fn preview_telemetry_event(
+    flash_id: String,
+    state: State<'_, Arc<AppState>>,
+) -> Result<TelemetryEvent, String> {
+    let installation_id = state.settings.lock().unwrap().telemetry.installation_id.clone();
+    let history = state.history.lock().unwrap();
+    let job = history
+        .jobs
+        .iter()
+        .find(|r| r.flash_id == flash_id)
+        .ok_or_else(|| format!("Flash job not found: {}", flash_id))?;
+    Ok(build_event(job, &installation_id))
+}
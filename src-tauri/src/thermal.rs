@@ -0,0 +1,231 @@
+// CFU - Cordatus Flash Utility - Thermal Report
+// Short tegrastats trace under load, summarized against the module's thermal and power limits
+
+use crate::asset::read_device_identity;
+use crate::benchmarks::{REFERENCE_ONNX_MODEL, TRTEXEC};
+use crate::history;
+use crate::ssh::{run_remote, SshTarget, SUDO};
+use crate::AppState;
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tauri::{command, State};
+use uuid::Uuid;
+
+const DEFAULT_DURATION_SECS: u64 = 60;
+
+// (model substring, max power mode in mW, temperature where software throttling starts)
+// Checked in order, so more specific names come first
+const MODULE_LIMITS: &[(&str, u64, f64)] = &[
+    ("AGX Orin", 60_000, 99.0),
+    ("Orin NX 16GB", 25_000, 99.0),
+    ("Orin NX", 20_000, 99.0),
+    ("Orin Nano", 25_000, 99.0),
+    ("Xavier NX", 20_000, 96.0),
+    ("AGX Xavier", 30_000, 96.0),
+    ("Nano", 10_000, 97.0),
+];
+
+// Alert when a reading gets this close to a limit
+const TEMPERATURE_MARGIN_C: f64 = 5.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZoneTemperature {
+    pub zone: String, // tegrastats name: 'cpu' | 'gpu' | 'tj' | 'soc0' ...
+    pub max_c: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThermalReport {
+    pub report_id: String,
+    pub serial_number: String,
+    pub device_model: Option<String>,
+    pub duration_secs: u64,
+    pub under_load: bool,
+    pub samples: usize,
+    pub max_temperatures: Vec<ZoneTemperature>,
+    pub max_power_mw: Option<u64>,
+    pub avg_power_mw: Option<u64>,
+    pub power_budget_mw: Option<u64>,
+    pub throttle_temp_c: Option<f64>,
+    pub warnings: Vec<String>,
+    pub passed: bool,
+    pub recorded_at: DateTime<Utc>,
+}
+
+impl ThermalReport {
+    // One line for device reports, e.g. "PASS: max 71.5C (tj), peak 18.2 W of 25 W under load"
+    pub fn summary(&self) -> String {
+        let hottest = self
+            .max_temperatures
+            .iter()
+            .max_by(|a, b| a.max_c.total_cmp(&b.max_c))
+            .map(|t| format!("max {:.1}C ({})", t.max_c, t.zone))
+            .unwrap_or_else(|| "no temperatures".to_string());
+        let power = match (self.max_power_mw, self.power_budget_mw) {
+            (Some(peak), Some(budget)) => format!("peak {:.1} W of {:.0} W", peak as f64 / 1000.0, budget as f64 / 1000.0),
+            (Some(peak), None) => format!("peak {:.1} W", peak as f64 / 1000.0),
+            _ => "no power readings".to_string(),
+        };
+        format!(
+            "{}: {}, {}{}",
+            if self.passed { "PASS" } else { "CHECK" },
+            hottest,
+            power,
+            if self.under_load { " under load" } else { " idle" }
+        )
+    }
+}
+
+struct TraceSummary {
+    samples: usize,
+    max_temperatures: BTreeMap<String, f64>,
+    max_power_mw: Option<u64>,
+    avg_power_mw: Option<u64>,
+}
+
+// Orin reports "VDD_IN 5123mW/4980mW", Xavier and Nano "POM_5V_IN 3012/2870"
+fn parse_tegrastats(output: &str) -> Option<TraceSummary> {
+    let temp_regex = Regex::new(r"(\w+)@(-?[\d.]+)C").ok()?;
+    let power_regex = Regex::new(r"(?:VDD_IN|POM_5V_IN)\s+(\d+)(?:mW)?/").ok()?;
+    let mut max_temperatures: BTreeMap<String, f64> = BTreeMap::new();
+    let mut power_samples = Vec::new();
+    let mut samples = 0;
+
+    for line in output.lines().filter(|line| line.contains("RAM")) {
+        samples += 1;
+        for caps in temp_regex.captures_iter(line) {
+            let Ok(value) = caps[2].parse::<f64>() else { continue };
+            // Unpopulated sensors read -256C
+            if value < -100.0 {
+                continue;
+            }
+            let zone = caps[1].to_lowercase();
+            let max = max_temperatures.entry(zone).or_insert(value);
+            *max = max.max(value);
+        }
+        if let Some(power) = power_regex.captures(line).and_then(|caps| caps[1].parse::<u64>().ok()) {
+            power_samples.push(power);
+        }
+    }
+
+    Some(TraceSummary {
+        samples,
+        max_temperatures,
+        max_power_mw: power_samples.iter().max().copied(),
+        avg_power_mw: (!power_samples.is_empty())
+            .then(|| power_samples.iter().sum::<u64>() / power_samples.len() as u64),
+    })
+}
+
+// CPU busy loops on every core plus the TensorRT reference model on the GPU
+fn load_script(duration: u64) -> String {
+    format!(
+        "for i in $(seq $(nproc)); do timeout {d} sh -c 'while :; do :; done' & done; \
+         [ -x {trtexec} ] && $S timeout {d} {trtexec} --onnx={model} --fp16 --duration={d} >/dev/null 2>&1 & ",
+        d = duration,
+        trtexec = TRTEXEC,
+        model = REFERENCE_ONNX_MODEL
+    )
+}
+
+fn module_limits(device_model: Option<&str>) -> Option<(u64, f64)> {
+    let model = device_model?;
+    MODULE_LIMITS
+        .iter()
+        .find(|(name, _, _)| model.contains(name))
+        .map(|(_, power, temp)| (*power, *temp))
+}
+
+// Record a tegrastats trace (optionally under synthetic load) and compare it with the module limits
+#[command]
+pub async fn run_thermal_report(
+    target: SshTarget,
+    duration_secs: Option<u64>,
+    under_load: bool,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<ThermalReport, String> {
+    let identity = read_device_identity(&target, "")
+        .await
+        .map_err(|e| format!("Failed to identify device: {}", e))?;
+    let duration = duration_secs.unwrap_or(DEFAULT_DURATION_SECS);
+    info!("Recording {}s thermal trace on {}", duration, identity.serial_number);
+
+    let script = format!(
+        "{}{}$S timeout {} tegrastats --interval 1000; wait",
+        SUDO,
+        if under_load { load_script(duration) } else { String::new() },
+        duration
+    );
+    // timeout exits non-zero when it stops tegrastats, so check the output instead
+    let output = run_remote(&target, &format!("{} ; true", script))
+        .await
+        .map_err(|e| format!("Failed to record tegrastats: {}", e))?;
+    let Some(trace) = parse_tegrastats(&output).filter(|trace| trace.samples > 0) else {
+        return Err("tegrastats produced no samples".to_string());
+    };
+
+    let limits = module_limits(identity.model.as_deref());
+    let mut warnings = Vec::new();
+    if let Some((power_budget, throttle_temp)) = limits {
+        for (zone, max) in &trace.max_temperatures {
+            if *max >= throttle_temp - TEMPERATURE_MARGIN_C {
+                warnings.push(format!("{} reached {:.1}C, within {}C of throttling ({:.0}C)", zone, max, TEMPERATURE_MARGIN_C, throttle_temp));
+            }
+        }
+        if let Some(max_power) = trace.max_power_mw.filter(|power| *power > power_budget) {
+            warnings.push(format!("Peak input power {:.1} W exceeds the {:.0} W module budget", max_power as f64 / 1000.0, power_budget as f64 / 1000.0));
+        }
+    } else {
+        warnings.push("No thermal limits known for this module, readings not evaluated".to_string());
+    }
+    for warning in &warnings {
+        warn!("Thermal report {}: {}", identity.serial_number, warning);
+    }
+
+    let report = ThermalReport {
+        report_id: Uuid::new_v4().to_string(),
+        serial_number: identity.serial_number,
+        device_model: identity.model,
+        duration_secs: duration,
+        under_load,
+        samples: trace.samples,
+        max_temperatures: trace
+            .max_temperatures
+            .into_iter()
+            .map(|(zone, max_c)| ZoneTemperature { zone, max_c })
+            .collect(),
+        max_power_mw: trace.max_power_mw,
+        avg_power_mw: trace.avg_power_mw,
+        power_budget_mw: limits.map(|(power, _)| power),
+        throttle_temp_c: limits.map(|(_, temp)| temp),
+        passed: limits.is_some() && warnings.is_empty(),
+        warnings,
+        recorded_at: Utc::now(),
+    };
+
+    info!("Thermal report for {}: {}", report.serial_number, report.summary());
+    let record = report.clone();
+    history::update_history(&app, &state, |history| history.thermal_reports.push(record));
+    Ok(report)
+}
+
+// Thermal reports recorded for a device, newest first
+#[command]
+pub async fn get_thermal_reports(
+    serial_number: String,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<ThermalReport>, String> {
+    let history = state.history.lock().unwrap();
+    Ok(history
+        .thermal_reports
+        .iter()
+        .rev()
+        .filter(|r| r.serial_number == serial_number)
+        .cloned()
+        .collect())
+}
@@ -0,0 +1,226 @@
+// CFU - Cordatus Flash Utility - Job Timelines
+// A compact time series of each job's progress (stage, percent, bytes downloaded) kept on disk as JSON lines,
+// so a post-mortem can see where the time went and when a job stalled
+
+use crate::job_host::JobHost;
+use crate::AppState;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, LineWriter, Write};
+use std::path::PathBuf;
+use tauri::command;
+
+const TIMELINE_DIR: &str = "job_timelines";
+
+// A new sample is only written when the job moved at least this much, or after HEARTBEAT_SECS
+const MIN_PERCENT_STEP: f32 = 1.0;
+const MIN_BYTES_STEP: u64 = 16 * 1024 * 1024;
+const HEARTBEAT_SECS: i64 = 10;
+
+// Gaps between samples longer than this are reported as stalls
+const STALL_SECS: i64 = 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimelineSample {
+    pub t: DateTime<Utc>,
+    pub stage: String,
+    pub percent: f32, // Overall job progress, as in FlashProgress
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<u64>, // Bytes of the current download
+}
+
+#[derive(Debug)]
+pub struct TimelineWriter {
+    file: Option<LineWriter<File>>,
+    last: Option<TimelineSample>,
+    bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StageSpan {
+    pub stage: String,
+    pub started_at: DateTime<Utc>,
+    pub duration_secs: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Stall {
+    pub stage: String,
+    pub from: DateTime<Utc>,
+    pub duration_secs: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobTimeline {
+    pub flash_id: String,
+    pub samples: Vec<TimelineSample>,
+    pub stages: Vec<StageSpan>, // Consecutive samples of one stage, in order
+    pub stalls: Vec<Stall>,     // Gaps of more than a minute without progress
+}
+
+fn timeline_path(app: &impl JobHost, flash_id: &str) -> Result<PathBuf> {
+    let dir = crate::app_data_file(app, TIMELINE_DIR)?;
+    std::fs::create_dir_all(&dir).context("Failed to create job timeline directory")?;
+    Ok(dir.join(format!("{}.jsonl", flash_id)))
+}
+
+pub fn open(app: &impl JobHost, state: &AppState, flash_id: &str) {
+    let file = timeline_path(app, flash_id)
+        .and_then(|path| File::create(&path).with_context(|| format!("Failed to create {}", path.display())));
+    let file = match file {
+        Ok(file) => Some(LineWriter::new(file)),
+        Err(e) => {
+            warn!("Timeline for {} is not recorded: {}", flash_id, e);
+            None
+        }
+    };
+    state.job_timelines.lock().unwrap().insert(
+        flash_id.to_string(),
+        TimelineWriter {
+            file,
+            last: None,
+            bytes: None,
+        },
+    );
+}
+
+fn write_sample(writer: &mut TimelineWriter, flash_id: &str, sample: TimelineSample) {
+    if let Some(file) = writer.file.as_mut() {
+        let line = serde_json::to_string(&sample).unwrap_or_default();
+        if let Err(e) = writeln!(file, "{}", line) {
+            warn!("Failed to write timeline for {}, stopping it: {}", flash_id, e);
+            writer.file = None;
+        }
+    }
+    writer.last = Some(sample);
+}
+
+fn due(last: Option<&TimelineSample>, sample: &TimelineSample) -> bool {
+    let Some(last) = last else {
+        return true;
+    };
+    last.stage != sample.stage
+        || (sample.percent - last.percent).abs() >= MIN_PERCENT_STEP
+        || sample.bytes.unwrap_or(0).abs_diff(last.bytes.unwrap_or(0)) >= MIN_BYTES_STEP
+        || (sample.t - last.t).num_seconds() >= HEARTBEAT_SECS
+}
+
+pub fn record(state: &AppState, flash_id: &str, stage: &str, percent: f32) {
+    let mut timelines = state.job_timelines.lock().unwrap();
+    let Some(writer) = timelines.get_mut(flash_id) else {
+        return;
+    };
+    // Byte counts belong to the download they were read from
+    if stage != "downloading" {
+        writer.bytes = None;
+    }
+    let sample = TimelineSample {
+        t: Utc::now(),
+        stage: stage.to_string(),
+        percent,
+        bytes: writer.bytes,
+    };
+    if due(writer.last.as_ref(), &sample) {
+        write_sample(writer, flash_id, sample);
+    }
+}
+
+// Bytes of the running download, added to the next sample
+pub fn record_bytes(state: &AppState, flash_id: &str, bytes: u64) {
+    let mut timelines = state.job_timelines.lock().unwrap();
+    let Some(writer) = timelines.get_mut(flash_id) else {
+        return;
+    };
+    writer.bytes = Some(bytes);
+    let Some(last) = writer.last.clone() else {
+        return;
+    };
+    let sample = TimelineSample {
+        t: Utc::now(),
+        bytes: writer.bytes,
+        ..last
+    };
+    if due(writer.last.as_ref(), &sample) {
+        write_sample(writer, flash_id, sample);
+    }
+}
+
+// Write the job's final state and stop recording
+pub fn close(state: &AppState, flash_id: &str) {
+    let progress = state.flash_progress.lock().unwrap().get(flash_id).cloned();
+    let Some(mut writer) = state.job_timelines.lock().unwrap().remove(flash_id) else {
+        return;
+    };
+    if let Some(progress) = progress {
+        let sample = TimelineSample {
+            t: Utc::now(),
+            stage: progress.stage,
+            percent: progress.progress,
+            bytes: None,
+        };
+        write_sample(&mut writer, flash_id, sample);
+    }
+}
+
+fn summarize(flash_id: String, samples: Vec<TimelineSample>) -> JobTimeline {
+    let mut stages: Vec<StageSpan> = Vec::new();
+    let mut stalls = Vec::new();
+    for (index, sample) in samples.iter().enumerate() {
+        if stages.last().is_none_or(|span| span.stage != sample.stage) {
+            stages.push(StageSpan {
+                stage: sample.stage.clone(),
+                started_at: sample.t,
+                duration_secs: 0,
+            });
+        }
+        // A stage lasts until the next one starts
+        let next = samples.get(index + 1).map(|next| next.t).unwrap_or(sample.t);
+        if let Some(span) = stages.last_mut() {
+            span.duration_secs = (next - span.started_at).num_seconds();
+        }
+        let gap = (next - sample.t).num_seconds();
+        if gap > STALL_SECS {
+            stalls.push(Stall {
+                stage: sample.stage.clone(),
+                from: sample.t,
+                duration_secs: gap,
+            });
+        }
+    }
+    JobTimeline {
+        flash_id,
+        samples,
+        stages,
+        stalls,
+    }
+}
+
+// The writer is line buffered, so a running job's file is complete up to its last sample
+fn read_samples(app: &impl JobHost, flash_id: &str) -> Result<Vec<TimelineSample>, String> {
+    let path = timeline_path(app, flash_id).map_err(|e| e.to_string())?;
+    let file = File::open(&path).map_err(|_| format!("No timeline recorded for job {}", flash_id))?;
+    Ok(BufReader::new(file)
+        .lines()
+        .map_while(|line| line.ok())
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect())
+}
+
+// The last stage a job reached, i.e. where a failed job stopped
+pub fn last_stage(app: &impl JobHost, flash_id: &str) -> Option<String> {
+    let samples = read_samples(app, flash_id).ok()?;
+    samples.into_iter().rev().map(|sample| sample.stage).find(|stage| stage != "error")
+}
+
+// A job's recorded progress samples with per-stage durations and stalls; readable while it runs
+#[command]
+pub async fn get_job_timeline(
+    flash_id: String,
+    app: tauri::AppHandle,
+) -> Result<JobTimeline, String> {
+    let samples = read_samples(&app, &flash_id)?;
+    Ok(summarize(flash_id, samples))
+}
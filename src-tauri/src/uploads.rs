@@ -0,0 +1,123 @@
+// CFU - Cordatus Flash Utility - Artifact Uploads
+// Copies of job logs, job reports and device backups in an S3-compatible bucket (AWS, MinIO, ...) under
+// a per-station prefix, tagged so bucket lifecycle rules can expire them
+
+use crate::job_host::JobHost;
+use crate::settings::ArtifactUploadSettings;
+use crate::{credentials, joblog, AppState};
+use anyhow::{Context, Result};
+use log::{info, warn};
+use s3::creds::Credentials;
+use s3::{Bucket, Region};
+use std::path::Path;
+use std::sync::Arc;
+use tauri::{command, State};
+
+pub fn bucket(settings: &ArtifactUploadSettings) -> Result<Box<Bucket>> {
+    if settings.endpoint.is_empty() || settings.bucket.is_empty() {
+        return Err(anyhow::anyhow!("Artifact uploads need an endpoint and bucket"));
+    }
+    let secret = credentials::get_secret("s3")?.context("No S3 secret key stored")?;
+    let region = Region::Custom {
+        region: settings.region.clone(),
+        endpoint: settings.endpoint.clone(),
+    };
+    let credentials = Credentials::new(Some(&settings.access_key_id), Some(&secret), None, None, None)?;
+    let bucket = Bucket::new(&settings.bucket, region, credentials)?;
+    Ok(if settings.path_style { bucket.with_path_style() } else { bucket })
+}
+
+// <prefix>/<station>/<path>
+fn object_key(settings: &ArtifactUploadSettings, state: &AppState, path: &str) -> String {
+    let station = crate::history::station_name(state).unwrap_or_else(|| "unknown".to_string());
+    let prefix = settings.prefix.trim_matches('/');
+    if prefix.is_empty() {
+        format!("{}/{}", station, path)
+    } else {
+        format!("{}/{}/{}", prefix, station, path)
+    }
+}
+
+// `cfu-kind` and `cfu-retention-days` tags for lifecycle rules to filter on
+async fn tag(bucket: &Bucket, key: &str, kind: &str, settings: &ArtifactUploadSettings) -> Result<()> {
+    let mut tags = vec![("cfu-kind".to_string(), kind.to_string())];
+    if settings.retention_days > 0 {
+        tags.push(("cfu-retention-days".to_string(), settings.retention_days.to_string()));
+    }
+    bucket.put_object_tagging(key, &tags).await?;
+    Ok(())
+}
+
+async fn upload_file(bucket: &Bucket, key: &str, path: &Path, kind: &str, settings: &ArtifactUploadSettings) -> Result<()> {
+    let mut file = tokio::fs::File::open(path)
+        .await
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    bucket.put_object_stream(&mut file, key).await?;
+    tag(bucket, key, kind, settings).await
+}
+
+// Upload a job's report (its history record) and output log; returns the object keys
+pub async fn upload_job(app: &impl JobHost, state: &AppState, flash_id: &str) -> Result<Vec<String>> {
+    let settings = state.settings.lock().unwrap().artifact_upload.clone();
+    let bucket = bucket(&settings)?;
+    let record = state
+        .history
+        .lock()
+        .unwrap()
+        .jobs
+        .iter()
+        .find(|r| r.flash_id == flash_id)
+        .cloned()
+        .with_context(|| format!("Flash job not found: {}", flash_id))?;
+
+    let mut keys = Vec::new();
+    let report_key = object_key(&settings, state, &format!("jobs/{}/job.json", flash_id));
+    bucket
+        .put_object_with_content_type(&report_key, &serde_json::to_vec_pretty(&record)?, "application/json")
+        .await?;
+    tag(&bucket, &report_key, "report", &settings).await?;
+    keys.push(report_key);
+
+    let log = joblog::log_path(app, flash_id)?;
+    if log.is_file() {
+        let log_key = object_key(&settings, state, &format!("jobs/{}/output.log", flash_id));
+        upload_file(&bucket, &log_key, &log, "log", &settings).await?;
+        keys.push(log_key);
+    }
+    info!("Uploaded {} artifacts of job {}", keys.len(), flash_id);
+    Ok(keys)
+}
+
+// Called when a job finishes; a failed upload is logged and can be retried from the UI
+pub async fn upload_finished_job(app: &impl JobHost, state: &AppState, flash_id: &str) {
+    if !state.settings.lock().unwrap().artifact_upload.enabled {
+        return;
+    }
+    if let Err(e) = upload_job(app, state, flash_id).await {
+        warn!("Failed to upload artifacts of job {}: {:#}", flash_id, e);
+    }
+}
+
+// Upload a device backup image when backups are configured to be kept off-station
+pub async fn upload_backup(state: &AppState, image_path: &Path) -> Result<Option<String>> {
+    let settings = state.settings.lock().unwrap().artifact_upload.clone();
+    if !settings.enabled || !settings.upload_backups {
+        return Ok(None);
+    }
+    let bucket = bucket(&settings)?;
+    let file_name = image_path.file_name().context("Backup has no file name")?.to_string_lossy();
+    let key = object_key(&settings, state, &format!("backups/{}", file_name));
+    upload_file(&bucket, &key, image_path, "backup", &settings).await?;
+    info!("Uploaded backup {} to {}", image_path.display(), key);
+    Ok(Some(key))
+}
+
+// Upload (or re-upload) a job's report and log
+#[command]
+pub async fn upload_job_artifacts(
+    flash_id: String,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<String>, String> {
+    upload_job(&app, &state, &flash_id).await.map_err(|e| e.to_string())
+}
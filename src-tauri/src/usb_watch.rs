@@ -0,0 +1,175 @@
+// CFU - Cordatus Flash Utility - USB Hotplug
+// Watches for NVIDIA USB devices arriving and leaving (libusb hotplug where available, polling otherwise)
+// and emits device-connected / device-disconnected, so the UI follows a Jetson entering or leaving recovery mode
+
+use crate::{AppState, JetsonDevice};
+use log::{info, warn};
+use rusb::{GlobalContext, Hotplug, HotplugBuilder, UsbContext};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{Emitter, Manager};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+const NVIDIA_VENDOR_ID: u16 = 0x0955;
+// A board switching to recovery re-enumerates in several steps; they are rescanned once
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+// A job waiting for a board also rescans this often, in case an arrival was missed
+const WAIT_RESCAN: Duration = Duration::from_secs(5);
+
+struct HotplugSignal(UnboundedSender<()>);
+
+impl Hotplug<GlobalContext> for HotplugSignal {
+    fn device_arrived(&mut self, _device: rusb::Device<GlobalContext>) {
+        let _ = self.0.send(());
+    }
+
+    fn device_left(&mut self, _device: rusb::Device<GlobalContext>) {
+        let _ = self.0.send(());
+    }
+}
+
+fn poll(signal: UnboundedSender<()>) {
+    while signal.send(()).is_ok() {
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+// Signal on every hotplug event, or every POLL_INTERVAL without libusb hotplug support
+fn watch(signal: UnboundedSender<()>) {
+    if !rusb::has_hotplug() {
+        info!("USB hotplug unsupported, polling for devices every {}s", POLL_INTERVAL.as_secs());
+        return poll(signal);
+    }
+    let context = GlobalContext::default();
+    let registration = HotplugBuilder::new()
+        .vendor_id(NVIDIA_VENDOR_ID)
+        .register(context, Box::new(HotplugSignal(signal.clone())));
+    let _registration = match registration {
+        Ok(registration) => registration,
+        Err(e) => {
+            warn!("USB hotplug registration failed, polling instead: {}", e);
+            return poll(signal);
+        }
+    };
+    info!("Watching USB hotplug events");
+    loop {
+        if let Err(e) = context.handle_events(None) {
+            warn!("USB hotplug event handling failed: {}", e);
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+// Rescan and emit the difference to the devices the watcher saw last. Every scan rewrites
+// connected_devices, so that list cannot tell which arrivals and departures were already reported.
+async fn rescan(app: &tauri::AppHandle, state: &AppState, known: &mut HashMap<String, JetsonDevice>) {
+    let devices = match crate::scan_usb_devices(state).await {
+        Ok(devices) => devices,
+        Err(e) => {
+            warn!("USB rescan after hotplug failed: {}", e);
+            return;
+        }
+    };
+    state.usb_scan_cache.invalidate().await;
+
+    for device in devices.iter().filter(|d| !known.contains_key(&d.id)) {
+        info!("Device connected: {} ({})", device.module, device.id);
+        let _ = app.emit("device-connected", device);
+    }
+    if devices.iter().any(|d| !known.contains_key(&d.id)) {
+        state.device_arrivals.notify_waiters();
+    }
+    for device in known.values().filter(|d| !devices.iter().any(|n| n.id == d.id)) {
+        info!("Device disconnected: {} ({})", device.module, device.id);
+        let _ = app.emit("device-disconnected", device);
+    }
+    *known = devices.into_iter().map(|device| (device.id.clone(), device)).collect();
+}
+
+// A board can only be flashed while it is in force recovery mode
+pub fn in_recovery(device: &JetsonDevice) -> bool {
+    device.usb_info.as_ref().is_some_and(|usb| usb.is_recovery_mode)
+}
+
+// Wait up to `timeout` for a board in recovery mode that `wanted` accepts, waking on hotplug arrivals.
+// Booted boards never count, whatever `wanted` says. False when the timeout passes or `stop` reports
+// the waiting job was cancelled.
+pub async fn wait_for_device(
+    state: &AppState,
+    timeout: Duration,
+    wanted: impl Fn(&JetsonDevice) -> bool,
+    stop: impl Fn() -> bool,
+) -> bool {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        // Registered before the scan so an arrival during it is not lost
+        let arrival = state.device_arrivals.notified();
+        if crate::scan_usb_devices(state).await.is_ok_and(|devices| devices.iter().any(|d| in_recovery(d) && wanted(d))) {
+            return true;
+        }
+        if stop() || tokio::time::Instant::now() >= deadline {
+            return false;
+        }
+        tokio::select! {
+            _ = arrival => {}
+            _ = tokio::time::sleep(WAIT_RESCAN) => {}
+            _ = tokio::time::sleep_until(deadline) => {}
+        }
+    }
+}
+
+// Reset the boards in recovery mode that `wanted` accepts, so one left mid-transfer by a cancelled job
+// drops its RCM session and can be flashed again without replugging. Returns how many were reset.
+pub async fn reset_recovery_devices(state: &AppState, wanted: impl Fn(&JetsonDevice) -> bool) -> usize {
+    let Ok(devices) = crate::scan_usb_devices(state).await else {
+        return 0;
+    };
+    let reset = reset_devices(devices.iter().filter(|d| wanted(d)));
+    state.usb_scan_cache.invalidate().await;
+    reset
+}
+
+fn reset_devices<'a>(devices: impl Iterator<Item = &'a JetsonDevice>) -> usize {
+    let Ok(usb_devices) = rusb::devices() else {
+        return 0;
+    };
+    let mut reset = 0;
+    for device in devices {
+        let Some(usb) = device.usb_info.as_ref().filter(|usb| usb.is_recovery_mode) else {
+            continue;
+        };
+        let Some(handle) = usb_devices
+            .iter()
+            .find(|d| d.bus_number() == usb.bus_number && d.address() == usb.device_address)
+            .and_then(|d| d.open().ok())
+        else {
+            warn!("Cannot open {} to reset it", usb.device_path);
+            continue;
+        };
+        match handle.reset() {
+            Ok(()) => {
+                info!("Reset {} ({})", device.module, usb.device_path);
+                reset += 1;
+            }
+            Err(e) => warn!("Failed to reset {}: {}", usb.device_path, e),
+        }
+    }
+    reset
+}
+
+pub fn start(app: tauri::AppHandle) {
+    let state = Arc::clone(app.state::<Arc<AppState>>().inner());
+    let (signal, mut events) = unbounded_channel();
+    std::thread::spawn(move || watch(signal));
+
+    tauri::async_runtime::spawn(async move {
+        let mut known = state.connected_devices.lock().unwrap().clone();
+        while events.recv().await.is_some() {
+            tokio::time::sleep(SETTLE_TIME).await;
+            while events.try_recv().is_ok() {}
+            rescan(&app, &state, &mut known).await;
+        }
+    });
+}
@@ -0,0 +1,260 @@
+// CFU - Cordatus Flash Utility - JetPack Component Matrix
+// Maps between L4T, JetPack, CUDA, cuDNN and TensorRT versions (bundled + remotely updatable)
+
+use crate::job_host::JobHost;
+use crate::AppState;
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tauri::{command, State};
+
+const BUNDLED_MATRIX: &str = include_str!("../../data/version_matrix.json");
+const CACHE_FILE: &str = "version_matrix.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReleaseInfo {
+    pub l4t: String,
+    pub jetpack: String,
+    pub cuda: String,
+    pub cudnn: String,
+    pub tensorrt: String,
+    pub ubuntu: String,
+}
+
+// A flag attached to a range of releases; empty module/host lists match everything
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionRule {
+    pub l4t_prefix: String,
+    #[serde(default)]
+    pub modules: Vec<String>,
+    #[serde(default)]
+    pub host_ubuntu: Vec<String>,
+    pub severity: String, // 'warning' | 'block'
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionMatrix {
+    pub revision: u32,
+    pub releases: Vec<ReleaseInfo>,
+    #[serde(default)]
+    pub rules: Vec<VersionRule>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompatibilityReport {
+    pub release: Option<ReleaseInfo>,
+    pub host_ubuntu: Option<String>,
+    pub warnings: Vec<String>,
+    pub blocks: Vec<String>,
+}
+
+impl CompatibilityReport {
+    pub fn is_blocked(&self) -> bool {
+        !self.blocks.is_empty()
+    }
+}
+
+enum VersionQuery {
+    L4t(String),
+    Jetpack(String),
+}
+
+impl VersionMatrix {
+    pub fn bundled() -> Self {
+        serde_json::from_str(BUNDLED_MATRIX).expect("bundled version matrix is valid JSON")
+    }
+
+    // Resolve any of "6.2", "JetPack 6.2", "L4T 36.4.3", "36.3" or "6.2 - L4T 36.4.3"
+    pub fn resolve(&self, version: &str) -> Option<&ReleaseInfo> {
+        match parse_query(version)? {
+            VersionQuery::L4t(l4t) => self.releases.iter().find(|r| r.l4t == l4t),
+            VersionQuery::Jetpack(jetpack) => self
+                .releases
+                .iter()
+                .find(|r| r.jetpack.eq_ignore_ascii_case(&jetpack)),
+        }
+    }
+
+    // Evaluate the matrix rules for a module/version on the given host
+    pub fn check(&self, module: &str, version: &str, host_ubuntu: Option<&str>) -> CompatibilityReport {
+        let mut report = CompatibilityReport {
+            release: self.resolve(version).cloned(),
+            host_ubuntu: host_ubuntu.map(|v| v.to_string()),
+            warnings: Vec::new(),
+            blocks: Vec::new(),
+        };
+
+        let Some(release) = &report.release else {
+            report.warnings.push(format!("{} is not in the version matrix; compatibility cannot be checked", version));
+            return report;
+        };
+
+        for rule in &self.rules {
+            let module_matches = rule.modules.is_empty() || rule.modules.iter().any(|m| module.starts_with(m.as_str()));
+            let host_matches = rule.host_ubuntu.is_empty()
+                || host_ubuntu.is_some_and(|host| rule.host_ubuntu.iter().any(|h| h == host));
+
+            if release.l4t.starts_with(&rule.l4t_prefix) && module_matches && host_matches {
+                match rule.severity.as_str() {
+                    "block" => report.blocks.push(rule.message.clone()),
+                    _ => report.warnings.push(rule.message.clone()),
+                }
+            }
+        }
+
+        report
+    }
+}
+
+// Read the host Ubuntu release (e.g. "22.04"), None on other distributions
+pub fn host_ubuntu_version() -> Option<String> {
+    let os_release = std::fs::read_to_string("/etc/os-release").ok()?;
+    let field = |key: &str| {
+        os_release
+            .lines()
+            .find_map(|line| line.strip_prefix(key))
+            .map(|value| value.trim_matches('"').to_string())
+    };
+
+    if field("ID=")? == "ubuntu" {
+        field("VERSION_ID=")
+    } else {
+        None
+    }
+}
+
+// Pad L4T versions to three components so "36.3" matches "36.3.0"
+fn normalize_l4t(version: &str) -> String {
+    let mut parts: Vec<&str> = version.split('.').collect();
+    while parts.len() < 3 {
+        parts.push("0");
+    }
+    parts.join(".")
+}
+
+fn parse_query(input: &str) -> Option<VersionQuery> {
+    // ASCII uppercasing keeps every byte where it was, so the index is valid in the input too
+    let upper = input.to_ascii_uppercase();
+
+    if let Some(index) = upper.find("L4T") {
+        let l4t = input[index + 3..].split_whitespace().next()?;
+        return Some(VersionQuery::L4t(normalize_l4t(l4t)));
+    }
+
+    let version = input
+        .trim()
+        .trim_start_matches(|c: char| c.is_ascii_alphabetic() || c.is_whitespace())
+        .replace(".DP", " DP");
+    let major: u32 = version.split(['.', ' ']).next()?.parse().ok()?;
+
+    // L4T majors start at 32, JetPack majors are single digits
+    if major >= 32 {
+        Some(VersionQuery::L4t(normalize_l4t(&version)))
+    } else {
+        Some(VersionQuery::Jetpack(version))
+    }
+}
+
+// Load the cached remote matrix if it is newer than the bundled one
+pub fn load_version_matrix(app: &impl JobHost) -> VersionMatrix {
+    let bundled = VersionMatrix::bundled();
+
+    let cached = crate::app_data_file(app, CACHE_FILE)
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str::<VersionMatrix>(&content).ok());
+
+    match cached {
+        Some(cached) if cached.revision > bundled.revision => {
+            info!("Using cached version matrix revision {}", cached.revision);
+            cached
+        }
+        _ => bundled,
+    }
+}
+
+async fn fetch_version_matrix(url: &str) -> Result<VersionMatrix> {
+    let matrix: VersionMatrix = reqwest::get(url)
+        .await
+        .context("Failed to download version matrix")?
+        .error_for_status()?
+        .json()
+        .await
+        .context("Invalid version matrix document")?;
+
+    if matrix.releases.is_empty() {
+        return Err(anyhow::anyhow!("Version matrix contains no releases"));
+    }
+    Ok(matrix)
+}
+
+// Get the active version matrix
+#[command]
+pub async fn get_version_matrix(state: State<'_, Arc<AppState>>) -> Result<VersionMatrix, String> {
+    Ok(state.version_matrix.lock().unwrap().clone())
+}
+
+// Resolve a JetPack or L4T version string to its full component set
+#[command]
+pub async fn resolve_release(version: String, state: State<'_, Arc<AppState>>) -> Result<Option<ReleaseInfo>, String> {
+    let matrix = state.version_matrix.lock().unwrap();
+    Ok(matrix.resolve(&version).cloned())
+}
+
+// Fetch a newer matrix from the given URL (or the configured one) and cache it
+#[command]
+pub async fn update_version_matrix(
+    url: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> Result<VersionMatrix, String> {
+    crate::freeze::ensure_not_frozen(&app, "Version matrix updates")?;
+    let url = url
+        .or_else(|| Some(state.settings.lock().unwrap().version_matrix_url.clone()))
+        .filter(|u| !u.is_empty())
+        .ok_or("No version matrix URL configured")?;
+
+    info!("Updating version matrix from {}", url);
+    let matrix = fetch_version_matrix(&url).await.map_err(|e| e.to_string())?;
+
+    let current_revision = state.version_matrix.lock().unwrap().revision;
+    if matrix.revision <= current_revision {
+        warn!("Remote version matrix revision {} is not newer than {}", matrix.revision, current_revision);
+        return Ok(state.version_matrix.lock().unwrap().clone());
+    }
+
+    let path = crate::app_data_file(&app, CACHE_FILE).map_err(|e| e.to_string())?;
+    let json = serde_json::to_string_pretty(&matrix).map_err(|e| e.to_string())?;
+    std::fs::write(&path, json).map_err(|e| format!("Failed to cache version matrix: {}", e))?;
+
+    *state.version_matrix.lock().unwrap() = matrix.clone();
+    info!("Version matrix updated to revision {}", matrix.revision);
+    Ok(matrix)
+}
+
+// Check whether a module/JetPack combination can be flashed from this host
+#[command]
+pub async fn check_version_compatibility(
+    module: String,
+    jetpack_version: String,
+    state: State<'_, Arc<AppState>>,
+) -> Result<CompatibilityReport, String> {
+    let host = host_ubuntu_version();
+    let matrix = state.version_matrix.lock().unwrap();
+    Ok(matrix.check(&module, &jetpack_version, host.as_deref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Full uppercasing changes the byte length of these, so an index found in it is off in the input
+    #[test]
+    fn parse_query_with_non_ascii_input() {
+        assert!(matches!(parse_query("ı L4T 36.3"), Some(VersionQuery::L4t(v)) if v == "36.3.0"));
+        assert!(matches!(parse_query("ǰ l4t 35.4.1"), Some(VersionQuery::L4t(v)) if v == "35.4.1"));
+        assert!(parse_query("ǰ L4Tä").is_some()); // Panicked slicing inside "ä"
+    }
+}
@@ -0,0 +1,277 @@
+// CFU - Cordatus Flash Utility - Write Target Confirmation
+// Describes the disk an image is about to be written to, read from sysfs, and ties the write to a token
+// of that description, so a confirmed SD card that was swapped for another disk is never overwritten.
+// Disks holding the host system and those deny-listed in the settings are never offered or used.
+
+use crate::AppState;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tauri::{command, State};
+
+// Disks above this size are rarely SD cards or USB sticks
+const LARGE_DISK_BYTES: u64 = 512 * 1000 * 1000 * 1000;
+const SECTOR_SIZE: u64 = 512;
+
+// The disks behind these mounts are the host system
+const SYSTEM_MOUNTS: [&str; 3] = ["/", "/boot", "/boot/efi"];
+// Virtual block devices that are never imaging targets
+const VIRTUAL_PREFIXES: [&str; 6] = ["loop", "ram", "zram", "dm-", "md", "sr"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TargetPartition {
+    pub device: String, // e.g. "/dev/sdb1"
+    pub size_bytes: u64,
+    pub mountpoint: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TargetSummary {
+    pub device: String, // Resolved block device, e.g. "/dev/sdb"
+    pub model: String,  // Vendor and model as the disk reports them
+    pub serial: Option<String>,
+    pub size_bytes: u64,
+    pub removable: bool,
+    pub transport: Option<String>, // 'usb' | 'mmc' | 'nvme' | ...
+    pub partitions: Vec<TargetPartition>,
+    pub warnings: Vec<String>,
+    pub description: String,  // One line for the confirmation dialog
+    pub confirmation: String, // Pass to write_image to confirm this exact target
+}
+
+fn read_attribute(path: &Path) -> Option<String> {
+    let value = std::fs::read_to_string(path).ok()?;
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+fn read_size(path: &Path) -> u64 {
+    read_attribute(&path.join("size")).and_then(|sectors| sectors.parse::<u64>().ok()).unwrap_or(0) * SECTOR_SIZE
+}
+
+// Bus the disk hangs off, from the sysfs device path
+fn transport(sys_block: &Path) -> Option<String> {
+    let path = std::fs::canonicalize(sys_block.join("device")).ok()?.to_string_lossy().to_string();
+    ["usb", "mmc", "nvme", "ata", "virtio"]
+        .into_iter()
+        .find(|bus| path.contains(&format!("/{}", bus)))
+        .map(str::to_string)
+}
+
+fn mountpoint(mounts: &str, device: &str) -> Option<String> {
+    mounts.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        (fields.next() == Some(device)).then(|| fields.next().map(|m| m.replace("\\040", " "))).flatten()
+    })
+}
+
+fn format_size(bytes: u64) -> String {
+    let units = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < units.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, units[unit])
+}
+
+pub fn describe(device: &str) -> Result<TargetSummary> {
+    let resolved = std::fs::canonicalize(device).with_context(|| format!("Device not found: {}", device))?;
+    let name = resolved
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .context("Invalid device path")?;
+    let sys_block = PathBuf::from("/sys/block").join(&name);
+    if !sys_block.exists() {
+        return Err(anyhow::anyhow!("{} is not a whole disk; select the disk, not a partition", device));
+    }
+    let attribute = |file: &str| read_attribute(&sys_block.join(file));
+    let model = [attribute("device/vendor"), attribute("device/model").or_else(|| attribute("device/name"))]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mounts = std::fs::read_to_string("/proc/mounts").unwrap_or_default();
+
+    let mut partitions = Vec::new();
+    for entry in std::fs::read_dir(&sys_block)?.flatten() {
+        let part_name = entry.file_name().to_string_lossy().to_string();
+        if !part_name.starts_with(&name) || !entry.path().join("partition").exists() {
+            continue;
+        }
+        let part_device = format!("/dev/{}", part_name);
+        partitions.push(TargetPartition {
+            size_bytes: read_size(&entry.path()),
+            mountpoint: mountpoint(&mounts, &part_device),
+            device: part_device,
+        });
+    }
+    partitions.sort_by(|a, b| a.device.cmp(&b.device));
+
+    let mut summary = TargetSummary {
+        device: format!("/dev/{}", name),
+        model: if model.is_empty() { "Unknown disk".to_string() } else { model },
+        serial: attribute("device/serial").or_else(|| attribute("serial")),
+        size_bytes: read_size(&sys_block),
+        removable: attribute("removable").as_deref() == Some("1"),
+        transport: transport(&sys_block),
+        partitions,
+        warnings: Vec::new(),
+        description: String::new(),
+        confirmation: String::new(),
+    };
+    if !summary.removable && summary.transport.as_deref() != Some("mmc") {
+        summary.warnings.push("Not a removable disk".to_string());
+    }
+    if summary.size_bytes > LARGE_DISK_BYTES {
+        summary.warnings.push(format!("Larger than {}", format_size(LARGE_DISK_BYTES)));
+    }
+    for partition in &summary.partitions {
+        if let Some(mountpoint) = &partition.mountpoint {
+            summary.warnings.push(format!("{} is mounted at {}", partition.device, mountpoint));
+        }
+    }
+    summary.description = format!(
+        "{} {} ({}{}, {} partition{})",
+        summary.device,
+        summary.model,
+        format_size(summary.size_bytes),
+        if summary.removable { ", removable" } else { "" },
+        summary.partitions.len(),
+        if summary.partitions.len() == 1 { "" } else { "s" }
+    );
+    summary.confirmation = token(&summary);
+    Ok(summary)
+}
+
+// Changes whenever the disk behind the path, its size or its partitioning changes
+fn token(summary: &TargetSummary) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(&summary.device);
+    hasher.update(&summary.model);
+    hasher.update(summary.serial.as_deref().unwrap_or(""));
+    hasher.update(summary.size_bytes.to_le_bytes());
+    hasher.update([summary.removable as u8]);
+    for partition in &summary.partitions {
+        hasher.update(&partition.device);
+        hasher.update(partition.size_bytes.to_le_bytes());
+    }
+    hasher.finalize()[..8].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Kernel name of the whole disk a block device belongs to, e.g. "sdb" for /dev/sdb1
+fn disk_name(device: &Path) -> Option<String> {
+    let name = std::fs::canonicalize(device).ok()?.file_name()?.to_string_lossy().to_string();
+    let class = Path::new("/sys/class/block").join(&name);
+    if class.join("partition").exists() {
+        let parent = std::fs::canonicalize(&class).ok()?.parent()?.file_name()?.to_string_lossy().to_string();
+        return Some(parent);
+    }
+    Some(name)
+}
+
+// Physical disks under a block device, following device-mapper and RAID layers down
+fn physical_disks(name: &str) -> Vec<String> {
+    let slaves: Vec<String> = std::fs::read_dir(Path::new("/sys/class/block").join(name).join("slaves"))
+        .map(|entries| entries.flatten().map(|e| e.file_name().to_string_lossy().to_string()).collect())
+        .unwrap_or_default();
+    if slaves.is_empty() {
+        return disk_name(&Path::new("/dev").join(name)).into_iter().collect();
+    }
+    slaves.iter().flat_map(|slave| physical_disks(slave)).collect()
+}
+
+// Disks the running system is on
+fn system_disks() -> Vec<String> {
+    let mounts = std::fs::read_to_string("/proc/mounts").unwrap_or_default();
+    let mut disks: Vec<String> = mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let (source, target) = (fields.next()?, fields.next()?);
+            (source.starts_with("/dev/") && SYSTEM_MOUNTS.contains(&target)).then(|| source.to_string())
+        })
+        .filter_map(|source| std::fs::canonicalize(source).ok())
+        .filter_map(|path| path.file_name().map(|n| n.to_string_lossy().to_string()))
+        .flat_map(|name| physical_disks(&name))
+        .collect();
+    disks.sort();
+    disks.dedup();
+    disks
+}
+
+// Why a disk may not be used, if it may not; `denied` entries are device paths (any /dev/disk link
+// works) or disk serial numbers
+fn denial(summary: &TargetSummary, denied: &[String]) -> Option<String> {
+    let name = summary.device.trim_start_matches("/dev/");
+    if system_disks().iter().any(|disk| disk == name) {
+        return Some(format!("{} holds the host system", summary.device));
+    }
+    denied
+        .iter()
+        .find(|entry| {
+            disk_name(Path::new(entry.as_str())).as_deref() == Some(name) || summary.serial.as_deref() == Some(entry.as_str())
+        })
+        .map(|entry| format!("{} is deny-listed in the settings ({})", summary.device, entry))
+}
+
+// Describe a disk, refusing system and deny-listed disks
+pub fn describe_allowed(device: &str, denied: &[String]) -> Result<TargetSummary> {
+    let summary = describe(device)?;
+    match denial(&summary, denied) {
+        Some(reason) => Err(anyhow::anyhow!("{} cannot be used as an imaging target", reason)),
+        None => Ok(summary),
+    }
+}
+
+// Refuse unless the device is still the one the user confirmed
+pub fn confirm(device: &str, confirmation: &str, denied: &[String]) -> Result<TargetSummary> {
+    let summary = describe_allowed(device, denied)?;
+    if summary.confirmation != confirmation {
+        return Err(anyhow::anyhow!(
+            "{} is not the target that was confirmed (now {}); review the target again",
+            device,
+            summary.description
+        ));
+    }
+    Ok(summary)
+}
+
+fn denied_devices(state: &AppState) -> Vec<String> {
+    state.settings.lock().unwrap().denied_block_devices.clone()
+}
+
+// Summary of a disk to show before writing an image to it
+#[command]
+pub async fn describe_write_target(device: String, state: State<'_, Arc<AppState>>) -> Result<TargetSummary, String> {
+    let denied = denied_devices(&state);
+    tokio::task::spawn_blocking(move || describe_allowed(&device, &denied))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+// Disks that may be offered for writing or backup
+#[command]
+pub async fn list_write_targets(state: State<'_, Arc<AppState>>) -> Result<Vec<TargetSummary>, String> {
+    let denied = denied_devices(&state);
+    tokio::task::spawn_blocking(move || {
+        let mut names: Vec<String> = std::fs::read_dir("/sys/block")
+            .map_err(|e| format!("Failed to list block devices: {}", e))?
+            .flatten()
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter(|name| !VIRTUAL_PREFIXES.iter().any(|prefix| name.starts_with(prefix)))
+            .collect();
+        names.sort();
+        Ok(names
+            .iter()
+            .filter_map(|name| describe_allowed(&format!("/dev/{}", name), &denied).ok())
+            .filter(|summary| summary.size_bytes > 0)
+            .collect())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}